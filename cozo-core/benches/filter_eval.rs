@@ -0,0 +1,84 @@
+/*
+ *  Copyright 2023, The Cozo Project Authors.
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ *  If a copy of the MPL was not distributed with this file,
+ *  You can obtain one at https://mozilla.org/MPL/2.0/.
+ *
+ */
+#![feature(test)]
+
+extern crate test;
+
+use cozo::{DataValue, DbInstance, ScriptMutability};
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use test::Bencher;
+
+const N_ROWS: i64 = 100_000;
+
+lazy_static! {
+    static ref TEST_DB: DbInstance = {
+        let db = DbInstance::default();
+        db.run_script(
+            ":create r {k: Int => v: String}",
+            Default::default(),
+            ScriptMutability::Mutable,
+        )
+        .unwrap();
+        let rows = (0..N_ROWS).map(|i| {
+            BTreeMap::from([
+                ("k".to_string(), DataValue::from(i)),
+                ("v".to_string(), DataValue::from(format!("value {i}"))),
+            ])
+        });
+        db.import_rows("r", rows, Default::default()).unwrap();
+        db
+    };
+}
+
+fn filter_with_constant(len: usize) {
+    let constant = DataValue::from("x".repeat(len));
+    let res = TEST_DB
+        .run_script(
+            "?[count(k)] := *r{k, v}, v != $c",
+            BTreeMap::from([("c".to_string(), constant)]),
+            ScriptMutability::Immutable,
+        )
+        .unwrap();
+    assert_eq!(res.rows[0][0], DataValue::from(N_ROWS));
+}
+
+/// Every row compares against the constant, which is passed to `!=` by reference
+/// and so costs the same as a small one.
+#[bench]
+fn filter_large_string_constant(b: &mut Bencher) {
+    b.iter(|| filter_with_constant(100_000))
+}
+
+#[bench]
+fn filter_small_string_constant(b: &mut Bencher) {
+    b.iter(|| filter_with_constant(10))
+}
+
+/// Every row compares a large value with itself, which is decided without reading the value.
+#[bench]
+fn filter_large_value_self_comparison(b: &mut Bencher) {
+    let db = DbInstance::default();
+    db.run_script(
+        "?[k, v] := k in int_range(1000), v = concat(to_string(k), $pad) :create wide {k => v}",
+        BTreeMap::from([("pad".to_string(), DataValue::from("x".repeat(100_000)))]),
+        ScriptMutability::Mutable,
+    )
+    .unwrap();
+    b.iter(|| {
+        let res = db
+            .run_script(
+                "?[count(k)] := *wide{k, v}, v == v",
+                Default::default(),
+                ScriptMutability::Immutable,
+            )
+            .unwrap();
+        assert_eq!(res.rows[0][0], DataValue::from(1000));
+    })
+}
@@ -0,0 +1,60 @@
+/*
+ *  Copyright 2023, The Cozo Project Authors.
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ *  If a copy of the MPL was not distributed with this file,
+ *  You can obtain one at https://mozilla.org/MPL/2.0/.
+ *
+ */
+#![feature(test)]
+
+extern crate test;
+
+use cozo::{DataValue, DbInstance, ScriptMutability};
+use std::collections::BTreeMap;
+use test::Bencher;
+
+const N_ROWS: i64 = 1000;
+
+fn new_db() -> DbInstance {
+    let db = DbInstance::default();
+    db.run_script(
+        ":create person {id: Int => name: String, age: Int}",
+        Default::default(),
+        ScriptMutability::Mutable,
+    )
+    .unwrap();
+    db
+}
+
+fn make_row(i: i64) -> BTreeMap<String, DataValue> {
+    BTreeMap::from([
+        ("id".to_string(), DataValue::from(i)),
+        ("name".to_string(), DataValue::from(format!("p{i}"))),
+        ("age".to_string(), DataValue::from(i % 100)),
+    ])
+}
+
+#[bench]
+fn import_per_row_queries(b: &mut Bencher) {
+    b.iter(|| {
+        let db = new_db();
+        for i in 0..N_ROWS {
+            db.run_script(
+                "?[id, name, age] <- [[$id, $name, $age]] :put person {id => name, age}",
+                make_row(i),
+                ScriptMutability::Mutable,
+            )
+            .unwrap();
+        }
+    })
+}
+
+#[bench]
+fn import_bulk_rows(b: &mut Bencher) {
+    b.iter(|| {
+        let db = new_db();
+        db.import_rows("person", (0..N_ROWS).map(make_row), Default::default())
+            .unwrap();
+    })
+}
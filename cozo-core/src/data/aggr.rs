@@ -12,11 +12,13 @@ use std::fmt::{Debug, Formatter};
 use miette::{bail, ensure, miette, Result};
 use rand::prelude::*;
 
-use crate::data::value::DataValue;
+use crate::data::value::{DataValue, Num};
 
 pub(crate) struct Aggregation {
     pub(crate) name: &'static str,
     pub(crate) is_meet: bool,
+    /// Whether repeated values and nulls are skipped, as in `count(distinct x)`
+    pub(crate) distinct: bool,
     pub(crate) meet_op: Option<Box<dyn MeetAggrObj>>,
     pub(crate) normal_op: Option<Box<dyn NormalAggrObj>>,
 }
@@ -26,6 +28,7 @@ impl Clone for Aggregation {
         Self {
             name: self.name,
             is_meet: self.is_meet,
+            distinct: self.distinct,
             meet_op: None,
             normal_op: None,
         }
@@ -44,7 +47,7 @@ pub(crate) trait MeetAggrObj: Send + Sync {
 
 impl PartialEq for Aggregation {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+        self.name == other.name && self.distinct == other.distinct
     }
 }
 
@@ -59,6 +62,7 @@ macro_rules! define_aggr {
         const $name: Aggregation = Aggregation {
             name: stringify!($name),
             is_meet: $is_meet,
+            distinct: false,
             meet_op: None,
             normal_op: None,
         };
@@ -501,14 +505,14 @@ define_aggr!(AGGR_MEAN, false);
 #[derive(Default)]
 pub(crate) struct AggrMean {
     count: i64,
-    sum: f64,
+    sum: WideSum,
 }
 
 impl NormalAggrObj for AggrMean {
     fn set(&mut self, value: &DataValue) -> Result<()> {
         match value {
             DataValue::Num(n) => {
-                self.sum += n.get_float();
+                self.sum.add(n);
                 self.count += 1;
             }
             v => bail!("cannot compute 'mean': encountered value {:?}", v),
@@ -517,7 +521,27 @@ impl NormalAggrObj for AggrMean {
     }
 
     fn get(&self) -> Result<DataValue> {
-        Ok(DataValue::from(self.sum / (self.count as f64)))
+        Ok(DataValue::from(self.sum.to_float() / (self.count as f64)))
+    }
+}
+
+/// Running sum that adds integers exactly in 128 bits, so that no
+/// intermediate result overflows, and keeps any floats apart.
+#[derive(Default)]
+struct WideSum {
+    int_sum: i128,
+    float_sum: f64,
+}
+
+impl WideSum {
+    fn add(&mut self, n: &Num) {
+        match n {
+            Num::Int(i) => self.int_sum += *i as i128,
+            Num::Float(f) => self.float_sum += f,
+        }
+    }
+    fn to_float(&self) -> f64 {
+        self.int_sum as f64 + self.float_sum
     }
 }
 
@@ -525,14 +549,14 @@ define_aggr!(AGGR_SUM, false);
 
 #[derive(Default)]
 pub(crate) struct AggrSum {
-    sum: f64,
+    sum: WideSum,
 }
 
 impl NormalAggrObj for AggrSum {
     fn set(&mut self, value: &DataValue) -> Result<()> {
         match value {
             DataValue::Num(n) => {
-                self.sum += n.get_float();
+                self.sum.add(n);
             }
             v => bail!("cannot compute 'sum': encountered value {:?}", v),
         }
@@ -540,7 +564,7 @@ impl NormalAggrObj for AggrSum {
     }
 
     fn get(&self) -> Result<DataValue> {
-        Ok(DataValue::from(self.sum))
+        Ok(DataValue::from(self.sum.to_float()))
     }
 }
 
@@ -1155,6 +1179,25 @@ impl NormalAggrObj for AggrBitXor {
     }
 }
 
+/// Wrapper feeding each distinct non-null value to the aggregation only once.
+struct AggrDistinct {
+    seen: BTreeSet<DataValue>,
+    inner: Box<dyn NormalAggrObj>,
+}
+
+impl NormalAggrObj for AggrDistinct {
+    fn set(&mut self, value: &DataValue) -> Result<()> {
+        if *value == DataValue::Null || !self.seen.insert(value.clone()) {
+            return Ok(());
+        }
+        self.inner.set(value)
+    }
+
+    fn get(&self) -> Result<DataValue> {
+        self.inner.get()
+    }
+}
+
 pub(crate) fn parse_aggr(name: &str) -> Option<&'static Aggregation> {
     Some(match name {
         "and" => &AGGR_AND,
@@ -1171,7 +1214,7 @@ pub(crate) fn parse_aggr(name: &str) -> Option<&'static Aggregation> {
         "product" => &AGGR_PRODUCT,
         "min" => &AGGR_MIN,
         "max" => &AGGR_MAX,
-        "mean" => &AGGR_MEAN,
+        "mean" | "avg" => &AGGR_MEAN,
         "choice" => &AGGR_CHOICE,
         "collect" => &AGGR_COLLECT,
         "shortest" => &AGGR_SHORTEST,
@@ -1206,7 +1249,7 @@ impl Aggregation {
     }
     pub(crate) fn normal_init(&mut self, args: &[DataValue]) -> Result<()> {
         #[allow(clippy::box_default)]
-        self.normal_op.replace(match self.name {
+        let op: Box<dyn NormalAggrObj> = match self.name {
             name if name == AGGR_AND.name => Box::new(AggrAnd::default()),
             name if name == AGGR_OR.name => Box::new(AggrOr::default()),
             name if name == AGGR_COUNT.name => Box::new(AggrCount::default()),
@@ -1250,6 +1293,14 @@ impl Aggregation {
                 }
             }),
             _ => unreachable!(),
+        };
+        self.normal_op.replace(if self.distinct {
+            Box::new(AggrDistinct {
+                seen: Default::default(),
+                inner: op,
+            })
+        } else {
+            op
         });
         Ok(())
     }
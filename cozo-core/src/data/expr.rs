@@ -6,6 +6,7 @@
  * You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use std::borrow::Cow;
 use std::cmp::{max, min};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Debug, Display, Formatter};
@@ -15,6 +16,7 @@ use itertools::Itertools;
 use miette::{bail, miette, Diagnostic, Result};
 use serde::de::{Error, Visitor};
 use serde::{Deserializer, Serializer};
+use smallvec::SmallVec;
 use smartstring::{LazyCompact, SmartString};
 use thiserror::Error;
 
@@ -57,6 +59,12 @@ pub enum Bytecode {
         #[serde(skip)]
         span: SourceSpan,
     },
+    /// pop 1 if null, otherwise unchanged
+    JumpIfNotNull {
+        jump_to: usize,
+        #[serde(skip)]
+        span: SourceSpan,
+    },
 }
 
 #[derive(Error, Diagnostic, Debug)]
@@ -76,71 +84,92 @@ pub fn eval_bytecode_pred(
     stack: &mut Vec<DataValue>,
     span: SourceSpan,
 ) -> Result<bool> {
-    match eval_bytecode(bytecodes, bindings, stack)? {
-        DataValue::Bool(b) => Ok(b),
-        v => bail!(PredicateTypeError(span, v)),
+    let val = eval_bytecode(bytecodes, bindings, stack)?;
+    match val.is_truthy() {
+        Ok(b) => Ok(b.unwrap_or(false)),
+        Err(_) => bail!(PredicateTypeError(span, val)),
     }
 }
 
+/// A value on the evaluation stack of [eval_bytecode].
+#[derive(Clone, Copy)]
+enum StackSlot<'a> {
+    /// A constant or a bound value, used in place
+    Borrowed(&'a DataValue),
+    /// A value computed by an op, at the given position in the stack of owned values
+    Owned(usize),
+}
+
+/// Evaluate `bytecodes` against `bindings`. Constants and bound values are never cloned,
+/// ops get references to them. Only the values computed by ops are kept in `stack`,
+/// which can be reused between calls.
 pub fn eval_bytecode(
     bytecodes: &[Bytecode],
     bindings: impl AsRef<[DataValue]>,
     stack: &mut Vec<DataValue>,
 ) -> Result<DataValue> {
+    let bindings = bindings.as_ref();
     stack.clear();
+    // the owned values in `stack` are exactly those of the `Owned` slots, in the same order
+    let mut slots: SmallVec<[StackSlot<'_>; 8]> = SmallVec::new();
     let mut pointer = 0;
-    // for (i, c) in bytecodes.iter().enumerate() {
-    //     println!("{i}  {c:?}");
-    // }
-    // println!();
     loop {
-        // println!("{pointer}  {stack:?}");
         if pointer == bytecodes.len() {
             break;
         }
         let current_instruction = &bytecodes[pointer];
-        // println!("{current_instruction:?}");
         match current_instruction {
             Bytecode::Binding { var, tuple_pos, .. } => match tuple_pos {
                 None => {
                     bail!(UnboundVariableError(var.name.to_string(), var.span))
                 }
                 Some(i) => {
-                    let val = bindings
-                        .as_ref()
-                        .get(*i)
-                        .ok_or_else(|| {
-                            TupleTooShortError(
-                                var.name.to_string(),
-                                *i,
-                                bindings.as_ref().len(),
-                                var.span,
-                            )
-                        })?
-                        .clone();
-                    stack.push(val);
+                    let val = bindings.get(*i).ok_or_else(|| {
+                        TupleTooShortError(var.name.to_string(), *i, bindings.len(), var.span)
+                    })?;
+                    slots.push(StackSlot::Borrowed(val));
                     pointer += 1;
                 }
             },
             Bytecode::Const { val, .. } => {
-                stack.push(val.clone());
+                slots.push(StackSlot::Borrowed(val));
                 pointer += 1;
             }
             Bytecode::Apply { op, arity, span } => {
-                let frame_start = stack.len() - *arity;
-                let args_frame = &stack[frame_start..];
-                let result = (op.inner)(args_frame)
-                    .map_err(|err| EvalRaisedError(*span, err.to_string()))?;
-                stack.truncate(frame_start);
+                let frame_start = slots.len() - *arity;
+                let frame = &slots[frame_start..];
+                let result = {
+                    let args: SmallVec<[&DataValue; 4]> = frame
+                        .iter()
+                        .map(|slot| match slot {
+                            StackSlot::Borrowed(val) => *val,
+                            StackSlot::Owned(i) => &stack[*i],
+                        })
+                        .collect();
+                    (op.inner)(&args).map_err(|err| EvalRaisedError(*span, err.to_string()))?
+                };
+                let n_owned = frame
+                    .iter()
+                    .filter(|slot| matches!(slot, StackSlot::Owned(_)))
+                    .count();
+                stack.truncate(stack.len() - n_owned);
+                slots.truncate(frame_start);
+                slots.push(StackSlot::Owned(stack.len()));
                 stack.push(result);
                 pointer += 1;
             }
             Bytecode::JumpIfFalse { jump_to, span } => {
-                let val = stack.pop().unwrap();
-                let cond = val
-                    .get_bool()
-                    .ok_or_else(|| PredicateTypeError(*span, val))?;
-                if cond {
+                let cond = match slots.pop().unwrap() {
+                    StackSlot::Borrowed(val) => val
+                        .is_truthy()
+                        .map_err(|_| PredicateTypeError(*span, val.clone()))?,
+                    StackSlot::Owned(_) => {
+                        let val = stack.pop().unwrap();
+                        val.is_truthy()
+                            .map_err(|_| PredicateTypeError(*span, val))?
+                    }
+                };
+                if cond == Some(true) {
                     pointer += 1;
                 } else {
                     pointer = *jump_to;
@@ -149,9 +178,26 @@ pub fn eval_bytecode(
             Bytecode::Goto { jump_to, .. } => {
                 pointer = *jump_to;
             }
+            Bytecode::JumpIfNotNull { jump_to, .. } => {
+                let is_null = match slots.last().unwrap() {
+                    StackSlot::Borrowed(val) => **val == DataValue::Null,
+                    StackSlot::Owned(i) => stack[*i] == DataValue::Null,
+                };
+                if is_null {
+                    if let StackSlot::Owned(_) = slots.pop().unwrap() {
+                        stack.pop();
+                    }
+                    pointer += 1;
+                } else {
+                    pointer = *jump_to;
+                }
+            }
         }
     }
-    Ok(stack.pop().unwrap())
+    Ok(match slots.pop().unwrap() {
+        StackSlot::Borrowed(val) => val.clone(),
+        StackSlot::Owned(_) => stack.pop().unwrap(),
+    })
 }
 
 /// Expression can be evaluated to yield a DataValue
@@ -406,13 +452,37 @@ impl Expr {
         self.partial_eval()?;
         match self {
             Expr::Const { val, .. } => Ok(val),
+            // non-deterministic calls are left alone by `partial_eval`, but can still be evaluated now
+            e if e.bindings()?.is_empty() => e.eval([]),
             _ => bail!(NotConstError),
         }
     }
     pub(crate) fn partial_eval(&mut self) -> Result<()> {
-        if let Expr::Apply { args, span, .. } = self {
+        if let Expr::Apply { op, args, .. } = self {
+            if op.name == OP_COALESCE.name {
+                // later arguments may never be evaluated, so errors are left for runtime
+                for (i, arg) in args.iter_mut().enumerate() {
+                    if arg.partial_eval().is_err() {
+                        break;
+                    }
+                    if matches!(arg.get_const(), Some(v) if *v != DataValue::Null) {
+                        let mut kept = mem::replace(args, Box::new([])).into_vec();
+                        kept.truncate(i + 1);
+                        *args = kept.into();
+                        break;
+                    }
+                }
+                if args.iter().all(|arg| arg.get_const().is_some()) {
+                    let span = self.span();
+                    let val = self.eval([])?;
+                    *self = Expr::Const { val, span };
+                }
+                return Ok(());
+            }
+        }
+        if let Expr::Apply { op, args, span } = self {
             let span = *span;
-            let mut all_evaluated = true;
+            let mut all_evaluated = op.is_deterministic();
             for arg in args.iter_mut() {
                 arg.partial_eval()?;
                 all_evaluated = all_evaluated && matches!(arg, Expr::Const { .. });
@@ -445,6 +515,114 @@ impl Expr {
         }
         Ok(())
     }
+    /// Push negations inward: `!(a && b)` becomes `!a || !b`, `!(a || b)` becomes
+    /// `!a && !b`, `!(a == b)` becomes `a != b` (and vice versa), and `!!a` becomes `a`.
+    /// These rewrites hold under the three-valued logic of the boolean operators.
+    ///
+    /// Also flattens nested `coalesce` calls, drops null constants from their arguments and
+    /// cuts them off at the first non-null constant.
+    pub(crate) fn simplify(&mut self) {
+        match self {
+            Expr::Apply { args, .. } => {
+                for arg in args.iter_mut() {
+                    arg.simplify();
+                }
+            }
+            Expr::Cond { clauses, .. } => {
+                for (cond, val) in clauses {
+                    cond.simplify();
+                    val.simplify();
+                }
+            }
+            _ => return,
+        }
+        let span = self.span();
+        let Expr::Apply { op, args, .. } = self else {
+            return;
+        };
+        if op.name == OP_COALESCE.name {
+            let mut kept = vec![];
+            let flattened = mem::replace(args, Box::new([]))
+                .into_vec()
+                .into_iter()
+                .flat_map(|arg| match arg {
+                    Expr::Apply { op, args, .. } if op.name == OP_COALESCE.name => args.into_vec(),
+                    arg => vec![arg],
+                });
+            for arg in flattened {
+                match arg.get_const() {
+                    Some(DataValue::Null) => continue,
+                    Some(_) => {
+                        kept.push(arg);
+                        break;
+                    }
+                    None => kept.push(arg),
+                }
+            }
+            let mut new_self = match kept.len() {
+                0 => Expr::Const {
+                    val: DataValue::Null,
+                    span,
+                },
+                1 => kept.pop().unwrap(),
+                _ => Expr::Apply {
+                    op: &OP_COALESCE,
+                    args: kept.into(),
+                    span,
+                },
+            };
+            mem::swap(self, &mut new_self);
+            return;
+        }
+        if op.name != OP_NEGATE.name {
+            return;
+        }
+        let Expr::Apply {
+            op: inner_op,
+            args: inner_args,
+            span: inner_span,
+        } = &mut args[0]
+        else {
+            return;
+        };
+        let inner_span = *inner_span;
+        let mut take_args = || {
+            mem::replace(inner_args, Box::new([]))
+                .into_vec()
+                .into_iter()
+        };
+        let mut new_self = if inner_op.name == OP_NEGATE.name {
+            take_args().next().unwrap()
+        } else if inner_op.name == OP_AND.name || inner_op.name == OP_OR.name {
+            let op = if inner_op.name == OP_AND.name {
+                &OP_OR
+            } else {
+                &OP_AND
+            };
+            let args = take_args()
+                .map(|arg| {
+                    let mut arg = arg.negate(span);
+                    arg.simplify();
+                    arg
+                })
+                .collect();
+            Expr::Apply { op, args, span }
+        } else if inner_op.name == OP_EQ.name || inner_op.name == OP_NEQ.name {
+            let op = if inner_op.name == OP_EQ.name {
+                &OP_NEQ
+            } else {
+                &OP_EQ
+            };
+            Expr::Apply {
+                op,
+                args: take_args().collect(),
+                span: inner_span,
+            }
+        } else {
+            return;
+        };
+        mem::swap(self, &mut new_self);
+    }
     pub(crate) fn bindings(&self) -> Result<BTreeSet<Symbol>> {
         let mut ret = BTreeSet::new();
         self.collect_bindings(&mut ret)?;
@@ -473,46 +651,52 @@ impl Expr {
         }
         Ok(())
     }
+    /// Evaluate to an owned value. Arguments of ops are borrowed as in [Self::eval_ref],
+    /// so only a result that is a constant or a bound value is cloned.
     pub(crate) fn eval(&self, bindings: impl AsRef<[DataValue]>) -> Result<DataValue> {
+        Ok(self.eval_ref(bindings.as_ref())?.into_owned())
+    }
+    /// Same as [Self::eval], but constants and bound values are borrowed instead of cloned.
+    pub(crate) fn eval_ref<'a>(&'a self, bindings: &'a [DataValue]) -> Result<Cow<'a, DataValue>> {
         match self {
             Expr::Binding { var, tuple_pos, .. } => match tuple_pos {
                 None => {
                     bail!(UnboundVariableError(var.name.to_string(), var.span))
                 }
-                Some(i) => Ok(bindings
-                    .as_ref()
-                    .get(*i)
-                    .ok_or_else(|| {
-                        TupleTooShortError(
-                            var.name.to_string(),
-                            *i,
-                            bindings.as_ref().len(),
-                            var.span,
-                        )
-                    })?
-                    .clone()),
+                Some(i) => Ok(Cow::Borrowed(bindings.get(*i).ok_or_else(|| {
+                    TupleTooShortError(var.name.to_string(), *i, bindings.len(), var.span)
+                })?)),
             },
-            Expr::Const { val, .. } => Ok(val.clone()),
+            Expr::Const { val, .. } => Ok(Cow::Borrowed(val)),
+            Expr::Apply { op, args, .. } if op.name == OP_COALESCE.name => {
+                for arg in args.iter() {
+                    let val = arg.eval_ref(bindings)?;
+                    if *val != DataValue::Null {
+                        return Ok(val);
+                    }
+                }
+                Ok(Cow::Owned(DataValue::Null))
+            }
             Expr::Apply { op, args, .. } => {
-                let args: Box<[DataValue]> = args
-                    .iter()
-                    .map(|v| v.eval(bindings.as_ref()))
-                    .try_collect()?;
-                Ok((op.inner)(&args)
-                    .map_err(|err| EvalRaisedError(self.span(), err.to_string()))?)
+                let args: SmallVec<[Cow<'_, DataValue>; 4]> =
+                    args.iter().map(|v| v.eval_ref(bindings)).try_collect()?;
+                let args: SmallVec<[&DataValue; 4]> = args.iter().map(|v| v.as_ref()).collect();
+                Ok(Cow::Owned((op.inner)(&args).map_err(|err| {
+                    EvalRaisedError(self.span(), err.to_string())
+                })?))
             }
             Expr::Cond { clauses, .. } => {
                 for (cond, val) in clauses {
-                    let cond_val = cond.eval(bindings.as_ref())?;
+                    let cond_val = cond.eval_ref(bindings)?;
                     let cond_val = cond_val
-                        .get_bool()
-                        .ok_or_else(|| PredicateTypeError(cond.span(), cond_val))?;
+                        .is_truthy()
+                        .map_err(|_| PredicateTypeError(cond.span(), cond_val.into_owned()))?;
 
-                    if cond_val {
-                        return val.eval(bindings.as_ref());
+                    if cond_val == Some(true) {
+                        return val.eval_ref(bindings);
                     }
                 }
-                Ok(DataValue::Null)
+                Ok(Cow::Owned(DataValue::Null))
             }
             Expr::UnboundApply { op, span, .. } => {
                 bail!(NoImplementationError(*span, op.to_string()));
@@ -729,7 +913,7 @@ pub struct Op {
     pub(crate) name: &'static str,
     pub(crate) min_arity: usize,
     pub(crate) vararg: bool,
-    pub(crate) inner: fn(&[DataValue]) -> Result<DataValue>,
+    pub(crate) inner: fn(&[&DataValue]) -> Result<DataValue>,
 }
 
 /// Used as `Arc<dyn CustomOp>`
@@ -801,6 +985,7 @@ pub(crate) fn get_op(name: &str) -> Option<&'static Op> {
         "parse_json" => &OP_PARSE_JSON,
         "dump_json" => &OP_DUMP_JSON,
         "json_object" => &OP_JSON_OBJECT,
+        "merge" => &OP_MERGE,
         "is_json" => &OP_IS_JSON,
         "json_to_scalar" => &OP_JSON_TO_SCALAR,
         "add" => &OP_ADD,
@@ -810,12 +995,15 @@ pub(crate) fn get_op(name: &str) -> Option<&'static Op> {
         "minus" => &OP_MINUS,
         "abs" => &OP_ABS,
         "signum" => &OP_SIGNUM,
+        "sign" => &OP_SIGNUM,
         "floor" => &OP_FLOOR,
         "ceil" => &OP_CEIL,
         "round" => &OP_ROUND,
+        "format_number" => &OP_FORMAT_NUMBER,
         "mod" => &OP_MOD,
         "max" => &OP_MAX,
         "min" => &OP_MIN,
+        "clamp" => &OP_CLAMP,
         "pow" => &OP_POW,
         "sqrt" => &OP_SQRT,
         "exp" => &OP_EXP,
@@ -837,6 +1025,7 @@ pub(crate) fn get_op(name: &str) -> Option<&'static Op> {
         "acosh" => &OP_ACOSH,
         "atanh" => &OP_ATANH,
         "eq" => &OP_EQ,
+        "approx_eq" => &OP_APPROX_EQ,
         "neq" => &OP_NEQ,
         "gt" => &OP_GT,
         "ge" => &OP_GE,
@@ -844,6 +1033,7 @@ pub(crate) fn get_op(name: &str) -> Option<&'static Op> {
         "le" => &OP_LE,
         "or" => &OP_OR,
         "and" => &OP_AND,
+        "chained_cmp" => &OP_CHAINED_CMP,
         "negate" => &OP_NEGATE,
         "bit_and" => &OP_BIT_AND,
         "bit_or" => &OP_BIT_OR,
@@ -858,9 +1048,12 @@ pub(crate) fn get_op(name: &str) -> Option<&'static Op> {
         "trim" => &OP_TRIM,
         "trim_start" => &OP_TRIM_START,
         "trim_end" => &OP_TRIM_END,
+        "lpad" => &OP_LPAD,
+        "rpad" => &OP_RPAD,
         "starts_with" => &OP_STARTS_WITH,
         "ends_with" => &OP_ENDS_WITH,
         "is_null" => &OP_IS_NULL,
+        "not_null" => &OP_NOT_NULL,
         "is_int" => &OP_IS_INT,
         "is_float" => &OP_IS_FLOAT,
         "is_num" => &OP_IS_NUM,
@@ -875,7 +1068,10 @@ pub(crate) fn get_op(name: &str) -> Option<&'static Op> {
         "is_vec" => &OP_IS_VEC,
         "length" => &OP_LENGTH,
         "sorted" => &OP_SORTED,
+        "sort" => &OP_SORTED,
         "reverse" => &OP_REVERSE,
+        "flatten" => &OP_FLATTEN,
+        "flatten_all" => &OP_FLATTEN_ALL,
         "append" => &OP_APPEND,
         "prepend" => &OP_PREPEND,
         "unicode_normalize" => &OP_UNICODE_NORMALIZE,
@@ -889,7 +1085,7 @@ pub(crate) fn get_op(name: &str) -> Option<&'static Op> {
         "slice_string" => &OP_SLICE_STRING,
         "from_substrings" => &OP_FROM_SUBSTRINGS,
         "slice" => &OP_SLICE,
-        "regex_matches" => &OP_REGEX_MATCHES,
+        "regex_matches" | "regex_match" => &OP_REGEX_MATCHES,
         "regex_replace" => &OP_REGEX_REPLACE,
         "regex_replace_all" => &OP_REGEX_REPLACE_ALL,
         "regex_extract" => &OP_REGEX_EXTRACT,
@@ -926,8 +1122,10 @@ pub(crate) fn get_op(name: &str) -> Option<&'static Op> {
         "uuid_timestamp" => &OP_UUID_TIMESTAMP,
         "validity" => &OP_VALIDITY,
         "now" => &OP_NOW,
+        "today" => &OP_TODAY,
         "format_timestamp" => &OP_FORMAT_TIMESTAMP,
         "parse_timestamp" => &OP_PARSE_TIMESTAMP,
+        "date_part" => &OP_DATE_PART,
         "vec" => &OP_VEC,
         "rand_vec" => &OP_RAND_VEC,
         _ => return None,
@@ -935,6 +1133,22 @@ pub(crate) fn get_op(name: &str) -> Option<&'static Op> {
 }
 
 impl Op {
+    /// Whether calling the op with the same arguments always gives the same result.
+    /// Non-deterministic ops such as `now()` are never folded into constants.
+    pub(crate) fn is_deterministic(&self) -> bool {
+        ![
+            OP_NOW.name,
+            OP_TODAY.name,
+            OP_RAND_FLOAT.name,
+            OP_RAND_BERNOULLI.name,
+            OP_RAND_INT.name,
+            OP_RAND_CHOOSE.name,
+            OP_RAND_UUID_V1.name,
+            OP_RAND_UUID_V4.name,
+            OP_RAND_VEC.name,
+        ]
+        .contains(&self.name)
+    }
     pub(crate) fn post_process_args(&self, args: &mut [Expr]) {
         if self.name.starts_with("OP_REGEX_") {
             args[1] = Expr::Apply {
@@ -6,16 +6,17 @@
  * You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use std::cmp::Reverse;
+use std::cmp::{Ordering, Reverse};
 use std::collections::BTreeSet;
 use std::mem;
 use std::ops::{Div, Rem};
+use std::ptr;
 use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Datelike, TimeZone, Timelike, Utc};
 use itertools::Itertools;
 #[cfg(target_arch = "wasm32")]
 use js_sys::Date;
@@ -69,23 +70,23 @@ fn ensure_same_value_type(a: &DataValue, b: &DataValue) -> Result<()> {
 }
 
 define_op!(OP_LIST, 0, true);
-pub(crate) fn op_list(args: &[DataValue]) -> Result<DataValue> {
-    Ok(DataValue::List(args.to_vec()))
+pub(crate) fn op_list(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(DataValue::List(args.iter().map(|&v| v.clone()).collect()))
 }
 
 define_op!(OP_JSON, 1, false);
-pub(crate) fn op_json(args: &[DataValue]) -> Result<DataValue> {
-    Ok(DataValue::Json(JsonData(to_json(&args[0]))))
+pub(crate) fn op_json(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(DataValue::Json(JsonData(to_json(args[0]))))
 }
 
 define_op!(OP_SET_JSON_PATH, 3, false);
-pub(crate) fn op_set_json_path(args: &[DataValue]) -> Result<DataValue> {
-    let mut result = to_json(&args[0]);
+pub(crate) fn op_set_json_path(args: &[&DataValue]) -> Result<DataValue> {
+    let mut result = to_json(args[0]);
     let path = args[1]
         .get_slice()
         .ok_or_else(|| miette!("json path must be a string"))?;
     let pointer = get_json_path(&mut result, path)?;
-    let new_val = to_json(&args[2]);
+    let new_val = to_json(args[2]);
     *pointer = new_val;
     Ok(DataValue::Json(JsonData(result)))
 }
@@ -154,8 +155,8 @@ fn get_json_path<'a>(
 }
 
 define_op!(OP_REMOVE_JSON_PATH, 2, false);
-pub(crate) fn op_remove_json_path(args: &[DataValue]) -> Result<DataValue> {
-    let mut result = to_json(&args[0]);
+pub(crate) fn op_remove_json_path(args: &[&DataValue]) -> Result<DataValue> {
+    let mut result = to_json(args[0]);
     let path = args[1]
         .get_slice()
         .ok_or_else(|| miette!("json path must be a string"))?;
@@ -183,15 +184,15 @@ pub(crate) fn op_remove_json_path(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_JSON_OBJECT, 0, true);
-pub(crate) fn op_json_object(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_json_object(args: &[&DataValue]) -> Result<DataValue> {
     ensure!(
         args.len() % 2 == 0,
         "json_object requires an even number of arguments"
     );
     let mut obj = serde_json::Map::with_capacity(args.len() / 2);
     for pair in args.chunks_exact(2) {
-        let key = val2str(&pair[0]);
-        let value = to_json(&pair[1]);
+        let key = val2str(pair[0]);
+        let value = to_json(pair[1]);
         obj.insert(key.to_string(), value);
     }
     Ok(DataValue::Json(JsonData(Value::Object(obj))))
@@ -266,7 +267,7 @@ fn to_json(d: &DataValue) -> JsonValue {
 }
 
 define_op!(OP_PARSE_JSON, 1, false);
-pub(crate) fn op_parse_json(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_parse_json(args: &[&DataValue]) -> Result<DataValue> {
     match args[0].get_str() {
         Some(s) => {
             let value = serde_json::from_str(s).into_diagnostic()?;
@@ -277,16 +278,16 @@ pub(crate) fn op_parse_json(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_DUMP_JSON, 1, false);
-pub(crate) fn op_dump_json(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_dump_json(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::Json(j) => Ok(DataValue::Str(j.0.to_string().into())),
         _ => bail!("dump_json requires a json argument"),
     }
 }
 
 define_op!(OP_COALESCE, 0, true);
-pub(crate) fn op_coalesce(args: &[DataValue]) -> Result<DataValue> {
-    for val in args {
+pub(crate) fn op_coalesce(args: &[&DataValue]) -> Result<DataValue> {
+    for &val in args {
         if *val != DataValue::Null {
             return Ok(val.clone());
         }
@@ -294,36 +295,112 @@ pub(crate) fn op_coalesce(args: &[DataValue]) -> Result<DataValue> {
     Ok(DataValue::Null)
 }
 
+/// Compares an integer with a float by their exact mathematical values.
+///
+/// Converting the integer to `f64` would round integers beyond 2^53, making e.g.
+/// `9007199254740993 == 9007199254740992.0` true, so the float is split into its
+/// integral and fractional parts instead. Returns `None` if the float is NaN,
+/// in which case every comparison except `!=` is false.
+pub(crate) fn cmp_int_float(i: i64, f: f64) -> Option<Ordering> {
+    if f.is_nan() {
+        return None;
+    }
+    // 2^63 is exactly representable, and every float at or beyond it is out of range
+    const BOUND: f64 = 9223372036854775808.0;
+    if f >= BOUND {
+        return Some(Ordering::Less);
+    }
+    if f < -BOUND {
+        return Some(Ordering::Greater);
+    }
+    let integral = f.trunc();
+    Some(match i.cmp(&(integral as i64)) {
+        Ordering::Equal => 0.0.partial_cmp(&(f - integral)).unwrap(),
+        o => o,
+    })
+}
+
+/// Compares two values numerically if one is an integer and the other a float.
+fn cmp_mixed_num(a: &DataValue, b: &DataValue) -> Option<Option<Ordering>> {
+    match (a, b) {
+        (DataValue::Num(Num::Int(i)), DataValue::Num(Num::Float(f))) => Some(cmp_int_float(*i, *f)),
+        (DataValue::Num(Num::Float(f)), DataValue::Num(Num::Int(i))) => {
+            Some(cmp_int_float(*i, *f).map(Ordering::reverse))
+        }
+        _ => None,
+    }
+}
+
+/// The ordering shared by the comparison operators and by `:order`, so that sorting a
+/// column agrees with comparing its values with `<` and `>`. Integers and floats compare by
+/// their numeric values, everything else by the ordering of [DataValue]. `None` only when
+/// an integer is compared with NaN.
+pub(crate) fn compare_values(a: &DataValue, b: &DataValue) -> Option<Ordering> {
+    match cmp_mixed_num(a, b) {
+        Some(o) => o,
+        None => Some(a.cmp(b)),
+    }
+}
+
+/// Whether `a` and `b` hold the same string, bytes or list in memory, and hence are equal
+/// without comparing their contents. This is the case when a value is compared with itself,
+/// as both arguments then borrow the same bound value.
+fn same_contents(a: &DataValue, b: &DataValue) -> bool {
+    match (a, b) {
+        (DataValue::Str(a), DataValue::Str(b)) => ptr::eq(a.as_str(), b.as_str()),
+        (DataValue::Bytes(a), DataValue::Bytes(b)) => ptr::eq(a.as_slice(), b.as_slice()),
+        (DataValue::List(a), DataValue::List(b)) => ptr::eq(a.as_slice(), b.as_slice()),
+        _ => false,
+    }
+}
+
 define_op!(OP_EQ, 2, false);
-pub(crate) fn op_eq(args: &[DataValue]) -> Result<DataValue> {
-    Ok(DataValue::from(match (&args[0], &args[1]) {
-        (DataValue::Num(Num::Float(f)), DataValue::Num(Num::Int(i)))
-        | (DataValue::Num(Num::Int(i)), DataValue::Num(Num::Float(f))) => *i as f64 == *f,
-        (a, b) => a == b,
+pub(crate) fn op_eq(args: &[&DataValue]) -> Result<DataValue> {
+    if same_contents(args[0], args[1]) {
+        return Ok(DataValue::from(true));
+    }
+    Ok(DataValue::from(match cmp_mixed_num(args[0], args[1]) {
+        Some(o) => o == Some(Ordering::Equal),
+        None => args[0] == args[1],
     }))
 }
 
+define_op!(OP_APPROX_EQ, 3, false);
+pub(crate) fn op_approx_eq(args: &[&DataValue]) -> Result<DataValue> {
+    let epsilon = match args[2].get_float() {
+        Some(f) if f >= 0. => f,
+        _ => bail!("'approx_eq' requires a non-negative number as the tolerance"),
+    };
+    match (args[0], args[1]) {
+        (DataValue::Null, _) | (_, DataValue::Null) => Ok(DataValue::Null),
+        (a @ DataValue::Num(_), b @ DataValue::Num(_)) => {
+            Ok(DataValue::from(a.approx_eq(b, epsilon)))
+        }
+        _ => bail!("'approx_eq' requires numbers"),
+    }
+}
+
 define_op!(OP_IS_UUID, 1, false);
-pub(crate) fn op_is_uuid(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_is_uuid(args: &[&DataValue]) -> Result<DataValue> {
     Ok(DataValue::from(matches!(args[0], DataValue::Uuid(_))))
 }
 
 define_op!(OP_IS_JSON, 1, false);
-pub(crate) fn op_is_json(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_is_json(args: &[&DataValue]) -> Result<DataValue> {
     Ok(DataValue::from(matches!(args[0], DataValue::Json(_))))
 }
 
 define_op!(OP_JSON_TO_SCALAR, 1, false);
-pub(crate) fn op_json_to_scalar(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+pub(crate) fn op_json_to_scalar(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         DataValue::Json(JsonData(j)) => json2val(j.clone()),
         d => d.clone(),
     })
 }
 
 define_op!(OP_IS_IN, 2, false);
-pub(crate) fn op_is_in(args: &[DataValue]) -> Result<DataValue> {
-    let left = &args[0];
+pub(crate) fn op_is_in(args: &[&DataValue]) -> Result<DataValue> {
+    let left = args[0];
     let right = args[1]
         .get_slice()
         .ok_or_else(|| miette!("right hand side of 'is_in' must be a list"))?;
@@ -331,56 +408,79 @@ pub(crate) fn op_is_in(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_NEQ, 2, false);
-pub(crate) fn op_neq(args: &[DataValue]) -> Result<DataValue> {
-    Ok(DataValue::from(match (&args[0], &args[1]) {
-        (DataValue::Num(Num::Float(f)), DataValue::Num(Num::Int(i)))
-        | (DataValue::Num(Num::Int(i)), DataValue::Num(Num::Float(f))) => *i as f64 != *f,
-        (a, b) => a != b,
+pub(crate) fn op_neq(args: &[&DataValue]) -> Result<DataValue> {
+    if same_contents(args[0], args[1]) {
+        return Ok(DataValue::from(false));
+    }
+    Ok(DataValue::from(match cmp_mixed_num(args[0], args[1]) {
+        Some(o) => o != Some(Ordering::Equal),
+        None => args[0] != args[1],
     }))
 }
 
 define_op!(OP_GT, 2, false);
-pub(crate) fn op_gt(args: &[DataValue]) -> Result<DataValue> {
-    ensure_same_value_type(&args[0], &args[1])?;
-    Ok(DataValue::from(match (&args[0], &args[1]) {
-        (DataValue::Num(Num::Float(l)), DataValue::Num(Num::Int(r))) => *l > *r as f64,
-        (DataValue::Num(Num::Int(l)), DataValue::Num(Num::Float(r))) => *l as f64 > *r,
-        (a, b) => a > b,
-    }))
+pub(crate) fn op_gt(args: &[&DataValue]) -> Result<DataValue> {
+    ensure_same_value_type(args[0], args[1])?;
+    Ok(DataValue::from(matches!(
+        compare_values(args[0], args[1]),
+        Some(Ordering::Greater)
+    )))
 }
 
 define_op!(OP_GE, 2, false);
-pub(crate) fn op_ge(args: &[DataValue]) -> Result<DataValue> {
-    ensure_same_value_type(&args[0], &args[1])?;
-    Ok(DataValue::from(match (&args[0], &args[1]) {
-        (DataValue::Num(Num::Float(l)), DataValue::Num(Num::Int(r))) => *l >= *r as f64,
-        (DataValue::Num(Num::Int(l)), DataValue::Num(Num::Float(r))) => *l as f64 >= *r,
-        (a, b) => a >= b,
-    }))
+pub(crate) fn op_ge(args: &[&DataValue]) -> Result<DataValue> {
+    ensure_same_value_type(args[0], args[1])?;
+    Ok(DataValue::from(matches!(
+        compare_values(args[0], args[1]),
+        Some(Ordering::Greater | Ordering::Equal)
+    )))
 }
 
 define_op!(OP_LT, 2, false);
-pub(crate) fn op_lt(args: &[DataValue]) -> Result<DataValue> {
-    ensure_same_value_type(&args[0], &args[1])?;
-    Ok(DataValue::from(match (&args[0], &args[1]) {
-        (DataValue::Num(Num::Float(l)), DataValue::Num(Num::Int(r))) => *l < (*r as f64),
-        (DataValue::Num(Num::Int(l)), DataValue::Num(Num::Float(r))) => (*l as f64) < *r,
-        (a, b) => a < b,
-    }))
+pub(crate) fn op_lt(args: &[&DataValue]) -> Result<DataValue> {
+    ensure_same_value_type(args[0], args[1])?;
+    Ok(DataValue::from(matches!(
+        compare_values(args[0], args[1]),
+        Some(Ordering::Less)
+    )))
+}
+
+define_op!(OP_CHAINED_CMP, 3, true);
+/// Chained comparisons such as `a < b <= c`: the arguments alternate between operands and
+/// the names of the comparisons, so that every operand is evaluated only once.
+pub(crate) fn op_chained_cmp(args: &[&DataValue]) -> Result<DataValue> {
+    ensure!(
+        args.len() % 2 == 1,
+        "'chained_cmp' requires operands alternating with comparisons"
+    );
+    let mut ret = true;
+    for i in (1..args.len()).step_by(2) {
+        let cmp = match args[i].get_str() {
+            Some("lt") => op_lt,
+            Some("le") => op_le,
+            Some("gt") => op_gt,
+            Some("ge") => op_ge,
+            _ => bail!("'chained_cmp' requires the comparisons 'lt', 'le', 'gt' or 'ge'"),
+        };
+        // all comparisons are evaluated, so that type errors surface as they would for `and`
+        if cmp(&[args[i - 1], args[i + 1]])? == DataValue::from(false) {
+            ret = false;
+        }
+    }
+    Ok(DataValue::from(ret))
 }
 
 define_op!(OP_LE, 2, false);
-pub(crate) fn op_le(args: &[DataValue]) -> Result<DataValue> {
-    ensure_same_value_type(&args[0], &args[1])?;
-    Ok(DataValue::from(match (&args[0], &args[1]) {
-        (DataValue::Num(Num::Float(l)), DataValue::Num(Num::Int(r))) => *l <= (*r as f64),
-        (DataValue::Num(Num::Int(l)), DataValue::Num(Num::Float(r))) => (*l as f64) <= *r,
-        (a, b) => a <= b,
-    }))
+pub(crate) fn op_le(args: &[&DataValue]) -> Result<DataValue> {
+    ensure_same_value_type(args[0], args[1])?;
+    Ok(DataValue::from(matches!(
+        compare_values(args[0], args[1]),
+        Some(Ordering::Less | Ordering::Equal)
+    )))
 }
 
 define_op!(OP_ADD, 0, true);
-pub(crate) fn op_add(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_add(args: &[&DataValue]) -> Result<DataValue> {
     let mut i_accum = 0i64;
     let mut f_accum = 0.0f64;
     for arg in args {
@@ -398,7 +498,7 @@ pub(crate) fn op_add(args: &[DataValue]) -> Result<DataValue> {
     }
 }
 
-fn add_vecs(args: &[DataValue]) -> Result<DataValue> {
+fn add_vecs(args: &[&DataValue]) -> Result<DataValue> {
     if args.len() == 1 {
         return Ok(args[0].clone());
     }
@@ -451,9 +551,10 @@ fn add_vecs(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_MAX, 1, true);
-pub(crate) fn op_max(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_max(args: &[&DataValue]) -> Result<DataValue> {
     let res = args
         .iter()
+        .copied()
         .try_fold(None, |accum, nxt| match (accum, nxt) {
             (None, d @ DataValue::Num(_)) => Ok(Some(d.clone())),
             (Some(DataValue::Num(a)), DataValue::Num(b)) => Ok(Some(DataValue::Num(a.max(*b)))),
@@ -466,9 +567,10 @@ pub(crate) fn op_max(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_MIN, 1, true);
-pub(crate) fn op_min(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_min(args: &[&DataValue]) -> Result<DataValue> {
     let res = args
         .iter()
+        .copied()
         .try_fold(None, |accum, nxt| match (accum, nxt) {
             (None, d @ DataValue::Num(_)) => Ok(Some(d.clone())),
             (Some(DataValue::Num(a)), DataValue::Num(b)) => Ok(Some(DataValue::Num(a.min(*b)))),
@@ -480,9 +582,35 @@ pub(crate) fn op_min(args: &[DataValue]) -> Result<DataValue> {
     }
 }
 
+define_op!(OP_CLAMP, 3, false);
+pub(crate) fn op_clamp(args: &[&DataValue]) -> Result<DataValue> {
+    match (args[0], args[1], args[2]) {
+        (x @ DataValue::Num(_), lo @ DataValue::Num(_), hi @ DataValue::Num(_)) => {
+            ensure!(
+                compare_values(lo, hi).is_some_and(|o| o != Ordering::Greater),
+                "'clamp' requires the lower bound {} to be at most the upper bound {}",
+                lo,
+                hi
+            );
+            // compared by their numeric values, so that `x` is kept if it equals a bound
+            Ok(if compare_values(x, lo) == Some(Ordering::Less) {
+                lo.clone()
+            } else if compare_values(x, hi) == Some(Ordering::Greater) {
+                hi.clone()
+            } else {
+                x.clone()
+            })
+        }
+        (DataValue::Null, _, _) | (_, DataValue::Null, _) | (_, _, DataValue::Null) => {
+            Ok(DataValue::Null)
+        }
+        _ => bail!("'clamp' requires numbers"),
+    }
+}
+
 define_op!(OP_SUB, 2, false);
-pub(crate) fn op_sub(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match (&args[0], &args[1]) {
+pub(crate) fn op_sub(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match (args[0], args[1]) {
         (DataValue::Num(Num::Int(a)), DataValue::Num(Num::Int(b))) => {
             DataValue::Num(Num::Int(*a - *b))
         }
@@ -542,7 +670,7 @@ pub(crate) fn op_sub(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_MUL, 0, true);
-pub(crate) fn op_mul(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_mul(args: &[&DataValue]) -> Result<DataValue> {
     let mut i_accum = 1i64;
     let mut f_accum = 1.0f64;
     for arg in args {
@@ -560,7 +688,7 @@ pub(crate) fn op_mul(args: &[DataValue]) -> Result<DataValue> {
     }
 }
 
-fn mul_vecs(args: &[DataValue]) -> Result<DataValue> {
+fn mul_vecs(args: &[&DataValue]) -> Result<DataValue> {
     if args.len() == 1 {
         return Ok(args[0].clone());
     }
@@ -613,8 +741,8 @@ fn mul_vecs(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_DIV, 2, false);
-pub(crate) fn op_div(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match (&args[0], &args[1]) {
+pub(crate) fn op_div(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match (args[0], args[1]) {
         (DataValue::Num(Num::Int(a)), DataValue::Num(Num::Int(b))) => {
             DataValue::Num(Num::Float((*a as f64) / (*b as f64)))
         }
@@ -668,8 +796,10 @@ pub(crate) fn op_div(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_MINUS, 1, false);
-pub(crate) fn op_minus(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+/// Arithmetic negation, the prefix `-`. Only numbers and vectors are accepted,
+/// and integers stay integers.
+pub(crate) fn op_minus(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         DataValue::Num(Num::Int(i)) => DataValue::Num(Num::Int(-(*i))),
         DataValue::Num(Num::Float(f)) => DataValue::Num(Num::Float(-(*f))),
         DataValue::Vec(Vector::F64(v)) => DataValue::Vec(Vector::F64(0. - v)),
@@ -679,8 +809,8 @@ pub(crate) fn op_minus(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ABS, 1, false);
-pub(crate) fn op_abs(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+pub(crate) fn op_abs(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         DataValue::Num(Num::Int(i)) => DataValue::Num(Num::Int(i.abs())),
         DataValue::Num(Num::Float(f)) => DataValue::Num(Num::Float(f.abs())),
         DataValue::Vec(Vector::F64(v)) => DataValue::Vec(Vector::F64(v.mapv(|x| x.abs()))),
@@ -690,8 +820,8 @@ pub(crate) fn op_abs(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_SIGNUM, 1, false);
-pub(crate) fn op_signum(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+pub(crate) fn op_signum(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         DataValue::Num(Num::Int(i)) => DataValue::Num(Num::Int(i.signum())),
         DataValue::Num(Num::Float(f)) => {
             if f.signum() < 0. {
@@ -704,13 +834,14 @@ pub(crate) fn op_signum(args: &[DataValue]) -> Result<DataValue> {
                 DataValue::from(f64::NAN)
             }
         }
+        DataValue::Null => DataValue::Null,
         _ => bail!("'signum' requires numbers"),
     })
 }
 
 define_op!(OP_FLOOR, 1, false);
-pub(crate) fn op_floor(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+pub(crate) fn op_floor(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         DataValue::Num(Num::Int(i)) => DataValue::Num(Num::Int(*i)),
         DataValue::Num(Num::Float(f)) => DataValue::Num(Num::Float(f.floor())),
         _ => bail!("'floor' requires numbers"),
@@ -718,8 +849,8 @@ pub(crate) fn op_floor(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_CEIL, 1, false);
-pub(crate) fn op_ceil(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+pub(crate) fn op_ceil(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         DataValue::Num(Num::Int(i)) => DataValue::Num(Num::Int(*i)),
         DataValue::Num(Num::Float(f)) => DataValue::Num(Num::Float(f.ceil())),
         _ => bail!("'ceil' requires numbers"),
@@ -727,17 +858,89 @@ pub(crate) fn op_ceil(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ROUND, 1, false);
-pub(crate) fn op_round(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+pub(crate) fn op_round(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         DataValue::Num(Num::Int(i)) => DataValue::Num(Num::Int(*i)),
         DataValue::Num(Num::Float(f)) => DataValue::Num(Num::Float(f.round())),
         _ => bail!("'round' requires numbers"),
     })
 }
 
+define_op!(OP_FORMAT_NUMBER, 2, false);
+/// Format a number as a string with a fixed number of decimals, rounding half away
+/// from zero as `round` does. Integers are formatted exactly.
+pub(crate) fn op_format_number(args: &[&DataValue]) -> Result<DataValue> {
+    let n = match args[0] {
+        DataValue::Null => return Ok(DataValue::Null),
+        DataValue::Num(n) => *n,
+        _ => bail!("'format_number' requires a number"),
+    };
+    let decimals = match args[1].get_int() {
+        Some(d) if (0..=100).contains(&d) => d as usize,
+        _ => bail!("'format_number' requires an integer between 0 and 100 for the decimals"),
+    };
+    Ok(DataValue::from(match n {
+        Num::Int(i) if decimals == 0 => i.to_string(),
+        Num::Int(i) => format!("{i}.{}", "0".repeat(decimals)),
+        Num::Float(f) => format_float_fixed(f, decimals),
+    }))
+}
+
+/// Format `f` with `decimals` decimals. The standard formatting rounds exact ties to even,
+/// so these are rounded away from zero here.
+fn format_float_fixed(f: f64, decimals: usize) -> String {
+    if !is_decimal_tie(f, decimals) {
+        return format!("{f:.decimals$}");
+    }
+    // a tie has exactly one more decimal, a five, which is dropped to round up the rest
+    let extended = format!("{f:.*}", decimals + 1);
+    let mut digits = extended.as_bytes()[..extended.len() - 1].to_vec();
+    let mut i = digits.len();
+    loop {
+        if i == 0 || digits[i - 1] == b'-' {
+            digits.insert(i, b'1');
+            break;
+        }
+        i -= 1;
+        match digits[i] {
+            b'.' => {}
+            b'9' => digits[i] = b'0',
+            d => {
+                digits[i] = d + 1;
+                break;
+            }
+        }
+    }
+    if decimals == 0 {
+        digits.pop();
+    }
+    String::from_utf8(digits).unwrap()
+}
+
+/// Whether `f` lies exactly halfway between two numbers with `decimals` decimals,
+/// i.e. whether `f * 2 * 10^decimals` is an odd integer. Writing `f` as `m * 2^e` with
+/// `m` odd, that is `m * 5^decimals * 2^(e + decimals + 1)`, so this holds exactly when
+/// `e + decimals + 1` is zero.
+fn is_decimal_tie(f: f64, decimals: usize) -> bool {
+    if !f.is_finite() || f == 0. {
+        return false;
+    }
+    let bits = f.to_bits();
+    let biased_exp = ((bits >> 52) & 0x7ff) as i64;
+    let mut mantissa = bits & ((1 << 52) - 1);
+    let mut exp = if biased_exp == 0 {
+        -1074
+    } else {
+        mantissa |= 1 << 52;
+        biased_exp - 1075
+    };
+    exp += mantissa.trailing_zeros() as i64;
+    exp == -(decimals as i64 + 1)
+}
+
 define_op!(OP_EXP, 1, false);
-pub(crate) fn op_exp(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_exp(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -752,8 +955,8 @@ pub(crate) fn op_exp(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_EXP2, 1, false);
-pub(crate) fn op_exp2(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_exp2(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -768,8 +971,8 @@ pub(crate) fn op_exp2(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_LN, 1, false);
-pub(crate) fn op_ln(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_ln(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -784,8 +987,8 @@ pub(crate) fn op_ln(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_LOG2, 1, false);
-pub(crate) fn op_log2(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_log2(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -800,8 +1003,8 @@ pub(crate) fn op_log2(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_LOG10, 1, false);
-pub(crate) fn op_log10(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_log10(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -816,8 +1019,8 @@ pub(crate) fn op_log10(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_SIN, 1, false);
-pub(crate) fn op_sin(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_sin(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -832,8 +1035,8 @@ pub(crate) fn op_sin(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_COS, 1, false);
-pub(crate) fn op_cos(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_cos(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -848,8 +1051,8 @@ pub(crate) fn op_cos(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_TAN, 1, false);
-pub(crate) fn op_tan(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_tan(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -864,8 +1067,8 @@ pub(crate) fn op_tan(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ASIN, 1, false);
-pub(crate) fn op_asin(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_asin(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -880,8 +1083,8 @@ pub(crate) fn op_asin(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ACOS, 1, false);
-pub(crate) fn op_acos(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_acos(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -896,8 +1099,8 @@ pub(crate) fn op_acos(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ATAN, 1, false);
-pub(crate) fn op_atan(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_atan(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -912,13 +1115,13 @@ pub(crate) fn op_atan(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ATAN2, 2, false);
-pub(crate) fn op_atan2(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_atan2(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         _ => bail!("'atan2' requires numbers"),
     };
-    let b = match &args[1] {
+    let b = match args[1] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         _ => bail!("'atan2' requires numbers"),
@@ -928,8 +1131,8 @@ pub(crate) fn op_atan2(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_SINH, 1, false);
-pub(crate) fn op_sinh(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_sinh(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -944,8 +1147,8 @@ pub(crate) fn op_sinh(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_COSH, 1, false);
-pub(crate) fn op_cosh(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_cosh(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -960,8 +1163,8 @@ pub(crate) fn op_cosh(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_TANH, 1, false);
-pub(crate) fn op_tanh(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_tanh(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -976,8 +1179,8 @@ pub(crate) fn op_tanh(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ASINH, 1, false);
-pub(crate) fn op_asinh(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_asinh(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -992,8 +1195,8 @@ pub(crate) fn op_asinh(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ACOSH, 1, false);
-pub(crate) fn op_acosh(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_acosh(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -1008,8 +1211,8 @@ pub(crate) fn op_acosh(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ATANH, 1, false);
-pub(crate) fn op_atanh(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_atanh(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -1024,8 +1227,8 @@ pub(crate) fn op_atanh(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_SQRT, 1, false);
-pub(crate) fn op_sqrt(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_sqrt(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -1040,8 +1243,8 @@ pub(crate) fn op_sqrt(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_POW, 2, false);
-pub(crate) fn op_pow(args: &[DataValue]) -> Result<DataValue> {
-    let a = match &args[0] {
+pub(crate) fn op_pow(args: &[&DataValue]) -> Result<DataValue> {
+    let a = match args[0] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         DataValue::Vec(Vector::F32(v)) => {
@@ -1058,7 +1261,7 @@ pub(crate) fn op_pow(args: &[DataValue]) -> Result<DataValue> {
         }
         _ => bail!("'pow' requires numbers"),
     };
-    let b = match &args[1] {
+    let b = match args[1] {
         DataValue::Num(Num::Int(i)) => *i as f64,
         DataValue::Num(Num::Float(f)) => *f,
         _ => bail!("'pow' requires numbers"),
@@ -1067,8 +1270,8 @@ pub(crate) fn op_pow(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_MOD, 2, false);
-pub(crate) fn op_mod(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match (&args[0], &args[1]) {
+pub(crate) fn op_mod(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match (args[0], args[1]) {
         (DataValue::Num(Num::Int(a)), DataValue::Num(Num::Int(b))) => {
             if *b == 0 {
                 bail!("'mod' requires non-zero divisor")
@@ -1089,43 +1292,55 @@ pub(crate) fn op_mod(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_AND, 0, true);
-pub(crate) fn op_and(args: &[DataValue]) -> Result<DataValue> {
+/// Logical conjunction, `&&`. Any `false` gives `false`, otherwise any null gives null.
+pub(crate) fn op_and(args: &[&DataValue]) -> Result<DataValue> {
+    let mut ret = Some(true);
     for arg in args {
-        if !arg
-            .get_bool()
-            .ok_or_else(|| miette!("'and' requires booleans"))?
+        match arg
+            .is_truthy()
+            .map_err(|_| miette!("'and' requires booleans"))?
         {
-            return Ok(DataValue::from(false));
+            Some(false) => ret = Some(false),
+            None if ret.is_some_and(|b| b) => ret = None,
+            _ => {}
         }
     }
-    Ok(DataValue::from(true))
+    Ok(ret.map_or(DataValue::Null, DataValue::from))
 }
 
 define_op!(OP_OR, 0, true);
-pub(crate) fn op_or(args: &[DataValue]) -> Result<DataValue> {
+/// Logical disjunction, `||`. Any `true` gives `true`, otherwise any null gives null.
+pub(crate) fn op_or(args: &[&DataValue]) -> Result<DataValue> {
+    let mut ret = Some(false);
     for arg in args {
-        if arg
-            .get_bool()
-            .ok_or_else(|| miette!("'or' requires booleans"))?
+        match arg
+            .is_truthy()
+            .map_err(|_| miette!("'or' requires booleans"))?
         {
-            return Ok(DataValue::from(true));
+            Some(true) => ret = Some(true),
+            None if ret.is_some_and(|b| !b) => ret = None,
+            _ => {}
         }
     }
-    Ok(DataValue::from(false))
+    Ok(ret.map_or(DataValue::Null, DataValue::from))
 }
 
 define_op!(OP_NEGATE, 1, false);
-pub(crate) fn op_negate(args: &[DataValue]) -> Result<DataValue> {
-    if let DataValue::Bool(b) = &args[0] {
-        Ok(DataValue::from(!*b))
-    } else {
-        bail!("'negate' requires booleans");
+/// Logical negation, the prefix `!`. Only booleans and null are accepted, there is no
+/// truthiness of other values.
+pub(crate) fn op_negate(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0]
+        .is_truthy()
+        .map_err(|_| miette!("'negate' requires booleans"))?
+    {
+        Some(b) => Ok(DataValue::from(!b)),
+        None => Ok(DataValue::Null),
     }
 }
 
 define_op!(OP_BIT_AND, 2, false);
-pub(crate) fn op_bit_and(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1]) {
+pub(crate) fn op_bit_and(args: &[&DataValue]) -> Result<DataValue> {
+    match (args[0], args[1]) {
         (DataValue::Bytes(left), DataValue::Bytes(right)) => {
             ensure!(
                 left.len() == right.len(),
@@ -1142,8 +1357,8 @@ pub(crate) fn op_bit_and(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_BIT_OR, 2, false);
-pub(crate) fn op_bit_or(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1]) {
+pub(crate) fn op_bit_or(args: &[&DataValue]) -> Result<DataValue> {
+    match (args[0], args[1]) {
         (DataValue::Bytes(left), DataValue::Bytes(right)) => {
             ensure!(
                 left.len() == right.len(),
@@ -1160,8 +1375,8 @@ pub(crate) fn op_bit_or(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_BIT_NOT, 1, false);
-pub(crate) fn op_bit_not(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_bit_not(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::Bytes(arg) => {
             let mut ret = arg.clone();
             for l in ret.iter_mut() {
@@ -1174,8 +1389,8 @@ pub(crate) fn op_bit_not(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_BIT_XOR, 2, false);
-pub(crate) fn op_bit_xor(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1]) {
+pub(crate) fn op_bit_xor(args: &[&DataValue]) -> Result<DataValue> {
+    match (args[0], args[1]) {
         (DataValue::Bytes(left), DataValue::Bytes(right)) => {
             ensure!(
                 left.len() == right.len(),
@@ -1192,8 +1407,8 @@ pub(crate) fn op_bit_xor(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_UNPACK_BITS, 1, false);
-pub(crate) fn op_unpack_bits(args: &[DataValue]) -> Result<DataValue> {
-    if let DataValue::Bytes(bs) = &args[0] {
+pub(crate) fn op_unpack_bits(args: &[&DataValue]) -> Result<DataValue> {
+    if let DataValue::Bytes(bs) = args[0] {
         let mut ret = vec![false; bs.len() * 8];
         for (chunk, byte) in bs.iter().enumerate() {
             ret[chunk * 8] = (*byte & 0b10000000) != 0;
@@ -1214,8 +1429,8 @@ pub(crate) fn op_unpack_bits(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_PACK_BITS, 1, false);
-pub(crate) fn op_pack_bits(args: &[DataValue]) -> Result<DataValue> {
-    if let DataValue::List(v) = &args[0] {
+pub(crate) fn op_pack_bits(args: &[&DataValue]) -> Result<DataValue> {
+    if let DataValue::List(v) = args[0] {
         let l = (v.len() as f64 / 8.).ceil() as usize;
         let mut res = vec![0u8; l];
         for (i, b) in v.iter().enumerate() {
@@ -1242,17 +1457,17 @@ pub(crate) fn op_pack_bits(args: &[DataValue]) -> Result<DataValue> {
             }
         }
         Ok(DataValue::Bytes(res))
-    } else if let DataValue::Set(v) = &args[0] {
+    } else if let DataValue::Set(v) = args[0] {
         let l = v.iter().cloned().collect_vec();
-        op_pack_bits(&[DataValue::List(l)])
+        op_pack_bits(&[&DataValue::List(l)])
     } else {
         bail!("'pack_bits' requires list of booleans")
     }
 }
 
 define_op!(OP_CONCAT, 1, true);
-pub(crate) fn op_concat(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_concat(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::Str(_) => {
             let mut ret: String = Default::default();
             for arg in args {
@@ -1309,57 +1524,157 @@ fn deep_merge_json(value1: JsonValue, value2: JsonValue) -> JsonValue {
     }
 }
 
+define_op!(OP_MERGE, 2, true);
+pub(crate) fn op_merge(args: &[&DataValue]) -> Result<DataValue> {
+    ensure!(
+        args.len() <= 3,
+        "'merge' takes a base object, an overlay object and an optional deep flag"
+    );
+    let deep = match args.get(2) {
+        None => false,
+        Some(DataValue::Bool(b)) => *b,
+        Some(_) => bail!("the deep flag of 'merge' must be a boolean"),
+    };
+    match (args[0], args[1]) {
+        (DataValue::Json(JsonData(base)), DataValue::Json(JsonData(overlay)))
+            if base.is_object() && overlay.is_object() =>
+        {
+            Ok(DataValue::Json(JsonData(merge_json(
+                base.clone(),
+                overlay.clone(),
+                deep,
+            ))))
+        }
+        _ => bail!("'merge' requires JSON objects"),
+    }
+}
+
+/// Overlay the fields of `overlay` on `base`, the last value wins.
+/// If `deep` is set, the two are merged as by [deep_merge_json] instead.
+fn merge_json(base: JsonValue, overlay: JsonValue, deep: bool) -> JsonValue {
+    if deep {
+        return deep_merge_json(base, overlay);
+    }
+    match (base, overlay) {
+        (JsonValue::Object(mut base), JsonValue::Object(overlay)) => {
+            base.extend(overlay);
+            JsonValue::Object(base)
+        }
+        (_, overlay) => overlay,
+    }
+}
+
 define_op!(OP_STR_INCLUDES, 2, false);
-pub(crate) fn op_str_includes(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1]) {
+pub(crate) fn op_str_includes(args: &[&DataValue]) -> Result<DataValue> {
+    match (args[0], args[1]) {
         (DataValue::Str(l), DataValue::Str(r)) => Ok(DataValue::from(l.find(r as &str).is_some())),
         _ => bail!("'str_includes' requires strings"),
     }
 }
 
 define_op!(OP_LOWERCASE, 1, false);
-pub(crate) fn op_lowercase(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_lowercase(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::Str(s) => Ok(DataValue::from(s.to_lowercase())),
         _ => bail!("'lowercase' requires strings"),
     }
 }
 
 define_op!(OP_UPPERCASE, 1, false);
-pub(crate) fn op_uppercase(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_uppercase(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::Str(s) => Ok(DataValue::from(s.to_uppercase())),
         _ => bail!("'uppercase' requires strings"),
     }
 }
 
 define_op!(OP_TRIM, 1, false);
-pub(crate) fn op_trim(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_trim(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::Str(s) => Ok(DataValue::from(s.trim())),
         _ => bail!("'trim' requires strings"),
     }
 }
 
 define_op!(OP_TRIM_START, 1, false);
-pub(crate) fn op_trim_start(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_trim_start(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::Str(s) => Ok(DataValue::from(s.trim_start())),
         v => bail!("'trim_start' requires strings, got {}", v),
     }
 }
 
 define_op!(OP_TRIM_END, 1, false);
-pub(crate) fn op_trim_end(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_trim_end(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::Str(s) => Ok(DataValue::from(s.trim_end())),
         _ => bail!("'trim_end' requires strings"),
     }
 }
 
+define_op!(OP_LPAD, 2, true);
+pub(crate) fn op_lpad(args: &[&DataValue]) -> Result<DataValue> {
+    pad_str("lpad", args, true)
+}
+
+define_op!(OP_RPAD, 2, true);
+pub(crate) fn op_rpad(args: &[&DataValue]) -> Result<DataValue> {
+    pad_str("rpad", args, false)
+}
+
+/// The largest width `lpad` and `rpad` pad to, so that a query cannot allocate
+/// arbitrarily large strings.
+pub(crate) const MAX_PAD_WIDTH: usize = 1 << 20;
+
+/// Pad a string with repeats of the fill string, a space by default, until it is
+/// `width` characters long. Strings already that long are returned unchanged.
+/// Widths above [MAX_PAD_WIDTH] are an error.
+fn pad_str(name: &str, args: &[&DataValue], left: bool) -> Result<DataValue> {
+    ensure!(
+        args.len() <= 3,
+        "'{}' takes a string, a width and an optional fill string",
+        name
+    );
+    let s = match args[0] {
+        DataValue::Str(s) => s,
+        DataValue::Null => return Ok(DataValue::Null),
+        v => bail!("'{}' requires strings, got {}", name, v),
+    };
+    let width = match args[1] {
+        DataValue::Null => return Ok(DataValue::Null),
+        v => match v.get_int() {
+            Some(i) if i >= 0 => i as usize,
+            _ => bail!("the width of '{}' must be a non-negative integer", name),
+        },
+    };
+    ensure!(
+        width <= MAX_PAD_WIDTH,
+        "the width of '{}' must be at most {}, got {}",
+        name,
+        MAX_PAD_WIDTH,
+        width
+    );
+    let fill: &str = match args.get(2) {
+        None => " ",
+        Some(DataValue::Str(fill)) if !fill.is_empty() => fill,
+        Some(DataValue::Null) => return Ok(DataValue::Null),
+        Some(v) => bail!("the fill of '{}' must be a non-empty string: {}", name, v),
+    };
+    let len = s.chars().count();
+    if len >= width {
+        return Ok(DataValue::Str(s.clone()));
+    }
+    let padding: String = fill.chars().cycle().take(width - len).collect();
+    Ok(DataValue::from(if left {
+        format!("{padding}{s}")
+    } else {
+        format!("{s}{padding}")
+    }))
+}
+
 define_op!(OP_STARTS_WITH, 2, false);
-pub(crate) fn op_starts_with(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1]) {
+pub(crate) fn op_starts_with(args: &[&DataValue]) -> Result<DataValue> {
+    match (args[0], args[1]) {
         (DataValue::Str(l), DataValue::Str(r)) => Ok(DataValue::from(l.starts_with(r as &str))),
         (DataValue::Bytes(l), DataValue::Bytes(r)) => {
             Ok(DataValue::from(l.starts_with(r as &[u8])))
@@ -1369,8 +1684,8 @@ pub(crate) fn op_starts_with(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ENDS_WITH, 2, false);
-pub(crate) fn op_ends_with(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1]) {
+pub(crate) fn op_ends_with(args: &[&DataValue]) -> Result<DataValue> {
+    match (args[0], args[1]) {
         (DataValue::Str(l), DataValue::Str(r)) => Ok(DataValue::from(l.ends_with(r as &str))),
         (DataValue::Bytes(l), DataValue::Bytes(r)) => Ok(DataValue::from(l.ends_with(r as &[u8]))),
         _ => bail!("'ends_with' requires strings or bytes"),
@@ -1378,86 +1693,144 @@ pub(crate) fn op_ends_with(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_REGEX, 1, false);
-pub(crate) fn op_regex(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+pub(crate) fn op_regex(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         r @ DataValue::Regex(_) => r.clone(),
         DataValue::Str(s) => {
             DataValue::Regex(RegexWrapper(regex::Regex::new(s).map_err(|err| {
                 miette!("The string cannot be interpreted as regex: {}", err)
             })?))
         }
+        DataValue::Null => DataValue::Null,
         _ => bail!("'regex' requires strings"),
     })
 }
 
 define_op!(OP_REGEX_MATCHES, 2, false);
-pub(crate) fn op_regex_matches(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1]) {
+pub(crate) fn op_regex_matches(args: &[&DataValue]) -> Result<DataValue> {
+    match (args[0], args[1]) {
         (DataValue::Str(s), DataValue::Regex(r)) => Ok(DataValue::from(r.0.is_match(s))),
+        (DataValue::Null, _) | (_, DataValue::Null) => Ok(DataValue::Null),
         _ => bail!("'regex_matches' requires strings"),
     }
 }
 
 define_op!(OP_REGEX_REPLACE, 3, false);
-pub(crate) fn op_regex_replace(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1], &args[2]) {
+pub(crate) fn op_regex_replace(args: &[&DataValue]) -> Result<DataValue> {
+    match (args[0], args[1], args[2]) {
         (DataValue::Str(s), DataValue::Regex(r), DataValue::Str(rp)) => {
             Ok(DataValue::Str(r.0.replace(s, rp as &str).into()))
         }
+        (DataValue::Null, _, _) | (_, DataValue::Null, _) => Ok(DataValue::Null),
         _ => bail!("'regex_replace' requires strings"),
     }
 }
 
 define_op!(OP_REGEX_REPLACE_ALL, 3, false);
-pub(crate) fn op_regex_replace_all(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1], &args[2]) {
+pub(crate) fn op_regex_replace_all(args: &[&DataValue]) -> Result<DataValue> {
+    match (args[0], args[1], args[2]) {
         (DataValue::Str(s), DataValue::Regex(r), DataValue::Str(rp)) => {
             Ok(DataValue::Str(r.0.replace_all(s, rp as &str).into()))
         }
+        (DataValue::Null, _, _) | (_, DataValue::Null, _) => Ok(DataValue::Null),
         _ => bail!("'regex_replace' requires strings"),
     }
 }
 
-define_op!(OP_REGEX_EXTRACT, 2, false);
-pub(crate) fn op_regex_extract(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1]) {
+/// Group of a regex match to extract, given as the optional third argument of the
+/// regex extracting functions. The whole match is extracted if it is not given,
+/// without going through the slower capture search.
+enum RegexGroup {
+    Index(usize),
+    Name(String),
+}
+
+impl RegexGroup {
+    fn from_args(name: &str, args: &[&DataValue]) -> Result<Option<Self>> {
+        ensure!(
+            args.len() <= 3,
+            "'{}' takes a string, a regex and an optional group",
+            name
+        );
+        Ok(match args.get(2) {
+            None => None,
+            Some(DataValue::Str(s)) => Some(RegexGroup::Name(s.to_string())),
+            Some(d) => match d.get_int() {
+                Some(i) if i >= 0 => Some(RegexGroup::Index(i as usize)),
+                _ => bail!(
+                    "the group of '{}' must be a non-negative integer or a name",
+                    name
+                ),
+            },
+        })
+    }
+    fn extract(&self, caps: regex::Captures<'_>) -> DataValue {
+        let found = match self {
+            RegexGroup::Index(i) => caps.get(*i),
+            RegexGroup::Name(n) => caps.name(n),
+        };
+        found
+            .map(|m| DataValue::from(m.as_str()))
+            .unwrap_or(DataValue::Null)
+    }
+}
+
+define_op!(OP_REGEX_EXTRACT, 2, true);
+pub(crate) fn op_regex_extract(args: &[&DataValue]) -> Result<DataValue> {
+    let group = RegexGroup::from_args("regex_extract", args)?;
+    match (args[0], args[1]) {
         (DataValue::Str(s), DataValue::Regex(r)) => {
-            let found =
-                r.0.find_iter(s)
-                    .map(|v| DataValue::from(v.as_str()))
-                    .collect_vec();
+            let found = match &group {
+                None => {
+                    r.0.find_iter(s)
+                        .map(|m| DataValue::from(m.as_str()))
+                        .collect_vec()
+                }
+                Some(group) => r.0.captures_iter(s).map(|c| group.extract(c)).collect_vec(),
+            };
             Ok(DataValue::List(found))
         }
+        (DataValue::Null, _) | (_, DataValue::Null) => Ok(DataValue::Null),
         _ => bail!("'regex_extract' requires strings"),
     }
 }
 
-define_op!(OP_REGEX_EXTRACT_FIRST, 2, false);
-pub(crate) fn op_regex_extract_first(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1]) {
+define_op!(OP_REGEX_EXTRACT_FIRST, 2, true);
+pub(crate) fn op_regex_extract_first(args: &[&DataValue]) -> Result<DataValue> {
+    let group = RegexGroup::from_args("regex_extract_first", args)?;
+    match (args[0], args[1]) {
         (DataValue::Str(s), DataValue::Regex(r)) => {
-            let found = r.0.find(s).map(|v| DataValue::from(v.as_str()));
+            let found = match &group {
+                None => r.0.find(s).map(|m| DataValue::from(m.as_str())),
+                Some(group) => r.0.captures(s).map(|c| group.extract(c)),
+            };
             Ok(found.unwrap_or(DataValue::Null))
         }
+        (DataValue::Null, _) | (_, DataValue::Null) => Ok(DataValue::Null),
         _ => bail!("'regex_extract_first' requires strings"),
     }
 }
 
 define_op!(OP_T2S, 1, false);
-fn op_t2s(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+fn op_t2s(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         DataValue::Str(s) => DataValue::Str(fast2s::convert(s).into()),
         d => d.clone(),
     })
 }
 
 define_op!(OP_IS_NULL, 1, false);
-pub(crate) fn op_is_null(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_is_null(args: &[&DataValue]) -> Result<DataValue> {
     Ok(DataValue::from(matches!(args[0], DataValue::Null)))
 }
 
+define_op!(OP_NOT_NULL, 1, false);
+pub(crate) fn op_not_null(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(DataValue::from(!matches!(args[0], DataValue::Null)))
+}
+
 define_op!(OP_IS_INT, 1, false);
-pub(crate) fn op_is_int(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_is_int(args: &[&DataValue]) -> Result<DataValue> {
     Ok(DataValue::from(matches!(
         args[0],
         DataValue::Num(Num::Int(_))
@@ -1465,7 +1838,7 @@ pub(crate) fn op_is_int(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_IS_FLOAT, 1, false);
-pub(crate) fn op_is_float(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_is_float(args: &[&DataValue]) -> Result<DataValue> {
     Ok(DataValue::from(matches!(
         args[0],
         DataValue::Num(Num::Float(_))
@@ -1473,7 +1846,7 @@ pub(crate) fn op_is_float(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_IS_NUM, 1, false);
-pub(crate) fn op_is_num(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_is_num(args: &[&DataValue]) -> Result<DataValue> {
     Ok(DataValue::from(matches!(
         args[0],
         DataValue::Num(Num::Int(_)) | DataValue::Num(Num::Float(_))
@@ -1481,8 +1854,8 @@ pub(crate) fn op_is_num(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_IS_FINITE, 1, false);
-pub(crate) fn op_is_finite(args: &[DataValue]) -> Result<DataValue> {
-    Ok(DataValue::from(match &args[0] {
+pub(crate) fn op_is_finite(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(DataValue::from(match args[0] {
         DataValue::Num(Num::Int(_)) => true,
         DataValue::Num(Num::Float(f)) => f.is_finite(),
         _ => false,
@@ -1490,28 +1863,28 @@ pub(crate) fn op_is_finite(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_IS_INFINITE, 1, false);
-pub(crate) fn op_is_infinite(args: &[DataValue]) -> Result<DataValue> {
-    Ok(DataValue::from(match &args[0] {
+pub(crate) fn op_is_infinite(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(DataValue::from(match args[0] {
         DataValue::Num(Num::Float(f)) => f.is_infinite(),
         _ => false,
     }))
 }
 
 define_op!(OP_IS_NAN, 1, false);
-pub(crate) fn op_is_nan(args: &[DataValue]) -> Result<DataValue> {
-    Ok(DataValue::from(match &args[0] {
+pub(crate) fn op_is_nan(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(DataValue::from(match args[0] {
         DataValue::Num(Num::Float(f)) => f.is_nan(),
         _ => false,
     }))
 }
 
 define_op!(OP_IS_STRING, 1, false);
-pub(crate) fn op_is_string(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_is_string(args: &[&DataValue]) -> Result<DataValue> {
     Ok(DataValue::from(matches!(args[0], DataValue::Str(_))))
 }
 
 define_op!(OP_IS_LIST, 1, false);
-pub(crate) fn op_is_list(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_is_list(args: &[&DataValue]) -> Result<DataValue> {
     Ok(DataValue::from(matches!(
         args[0],
         DataValue::List(_) | DataValue::Set(_)
@@ -1519,13 +1892,13 @@ pub(crate) fn op_is_list(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_IS_VEC, 1, false);
-pub(crate) fn op_is_vec(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_is_vec(args: &[&DataValue]) -> Result<DataValue> {
     Ok(DataValue::from(matches!(args[0], DataValue::Vec(_))))
 }
 
 define_op!(OP_APPEND, 2, false);
-pub(crate) fn op_append(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_append(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::List(l) => {
             let mut l = l.clone();
             l.push(args[1].clone());
@@ -1541,8 +1914,8 @@ pub(crate) fn op_append(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_PREPEND, 2, false);
-pub(crate) fn op_prepend(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_prepend(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::List(pl) => {
             let mut l = vec![args[1].clone()];
             l.extend_from_slice(pl);
@@ -1558,25 +1931,29 @@ pub(crate) fn op_prepend(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_IS_BYTES, 1, false);
-pub(crate) fn op_is_bytes(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_is_bytes(args: &[&DataValue]) -> Result<DataValue> {
     Ok(DataValue::from(matches!(args[0], DataValue::Bytes(_))))
 }
 
 define_op!(OP_LENGTH, 1, false);
-pub(crate) fn op_length(args: &[DataValue]) -> Result<DataValue> {
-    Ok(DataValue::from(match &args[0] {
+pub(crate) fn op_length(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(DataValue::from(match args[0] {
+        DataValue::Null => return Ok(DataValue::Null),
         DataValue::Set(s) => s.len() as i64,
         DataValue::List(l) => l.len() as i64,
         DataValue::Str(s) => s.chars().count() as i64,
         DataValue::Bytes(b) => b.len() as i64,
         DataValue::Vec(v) => v.len() as i64,
-        _ => bail!("'length' requires lists"),
+        DataValue::Json(JsonData(Value::Object(o))) => o.len() as i64,
+        DataValue::Json(JsonData(Value::Array(a))) => a.len() as i64,
+        DataValue::Json(JsonData(Value::String(s))) => s.chars().count() as i64,
+        _ => bail!("'length' requires strings, lists or JSON objects"),
     }))
 }
 
 define_op!(OP_UNICODE_NORMALIZE, 2, false);
-pub(crate) fn op_unicode_normalize(args: &[DataValue]) -> Result<DataValue> {
-    match (&args[0], &args[1]) {
+pub(crate) fn op_unicode_normalize(args: &[&DataValue]) -> Result<DataValue> {
+    match (args[0], args[1]) {
         (DataValue::Str(s), DataValue::Str(n)) => Ok(DataValue::Str(match n as &str {
             "nfc" => s.nfc().collect(),
             "nfd" => s.nfd().collect(),
@@ -1588,18 +1965,31 @@ pub(crate) fn op_unicode_normalize(args: &[DataValue]) -> Result<DataValue> {
     }
 }
 
-define_op!(OP_SORTED, 1, false);
-pub(crate) fn op_sorted(args: &[DataValue]) -> Result<DataValue> {
+define_op!(OP_SORTED, 1, true);
+pub(crate) fn op_sorted(args: &[&DataValue]) -> Result<DataValue> {
+    ensure!(
+        args.len() <= 2,
+        "'sorted' takes a list and an optional descending flag"
+    );
+    let desc = match args.get(1) {
+        None => false,
+        Some(DataValue::Bool(b)) => *b,
+        Some(_) => bail!("the descending flag of 'sorted' must be a boolean"),
+    };
     let mut arg = args[0]
         .get_slice()
         .ok_or_else(|| miette!("'sort' requires lists"))?
         .to_vec();
-    arg.sort();
+    if desc {
+        arg.sort_by(|a, b| b.cmp(a));
+    } else {
+        arg.sort();
+    }
     Ok(DataValue::List(arg))
 }
 
 define_op!(OP_REVERSE, 1, false);
-pub(crate) fn op_reverse(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_reverse(args: &[&DataValue]) -> Result<DataValue> {
     let mut arg = args[0]
         .get_slice()
         .ok_or_else(|| miette!("'reverse' requires lists"))?
@@ -1608,8 +1998,50 @@ pub(crate) fn op_reverse(args: &[DataValue]) -> Result<DataValue> {
     Ok(DataValue::List(arg))
 }
 
+define_op!(OP_FLATTEN, 1, false);
+/// Concatenate the lists nested one level deep in a list. Elements that are not
+/// lists are kept as they are, deeper lists are not flattened.
+pub(crate) fn op_flatten(args: &[&DataValue]) -> Result<DataValue> {
+    let arg = match args[0] {
+        DataValue::Null => return Ok(DataValue::Null),
+        arg => arg
+            .get_slice()
+            .ok_or_else(|| miette!("'flatten' requires lists"))?,
+    };
+    let mut ret = vec![];
+    for el in arg {
+        match el {
+            DataValue::List(l) => ret.extend_from_slice(l),
+            el => ret.push(el.clone()),
+        }
+    }
+    Ok(DataValue::List(ret))
+}
+
+define_op!(OP_FLATTEN_ALL, 1, false);
+/// Like `flatten`, but flattens lists nested at any depth.
+pub(crate) fn op_flatten_all(args: &[&DataValue]) -> Result<DataValue> {
+    fn collect(list: &[DataValue], ret: &mut Vec<DataValue>) {
+        for el in list {
+            match el {
+                DataValue::List(l) => collect(l, ret),
+                el => ret.push(el.clone()),
+            }
+        }
+    }
+    let arg = match args[0] {
+        DataValue::Null => return Ok(DataValue::Null),
+        arg => arg
+            .get_slice()
+            .ok_or_else(|| miette!("'flatten_all' requires lists"))?,
+    };
+    let mut ret = vec![];
+    collect(arg, &mut ret);
+    Ok(DataValue::List(ret))
+}
+
 define_op!(OP_HAVERSINE, 4, false);
-pub(crate) fn op_haversine(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_haversine(args: &[&DataValue]) -> Result<DataValue> {
     let miette = || miette!("'haversine' requires numbers");
     let lat1 = args[0].get_float().ok_or_else(miette)?;
     let lon1 = args[1].get_float().ok_or_else(miette)?;
@@ -1624,7 +2056,7 @@ pub(crate) fn op_haversine(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_HAVERSINE_DEG_INPUT, 4, false);
-pub(crate) fn op_haversine_deg_input(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_haversine_deg_input(args: &[&DataValue]) -> Result<DataValue> {
     let miette = || miette!("'haversine_deg_input' requires numbers");
     let lat1 = args[0].get_float().ok_or_else(miette)? * f64::PI() / 180.;
     let lon1 = args[1].get_float().ok_or_else(miette)? * f64::PI() / 180.;
@@ -1639,7 +2071,7 @@ pub(crate) fn op_haversine_deg_input(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_DEG_TO_RAD, 1, false);
-pub(crate) fn op_deg_to_rad(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_deg_to_rad(args: &[&DataValue]) -> Result<DataValue> {
     let x = args[0]
         .get_float()
         .ok_or_else(|| miette!("'deg_to_rad' requires numbers"))?;
@@ -1647,7 +2079,7 @@ pub(crate) fn op_deg_to_rad(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_RAD_TO_DEG, 1, false);
-pub(crate) fn op_rad_to_deg(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_rad_to_deg(args: &[&DataValue]) -> Result<DataValue> {
     let x = args[0]
         .get_float()
         .ok_or_else(|| miette!("'rad_to_deg' requires numbers"))?;
@@ -1655,7 +2087,7 @@ pub(crate) fn op_rad_to_deg(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_FIRST, 1, false);
-pub(crate) fn op_first(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_first(args: &[&DataValue]) -> Result<DataValue> {
     Ok(args[0]
         .get_slice()
         .ok_or_else(|| miette!("'first' requires lists"))?
@@ -1665,7 +2097,7 @@ pub(crate) fn op_first(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_LAST, 1, false);
-pub(crate) fn op_last(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_last(args: &[&DataValue]) -> Result<DataValue> {
     Ok(args[0]
         .get_slice()
         .ok_or_else(|| miette!("'last' requires lists"))?
@@ -1675,7 +2107,7 @@ pub(crate) fn op_last(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_CHUNKS, 2, false);
-pub(crate) fn op_chunks(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_chunks(args: &[&DataValue]) -> Result<DataValue> {
     let arg = args[0]
         .get_slice()
         .ok_or_else(|| miette!("first argument of 'chunks' must be a list"))?;
@@ -1691,7 +2123,7 @@ pub(crate) fn op_chunks(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_CHUNKS_EXACT, 2, false);
-pub(crate) fn op_chunks_exact(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_chunks_exact(args: &[&DataValue]) -> Result<DataValue> {
     let arg = args[0]
         .get_slice()
         .ok_or_else(|| miette!("first argument of 'chunks_exact' must be a list"))?;
@@ -1707,7 +2139,7 @@ pub(crate) fn op_chunks_exact(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_WINDOWS, 2, false);
-pub(crate) fn op_windows(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_windows(args: &[&DataValue]) -> Result<DataValue> {
     let arg = args[0]
         .get_slice()
         .ok_or_else(|| miette!("first argument of 'windows' must be a list"))?;
@@ -1739,11 +2171,11 @@ fn get_index(mut i: i64, total: usize, is_upper: bool) -> Result<usize> {
 }
 
 define_op!(OP_GET, 2, true);
-pub(crate) fn op_get(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_get(args: &[&DataValue]) -> Result<DataValue> {
     match get_impl(args) {
         Ok(res) => Ok(res),
         Err(err) => {
-            if let Some(default) = args.get(2) {
+            if let Some(&default) = args.get(2) {
                 Ok(default.clone())
             } else {
                 Err(err)
@@ -1752,8 +2184,8 @@ pub(crate) fn op_get(args: &[DataValue]) -> Result<DataValue> {
     }
 }
 
-fn get_impl(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+fn get_impl(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::List(l) => {
             let n = args[1]
                 .get_int()
@@ -1762,7 +2194,7 @@ fn get_impl(args: &[DataValue]) -> Result<DataValue> {
             Ok(l[idx].clone())
         }
         DataValue::Json(json) => {
-            let res = match &args[1] {
+            let res = match args[1] {
                 DataValue::Str(s) => json
                     .get(s as &str)
                     .ok_or_else(|| miette!("key '{}' not found in json", s))?
@@ -1805,7 +2237,7 @@ fn json2val(res: Value) -> DataValue {
 }
 
 define_op!(OP_MAYBE_GET, 2, false);
-pub(crate) fn op_maybe_get(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_maybe_get(args: &[&DataValue]) -> Result<DataValue> {
     match get_impl(args) {
         Ok(res) => Ok(res),
         Err(_) => Ok(DataValue::Null),
@@ -1813,7 +2245,7 @@ pub(crate) fn op_maybe_get(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_SLICE, 3, false);
-pub(crate) fn op_slice(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_slice(args: &[&DataValue]) -> Result<DataValue> {
     let l = args[0]
         .get_slice()
         .ok_or_else(|| miette!("first argument to 'slice' mut be a list"))?;
@@ -1829,7 +2261,7 @@ pub(crate) fn op_slice(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_CHARS, 1, false);
-pub(crate) fn op_chars(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_chars(args: &[&DataValue]) -> Result<DataValue> {
     Ok(DataValue::List(
         args[0]
             .get_str()
@@ -1845,7 +2277,7 @@ pub(crate) fn op_chars(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_SLICE_STRING, 3, false);
-pub(crate) fn op_slice_string(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_slice_string(args: &[&DataValue]) -> Result<DataValue> {
     let s = args[0]
         .get_str()
         .ok_or_else(|| miette!("first argument to 'slice_string' mut be a string"))?;
@@ -1866,9 +2298,9 @@ pub(crate) fn op_slice_string(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_FROM_SUBSTRINGS, 1, false);
-pub(crate) fn op_from_substrings(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_from_substrings(args: &[&DataValue]) -> Result<DataValue> {
     let mut ret = String::new();
-    match &args[0] {
+    match args[0] {
         DataValue::List(ss) => {
             for arg in ss {
                 if let DataValue::Str(s) = arg {
@@ -1893,8 +2325,8 @@ pub(crate) fn op_from_substrings(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ENCODE_BASE64, 1, false);
-pub(crate) fn op_encode_base64(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_encode_base64(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::Bytes(b) => {
             let s = STANDARD.encode(b);
             Ok(DataValue::from(s))
@@ -1904,8 +2336,8 @@ pub(crate) fn op_encode_base64(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_DECODE_BASE64, 1, false);
-pub(crate) fn op_decode_base64(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_decode_base64(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::Str(s) => {
             let b = STANDARD
                 .decode(s)
@@ -1917,8 +2349,8 @@ pub(crate) fn op_decode_base64(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_TO_BOOL, 1, false);
-pub(crate) fn op_to_bool(args: &[DataValue]) -> Result<DataValue> {
-    Ok(DataValue::from(match &args[0] {
+pub(crate) fn op_to_bool(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(DataValue::from(match args[0] {
         DataValue::Null => false,
         DataValue::Bool(b) => *b,
         DataValue::Num(n) => n.get_int() != Some(0),
@@ -1943,8 +2375,8 @@ pub(crate) fn op_to_bool(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_TO_UNITY, 1, false);
-pub(crate) fn op_to_unity(args: &[DataValue]) -> Result<DataValue> {
-    Ok(DataValue::from(match &args[0] {
+pub(crate) fn op_to_unity(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(DataValue::from(match args[0] {
         DataValue::Null => 0,
         DataValue::Bool(b) => *b as i64,
         DataValue::Num(n) => (n.get_float() != 0.) as i64,
@@ -1969,8 +2401,8 @@ pub(crate) fn op_to_unity(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_TO_INT, 1, false);
-pub(crate) fn op_to_int(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+pub(crate) fn op_to_int(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         DataValue::Num(n) => match n.get_int() {
             None => {
                 let f = n.get_float();
@@ -1992,8 +2424,8 @@ pub(crate) fn op_to_int(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_TO_FLOAT, 1, false);
-pub(crate) fn op_to_float(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+pub(crate) fn op_to_float(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         DataValue::Num(n) => n.get_float().into(),
         DataValue::Null => DataValue::from(0.0),
         DataValue::Bool(b) => DataValue::from(if *b { 1.0 } else { 0.0 }),
@@ -2012,8 +2444,8 @@ pub(crate) fn op_to_float(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_TO_STRING, 1, false);
-pub(crate) fn op_to_string(args: &[DataValue]) -> Result<DataValue> {
-    Ok(DataValue::Str(val2str(&args[0]).into()))
+pub(crate) fn op_to_string(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(DataValue::Str(val2str(args[0]).into()))
 }
 
 fn val2str(arg: &DataValue) -> String {
@@ -2028,7 +2460,7 @@ fn val2str(arg: &DataValue) -> String {
 }
 
 define_op!(OP_VEC, 1, true);
-pub(crate) fn op_vec(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_vec(args: &[&DataValue]) -> Result<DataValue> {
     let t = match args.get(1) {
         Some(DataValue::Str(s)) => match s as &str {
             "F32" | "Float" => VecElementType::F32,
@@ -2039,7 +2471,7 @@ pub(crate) fn op_vec(args: &[DataValue]) -> Result<DataValue> {
         _ => bail!("'vec' requires a string as second argument"),
     };
 
-    match &args[0] {
+    match args[0] {
         DataValue::Json(j) => match t {
             VecElementType::F32 => {
                 let mut res_arr = ndarray::Array1::zeros(j.0.as_array().unwrap().len());
@@ -2132,7 +2564,7 @@ pub(crate) fn op_vec(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_RAND_VEC, 1, true);
-pub(crate) fn op_rand_vec(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_rand_vec(args: &[&DataValue]) -> Result<DataValue> {
     let len = args[0]
         .get_int()
         .ok_or_else(|| miette!("'rand_vec' requires an integer"))? as usize;
@@ -2166,8 +2598,8 @@ pub(crate) fn op_rand_vec(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_L2_NORMALIZE, 1, false);
-pub(crate) fn op_l2_normalize(args: &[DataValue]) -> Result<DataValue> {
-    let a = &args[0];
+pub(crate) fn op_l2_normalize(args: &[&DataValue]) -> Result<DataValue> {
+    let a = args[0];
     match a {
         DataValue::Vec(Vector::F32(a)) => {
             let norm = a.dot(a).sqrt();
@@ -2182,9 +2614,9 @@ pub(crate) fn op_l2_normalize(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_L2_DIST, 2, false);
-pub(crate) fn op_l2_dist(args: &[DataValue]) -> Result<DataValue> {
-    let a = &args[0];
-    let b = &args[1];
+pub(crate) fn op_l2_dist(args: &[&DataValue]) -> Result<DataValue> {
+    let a = args[0];
+    let b = args[1];
     match (a, b) {
         (DataValue::Vec(Vector::F32(a)), DataValue::Vec(Vector::F32(b))) => {
             if a.len() != b.len() {
@@ -2205,9 +2637,9 @@ pub(crate) fn op_l2_dist(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_IP_DIST, 2, false);
-pub(crate) fn op_ip_dist(args: &[DataValue]) -> Result<DataValue> {
-    let a = &args[0];
-    let b = &args[1];
+pub(crate) fn op_ip_dist(args: &[&DataValue]) -> Result<DataValue> {
+    let a = args[0];
+    let b = args[1];
     match (a, b) {
         (DataValue::Vec(Vector::F32(a)), DataValue::Vec(Vector::F32(b))) => {
             if a.len() != b.len() {
@@ -2228,9 +2660,9 @@ pub(crate) fn op_ip_dist(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_COS_DIST, 2, false);
-pub(crate) fn op_cos_dist(args: &[DataValue]) -> Result<DataValue> {
-    let a = &args[0];
-    let b = &args[1];
+pub(crate) fn op_cos_dist(args: &[&DataValue]) -> Result<DataValue> {
+    let a = args[0];
+    let b = args[1];
     match (a, b) {
         (DataValue::Vec(Vector::F32(a)), DataValue::Vec(Vector::F32(b))) => {
             if a.len() != b.len() {
@@ -2255,7 +2687,7 @@ pub(crate) fn op_cos_dist(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_INT_RANGE, 1, true);
-pub(crate) fn op_int_range(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_int_range(args: &[&DataValue]) -> Result<DataValue> {
     let [start, end] = match args.len() {
         1 => {
             let end = args[0]
@@ -2303,13 +2735,13 @@ pub(crate) fn op_int_range(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_RAND_FLOAT, 0, false);
-pub(crate) fn op_rand_float(_args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_rand_float(_args: &[&DataValue]) -> Result<DataValue> {
     Ok(thread_rng().gen::<f64>().into())
 }
 
 define_op!(OP_RAND_BERNOULLI, 1, false);
-pub(crate) fn op_rand_bernoulli(args: &[DataValue]) -> Result<DataValue> {
-    let prob = match &args[0] {
+pub(crate) fn op_rand_bernoulli(args: &[&DataValue]) -> Result<DataValue> {
+    let prob = match args[0] {
         DataValue::Num(n) => {
             let f = n.get_float();
             ensure!(
@@ -2324,19 +2756,19 @@ pub(crate) fn op_rand_bernoulli(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_RAND_INT, 2, false);
-pub(crate) fn op_rand_int(args: &[DataValue]) -> Result<DataValue> {
-    let lower = &args[0]
+pub(crate) fn op_rand_int(args: &[&DataValue]) -> Result<DataValue> {
+    let lower = args[0]
         .get_int()
         .ok_or_else(|| miette!("'rand_int' requires integers"))?;
-    let upper = &args[1]
+    let upper = args[1]
         .get_int()
         .ok_or_else(|| miette!("'rand_int' requires integers"))?;
-    Ok(thread_rng().gen_range(*lower..=*upper).into())
+    Ok(thread_rng().gen_range(lower..=upper).into())
 }
 
 define_op!(OP_RAND_CHOOSE, 1, false);
-pub(crate) fn op_rand_choose(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_rand_choose(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::List(l) => Ok(l
             .choose(&mut thread_rng())
             .cloned()
@@ -2353,15 +2785,15 @@ pub(crate) fn op_rand_choose(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_ASSERT, 1, true);
-pub(crate) fn op_assert(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_assert(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         DataValue::Bool(true) => Ok(DataValue::from(true)),
         _ => bail!("assertion failed: {:?}", args),
     }
 }
 
 define_op!(OP_UNION, 1, true);
-pub(crate) fn op_union(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_union(args: &[&DataValue]) -> Result<DataValue> {
     let mut ret = BTreeSet::new();
     for arg in args {
         match arg {
@@ -2382,8 +2814,8 @@ pub(crate) fn op_union(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_DIFFERENCE, 2, true);
-pub(crate) fn op_difference(args: &[DataValue]) -> Result<DataValue> {
-    let mut start: BTreeSet<_> = match &args[0] {
+pub(crate) fn op_difference(args: &[&DataValue]) -> Result<DataValue> {
+    let mut start: BTreeSet<_> = match args[0] {
         DataValue::List(l) => l.iter().cloned().collect(),
         DataValue::Set(s) => s.iter().cloned().collect(),
         _ => bail!("'difference' requires lists"),
@@ -2407,8 +2839,8 @@ pub(crate) fn op_difference(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_INTERSECTION, 1, true);
-pub(crate) fn op_intersection(args: &[DataValue]) -> Result<DataValue> {
-    let mut start: BTreeSet<_> = match &args[0] {
+pub(crate) fn op_intersection(args: &[&DataValue]) -> Result<DataValue> {
+    let mut start: BTreeSet<_> = match args[0] {
         DataValue::List(l) => l.iter().cloned().collect(),
         DataValue::Set(s) => s.iter().cloned().collect(),
         _ => bail!("'intersection' requires lists"),
@@ -2427,8 +2859,8 @@ pub(crate) fn op_intersection(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_TO_UUID, 1, false);
-pub(crate) fn op_to_uuid(args: &[DataValue]) -> Result<DataValue> {
-    match &args[0] {
+pub(crate) fn op_to_uuid(args: &[&DataValue]) -> Result<DataValue> {
+    match args[0] {
         d @ DataValue::Uuid(_u) => Ok(d.clone()),
         DataValue::Str(s) => {
             let id = uuid::Uuid::try_parse(s).map_err(|_| miette!("invalid UUID"))?;
@@ -2440,19 +2872,25 @@ pub(crate) fn op_to_uuid(args: &[DataValue]) -> Result<DataValue> {
 
 define_op!(OP_NOW, 0, false);
 #[cfg(target_arch = "wasm32")]
-pub(crate) fn op_now(_args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_now(_args: &[&DataValue]) -> Result<DataValue> {
     let d: f64 = Date::now() / 1000.;
     Ok(DataValue::from(d))
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-pub(crate) fn op_now(_args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_now(_args: &[&DataValue]) -> Result<DataValue> {
     let now = SystemTime::now();
     Ok(DataValue::from(
         now.duration_since(UNIX_EPOCH).unwrap().as_secs_f64(),
     ))
 }
 
+define_op!(OP_TODAY, 0, false);
+pub(crate) fn op_today(_args: &[&DataValue]) -> Result<DataValue> {
+    let now = op_now(&[])?.get_float().unwrap();
+    Ok(DataValue::from((now / 86400.).floor() * 86400.))
+}
+
 pub(crate) fn current_validity() -> ValidityTs {
     #[cfg(not(target_arch = "wasm32"))]
     let ts_micros = {
@@ -2471,22 +2909,24 @@ pub(crate) const TERMINAL_VALIDITY: Validity = Validity {
     is_assert: Reverse(false),
 };
 
-define_op!(OP_FORMAT_TIMESTAMP, 1, true);
-pub(crate) fn op_format_timestamp(args: &[DataValue]) -> Result<DataValue> {
-    let dt = {
-        let millis = match &args[0] {
-            DataValue::Validity(vld) => vld.timestamp.0 .0 / 1000,
-            v => {
-                let f = v
-                    .get_float()
-                    .ok_or_else(|| miette!("'format_timestamp' expects a number"))?;
-                (f * 1000.) as i64
-            }
-        };
-        Utc.timestamp_millis_opt(millis)
-            .latest()
-            .ok_or_else(|| miette!("bad time: {}", &args[0]))?
+fn timestamp_to_datetime(v: &DataValue, op_name: &str) -> Result<DateTime<Utc>> {
+    let millis = match v {
+        DataValue::Validity(vld) => vld.timestamp.0 .0 / 1000,
+        v => {
+            let f = v
+                .get_float()
+                .ok_or_else(|| miette!("'{}' expects a number", op_name))?;
+            (f * 1000.) as i64
+        }
     };
+    Utc.timestamp_millis_opt(millis)
+        .latest()
+        .ok_or_else(|| miette!("bad time: {}", v))
+}
+
+define_op!(OP_FORMAT_TIMESTAMP, 1, true);
+pub(crate) fn op_format_timestamp(args: &[&DataValue]) -> Result<DataValue> {
+    let dt = timestamp_to_datetime(args[0], "format_timestamp")?;
     match args.get(1) {
         Some(tz_v) => {
             let tz_s = tz_v.get_str().ok_or_else(|| {
@@ -2505,8 +2945,32 @@ pub(crate) fn op_format_timestamp(args: &[DataValue]) -> Result<DataValue> {
     }
 }
 
+define_op!(OP_DATE_PART, 2, false);
+pub(crate) fn op_date_part(args: &[&DataValue]) -> Result<DataValue> {
+    if *args[0] == DataValue::Null {
+        return Ok(DataValue::Null);
+    }
+    let dt = timestamp_to_datetime(args[0], "date_part")?;
+    let unit = args[1]
+        .get_str()
+        .ok_or_else(|| miette!("'date_part' requires a string as the unit"))?;
+    Ok(DataValue::from(match unit {
+        "year" => dt.year() as i64,
+        "month" => dt.month() as i64,
+        "day" => dt.day() as i64,
+        "hour" => dt.hour() as i64,
+        "minute" => dt.minute() as i64,
+        "second" => dt.second() as i64,
+        "millisecond" => dt.timestamp_subsec_millis() as i64,
+        // 0 for Sunday, as in SQL
+        "dow" => dt.weekday().num_days_from_sunday() as i64,
+        "doy" => dt.ordinal() as i64,
+        u => bail!("unknown unit {} for 'date_part'", u),
+    }))
+}
+
 define_op!(OP_PARSE_TIMESTAMP, 1, false);
-pub(crate) fn op_parse_timestamp(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_parse_timestamp(args: &[&DataValue]) -> Result<DataValue> {
     let s = args[0]
         .get_str()
         .ok_or_else(|| miette!("'parse_timestamp' expects a string"))?;
@@ -2525,7 +2989,7 @@ pub(crate) fn str2vld(s: &str) -> Result<ValidityTs> {
 }
 
 define_op!(OP_RAND_UUID_V1, 0, false);
-pub(crate) fn op_rand_uuid_v1(_args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_rand_uuid_v1(_args: &[&DataValue]) -> Result<DataValue> {
     let mut rng = rand::thread_rng();
     let uuid_ctx = uuid::v1::Context::new(rng.gen());
     #[cfg(target_arch = "wasm32")]
@@ -2548,14 +3012,14 @@ pub(crate) fn op_rand_uuid_v1(_args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_RAND_UUID_V4, 0, false);
-pub(crate) fn op_rand_uuid_v4(_args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_rand_uuid_v4(_args: &[&DataValue]) -> Result<DataValue> {
     let id = uuid::Uuid::new_v4();
     Ok(DataValue::uuid(id))
 }
 
 define_op!(OP_UUID_TIMESTAMP, 1, false);
-pub(crate) fn op_uuid_timestamp(args: &[DataValue]) -> Result<DataValue> {
-    Ok(match &args[0] {
+pub(crate) fn op_uuid_timestamp(args: &[&DataValue]) -> Result<DataValue> {
+    Ok(match args[0] {
         DataValue::Uuid(UuidWrapper(id)) => match id.get_timestamp() {
             None => DataValue::Null,
             Some(t) => {
@@ -2569,7 +3033,7 @@ pub(crate) fn op_uuid_timestamp(args: &[DataValue]) -> Result<DataValue> {
 }
 
 define_op!(OP_VALIDITY, 1, true);
-pub(crate) fn op_validity(args: &[DataValue]) -> Result<DataValue> {
+pub(crate) fn op_validity(args: &[&DataValue]) -> Result<DataValue> {
     let ts = args[0]
         .get_int()
         .ok_or_else(|| miette!("'validity' expects an integer"))?;
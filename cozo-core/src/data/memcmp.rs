@@ -15,7 +15,8 @@ use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
 use regex::Regex;
 
 use crate::data::value::{
-    DataValue, JsonData, Num, RegexWrapper, UuidWrapper, Validity, ValidityTs, Vector,
+    normalize_float, DataValue, JsonData, Num, RegexWrapper, UuidWrapper, Validity, ValidityTs,
+    Vector,
 };
 
 const INIT_TAG: u8 = 0x00;
@@ -125,7 +126,7 @@ pub(crate) trait MemCmpEncoder: Write {
         }
     }
     fn encode_num(&mut self, v: Num) {
-        let f = v.get_float();
+        let f = normalize_float(v.get_float());
         let u = order_encode_f64(f);
         self.write_u64::<BigEndian>(u).unwrap();
         match v {
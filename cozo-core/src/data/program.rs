@@ -9,6 +9,7 @@
 use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Debug, Display, Formatter};
+use std::mem;
 use std::sync::Arc;
 
 use miette::{bail, ensure, miette, Diagnostic, Result};
@@ -19,7 +20,7 @@ use thiserror::Error;
 use crate::data::aggr::Aggregation;
 use crate::data::expr::Expr;
 use crate::data::relation::StoredRelationMetadata;
-use crate::data::symb::{Symbol, PROG_ENTRY};
+use crate::data::symb::{fold_keys, fold_name, Symbol, PROG_ENTRY};
 use crate::data::value::{DataValue, ValidityTs};
 use crate::fixed_rule::{FixedRule, FixedRuleHandle};
 use crate::fts::FtsIndexManifest;
@@ -53,6 +54,11 @@ pub(crate) struct QueryOutOptions {
     pub(crate) timeout: Option<f64>,
     pub(crate) sleep: Option<f64>,
     pub(crate) sorters: Vec<(Symbol, SortDir)>,
+    /// Where nulls go when sorting; without it, they sort as the smallest values
+    pub(crate) nulls: Option<NullsOrder>,
+    /// Filter applied to the rows of the entry rule after aggregation,
+    /// with bindings for the output columns named as in the head, e.g. `count(id)`
+    pub(crate) having: Option<Expr>,
     pub(crate) store_relation: Option<(InputRelationHandle, RelationOp, ReturnMutation)>,
     pub(crate) assertion: Option<QueryAssertion>,
 }
@@ -81,6 +87,14 @@ impl Display for QueryOutOptions {
             }
             writeln!(f, "{symb};")?;
         }
+        match self.nulls {
+            Some(NullsOrder::First) => writeln!(f, ":nulls first;")?,
+            Some(NullsOrder::Last) => writeln!(f, ":nulls last;")?,
+            None => {}
+        }
+        if let Some(having) = &self.having {
+            writeln!(f, ":having {having};")?;
+        }
         if let Some((
                         InputRelationHandle {
                             name,
@@ -189,6 +203,14 @@ pub(crate) enum SortDir {
     Dsc,
 }
 
+/// Where nulls are placed when sorting the output, regardless of the sort direction.
+/// Only affects `:order`, the storage encoding always puts nulls first.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum NullsOrder {
+    First,
+    Last,
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub(crate) enum RelationOp {
     Create,
@@ -483,7 +505,11 @@ impl Display for InputProgram {
                                 write!(f, ", ")?;
                             }
                             if let Some((aggr, aggr_args)) = a {
-                                write!(f, "{}({}", aggr.name, h)?;
+                                write!(f, "{}(", aggr.name)?;
+                                if aggr.distinct {
+                                    write!(f, "distinct ")?;
+                                }
+                                write!(f, "{h}")?;
                                 for aga in aggr_args {
                                     write!(f, ", {aga}")?;
                                 }
@@ -555,6 +581,42 @@ struct EntryHeadNotExplicitlyDefinedError(#[label] SourceSpan);
 pub(crate) struct NoEntryError;
 
 impl InputProgram {
+    /// Lowercase the names of the stored relations and columns used by the program,
+    /// see [crate::Db::set_case_insensitive_names]. Rule names and variables are kept.
+    pub(crate) fn fold_case(&mut self) {
+        for rules in self.prog.values_mut() {
+            match rules {
+                InputInlineRulesOrFixed::Rules { rules } => {
+                    for atom in rules.iter_mut().flat_map(|rule| rule.body.iter_mut()) {
+                        atom.fold_case();
+                    }
+                }
+                InputInlineRulesOrFixed::Fixed { fixed } => {
+                    for arg in fixed.rule_args.iter_mut() {
+                        match arg {
+                            FixedRuleArg::InMem { .. } => {}
+                            FixedRuleArg::Stored { name, .. } => name.fold_case(),
+                            FixedRuleArg::NamedStored { name, bindings, .. } => {
+                                name.fold_case();
+                                fold_keys(bindings);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if let Some((handle, _, _)) = &mut self.out_opts.store_relation {
+            handle.name.fold_case();
+            for col in handle
+                .metadata
+                .keys
+                .iter_mut()
+                .chain(handle.metadata.non_keys.iter_mut())
+            {
+                col.name = fold_name(&col.name);
+            }
+        }
+    }
     pub(crate) fn needs_write_lock(&self) -> Option<SmartString<LazyCompact>> {
         if let Some((h, _, _)) = &self.out_opts.store_relation {
             if !h.name.name.starts_with('_') {
@@ -566,6 +628,133 @@ impl InputProgram {
             None
         }
     }
+    /// The names of the stored relations read by the rules of the program.
+    pub(crate) fn stored_relations_used(&self) -> BTreeSet<Symbol> {
+        let mut ret = BTreeSet::new();
+        for rules in self.prog.values() {
+            match rules {
+                InputInlineRulesOrFixed::Rules { rules } => {
+                    for atom in rules.iter().flat_map(|rule| rule.body.iter()) {
+                        atom.collect_stored_relations(&mut ret);
+                    }
+                }
+                InputInlineRulesOrFixed::Fixed { fixed } => {
+                    for arg in fixed.rule_args.iter() {
+                        match arg {
+                            FixedRuleArg::InMem { .. } => {}
+                            FixedRuleArg::Stored { name, .. }
+                            | FixedRuleArg::NamedStored { name, .. } => {
+                                ret.insert(name.clone());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        ret
+    }
+    /// Add the rules of `view`, the program defining a view, with its entry rule
+    /// named `rule` and its other rules prefixed by `rule`, so that they cannot clash
+    /// with the rules of this program. Returns the columns of the view.
+    pub(crate) fn add_view_rules(
+        &mut self,
+        rule: &Symbol,
+        view: InputProgram,
+    ) -> Result<Vec<Symbol>> {
+        let head = view.get_entry_out_head_or_default()?;
+        let rename = |name: &Symbol| {
+            if name.is_prog_entry() {
+                rule.clone()
+            } else {
+                Symbol::new(format!("{}:{}", rule, name), name.span)
+            }
+        };
+        for (name, mut rules) in view.prog {
+            match &mut rules {
+                InputInlineRulesOrFixed::Rules { rules } => {
+                    for atom in rules.iter_mut().flat_map(|rule| rule.body.iter_mut()) {
+                        atom.rename_rules(&rename);
+                    }
+                }
+                InputInlineRulesOrFixed::Fixed { fixed } => {
+                    for arg in fixed.rule_args.iter_mut() {
+                        if let FixedRuleArg::InMem { name, .. } = arg {
+                            *name = rename(name);
+                        }
+                    }
+                }
+            }
+            self.prog.insert(rename(&name), rules);
+        }
+        Ok(head)
+    }
+    /// Make every read of the stored relation `name` apply the rule `rule`
+    /// instead, whose columns are `head`.
+    pub(crate) fn read_from_rule(
+        &mut self,
+        name: &Symbol,
+        rule: &Symbol,
+        head: &[Symbol],
+    ) -> Result<()> {
+        for rules in self.prog.values_mut() {
+            match rules {
+                InputInlineRulesOrFixed::Rules { rules } => {
+                    for atom in rules.iter_mut().flat_map(|rule| rule.body.iter_mut()) {
+                        atom.read_from_rule(name, rule, head)?;
+                    }
+                }
+                InputInlineRulesOrFixed::Fixed { fixed } => {
+                    for arg in fixed.rule_args.iter_mut() {
+                        match arg {
+                            FixedRuleArg::Stored {
+                                name: n,
+                                bindings,
+                                span,
+                                ..
+                            } if n == name => {
+                                *arg = FixedRuleArg::InMem {
+                                    name: rule.clone(),
+                                    bindings: mem::take(bindings),
+                                    span: *span,
+                                };
+                            }
+                            FixedRuleArg::NamedStored {
+                                name: n,
+                                bindings,
+                                span,
+                                ..
+                            } if n == name => {
+                                let mut bindings = mem::take(bindings);
+                                let positional = head
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(i, col)| {
+                                        bindings.remove(&col.name).unwrap_or_else(|| {
+                                            Symbol::new(format!("*_*{rule}:{i}"), *span)
+                                        })
+                                    })
+                                    .collect();
+                                if let Some(field) = bindings.keys().next() {
+                                    bail!(NamedFieldNotFound(
+                                        name.to_string(),
+                                        field.to_string(),
+                                        *span
+                                    ));
+                                }
+                                *arg = FixedRuleArg::InMem {
+                                    name: rule.clone(),
+                                    bindings: positional,
+                                    span: *span,
+                                };
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 
     pub(crate) fn get_entry_arity(&self) -> Result<usize> {
         if let Some(entry) = self.prog.get(&Symbol::new(PROG_ENTRY, SourceSpan(0, 0))) {
@@ -635,7 +824,8 @@ impl InputProgram {
             match rules_or_fixed {
                 InputInlineRulesOrFixed::Rules { rules } => {
                     let mut collected_rules = vec![];
-                    for rule in rules {
+                    for mut rule in rules {
+                        rule.bind_qualified_fields()?;
                         let mut counter = -1;
                         let mut gen_symb = |span| {
                             counter += 1;
@@ -886,6 +1076,85 @@ pub(crate) struct InputInlineRule {
     pub(crate) span: SourceSpan,
 }
 
+impl InputInlineRule {
+    /// Bind the variables of the form `rel.col` to the column `col` of the stored relation
+    /// `rel` read with named fields in the body, so that columns of different relations
+    /// can be told apart without naming them in the atoms, as in
+    /// `?[person.name, job.title] := *person{id}, *job{person_id: id}`.
+    ///
+    /// Such variables are left alone if no relation of that name is read by the rule,
+    /// and are an error if they are also bound in some other way.
+    fn bind_qualified_fields(&mut self) -> Result<()> {
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("Variable '{0}' is ambiguous as relation '{1}' is read more than once")]
+        #[diagnostic(code(parser::ambiguous_qualified_var))]
+        #[diagnostic(help("Bind the column to a variable in one of the atoms instead"))]
+        struct AmbiguousQualifiedVar(String, String, #[label] SourceSpan);
+
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("Variable '{0}' names a column of relation '{1}' but is bound elsewhere")]
+        #[diagnostic(code(parser::rebound_qualified_var))]
+        #[diagnostic(help("Rename the variable, as it is already bound to the column"))]
+        struct ReboundQualifiedVar(String, String, #[label] SourceSpan);
+
+        let mut vars: BTreeSet<Symbol> = self.head.iter().cloned().collect();
+        for atom in &self.body {
+            atom.collect_vars(&mut vars)?;
+        }
+        let mut unifications = vec![];
+        for var in vars {
+            let Some((rel, col)) = var.name.rsplit_once('.') else {
+                continue;
+            };
+            let rebound = self.body.iter().any(|atom| atom.binds_var(&var));
+            let mut atoms = self.body.iter_mut().filter_map(|atom| match atom {
+                InputAtom::NamedFieldRelation { inner } if inner.name.name == rel => Some(inner),
+                _ => None,
+            });
+            let Some(atom) = atoms.next() else {
+                continue;
+            };
+            if atoms.next().is_some() {
+                bail!(AmbiguousQualifiedVar(
+                    var.to_string(),
+                    rel.to_string(),
+                    var.span
+                ))
+            }
+            if rebound {
+                bail!(ReboundQualifiedVar(
+                    var.to_string(),
+                    rel.to_string(),
+                    var.span
+                ))
+            }
+            let binding = Expr::Binding {
+                var: var.clone(),
+                tuple_pos: None,
+            };
+            match atom.args.entry(SmartString::from(col)) {
+                Entry::Vacant(e) => {
+                    e.insert(binding);
+                }
+                Entry::Occupied(e) => {
+                    if *e.get() != binding {
+                        unifications.push(InputAtom::Unification {
+                            inner: Unification {
+                                binding: var.clone(),
+                                expr: e.get().clone(),
+                                one_many_unif: false,
+                                span: var.span,
+                            },
+                        })
+                    }
+                }
+            }
+        }
+        self.body.extend(unifications);
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct NormalFormInlineRule {
     pub(crate) head: Vec<Symbol>,
@@ -956,6 +1225,182 @@ pub(crate) enum InputAtom {
     },
 }
 
+impl InputAtom {
+    fn fold_case(&mut self) {
+        match self {
+            InputAtom::NamedFieldRelation { inner } => {
+                inner.name.fold_case();
+                fold_keys(&mut inner.args);
+            }
+            InputAtom::Relation { inner } => inner.name.fold_case(),
+            InputAtom::Search { inner } => {
+                inner.relation.fold_case();
+                inner.index.fold_case();
+                fold_keys(&mut inner.bindings);
+            }
+            InputAtom::Negation { inner, .. } => inner.fold_case(),
+            InputAtom::Conjunction { inner, .. } | InputAtom::Disjunction { inner, .. } => {
+                for atom in inner {
+                    atom.fold_case();
+                }
+            }
+            InputAtom::Rule { .. }
+            | InputAtom::Predicate { .. }
+            | InputAtom::Unification { .. } => {}
+        }
+    }
+    fn collect_vars(&self, coll: &mut BTreeSet<Symbol>) -> Result<()> {
+        match self {
+            InputAtom::Rule { inner } => {
+                for arg in &inner.args {
+                    arg.collect_bindings(coll)?;
+                }
+            }
+            InputAtom::NamedFieldRelation { inner } => {
+                for arg in inner.args.values() {
+                    arg.collect_bindings(coll)?;
+                }
+            }
+            InputAtom::Relation { inner } => {
+                for arg in &inner.args {
+                    arg.collect_bindings(coll)?;
+                }
+            }
+            InputAtom::Predicate { inner } => inner.collect_bindings(coll)?,
+            InputAtom::Negation { inner, .. } => inner.collect_vars(coll)?,
+            InputAtom::Conjunction { inner, .. } | InputAtom::Disjunction { inner, .. } => {
+                for atom in inner {
+                    atom.collect_vars(coll)?;
+                }
+            }
+            InputAtom::Unification { inner } => {
+                coll.insert(inner.binding.clone());
+                inner.expr.collect_bindings(coll)?;
+            }
+            InputAtom::Search { inner } => {
+                for arg in inner.bindings.values().chain(inner.parameters.values()) {
+                    arg.collect_bindings(coll)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Whether the atom binds `var` other than through the column it names if it is
+    /// of the form `rel.col`, see [InputInlineRule::bind_qualified_fields].
+    fn binds_var(&self, var: &Symbol) -> bool {
+        let is_var = |arg: &Expr| matches!(arg, Expr::Binding { var: v, .. } if v == var);
+        match self {
+            InputAtom::Rule { inner } => inner.args.iter().any(is_var),
+            InputAtom::Relation { inner } => inner.args.iter().any(is_var),
+            InputAtom::NamedFieldRelation { inner } => inner.args.iter().any(|(col, arg)| {
+                is_var(arg) && var.name != format!("{}.{}", inner.name.name, col)
+            }),
+            InputAtom::Conjunction { inner, .. } | InputAtom::Disjunction { inner, .. } => {
+                inner.iter().any(|atom| atom.binds_var(var))
+            }
+            InputAtom::Unification { inner } => inner.binding == *var,
+            InputAtom::Search { inner } => inner.bindings.values().any(is_var),
+            InputAtom::Predicate { .. } | InputAtom::Negation { .. } => false,
+        }
+    }
+    fn collect_stored_relations(&self, names: &mut BTreeSet<Symbol>) {
+        match self {
+            InputAtom::NamedFieldRelation { inner } => {
+                names.insert(inner.name.clone());
+            }
+            InputAtom::Relation { inner } => {
+                names.insert(inner.name.clone());
+            }
+            InputAtom::Negation { inner, .. } => inner.collect_stored_relations(names),
+            InputAtom::Conjunction { inner, .. } | InputAtom::Disjunction { inner, .. } => {
+                for atom in inner {
+                    atom.collect_stored_relations(names);
+                }
+            }
+            InputAtom::Rule { .. }
+            | InputAtom::Predicate { .. }
+            | InputAtom::Unification { .. }
+            | InputAtom::Search { .. } => {}
+        }
+    }
+    fn rename_rules(&mut self, rename: &impl Fn(&Symbol) -> Symbol) {
+        match self {
+            InputAtom::Rule { inner } => inner.name = rename(&inner.name),
+            InputAtom::Negation { inner, .. } => inner.rename_rules(rename),
+            InputAtom::Conjunction { inner, .. } | InputAtom::Disjunction { inner, .. } => {
+                for atom in inner {
+                    atom.rename_rules(rename);
+                }
+            }
+            InputAtom::NamedFieldRelation { .. }
+            | InputAtom::Relation { .. }
+            | InputAtom::Predicate { .. }
+            | InputAtom::Unification { .. }
+            | InputAtom::Search { .. } => {}
+        }
+    }
+    fn read_from_rule(&mut self, name: &Symbol, rule: &Symbol, head: &[Symbol]) -> Result<()> {
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("View '{0}' cannot be read at a validity")]
+        #[diagnostic(code(eval::view_with_validity))]
+        struct ViewWithValidity(String, #[label] SourceSpan);
+
+        match self {
+            InputAtom::Relation { inner } if inner.name == *name => {
+                ensure!(
+                    inner.valid_at.is_none(),
+                    ViewWithValidity(name.to_string(), inner.span)
+                );
+                *self = InputAtom::Rule {
+                    inner: InputRuleApplyAtom {
+                        name: rule.clone(),
+                        args: mem::take(&mut inner.args),
+                        span: inner.span,
+                    },
+                };
+            }
+            InputAtom::NamedFieldRelation { inner } if inner.name == *name => {
+                ensure!(
+                    inner.valid_at.is_none(),
+                    ViewWithValidity(name.to_string(), inner.span)
+                );
+                let span = inner.span;
+                let args = head
+                    .iter()
+                    .map(|col| {
+                        inner.args.remove(&col.name).unwrap_or(Expr::Binding {
+                            var: Symbol::new("_", span),
+                            tuple_pos: None,
+                        })
+                    })
+                    .collect();
+                if let Some(field) = inner.args.keys().next() {
+                    bail!(NamedFieldNotFound(
+                        name.to_string(),
+                        field.to_string(),
+                        span
+                    ));
+                }
+                *self = InputAtom::Rule {
+                    inner: InputRuleApplyAtom {
+                        name: rule.clone(),
+                        args,
+                        span,
+                    },
+                };
+            }
+            InputAtom::Negation { inner, .. } => inner.read_from_rule(name, rule, head)?,
+            InputAtom::Conjunction { inner, .. } | InputAtom::Disjunction { inner, .. } => {
+                for atom in inner {
+                    atom.read_from_rule(name, rule, head)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct SearchInput {
     pub(crate) relation: Symbol,
@@ -22,11 +22,16 @@ use thiserror::Error;
 
 use crate::data::expr::Expr;
 use crate::data::value::{DataValue, JsonData, UuidWrapper, Validity, ValidityTs, Vector};
+use crate::parse::SourceSpan;
+use crate::query::logical::NamedFieldNotFound;
 use crate::Num;
 
+/// Type of a stored column, such as `Int` or `[String?]?`.
 #[derive(Debug, Clone, Eq, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
 pub struct NullableColType {
+    /// The underlying type
     pub coltype: ColType,
+    /// Whether null is accepted in addition to the underlying type
     pub nullable: bool,
 }
 
@@ -80,33 +85,53 @@ impl Display for NullableColType {
     }
 }
 
+/// Column type without nullability.
 #[derive(Debug, Clone, Eq, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
 pub enum ColType {
+    /// Any value
     Any,
+    /// Booleans
     Bool,
+    /// Integers
     Int,
+    /// Floats, integers are accepted and converted
     Float,
+    /// Strings
     String,
+    /// Byte arrays
     Bytes,
+    /// UUIDs
     Uuid,
+    /// Lists with elements of the given type, optionally of fixed length
     List {
+        /// Type of the elements
         eltype: Box<NullableColType>,
+        /// Required length, if any
         len: Option<usize>,
     },
+    /// Vectors for vector search
     Vec {
+        /// Type of the elements
         eltype: VecElementType,
+        /// Dimension of the vector
         len: usize,
     },
+    /// Lists of fixed length with elements of the given types
     Tuple(Vec<NullableColType>),
+    /// Validity for time travel
     Validity,
+    /// JSON values
     Json,
 }
 
+/// Element type of vectors.
 #[derive(
     Debug, Copy, Clone, Eq, PartialEq, Hash, serde_derive::Deserialize, serde_derive::Serialize,
 )]
 pub enum VecElementType {
+    /// 32-bit floats
     F32,
+    /// 64-bit floats
     F64,
 }
 
@@ -130,7 +155,8 @@ impl StoredRelationMetadata {
                 return Ok(());
             }
         }
-        if col.default_gen.is_none() {
+        // absent nullable columns without a default are null
+        if col.default_gen.is_none() && !col.typing.is_nullable() {
             #[derive(Debug, Error, Diagnostic)]
             #[error("required column {0} not provided by input")]
             #[diagnostic(code(eval::required_col_not_provided))]
@@ -140,14 +166,19 @@ impl StoredRelationMetadata {
         }
         Ok(())
     }
-    pub(crate) fn compatible_with_col(&self, col: &ColumnDef) -> Result<()> {
+    pub(crate) fn compatible_with_col(
+        &self,
+        rel_name: &str,
+        col: &ColumnDef,
+        span: SourceSpan,
+    ) -> Result<()> {
         for target in self.keys.iter().chain(self.non_keys.iter()) {
             if target.name == col.name {
                 #[derive(Debug, Error, Diagnostic)]
                 #[error("requested column {0} has typing {1}, but the requested typing is {2}")]
                 #[diagnostic(code(eval::col_type_mismatch))]
                 struct IncompatibleTyping(String, NullableColType, NullableColType);
-                if (!col.typing.nullable || col.typing.coltype != ColType::Any)
+                if (!col.typing.is_nullable() || *col.typing.strip_optional() != ColType::Any)
                     && target.typing != col.typing
                 {
                     bail!(IncompatibleTyping(
@@ -161,19 +192,33 @@ impl StoredRelationMetadata {
             }
         }
 
-        #[derive(Debug, Error, Diagnostic)]
-        #[error("required column {0} not found")]
-        #[diagnostic(code(eval::required_col_not_found))]
-        struct ColumnNotFound(String);
-
-        bail!(ColumnNotFound(col.name.to_string()))
+        bail!(NamedFieldNotFound(
+            rel_name.to_string(),
+            col.name.to_string(),
+            span
+        ))
     }
 }
 
 impl NullableColType {
+    /// Whether null is accepted, i.e. the type is written with a trailing `?`.
+    pub(crate) fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+    /// The underlying type, disregarding whether null is accepted.
+    pub(crate) fn strip_optional(&self) -> &ColType {
+        &self.coltype
+    }
+    /// The type of the elements if this is a list type.
+    pub(crate) fn element_type(&self) -> Option<&NullableColType> {
+        match &self.coltype {
+            ColType::List { eltype, .. } => Some(eltype),
+            _ => None,
+        }
+    }
     pub(crate) fn coerce(&self, data: DataValue, cur_vld: ValidityTs) -> Result<DataValue> {
         if matches!(data, DataValue::Null) {
-            return if self.nullable {
+            return if self.is_nullable() {
                 Ok(data)
             } else {
                 #[derive(Debug, Error, Diagnostic)]
@@ -7,6 +7,7 @@
  */
 
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
@@ -72,6 +73,20 @@ impl Debug for Symbol {
     }
 }
 
+/// The name of a stored relation or column as it is stored when names are
+/// case-insensitive, see [crate::Db::set_case_insensitive_names].
+pub(crate) fn fold_name(name: &str) -> SmartString<LazyCompact> {
+    name.to_lowercase().into()
+}
+
+/// Fold the keys of a map from column names, see [fold_name].
+pub(crate) fn fold_keys<T>(map: &mut BTreeMap<SmartString<LazyCompact>, T>) {
+    *map = std::mem::take(map)
+        .into_iter()
+        .map(|(k, v)| (fold_name(&k), v))
+        .collect();
+}
+
 impl Symbol {
     pub(crate) fn new(name: impl Into<SmartString<LazyCompact>>, span: SourceSpan) -> Self {
         Self {
@@ -79,6 +94,10 @@ impl Symbol {
             span,
         }
     }
+    /// Lowercase the name, see [fold_name].
+    pub(crate) fn fold_case(&mut self) {
+        self.name = fold_name(&self.name);
+    }
     pub(crate) fn is_temp_store_name(&self) -> bool {
         self.name.starts_with('_')
     }
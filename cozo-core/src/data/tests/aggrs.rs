@@ -299,6 +299,41 @@ fn test_sum() {
     assert_eq!(sum_aggr.get().unwrap(), DataValue::from(15.));
 }
 
+#[test]
+fn test_sum_widening() {
+    let run = |name: &str, vals: &[DataValue]| {
+        let mut aggr = parse_aggr(name).unwrap().clone();
+        aggr.normal_init(&[]).unwrap();
+        let mut op = aggr.normal_op.unwrap();
+        for val in vals {
+            op.set(val).unwrap();
+        }
+        op.get()
+    };
+    let max = DataValue::from(i64::MAX);
+    let min = DataValue::from(-i64::MAX);
+
+    // intermediate sums beyond the range of i64 are still exact
+    let vals = [
+        max.clone(),
+        max.clone(),
+        min.clone(),
+        DataValue::from(1),
+        DataValue::from(-1),
+    ];
+    assert_eq!(run("sum", &vals).unwrap(), DataValue::from(i64::MAX as f64));
+    // and so are sums of integers too large to be told apart as floats
+    let vals = [max.clone(), DataValue::from(1), min];
+    assert_eq!(run("sum", &vals).unwrap(), DataValue::from(1.));
+    let vals = [DataValue::from(1), DataValue::from(0.5)];
+    assert_eq!(run("sum", &vals).unwrap(), DataValue::from(1.5));
+
+    assert_eq!(
+        run("mean", &[max.clone(), max]).unwrap(),
+        DataValue::from(i64::MAX as f64)
+    );
+}
+
 #[test]
 fn test_product() {
     let mut aggr = parse_aggr("product").unwrap().clone();
@@ -6,7 +6,12 @@
  * You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use crate::{DataValue, DbInstance};
+use std::collections::BTreeSet;
+
+use crate::data::expr::Expr;
+use crate::data::functions::OP_MINUS;
+use crate::parse::parse_expressions;
+use crate::{evaluate_expressions, get_variables, DataValue, DbInstance};
 
 #[test]
 fn expression_eval() {
@@ -30,3 +35,379 @@ fn expression_eval() {
         .unwrap();
     assert_eq!(res.rows[0][0].get_bool().unwrap(), true);
 }
+
+#[test]
+fn expression_variables() {
+    let vars = get_variables("a + b * c", &Default::default()).unwrap();
+    assert_eq!(
+        vars,
+        BTreeSet::from(["a".to_string(), "b".to_string(), "c".to_string()])
+    );
+    let vars = get_variables("if(x > 0, concat(y, 'z'), x)", &Default::default()).unwrap();
+    assert_eq!(vars, BTreeSet::from(["x".to_string(), "y".to_string()]));
+    let vars = get_variables("1 + 2 * 3", &Default::default()).unwrap();
+    assert!(vars.is_empty());
+}
+
+#[test]
+fn float_literals() {
+    let eval =
+        |src: &str| evaluate_expressions(src, &Default::default(), &Default::default()).unwrap();
+    assert_eq!(eval(".5"), DataValue::from(0.5));
+    assert_eq!(eval(".25e2"), DataValue::from(25.0));
+    assert_eq!(eval("1_000.5"), DataValue::from(1000.5));
+    assert_eq!(eval("1_000.000_1"), DataValue::from(1000.0001));
+    assert_eq!(eval("-0.5"), DataValue::from(-0.5));
+    assert_eq!(eval("-.5"), DataValue::from(-0.5));
+    assert_eq!(eval("1 - .5"), DataValue::from(0.5));
+
+    match parse_expressions("-0.5", &Default::default()).unwrap() {
+        Expr::Apply { op, args, .. } => {
+            assert_eq!(op.name, OP_MINUS.name);
+            assert_eq!(args[0].get_const(), Some(&DataValue::from(0.5)));
+        }
+        e => panic!("unexpected expression {e:?}"),
+    }
+}
+
+#[test]
+fn mixed_numeric_comparisons() {
+    let eval =
+        |src: &str| evaluate_expressions(src, &Default::default(), &Default::default()).unwrap();
+    assert_eq!(eval("3 == 3.0"), DataValue::from(true));
+    assert_eq!(eval("3.0 != 3"), DataValue::from(false));
+    assert_eq!(eval("3 < 3.5"), DataValue::from(true));
+    assert_eq!(eval("3.5 <= 3"), DataValue::from(false));
+    assert_eq!(
+        eval("9007199254740993 > 9007199254740992.0"),
+        DataValue::from(true)
+    );
+}
+
+#[test]
+fn impure_calls_are_not_folded() {
+    for src in [
+        "now()",
+        "today()",
+        "rand_float() + 1",
+        "rand_int(1, 10) > 5",
+    ] {
+        let mut expr = parse_expressions(src, &Default::default()).unwrap();
+        expr.partial_eval().unwrap();
+        assert!(expr.get_const().is_none(), "{src} was folded");
+        assert!(expr.eval_to_const().is_ok());
+    }
+    let mut expr = parse_expressions("1 + 2", &Default::default()).unwrap();
+    expr.partial_eval().unwrap();
+    assert_eq!(expr.get_const(), Some(&DataValue::from(3)));
+}
+
+#[test]
+fn modulo_precedence() {
+    let parse = |src: &str| {
+        parse_expressions(src, &Default::default())
+            .unwrap()
+            .to_string()
+    };
+    // `%` binds as tightly as `*` and `/`, and they associate to the left
+    assert_eq!(parse("10 + 3 % 2"), parse("add(10, mod(3, 2))"));
+    assert_eq!(parse("2 * 3 % 4"), parse("mod(mul(2, 3), 4)"));
+    assert_eq!(parse("7 % 4 * 2"), parse("mul(mod(7, 4), 2)"));
+    assert_eq!(parse("7 % 4 == 3"), parse("eq(mod(7, 4), 3)"));
+    let eval =
+        |src: &str| evaluate_expressions(src, &Default::default(), &Default::default()).unwrap();
+    assert_eq!(eval("10 + 3 % 2"), DataValue::from(11));
+    assert_eq!(eval("2 * 3 % 4"), DataValue::from(2));
+}
+
+#[test]
+fn trailing_commas_in_literals() {
+    let eval =
+        |src: &str| evaluate_expressions(src, &Default::default(), &Default::default()).unwrap();
+    assert_eq!(eval("[1, 2, 3,]"), eval("[1, 2, 3]"));
+    assert_eq!(eval("[[1,], [2, 3,],]"), eval("[[1], [2, 3]]"));
+    assert_eq!(eval("{'a': 1, 'b': 2,}"), eval("{'a': 1, 'b': 2}"));
+    assert_eq!(eval("concat([1,], [2,],)"), eval("[1, 2]"));
+    assert!(parse_expressions("[1,,]", &Default::default()).is_err());
+    assert!(parse_expressions("[,]", &Default::default()).is_err());
+}
+
+#[test]
+fn minus_and_negate_are_distinct() {
+    let eval = |src: &str| evaluate_expressions(src, &Default::default(), &Default::default());
+    assert_eq!(eval("-5").unwrap(), DataValue::from(-5));
+    assert_eq!(eval("-(2.5)").unwrap(), DataValue::from(-2.5));
+    assert_eq!(eval("-(-2)").unwrap(), DataValue::from(2));
+    assert_eq!(eval("!true").unwrap(), DataValue::from(false));
+    // `-` is arithmetic only, `!` is logical only
+    assert!(eval("-'x'").is_err());
+    assert!(eval("-true").is_err());
+    assert!(eval("!5").is_err());
+    assert!(eval("!'x'").is_err());
+}
+
+#[test]
+fn chained_comparisons() {
+    let eval = |src: &str| evaluate_expressions(src, &Default::default(), &Default::default());
+    let parse = |src: &str| {
+        parse_expressions(src, &Default::default())
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(eval("1 < 2 < 3").unwrap(), DataValue::from(true));
+    assert_eq!(eval("1 < 5 < 3").unwrap(), DataValue::from(false));
+    assert_eq!(eval("3 >= 3 > 1 >= 1").unwrap(), DataValue::from(true));
+    assert_eq!(eval("1 < 2 < 3 < 2").unwrap(), DataValue::from(false));
+    assert_eq!(eval("1 < 1 + 1 <= 2").unwrap(), DataValue::from(true));
+    assert_eq!(eval("1 < 1 + 1 < 2").unwrap(), DataValue::from(false));
+    // nulls are treated as by the individual comparisons
+    assert_eq!(eval("1 < null < 3").is_err(), eval("1 < null").is_err());
+
+    // simple operands become a conjunction, so that ranges can use indices
+    assert_eq!(parse("1 < x < 10"), parse("and(lt(1, x), lt(x, 10))"));
+    assert_eq!(
+        parse("a <= b < c >= d"),
+        parse("and(le(a, b), lt(b, c), ge(c, d))")
+    );
+    // other operands are evaluated only once
+    assert_eq!(
+        parse("0 <= rand_int(0, 5) < 5"),
+        parse("chained_cmp(0, 'le', rand_int(0, 5), 'lt', 5)")
+    );
+    assert_eq!(
+        parse("0 < x <= y + 1 < 2"),
+        parse("chained_cmp(0, 'lt', x, 'le', add(y, 1), 'lt', 2)")
+    );
+    // parentheses prevent chaining
+    assert_eq!(parse("(a < b) < c"), parse("lt(lt(a, b), c)"));
+    assert_eq!(parse("a < (b) < c"), parse("and(lt(a, b), lt(b, c))"));
+    assert!(eval("(1 < 2) < 3").is_err());
+    let db = DbInstance::default();
+    db.run_default("?[x] <- [[1], [2], [3], [4]] :create t {x}")
+        .unwrap();
+    let res = db.run_default("?[x] := *t{x}, 1 < x <= 3").unwrap();
+    assert_eq!(
+        res.rows,
+        vec![vec![DataValue::from(2)], vec![DataValue::from(3)]]
+    );
+    // equality binds tighter and does not chain
+    assert_eq!(parse("a == b == c"), parse("eq(eq(a, b), c)"));
+    assert_eq!(
+        parse("a < b == c < d"),
+        parse("chained_cmp(a, 'lt', eq(b, c), 'lt', d)")
+    );
+}
+
+#[test]
+fn strict_truthiness() {
+    let eval = |src: &str| evaluate_expressions(src, &Default::default(), &Default::default());
+    // numbers and strings are not implicitly boolean
+    for src in [
+        "1 && true",
+        "true && 1",
+        "false && 1",
+        "'x' || false",
+        "0 || true",
+        "if(1, 2, 3)",
+        "cond(false, 1, 'true', 2)",
+    ] {
+        assert!(eval(src).is_err(), "{src} should not evaluate");
+    }
+    // null is neither true nor false
+    assert_eq!(eval("null && true").unwrap(), DataValue::Null);
+    assert_eq!(eval("null && false").unwrap(), DataValue::from(false));
+    assert_eq!(eval("null || false").unwrap(), DataValue::Null);
+    assert_eq!(eval("null || true").unwrap(), DataValue::from(true));
+    assert_eq!(eval("!null").unwrap(), DataValue::Null);
+    assert_eq!(eval("if(null, 1, 2)").unwrap(), DataValue::from(2));
+    assert_eq!(eval("cond(null, 1, true, 2)").unwrap(), DataValue::from(2));
+
+    // a null filter does not hold
+    let db = DbInstance::default();
+    let res = db
+        .run_default("?[x] := x in [1, 2, 3], x == 3 || (x == 1 && null)")
+        .unwrap();
+    assert_eq!(res.rows, vec![vec![DataValue::from(3)]]);
+    assert!(db.run_default("?[x] := x in [1, 2, 3], x").is_err());
+}
+
+#[test]
+fn string_comparison_by_code_points() {
+    let eval =
+        |src: &str| evaluate_expressions(src, &Default::default(), &Default::default()).unwrap();
+    // U+00E9 > U+007A, although 'é' sorts before 'z' in many locales
+    assert_eq!(eval("'z' < 'é'"), DataValue::from(true));
+    assert_eq!(eval("'é' < 'ë'"), DataValue::from(true));
+    // in UTF-16, the surrogates of U+1F600 would sort before U+FF5A
+    assert_eq!(eval("'ｚ' < '😀'"), DataValue::from(true));
+    assert_eq!(eval("'a😀' > 'aｚb'"), DataValue::from(true));
+    // a prefix sorts first
+    assert_eq!(eval("'é' < 'éa'"), DataValue::from(true));
+
+    // stored keys and sorting agree with the comparisons
+    let db = DbInstance::default();
+    db.run_default(
+        "?[s] <- [['😀'], ['z'], ['ë'], ['aｚb'], ['é'], ['ｚ'], ['a😀']] :create strs {s}",
+    )
+    .unwrap();
+    let expected = ["aｚb", "a😀", "z", "é", "ë", "ｚ", "😀"]
+        .map(|s| vec![DataValue::from(s)])
+        .to_vec();
+    let res = db.run_default("?[s] := *strs{s}").unwrap();
+    assert_eq!(res.rows, expected);
+    let res = db
+        .run_default("?[s] := s in ['😀', 'z', 'ë', 'aｚb', 'é', 'ｚ', 'a😀'] :order s")
+        .unwrap();
+    assert_eq!(res.rows, expected);
+    let res = db.run_default("?[s] := *strs{s}, s > 'ｚ'").unwrap();
+    assert_eq!(res.rows, vec![vec![DataValue::from("😀")]]);
+}
+
+#[test]
+fn null_checks_are_never_null() {
+    let eval = |src: &str| evaluate_expressions(src, &Default::default(), &Default::default());
+    assert_eq!(eval("is_null(null)").unwrap(), DataValue::from(true));
+    assert_eq!(eval("not_null(null)").unwrap(), DataValue::from(false));
+    assert_eq!(eval("is_null(false)").unwrap(), DataValue::from(false));
+    assert_eq!(eval("not_null([])").unwrap(), DataValue::from(true));
+    // null coming out of an expression is caught as well
+    assert_eq!(eval("is_null(!null)").unwrap(), DataValue::from(true));
+    assert_eq!(
+        eval("is_null(null && true)").unwrap(),
+        DataValue::from(true)
+    );
+    assert_eq!(eval("!is_null(null)").unwrap(), DataValue::from(false));
+    // `(1 + null) is null` is an error rather than true: arithmetic in CozoScript
+    // rejects null instead of propagating it, and the null checks do not swallow
+    // errors raised while evaluating their argument
+    assert!(eval("is_null(1 + null)").is_err());
+
+    let db = DbInstance::default();
+    let res = db
+        .run_default("?[x] := x in [1, null, 3], not_null(x)")
+        .unwrap();
+    assert_eq!(
+        res.rows,
+        vec![vec![DataValue::from(1)], vec![DataValue::from(3)]]
+    );
+    let res = db
+        .run_default("?[x] := x in [1, null, 3], is_null(x)")
+        .unwrap();
+    assert_eq!(res.rows, vec![vec![DataValue::Null]]);
+}
+
+#[test]
+fn signed_zero_and_nan() {
+    let eval =
+        |src: &str| evaluate_expressions(src, &Default::default(), &Default::default()).unwrap();
+    assert_eq!(eval("-0.0 == 0.0"), DataValue::from(true));
+    assert_eq!(eval("-0.0 != 0.0"), DataValue::from(false));
+    assert_eq!(eval("-0.0 < 0.0"), DataValue::from(false));
+    assert_eq!(eval("0 == -0.0"), DataValue::from(true));
+    assert_eq!(
+        eval("to_float('NAN') == -to_float('NAN')"),
+        DataValue::from(true)
+    );
+
+    // keys do not split on the sign of zero
+    let db = DbInstance::default();
+    db.run_default("?[x, v] <- [[-0.0, 'neg']] :create floats {x => v}")
+        .unwrap();
+    let res = db.run_default("?[v] := *floats{x: 0.0, v}").unwrap();
+    assert_eq!(res.rows, vec![vec![DataValue::from("neg")]]);
+    db.run_default("?[x, v] <- [[0.0, 'pos']] :put floats {x => v}")
+        .unwrap();
+    let res = db.run_default("?[x, v] := *floats{x, v}").unwrap();
+    assert_eq!(
+        res.rows,
+        vec![vec![DataValue::from(0.0), DataValue::from("pos")]]
+    );
+}
+
+#[test]
+fn negation_pushed_inward() {
+    let simplified = |src: &str| {
+        let mut expr = parse_expressions(src, &Default::default()).unwrap();
+        expr.simplify();
+        expr
+    };
+    let parsed = |src: &str| parse_expressions(src, &Default::default()).unwrap();
+    for (src, expected) in [
+        ("!(a && b)", "!a || !b"),
+        ("!(a || b)", "!a && !b"),
+        ("!(a == b)", "a != b"),
+        ("!(a != b)", "a == b"),
+        ("!!a", "a"),
+        ("!!!a", "!a"),
+        ("!(!a && b == c)", "a || b != c"),
+        ("!(a || !(b || c))", "!a && (b || c)"),
+        ("x + 1 > 2 && !(y == 1)", "x + 1 > 2 && y != 1"),
+        ("!(a < b)", "!(a < b)"),
+    ] {
+        assert_eq!(
+            simplified(src).to_string(),
+            parsed(expected).to_string(),
+            "{src}"
+        );
+    }
+
+    let db = DbInstance::default();
+    let res = db
+        .run_default("?[a, b] := a in [1, 2], b in [1, 2], !(a == b || a > b)")
+        .unwrap();
+    assert_eq!(res.rows, vec![vec![DataValue::from(1), DataValue::from(2)]]);
+}
+
+#[test]
+fn coalesce_simplified() {
+    let simplified = |src: &str| {
+        let mut expr = parse_expressions(src, &Default::default()).unwrap();
+        expr.simplify();
+        expr
+    };
+    let parsed = |src: &str| parse_expressions(src, &Default::default()).unwrap();
+    for (src, expected) in [
+        ("coalesce(null, x)", "x"),
+        ("coalesce(1, x)", "1"),
+        ("coalesce(x, null, y)", "coalesce(x, y)"),
+        ("coalesce(x, 'a', y)", "coalesce(x, 'a')"),
+        ("coalesce(coalesce(null, a), b)", "coalesce(a, b)"),
+        ("coalesce(a, coalesce(b, 2, c), d)", "coalesce(a, b, 2)"),
+        ("null ~ a ~ null ~ b", "coalesce(a, b)"),
+        ("coalesce(null, null)", "null"),
+        ("coalesce(x)", "x"),
+        ("!(coalesce(null, a) == b)", "a != b"),
+    ] {
+        assert_eq!(
+            simplified(src).to_string(),
+            parsed(expected).to_string(),
+            "{src}"
+        );
+    }
+}
+
+#[test]
+fn coalesce_short_circuits() {
+    let eval = |src: &str| evaluate_expressions(src, &Default::default(), &Default::default());
+    assert_eq!(eval("1 ~ (1/0)").unwrap(), DataValue::from(1));
+    assert_eq!(eval("1 ~ (1 + 'a')").unwrap(), DataValue::from(1));
+    assert_eq!(eval("null ~ 2 ~ (1 + 'a')").unwrap(), DataValue::from(2));
+    assert_eq!(eval("coalesce(null, null)").unwrap(), DataValue::Null);
+    assert!(eval("null ~ (1 + 'a')").is_err());
+
+    let db = DbInstance::default();
+    assert!(db
+        .run_default("?[x, y] := x in [1, null, 3], y = x ~ (x + 'a') ~ 0")
+        .is_err());
+    let res = db
+        .run_default("?[y] := x in [1, 2], y = x ~ (x + 'a')")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], serde_json::json!([[1], [2]]));
+    assert!(db
+        .run_default("?[x] := x in [1, null, 3], (x ~ (x + 'a')) > 1")
+        .is_err());
+    let res = db
+        .run_default("?[x] := x in [1, 2, 3], (x ~ (x + 'a')) > 1")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], serde_json::json!([[2], [3]]));
+}
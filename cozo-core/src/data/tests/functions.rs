@@ -12,23 +12,23 @@ use regex::Regex;
 use serde_json::json;
 
 use crate::data::functions::*;
-use crate::data::value::{DataValue, RegexWrapper};
+use crate::data::value::{DataValue, JsonData, RegexWrapper};
 use crate::DbInstance;
 
 #[test]
 fn test_add() {
     assert_eq!(op_add(&[]).unwrap(), DataValue::from(0));
-    assert_eq!(op_add(&[DataValue::from(1)]).unwrap(), DataValue::from(1));
+    assert_eq!(op_add(&[&DataValue::from(1)]).unwrap(), DataValue::from(1));
     assert_eq!(
-        op_add(&[DataValue::from(1), DataValue::from(2)]).unwrap(),
+        op_add(&[&DataValue::from(1), &DataValue::from(2)]).unwrap(),
         DataValue::from(3)
     );
     assert_eq!(
-        op_add(&[DataValue::from(1), DataValue::from(2.5)]).unwrap(),
+        op_add(&[&DataValue::from(1), &DataValue::from(2.5)]).unwrap(),
         DataValue::from(3.5)
     );
     assert_eq!(
-        op_add(&[DataValue::from(1.5), DataValue::from(2.5)]).unwrap(),
+        op_add(&[&DataValue::from(1.5), &DataValue::from(2.5)]).unwrap(),
         DataValue::from(4.0)
     );
 }
@@ -36,15 +36,15 @@ fn test_add() {
 #[test]
 fn test_sub() {
     assert_eq!(
-        op_sub(&[DataValue::from(1), DataValue::from(2)]).unwrap(),
+        op_sub(&[&DataValue::from(1), &DataValue::from(2)]).unwrap(),
         DataValue::from(-1)
     );
     assert_eq!(
-        op_sub(&[DataValue::from(1), DataValue::from(2.5)]).unwrap(),
+        op_sub(&[&DataValue::from(1), &DataValue::from(2.5)]).unwrap(),
         DataValue::from(-1.5)
     );
     assert_eq!(
-        op_sub(&[DataValue::from(1.5), DataValue::from(2.5)]).unwrap(),
+        op_sub(&[&DataValue::from(1.5), &DataValue::from(2.5)]).unwrap(),
         DataValue::from(-1.0)
     );
 }
@@ -53,15 +53,15 @@ fn test_sub() {
 fn test_mul() {
     assert_eq!(op_mul(&[]).unwrap(), DataValue::from(1));
     assert_eq!(
-        op_mul(&[DataValue::from(2), DataValue::from(3)]).unwrap(),
+        op_mul(&[&DataValue::from(2), &DataValue::from(3)]).unwrap(),
         DataValue::from(6)
     );
     assert_eq!(
-        op_mul(&[DataValue::from(0.5), DataValue::from(0.25)]).unwrap(),
+        op_mul(&[&DataValue::from(0.5), &DataValue::from(0.25)]).unwrap(),
         DataValue::from(0.125)
     );
     assert_eq!(
-        op_mul(&[DataValue::from(0.5), DataValue::from(3)]).unwrap(),
+        op_mul(&[&DataValue::from(0.5), &DataValue::from(3)]).unwrap(),
         DataValue::from(1.5)
     );
 }
@@ -69,53 +69,187 @@ fn test_mul() {
 #[test]
 fn test_div() {
     assert_eq!(
-        op_div(&[DataValue::from(1), DataValue::from(1)]).unwrap(),
+        op_div(&[&DataValue::from(1), &DataValue::from(1)]).unwrap(),
         DataValue::from(1.0)
     );
     assert_eq!(
-        op_div(&[DataValue::from(1), DataValue::from(2)]).unwrap(),
+        op_div(&[&DataValue::from(1), &DataValue::from(2)]).unwrap(),
         DataValue::from(0.5)
     );
     assert_eq!(
-        op_div(&[DataValue::from(7.0), DataValue::from(0.5)]).unwrap(),
+        op_div(&[&DataValue::from(7.0), &DataValue::from(0.5)]).unwrap(),
         DataValue::from(14.0)
     );
-    assert!(op_div(&[DataValue::from(1), DataValue::from(0)]).is_ok());
+    assert!(op_div(&[&DataValue::from(1), &DataValue::from(0)]).is_ok());
 }
 
 #[test]
 fn test_eq_neq() {
     assert_eq!(
-        op_eq(&[DataValue::from(1), DataValue::from(1.0)]).unwrap(),
+        op_eq(&[&DataValue::from(1), &DataValue::from(1.0)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_eq(&[DataValue::from(123), DataValue::from(123)]).unwrap(),
+        op_eq(&[&DataValue::from(123), &DataValue::from(123)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_neq(&[DataValue::from(1), DataValue::from(1.0)]).unwrap(),
+        op_neq(&[&DataValue::from(1), &DataValue::from(1.0)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_neq(&[DataValue::from(123), DataValue::from(123.0)]).unwrap(),
+        op_neq(&[&DataValue::from(123), &DataValue::from(123.0)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_eq(&[DataValue::from(123), DataValue::from(123.1)]).unwrap(),
+        op_eq(&[&DataValue::from(123), &DataValue::from(123.1)]).unwrap(),
         DataValue::from(false)
     );
+    // no rounding of large integers when compared with floats
+    assert_eq!(
+        op_eq(&[
+            &DataValue::from(9007199254740993),
+            &DataValue::from(9007199254740992.0)
+        ])
+        .unwrap(),
+        DataValue::from(false)
+    );
+    assert_eq!(
+        op_eq(&[
+            &DataValue::from(i64::MAX),
+            &DataValue::from(i64::MAX as f64)
+        ])
+        .unwrap(),
+        DataValue::from(false)
+    );
+    assert_eq!(
+        op_eq(&[&DataValue::from(f64::NAN), &DataValue::from(0)]).unwrap(),
+        DataValue::from(false)
+    );
+    assert_eq!(
+        op_neq(&[&DataValue::from(0), &DataValue::from(f64::NAN)]).unwrap(),
+        DataValue::from(true)
+    );
+}
+
+#[test]
+fn test_eq_large_values() {
+    let list = DataValue::List((0..10_000).map(DataValue::from).collect());
+    let string = DataValue::from("x".repeat(10_000));
+    // equal values in different places in memory
+    for v in [&list, &string] {
+        assert_eq!(
+            op_eq(&[&v.clone(), &v.clone()]).unwrap(),
+            DataValue::from(true)
+        );
+        assert_eq!(
+            op_neq(&[&v.clone(), &v.clone()]).unwrap(),
+            DataValue::from(false)
+        );
+        // the very same value, decided without comparing contents
+        assert_eq!(op_eq(&[v, v]).unwrap(), DataValue::from(true));
+        assert_eq!(op_neq(&[v, v]).unwrap(), DataValue::from(false));
+    }
+    let mut other = (0..10_000).map(DataValue::from).collect::<Vec<_>>();
+    other[9_999] = DataValue::from(-1);
+    assert_eq!(
+        op_eq(&[&list, &DataValue::List(other)]).unwrap(),
+        DataValue::from(false)
+    );
+    let other = DataValue::from("x".repeat(9_999) + "y");
+    assert_eq!(op_neq(&[&string, &other]).unwrap(), DataValue::from(true));
+
+    // the same value compared with itself, where both arguments borrow the same value
+    let db = DbInstance::default();
+    let res = db
+        .run_default(
+            "?[a, b, c] := v = [1, 'x', [2.5, -1]], w = [1, 'x', [2.5, -1]], \
+             a = v == v, b = v == w, c = v != v",
+        )
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[true, true, false]]));
+}
+
+#[test]
+fn test_approx_eq() {
+    let eps = DataValue::from(1e-9);
+    assert_eq!(
+        op_approx_eq(&[&DataValue::from(0.1 + 0.2), &DataValue::from(0.3), &eps]).unwrap(),
+        DataValue::from(true)
+    );
+    assert_eq!(
+        op_approx_eq(&[&DataValue::from(0.3), &DataValue::from(0.31), &eps]).unwrap(),
+        DataValue::from(false)
+    );
+    assert_eq!(
+        op_approx_eq(&[&DataValue::from(1), &DataValue::from(1.0000000001), &eps]).unwrap(),
+        DataValue::from(true)
+    );
+    // integers are compared exactly
+    assert_eq!(
+        op_approx_eq(&[
+            &DataValue::from(1),
+            &DataValue::from(2),
+            &DataValue::from(5)
+        ])
+        .unwrap(),
+        DataValue::from(false)
+    );
+    assert_eq!(
+        op_approx_eq(&[
+            &DataValue::from(1),
+            &DataValue::from(2.),
+            &DataValue::from(5)
+        ])
+        .unwrap(),
+        DataValue::from(true)
+    );
+    assert_eq!(
+        op_approx_eq(&[
+            &DataValue::from(f64::INFINITY),
+            &DataValue::from(f64::INFINITY),
+            &eps
+        ])
+        .unwrap(),
+        DataValue::from(true)
+    );
+    assert_eq!(
+        op_approx_eq(&[&DataValue::Null, &DataValue::from(0.3), &eps]).unwrap(),
+        DataValue::Null
+    );
+    assert!(op_approx_eq(&[&DataValue::from("a"), &DataValue::from("a"), &eps]).is_err());
+    assert!(op_approx_eq(&[
+        &DataValue::from(1.),
+        &DataValue::from(1.),
+        &DataValue::from(-1.)
+    ])
+    .is_err());
+
+    assert!(DataValue::from(0.1 + 0.2).approx_eq(&DataValue::from(0.3), 1e-9));
+    assert!(!DataValue::from(0.1 + 0.2).approx_eq(&DataValue::from(0.3), 1e-20));
+    assert!(DataValue::from("a").approx_eq(&DataValue::from("a"), 0.));
+
+    let db = DbInstance::default();
+    let res = db
+        .run_default(
+            "?[a, b] := a = approx_eq(0.1 + 0.2, 0.3, 1e-9), b = approx_eq(0.1, 0.2, 1e-9)",
+        )
+        .unwrap();
+    assert_eq!(
+        res.rows,
+        vec![vec![DataValue::from(true), DataValue::from(false)]]
+    );
 }
 
 #[test]
 fn test_list() {
     assert_eq!(op_list(&[]).unwrap(), DataValue::List(vec![]));
     assert_eq!(
-        op_list(&[DataValue::from(1)]).unwrap(),
+        op_list(&[&DataValue::from(1)]).unwrap(),
         DataValue::List(vec![DataValue::from(1)])
     );
     assert_eq!(
-        op_list(&[DataValue::from(1), DataValue::List(vec![])]).unwrap(),
+        op_list(&[&DataValue::from(1), &DataValue::List(vec![])]).unwrap(),
         DataValue::List(vec![DataValue::from(1), DataValue::List(vec![])])
     );
 }
@@ -124,22 +258,22 @@ fn test_list() {
 fn test_is_in() {
     assert_eq!(
         op_is_in(&[
-            DataValue::from(1),
-            DataValue::List(vec![DataValue::from(1), DataValue::from(2)])
+            &DataValue::from(1),
+            &DataValue::List(vec![DataValue::from(1), DataValue::from(2)])
         ])
         .unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
         op_is_in(&[
-            DataValue::from(3),
-            DataValue::List(vec![DataValue::from(1), DataValue::from(2)])
+            &DataValue::from(3),
+            &DataValue::List(vec![DataValue::from(1), DataValue::from(2)])
         ])
         .unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_in(&[DataValue::from(3), DataValue::List(vec![])]).unwrap(),
+        op_is_in(&[&DataValue::from(3), &DataValue::List(vec![])]).unwrap(),
         DataValue::from(false)
     );
 }
@@ -147,203 +281,235 @@ fn test_is_in() {
 #[test]
 fn test_comparators() {
     assert_eq!(
-        op_ge(&[DataValue::from(2), DataValue::from(1)]).unwrap(),
+        op_ge(&[&DataValue::from(2), &DataValue::from(1)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_ge(&[DataValue::from(2.), DataValue::from(1)]).unwrap(),
+        op_ge(&[&DataValue::from(2.), &DataValue::from(1)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_ge(&[DataValue::from(2), DataValue::from(1.)]).unwrap(),
+        op_ge(&[&DataValue::from(2), &DataValue::from(1.)]).unwrap(),
         DataValue::from(true)
     );
 
     assert_eq!(
-        op_ge(&[DataValue::from(1), DataValue::from(1)]).unwrap(),
+        op_ge(&[&DataValue::from(1), &DataValue::from(1)]).unwrap(),
+        DataValue::from(true)
+    );
+    assert_eq!(
+        op_ge(&[&DataValue::from(1), &DataValue::from(1.0)]).unwrap(),
+        DataValue::from(true)
+    );
+    assert_eq!(
+        op_ge(&[&DataValue::from(1), &DataValue::from(2)]).unwrap(),
+        DataValue::from(false)
+    );
+    assert!(op_ge(&[&DataValue::Null, &DataValue::from(true)]).is_err());
+    assert_eq!(
+        op_lt(&[&DataValue::from(3), &DataValue::from(3.5)]).unwrap(),
+        DataValue::from(true)
+    );
+    assert_eq!(
+        op_lt(&[&DataValue::from(-3), &DataValue::from(-3.5)]).unwrap(),
+        DataValue::from(false)
+    );
+    assert_eq!(
+        op_gt(&[
+            &DataValue::from(9007199254740993),
+            &DataValue::from(9007199254740992.0)
+        ])
+        .unwrap(),
+        DataValue::from(true)
+    );
+    assert_eq!(
+        op_le(&[
+            &DataValue::from(i64::MAX),
+            &DataValue::from(i64::MAX as f64)
+        ])
+        .unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_ge(&[DataValue::from(1), DataValue::from(1.0)]).unwrap(),
+        op_ge(&[&DataValue::from(f64::INFINITY), &DataValue::from(i64::MAX)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_ge(&[DataValue::from(1), DataValue::from(2)]).unwrap(),
+        op_lt(&[&DataValue::from(f64::NAN), &DataValue::from(1)]).unwrap(),
         DataValue::from(false)
     );
-    assert!(op_ge(&[DataValue::Null, DataValue::from(true)]).is_err());
     assert_eq!(
-        op_gt(&[DataValue::from(2), DataValue::from(1)]).unwrap(),
+        op_gt(&[&DataValue::from(2), &DataValue::from(1)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_gt(&[DataValue::from(2.), DataValue::from(1)]).unwrap(),
+        op_gt(&[&DataValue::from(2.), &DataValue::from(1)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_gt(&[DataValue::from(2), DataValue::from(1.)]).unwrap(),
+        op_gt(&[&DataValue::from(2), &DataValue::from(1.)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_gt(&[DataValue::from(1), DataValue::from(1)]).unwrap(),
+        op_gt(&[&DataValue::from(1), &DataValue::from(1)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_gt(&[DataValue::from(1), DataValue::from(1.0)]).unwrap(),
+        op_gt(&[&DataValue::from(1), &DataValue::from(1.0)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_gt(&[DataValue::from(1), DataValue::from(2)]).unwrap(),
+        op_gt(&[&DataValue::from(1), &DataValue::from(2)]).unwrap(),
         DataValue::from(false)
     );
-    assert!(op_gt(&[DataValue::Null, DataValue::from(true)]).is_err());
+    assert!(op_gt(&[&DataValue::Null, &DataValue::from(true)]).is_err());
     assert_eq!(
-        op_le(&[DataValue::from(2), DataValue::from(1)]).unwrap(),
+        op_le(&[&DataValue::from(2), &DataValue::from(1)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_le(&[DataValue::from(2.), DataValue::from(1)]).unwrap(),
+        op_le(&[&DataValue::from(2.), &DataValue::from(1)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_le(&[DataValue::from(2), DataValue::from(1.)]).unwrap(),
+        op_le(&[&DataValue::from(2), &DataValue::from(1.)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_le(&[DataValue::from(1), DataValue::from(1)]).unwrap(),
+        op_le(&[&DataValue::from(1), &DataValue::from(1)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_le(&[DataValue::from(1), DataValue::from(1.0)]).unwrap(),
+        op_le(&[&DataValue::from(1), &DataValue::from(1.0)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_le(&[DataValue::from(1), DataValue::from(2)]).unwrap(),
+        op_le(&[&DataValue::from(1), &DataValue::from(2)]).unwrap(),
         DataValue::from(true)
     );
-    assert!(op_le(&[DataValue::Null, DataValue::from(true)]).is_err());
+    assert!(op_le(&[&DataValue::Null, &DataValue::from(true)]).is_err());
     assert_eq!(
-        op_lt(&[DataValue::from(2), DataValue::from(1)]).unwrap(),
+        op_lt(&[&DataValue::from(2), &DataValue::from(1)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_lt(&[DataValue::from(2.), DataValue::from(1)]).unwrap(),
+        op_lt(&[&DataValue::from(2.), &DataValue::from(1)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_lt(&[DataValue::from(2), DataValue::from(1.)]).unwrap(),
+        op_lt(&[&DataValue::from(2), &DataValue::from(1.)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_lt(&[DataValue::from(1), DataValue::from(1)]).unwrap(),
+        op_lt(&[&DataValue::from(1), &DataValue::from(1)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_lt(&[DataValue::from(1), DataValue::from(1.0)]).unwrap(),
+        op_lt(&[&DataValue::from(1), &DataValue::from(1.0)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_lt(&[DataValue::from(1), DataValue::from(2)]).unwrap(),
+        op_lt(&[&DataValue::from(1), &DataValue::from(2)]).unwrap(),
         DataValue::from(true)
     );
-    assert!(op_lt(&[DataValue::Null, DataValue::from(true)]).is_err());
+    assert!(op_lt(&[&DataValue::Null, &DataValue::from(true)]).is_err());
 }
 
 #[test]
 fn test_max_min() {
-    assert_eq!(op_max(&[DataValue::from(1),]).unwrap(), DataValue::from(1));
+    assert_eq!(op_max(&[&DataValue::from(1),]).unwrap(), DataValue::from(1));
     assert_eq!(
         op_max(&[
-            DataValue::from(1),
-            DataValue::from(2),
-            DataValue::from(3),
-            DataValue::from(4)
+            &DataValue::from(1),
+            &DataValue::from(2),
+            &DataValue::from(3),
+            &DataValue::from(4)
         ])
         .unwrap(),
         DataValue::from(4)
     );
     assert_eq!(
         op_max(&[
-            DataValue::from(1.0),
-            DataValue::from(2),
-            DataValue::from(3),
-            DataValue::from(4)
+            &DataValue::from(1.0),
+            &DataValue::from(2),
+            &DataValue::from(3),
+            &DataValue::from(4)
         ])
         .unwrap(),
         DataValue::from(4)
     );
     assert_eq!(
         op_max(&[
-            DataValue::from(1),
-            DataValue::from(2),
-            DataValue::from(3),
-            DataValue::from(4.0)
+            &DataValue::from(1),
+            &DataValue::from(2),
+            &DataValue::from(3),
+            &DataValue::from(4.0)
         ])
         .unwrap(),
         DataValue::from(4.0)
     );
-    assert!(op_max(&[DataValue::from(true)]).is_err());
+    assert!(op_max(&[&DataValue::from(true)]).is_err());
 
-    assert_eq!(op_min(&[DataValue::from(1),]).unwrap(), DataValue::from(1));
+    assert_eq!(op_min(&[&DataValue::from(1),]).unwrap(), DataValue::from(1));
     assert_eq!(
         op_min(&[
-            DataValue::from(1),
-            DataValue::from(2),
-            DataValue::from(3),
-            DataValue::from(4)
+            &DataValue::from(1),
+            &DataValue::from(2),
+            &DataValue::from(3),
+            &DataValue::from(4)
         ])
         .unwrap(),
         DataValue::from(1)
     );
     assert_eq!(
         op_min(&[
-            DataValue::from(1.0),
-            DataValue::from(2),
-            DataValue::from(3),
-            DataValue::from(4)
+            &DataValue::from(1.0),
+            &DataValue::from(2),
+            &DataValue::from(3),
+            &DataValue::from(4)
         ])
         .unwrap(),
         DataValue::from(1.0)
     );
     assert_eq!(
         op_min(&[
-            DataValue::from(1),
-            DataValue::from(2),
-            DataValue::from(3),
-            DataValue::from(4.0)
+            &DataValue::from(1),
+            &DataValue::from(2),
+            &DataValue::from(3),
+            &DataValue::from(4.0)
         ])
         .unwrap(),
         DataValue::from(1)
     );
-    assert!(op_max(&[DataValue::from(true)]).is_err());
+    assert!(op_max(&[&DataValue::from(true)]).is_err());
 }
 
 #[test]
 fn test_minus() {
     assert_eq!(
-        op_minus(&[DataValue::from(-1)]).unwrap(),
+        op_minus(&[&DataValue::from(-1)]).unwrap(),
         DataValue::from(1)
     );
     assert_eq!(
-        op_minus(&[DataValue::from(1)]).unwrap(),
+        op_minus(&[&DataValue::from(1)]).unwrap(),
         DataValue::from(-1)
     );
     assert_eq!(
-        op_minus(&[DataValue::from(f64::INFINITY)]).unwrap(),
+        op_minus(&[&DataValue::from(f64::INFINITY)]).unwrap(),
         DataValue::from(f64::NEG_INFINITY)
     );
     assert_eq!(
-        op_minus(&[DataValue::from(f64::NEG_INFINITY)]).unwrap(),
+        op_minus(&[&DataValue::from(f64::NEG_INFINITY)]).unwrap(),
         DataValue::from(f64::INFINITY)
     );
 }
 
 #[test]
 fn test_abs() {
-    assert_eq!(op_abs(&[DataValue::from(-1)]).unwrap(), DataValue::from(1));
-    assert_eq!(op_abs(&[DataValue::from(1)]).unwrap(), DataValue::from(1));
+    assert_eq!(op_abs(&[&DataValue::from(-1)]).unwrap(), DataValue::from(1));
+    assert_eq!(op_abs(&[&DataValue::from(1)]).unwrap(), DataValue::from(1));
     assert_eq!(
-        op_abs(&[DataValue::from(-1.5)]).unwrap(),
+        op_abs(&[&DataValue::from(-1.5)]).unwrap(),
         DataValue::from(1.5)
     );
 }
@@ -351,98 +517,230 @@ fn test_abs() {
 #[test]
 fn test_signum() {
     assert_eq!(
-        op_signum(&[DataValue::from(0.1)]).unwrap(),
+        op_signum(&[&DataValue::from(0.1)]).unwrap(),
         DataValue::from(1)
     );
     assert_eq!(
-        op_signum(&[DataValue::from(-0.1)]).unwrap(),
+        op_signum(&[&DataValue::from(-0.1)]).unwrap(),
         DataValue::from(-1)
     );
     assert_eq!(
-        op_signum(&[DataValue::from(0.0)]).unwrap(),
+        op_signum(&[&DataValue::from(0.0)]).unwrap(),
         DataValue::from(0)
     );
     assert_eq!(
-        op_signum(&[DataValue::from(-0.0)]).unwrap(),
+        op_signum(&[&DataValue::from(-0.0)]).unwrap(),
         DataValue::from(-1)
     );
     assert_eq!(
-        op_signum(&[DataValue::from(-3)]).unwrap(),
+        op_signum(&[&DataValue::from(-3)]).unwrap(),
         DataValue::from(-1)
     );
     assert_eq!(
-        op_signum(&[DataValue::from(f64::NEG_INFINITY)]).unwrap(),
+        op_signum(&[&DataValue::from(f64::NEG_INFINITY)]).unwrap(),
         DataValue::from(-1)
     );
-    assert!(op_signum(&[DataValue::from(f64::NAN)])
+    assert!(op_signum(&[&DataValue::from(f64::NAN)])
         .unwrap()
         .get_float()
         .unwrap()
         .is_nan());
+    assert_eq!(op_signum(&[&DataValue::Null]).unwrap(), DataValue::Null);
+}
+
+#[test]
+fn test_sign_clamp() {
+    assert_eq!(
+        op_signum(&[&DataValue::from(-5)]).unwrap(),
+        DataValue::from(-1)
+    );
+    assert_eq!(
+        op_signum(&[&DataValue::from(0)]).unwrap(),
+        DataValue::from(0)
+    );
+    let clamp = |x: DataValue, lo: DataValue, hi: DataValue| op_clamp(&[&x, &lo, &hi]);
+    assert_eq!(
+        clamp(DataValue::from(15), DataValue::from(0), DataValue::from(10)).unwrap(),
+        DataValue::from(10)
+    );
+    assert_eq!(
+        clamp(
+            DataValue::from(-1.5),
+            DataValue::from(0),
+            DataValue::from(10)
+        )
+        .unwrap(),
+        DataValue::from(0)
+    );
+    assert_eq!(
+        clamp(
+            DataValue::from(2.5),
+            DataValue::from(0),
+            DataValue::from(10)
+        )
+        .unwrap(),
+        DataValue::from(2.5)
+    );
+    // bounds are compared with the value by their numeric values
+    assert_eq!(
+        clamp(DataValue::from(5), DataValue::from(2.0), DataValue::from(2)).unwrap(),
+        DataValue::from(2)
+    );
+    assert_eq!(
+        clamp(
+            DataValue::from(2),
+            DataValue::from(2.0),
+            DataValue::from(10)
+        )
+        .unwrap(),
+        DataValue::from(2)
+    );
+    assert!(clamp(DataValue::from(5), DataValue::from(10), DataValue::from(0)).is_err());
+    assert!(clamp(
+        DataValue::from("5"),
+        DataValue::from(0),
+        DataValue::from(10)
+    )
+    .is_err());
+    assert_eq!(
+        clamp(DataValue::Null, DataValue::from(0), DataValue::from(10)).unwrap(),
+        DataValue::Null
+    );
+    assert_eq!(
+        clamp(DataValue::from(5), DataValue::from(0), DataValue::Null).unwrap(),
+        DataValue::Null
+    );
+
+    let db = DbInstance::default();
+    let res = db
+        .run_default("?[a, b, c] := a = sign(-5), b = sign(0), c = clamp(15, 0, 10)")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[-1, 0, 10]]));
+    assert!(db.run_default("?[a] := a = clamp(5, 10, 0)").is_err());
 }
 
 #[test]
 fn test_floor_ceil() {
     assert_eq!(
-        op_floor(&[DataValue::from(-1)]).unwrap(),
+        op_floor(&[&DataValue::from(-1)]).unwrap(),
         DataValue::from(-1)
     );
     assert_eq!(
-        op_floor(&[DataValue::from(-1.5)]).unwrap(),
+        op_floor(&[&DataValue::from(-1.5)]).unwrap(),
         DataValue::from(-2.0)
     );
     assert_eq!(
-        op_floor(&[DataValue::from(1.5)]).unwrap(),
+        op_floor(&[&DataValue::from(1.5)]).unwrap(),
         DataValue::from(1.0)
     );
     assert_eq!(
-        op_ceil(&[DataValue::from(-1)]).unwrap(),
+        op_ceil(&[&DataValue::from(-1)]).unwrap(),
         DataValue::from(-1)
     );
     assert_eq!(
-        op_ceil(&[DataValue::from(-1.5)]).unwrap(),
+        op_ceil(&[&DataValue::from(-1.5)]).unwrap(),
         DataValue::from(-1.0)
     );
     assert_eq!(
-        op_ceil(&[DataValue::from(1.5)]).unwrap(),
+        op_ceil(&[&DataValue::from(1.5)]).unwrap(),
         DataValue::from(2.0)
     );
 }
 
+#[test]
+fn test_format_number() {
+    let fmt = |x: DataValue, d: i64| op_format_number(&[&x, &DataValue::from(d)]);
+    assert_eq!(
+        fmt(DataValue::from(3.14259), 2).unwrap(),
+        DataValue::from("3.14")
+    );
+    assert_eq!(
+        fmt(DataValue::from(2.71868), 3).unwrap(),
+        DataValue::from("2.719")
+    );
+    assert_eq!(
+        fmt(DataValue::from(1.996), 2).unwrap(),
+        DataValue::from("2.00")
+    );
+    assert_eq!(
+        fmt(DataValue::from(-1.234), 1).unwrap(),
+        DataValue::from("-1.2")
+    );
+    assert_eq!(fmt(DataValue::from(0.6), 0).unwrap(), DataValue::from("1"));
+    // exact ties are rounded away from zero, as by `round`
+    assert_eq!(fmt(DataValue::from(2.5), 0).unwrap(), DataValue::from("3"));
+    assert_eq!(
+        fmt(DataValue::from(-0.5), 0).unwrap(),
+        DataValue::from("-1")
+    );
+    assert_eq!(
+        fmt(DataValue::from(0.125), 2).unwrap(),
+        DataValue::from("0.13")
+    );
+    assert_eq!(
+        fmt(DataValue::from(-9.96875), 4).unwrap(),
+        DataValue::from("-9.9688")
+    );
+    assert_eq!(fmt(DataValue::from(9.5), 0).unwrap(), DataValue::from("10"));
+    assert_eq!(
+        fmt(DataValue::from(99.75), 1).unwrap(),
+        DataValue::from("99.8")
+    );
+    // 0.15 is slightly below the tie in binary
+    assert_eq!(
+        fmt(DataValue::from(0.15), 1).unwrap(),
+        DataValue::from("0.1")
+    );
+    assert_eq!(
+        fmt(DataValue::from(42), 2).unwrap(),
+        DataValue::from("42.00")
+    );
+    assert_eq!(fmt(DataValue::from(-7), 0).unwrap(), DataValue::from("-7"));
+    // integers beyond the precision of floats are kept exact
+    assert_eq!(
+        fmt(DataValue::from(i64::MAX), 1).unwrap(),
+        DataValue::from("9223372036854775807.0")
+    );
+    assert_eq!(fmt(DataValue::Null, 2).unwrap(), DataValue::Null);
+    assert!(fmt(DataValue::from(1.5), -1).is_err());
+    assert!(fmt(DataValue::from("1.5"), 1).is_err());
+    assert!(op_format_number(&[&DataValue::from(1.5), &DataValue::from(1.5)]).is_err());
+}
+
 #[test]
 fn test_round() {
     assert_eq!(
-        op_round(&[DataValue::from(0.6)]).unwrap(),
+        op_round(&[&DataValue::from(0.6)]).unwrap(),
         DataValue::from(1.0)
     );
     assert_eq!(
-        op_round(&[DataValue::from(0.5)]).unwrap(),
+        op_round(&[&DataValue::from(0.5)]).unwrap(),
         DataValue::from(1.0)
     );
     assert_eq!(
-        op_round(&[DataValue::from(1.5)]).unwrap(),
+        op_round(&[&DataValue::from(1.5)]).unwrap(),
         DataValue::from(2.0)
     );
     assert_eq!(
-        op_round(&[DataValue::from(-0.6)]).unwrap(),
+        op_round(&[&DataValue::from(-0.6)]).unwrap(),
         DataValue::from(-1.0)
     );
     assert_eq!(
-        op_round(&[DataValue::from(-0.5)]).unwrap(),
+        op_round(&[&DataValue::from(-0.5)]).unwrap(),
         DataValue::from(-1.0)
     );
     assert_eq!(
-        op_round(&[DataValue::from(-1.5)]).unwrap(),
+        op_round(&[&DataValue::from(-1.5)]).unwrap(),
         DataValue::from(-2.0)
     );
 }
 
 #[test]
 fn test_exp() {
-    let n = op_exp(&[DataValue::from(1)]).unwrap().get_float().unwrap();
+    let n = op_exp(&[&DataValue::from(1)]).unwrap().get_float().unwrap();
     assert!(n.abs_diff_eq(&f64::E(), 1E-5));
 
-    let n = op_exp(&[DataValue::from(50.1)])
+    let n = op_exp(&[&DataValue::from(50.1)])
         .unwrap()
         .get_float()
         .unwrap();
@@ -451,7 +749,7 @@ fn test_exp() {
 
 #[test]
 fn test_exp2() {
-    let n = op_exp2(&[DataValue::from(10.)])
+    let n = op_exp2(&[&DataValue::from(10.)])
         .unwrap()
         .get_float()
         .unwrap();
@@ -461,7 +759,7 @@ fn test_exp2() {
 #[test]
 fn test_ln() {
     assert_eq!(
-        op_ln(&[DataValue::from(f64::E())]).unwrap(),
+        op_ln(&[&DataValue::from(f64::E())]).unwrap(),
         DataValue::from(1.0)
     );
 }
@@ -469,7 +767,7 @@ fn test_ln() {
 #[test]
 fn test_log2() {
     assert_eq!(
-        op_log2(&[DataValue::from(1024)]).unwrap(),
+        op_log2(&[&DataValue::from(1024)]).unwrap(),
         DataValue::from(10.)
     );
 }
@@ -477,24 +775,24 @@ fn test_log2() {
 #[test]
 fn test_log10() {
     assert_eq!(
-        op_log10(&[DataValue::from(1000)]).unwrap(),
+        op_log10(&[&DataValue::from(1000)]).unwrap(),
         DataValue::from(3.0)
     );
 }
 
 #[test]
 fn test_trig() {
-    assert!(op_sin(&[DataValue::from(f64::PI() / 2.)])
+    assert!(op_sin(&[&DataValue::from(f64::PI() / 2.)])
         .unwrap()
         .get_float()
         .unwrap()
         .abs_diff_eq(&1.0, 1e-5));
-    assert!(op_cos(&[DataValue::from(f64::PI() / 2.)])
+    assert!(op_cos(&[&DataValue::from(f64::PI() / 2.)])
         .unwrap()
         .get_float()
         .unwrap()
         .abs_diff_eq(&0.0, 1e-5));
-    assert!(op_tan(&[DataValue::from(f64::PI() / 4.)])
+    assert!(op_tan(&[&DataValue::from(f64::PI() / 4.)])
         .unwrap()
         .get_float()
         .unwrap()
@@ -503,22 +801,22 @@ fn test_trig() {
 
 #[test]
 fn test_inv_trig() {
-    assert!(op_asin(&[DataValue::from(1.0)])
+    assert!(op_asin(&[&DataValue::from(1.0)])
         .unwrap()
         .get_float()
         .unwrap()
         .abs_diff_eq(&(f64::PI() / 2.), 1e-5));
-    assert!(op_acos(&[DataValue::from(0)])
+    assert!(op_acos(&[&DataValue::from(0)])
         .unwrap()
         .get_float()
         .unwrap()
         .abs_diff_eq(&(f64::PI() / 2.), 1e-5));
-    assert!(op_atan(&[DataValue::from(1)])
+    assert!(op_atan(&[&DataValue::from(1)])
         .unwrap()
         .get_float()
         .unwrap()
         .abs_diff_eq(&(f64::PI() / 4.), 1e-5));
-    assert!(op_atan2(&[DataValue::from(-1), DataValue::from(-1)])
+    assert!(op_atan2(&[&DataValue::from(-1), &DataValue::from(-1)])
         .unwrap()
         .get_float()
         .unwrap()
@@ -528,7 +826,7 @@ fn test_inv_trig() {
 #[test]
 fn test_pow() {
     assert_eq!(
-        op_pow(&[DataValue::from(2), DataValue::from(10)]).unwrap(),
+        op_pow(&[&DataValue::from(2), &DataValue::from(10)]).unwrap(),
         DataValue::from(1024.0)
     );
 }
@@ -536,59 +834,69 @@ fn test_pow() {
 #[test]
 fn test_mod() {
     assert_eq!(
-        op_mod(&[DataValue::from(-10), DataValue::from(7)]).unwrap(),
+        op_mod(&[&DataValue::from(-10), &DataValue::from(7)]).unwrap(),
         DataValue::from(-3)
     );
-    assert!(op_mod(&[DataValue::from(5), DataValue::from(0.)]).is_ok());
-    assert!(op_mod(&[DataValue::from(5.), DataValue::from(0.)]).is_ok());
-    assert!(op_mod(&[DataValue::from(5.), DataValue::from(0)]).is_ok());
-    assert!(op_mod(&[DataValue::from(5), DataValue::from(0)]).is_err());
+    assert!(op_mod(&[&DataValue::from(5), &DataValue::from(0.)]).is_ok());
+    assert!(op_mod(&[&DataValue::from(5.), &DataValue::from(0.)]).is_ok());
+    assert!(op_mod(&[&DataValue::from(5.), &DataValue::from(0)]).is_ok());
+    assert!(op_mod(&[&DataValue::from(5), &DataValue::from(0)]).is_err());
 }
 
 #[test]
 fn test_boolean() {
     assert_eq!(op_and(&[]).unwrap(), DataValue::from(true));
     assert_eq!(
-        op_and(&[DataValue::from(true), DataValue::from(false)]).unwrap(),
+        op_and(&[&DataValue::from(true), &DataValue::from(false)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(op_or(&[]).unwrap(), DataValue::from(false));
     assert_eq!(
-        op_or(&[DataValue::from(true), DataValue::from(false)]).unwrap(),
+        op_or(&[&DataValue::from(true), &DataValue::from(false)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_negate(&[DataValue::from(false)]).unwrap(),
+        op_negate(&[&DataValue::from(false)]).unwrap(),
         DataValue::from(true)
     );
+    assert_eq!(
+        op_and(&[&DataValue::Null, &DataValue::from(true)]).unwrap(),
+        DataValue::Null
+    );
+    assert_eq!(
+        op_or(&[&DataValue::Null, &DataValue::from(true)]).unwrap(),
+        DataValue::from(true)
+    );
+    assert!(op_and(&[&DataValue::from(false), &DataValue::from(1)]).is_err());
+    assert!(op_or(&[&DataValue::from(true), &DataValue::from("x")]).is_err());
 }
 
 #[test]
 fn test_bits() {
     assert_eq!(
         op_bit_and(&[
-            DataValue::Bytes([0b111000].into()),
-            DataValue::Bytes([0b010101].into())
+            &DataValue::Bytes([0b111000].into()),
+            &DataValue::Bytes([0b010101].into())
         ])
         .unwrap(),
         DataValue::Bytes([0b010000].into())
     );
     assert_eq!(
         op_bit_or(&[
-            DataValue::Bytes([0b111000].into()),
-            DataValue::Bytes([0b010101].into())
+            &DataValue::Bytes([0b111000].into()),
+            &DataValue::Bytes([0b010101].into())
         ])
         .unwrap(),
         DataValue::Bytes([0b111101].into())
     );
     assert_eq!(
-        op_bit_not(&[DataValue::Bytes([0b00111000].into())]).unwrap(),
+        op_bit_not(&[&DataValue::Bytes([0b00111000].into())]).unwrap(),
         DataValue::Bytes([0b11000111].into())
     );
     assert_eq!(
         op_bit_xor(&[
-            DataValue::Bytes([0b111000].into()),
-            DataValue::Bytes([0b010101].into())
+            &DataValue::Bytes([0b111000].into()),
+            &DataValue::Bytes([0b010101].into())
         ])
         .unwrap(),
         DataValue::Bytes([0b101101].into())
@@ -598,7 +906,7 @@ fn test_bits() {
 #[test]
 fn test_pack_bits() {
     assert_eq!(
-        op_pack_bits(&[DataValue::List(vec![DataValue::from(true)])]).unwrap(),
+        op_pack_bits(&[&DataValue::List(vec![DataValue::from(true)])]).unwrap(),
         DataValue::Bytes([0b10000000].into())
     )
 }
@@ -606,7 +914,7 @@ fn test_pack_bits() {
 #[test]
 fn test_unpack_bits() {
     assert_eq!(
-        op_unpack_bits(&[DataValue::Bytes([0b10101010].into())]).unwrap(),
+        op_unpack_bits(&[&DataValue::Bytes([0b10101010].into())]).unwrap(),
         DataValue::List(
             [true, false, true, false, true, false, true, false]
                 .into_iter()
@@ -619,14 +927,14 @@ fn test_unpack_bits() {
 #[test]
 fn test_concat() {
     assert_eq!(
-        op_concat(&[DataValue::Str("abc".into()), DataValue::Str("def".into())]).unwrap(),
+        op_concat(&[&DataValue::Str("abc".into()), &DataValue::Str("def".into())]).unwrap(),
         DataValue::Str("abcdef".into())
     );
 
     assert_eq!(
         op_concat(&[
-            DataValue::List(vec![DataValue::from(true), DataValue::from(false)]),
-            DataValue::List(vec![DataValue::from(true)])
+            &DataValue::List(vec![DataValue::from(true), DataValue::from(false)]),
+            &DataValue::List(vec![DataValue::from(true)])
         ])
         .unwrap(),
         DataValue::List(vec![
@@ -637,18 +945,48 @@ fn test_concat() {
     );
 }
 
+#[test]
+fn test_merge() {
+    let base = DataValue::Json(JsonData(json!({"a": 1, "b": {"c": 2, "d": 3}})));
+    let overlay = DataValue::Json(JsonData(json!({"a": 10, "b": {"c": 20}, "e": 5})));
+    assert_eq!(
+        op_merge(&[&base, &overlay]).unwrap(),
+        DataValue::Json(JsonData(json!({"a": 10, "b": {"c": 20}, "e": 5})))
+    );
+    assert_eq!(
+        op_merge(&[&base, &overlay, &DataValue::from(true)]).unwrap(),
+        DataValue::Json(JsonData(json!({"a": 10, "b": {"c": 20, "d": 3}, "e": 5})))
+    );
+    // deep merging concatenates lists, as `concat` does
+    assert_eq!(
+        op_merge(&[
+            &DataValue::Json(JsonData(json!({"b": {"l": [1]}}))),
+            &DataValue::Json(JsonData(json!({"b": {"l": [2]}}))),
+            &DataValue::from(true)
+        ])
+        .unwrap(),
+        DataValue::Json(JsonData(json!({"b": {"l": [1, 2]}})))
+    );
+    assert!(op_merge(&[&base, &DataValue::from(1)]).is_err());
+    assert!(op_merge(&[&DataValue::Json(JsonData(json!([1]))), &overlay]).is_err());
+}
+
 #[test]
 fn test_str_includes() {
     assert_eq!(
         op_str_includes(&[
-            DataValue::Str("abcdef".into()),
-            DataValue::Str("bcd".into())
+            &DataValue::Str("abcdef".into()),
+            &DataValue::Str("bcd".into())
         ])
         .unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_str_includes(&[DataValue::Str("abcdef".into()), DataValue::Str("bd".into())]).unwrap(),
+        op_str_includes(&[
+            &DataValue::Str("abcdef".into()),
+            &DataValue::Str("bd".into())
+        ])
+        .unwrap(),
         DataValue::from(false)
     );
 }
@@ -656,11 +994,11 @@ fn test_str_includes() {
 #[test]
 fn test_casings() {
     assert_eq!(
-        op_lowercase(&[DataValue::Str("NAÏVE".into())]).unwrap(),
+        op_lowercase(&[&DataValue::Str("NAÏVE".into())]).unwrap(),
         DataValue::Str("naïve".into())
     );
     assert_eq!(
-        op_uppercase(&[DataValue::Str("naïve".into())]).unwrap(),
+        op_uppercase(&[&DataValue::Str("naïve".into())]).unwrap(),
         DataValue::Str("NAÏVE".into())
     );
 }
@@ -668,43 +1006,92 @@ fn test_casings() {
 #[test]
 fn test_trim() {
     assert_eq!(
-        op_trim(&[DataValue::Str(" a ".into())]).unwrap(),
+        op_trim(&[&DataValue::Str(" a ".into())]).unwrap(),
         DataValue::Str("a".into())
     );
     assert_eq!(
-        op_trim_start(&[DataValue::Str(" a ".into())]).unwrap(),
+        op_trim_start(&[&DataValue::Str(" a ".into())]).unwrap(),
         DataValue::Str("a ".into())
     );
     assert_eq!(
-        op_trim_end(&[DataValue::Str(" a ".into())]).unwrap(),
+        op_trim_end(&[&DataValue::Str(" a ".into())]).unwrap(),
         DataValue::Str(" a".into())
     );
 }
 
+#[test]
+fn test_pad() {
+    let s = DataValue::from("ab");
+    assert_eq!(
+        op_lpad(&[&s, &DataValue::from(5)]).unwrap(),
+        DataValue::from("   ab")
+    );
+    assert_eq!(
+        op_rpad(&[&s, &DataValue::from(5), &DataValue::from("xy")]).unwrap(),
+        DataValue::from("abxyx")
+    );
+    assert_eq!(
+        op_lpad(&[
+            &DataValue::from("日本"),
+            &DataValue::from(3),
+            &DataValue::from("*")
+        ])
+        .unwrap(),
+        DataValue::from("*日本")
+    );
+    // strings at or over the width are left unchanged
+    assert_eq!(op_lpad(&[&s, &DataValue::from(2)]).unwrap(), s);
+    assert_eq!(op_rpad(&[&s, &DataValue::from(1)]).unwrap(), s);
+
+    assert!(op_lpad(&[&s, &DataValue::from(5), &DataValue::from("")]).is_err());
+    assert!(op_lpad(&[&s, &DataValue::from(-1)]).is_err());
+    // the width is bounded
+    let longest = op_rpad(&[&s, &DataValue::from(MAX_PAD_WIDTH as i64)]).unwrap();
+    assert_eq!(longest.get_str().unwrap().len(), MAX_PAD_WIDTH);
+    assert!(op_rpad(&[&s, &DataValue::from(MAX_PAD_WIDTH as i64 + 1)]).is_err());
+    assert!(op_lpad(&[&s, &DataValue::from(i64::MAX)]).is_err());
+    assert_eq!(
+        op_rpad(&[&DataValue::Null, &DataValue::from(5)]).unwrap(),
+        DataValue::Null
+    );
+    assert_eq!(
+        op_rpad(&[&s, &DataValue::from(5), &DataValue::Null]).unwrap(),
+        DataValue::Null
+    );
+}
+
 #[test]
 fn test_starts_ends_with() {
     assert_eq!(
         op_starts_with(&[
-            DataValue::Str("abcdef".into()),
-            DataValue::Str("abc".into())
+            &DataValue::Str("abcdef".into()),
+            &DataValue::Str("abc".into())
         ])
         .unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_starts_with(&[DataValue::Str("abcdef".into()), DataValue::Str("bc".into())]).unwrap(),
+        op_starts_with(&[
+            &DataValue::Str("abcdef".into()),
+            &DataValue::Str("bc".into())
+        ])
+        .unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
         op_ends_with(&[
-            DataValue::Str("abcdef".into()),
-            DataValue::Str("def".into())
+            &DataValue::Str("abcdef".into()),
+            &DataValue::Str("def".into())
         ])
         .unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_ends_with(&[DataValue::Str("abcdef".into()), DataValue::Str("bc".into())]).unwrap(),
+        op_ends_with(&[
+            &DataValue::Str("abcdef".into()),
+            &DataValue::Str("bc".into())
+        ])
+        .unwrap(),
         DataValue::from(false)
     );
 }
@@ -713,8 +1100,8 @@ fn test_starts_ends_with() {
 fn test_regex() {
     assert_eq!(
         op_regex_matches(&[
-            DataValue::Str("abcdef".into()),
-            DataValue::Regex(RegexWrapper(Regex::new("c.e").unwrap()))
+            &DataValue::Str("abcdef".into()),
+            &DataValue::Regex(RegexWrapper(Regex::new("c.e").unwrap()))
         ])
         .unwrap(),
         DataValue::from(true)
@@ -722,8 +1109,8 @@ fn test_regex() {
 
     assert_eq!(
         op_regex_matches(&[
-            DataValue::Str("abcdef".into()),
-            DataValue::Regex(RegexWrapper(Regex::new("c.ef$").unwrap()))
+            &DataValue::Str("abcdef".into()),
+            &DataValue::Regex(RegexWrapper(Regex::new("c.ef$").unwrap()))
         ])
         .unwrap(),
         DataValue::from(true)
@@ -731,8 +1118,8 @@ fn test_regex() {
 
     assert_eq!(
         op_regex_matches(&[
-            DataValue::Str("abcdef".into()),
-            DataValue::Regex(RegexWrapper(Regex::new("c.e$").unwrap()))
+            &DataValue::Str("abcdef".into()),
+            &DataValue::Regex(RegexWrapper(Regex::new("c.e$").unwrap()))
         ])
         .unwrap(),
         DataValue::from(false)
@@ -740,9 +1127,9 @@ fn test_regex() {
 
     assert_eq!(
         op_regex_replace(&[
-            DataValue::Str("abcdef".into()),
-            DataValue::Regex(RegexWrapper(Regex::new("[be]").unwrap())),
-            DataValue::Str("x".into())
+            &DataValue::Str("abcdef".into()),
+            &DataValue::Regex(RegexWrapper(Regex::new("[be]").unwrap())),
+            &DataValue::Str("x".into())
         ])
         .unwrap(),
         DataValue::Str("axcdef".into())
@@ -750,17 +1137,17 @@ fn test_regex() {
 
     assert_eq!(
         op_regex_replace_all(&[
-            DataValue::Str("abcdef".into()),
-            DataValue::Regex(RegexWrapper(Regex::new("[be]").unwrap())),
-            DataValue::Str("x".into())
+            &DataValue::Str("abcdef".into()),
+            &DataValue::Regex(RegexWrapper(Regex::new("[be]").unwrap())),
+            &DataValue::Str("x".into())
         ])
         .unwrap(),
         DataValue::Str("axcdxf".into())
     );
     assert_eq!(
         op_regex_extract(&[
-            DataValue::Str("abCDefGH".into()),
-            DataValue::Regex(RegexWrapper(Regex::new("[xayef]|(GH)").unwrap()))
+            &DataValue::Str("abCDefGH".into()),
+            &DataValue::Regex(RegexWrapper(Regex::new("[xayef]|(GH)").unwrap()))
         ])
         .unwrap(),
         DataValue::List(vec![
@@ -772,16 +1159,16 @@ fn test_regex() {
     );
     assert_eq!(
         op_regex_extract_first(&[
-            DataValue::Str("abCDefGH".into()),
-            DataValue::Regex(RegexWrapper(Regex::new("[xayef]|(GH)").unwrap()))
+            &DataValue::Str("abCDefGH".into()),
+            &DataValue::Regex(RegexWrapper(Regex::new("[xayef]|(GH)").unwrap()))
         ])
         .unwrap(),
         DataValue::Str("a".into()),
     );
     assert_eq!(
         op_regex_extract(&[
-            DataValue::Str("abCDefGH".into()),
-            DataValue::Regex(RegexWrapper(Regex::new("xyz").unwrap()))
+            &DataValue::Str("abCDefGH".into()),
+            &DataValue::Regex(RegexWrapper(Regex::new("xyz").unwrap()))
         ])
         .unwrap(),
         DataValue::List(vec![])
@@ -789,118 +1176,172 @@ fn test_regex() {
 
     assert_eq!(
         op_regex_extract_first(&[
-            DataValue::Str("abCDefGH".into()),
-            DataValue::Regex(RegexWrapper(Regex::new("xyz").unwrap()))
+            &DataValue::Str("abCDefGH".into()),
+            &DataValue::Regex(RegexWrapper(Regex::new("xyz").unwrap()))
         ])
         .unwrap(),
         DataValue::Null
     );
 }
 
+#[test]
+fn test_regex_groups() {
+    let re = |p: &str| DataValue::Regex(RegexWrapper(Regex::new(p).unwrap()));
+    let s = DataValue::from("a1 b22 c333");
+    let pattern = re(r"(?P<letter>[a-z])(\d+)");
+    assert_eq!(
+        op_regex_extract(&[&s, &pattern, &DataValue::from(2)]).unwrap(),
+        DataValue::List(vec!["1".into(), "22".into(), "333".into()])
+    );
+    assert_eq!(
+        op_regex_extract_first(&[&s, &pattern, &DataValue::from("letter")]).unwrap(),
+        DataValue::from("a")
+    );
+    // a group that did not take part in the match
+    assert_eq!(
+        op_regex_extract_first(&[&s, &re("a(x)?"), &DataValue::from(1)]).unwrap(),
+        DataValue::Null
+    );
+    assert!(op_regex_extract(&[&s, &pattern, &DataValue::from(-1)]).is_err());
+
+    // nulls propagate
+    assert_eq!(
+        op_regex_matches(&[&DataValue::Null, &pattern]).unwrap(),
+        DataValue::Null
+    );
+    assert_eq!(op_regex(&[&DataValue::Null]).unwrap(), DataValue::Null);
+    assert_eq!(
+        op_regex_extract(&[&s, &DataValue::Null]).unwrap(),
+        DataValue::Null
+    );
+
+    assert!(op_regex(&[&DataValue::from("[a-")]).is_err());
+
+    let db = DbInstance::default();
+    let res = db
+        .run_default(
+            r#"?[a, b] := a = regex_match("abc123", "[a-z]+[0-9]+"),
+                        b = regex_match("abc", "^[0-9]+$")"#,
+        )
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[true, false]]));
+    assert!(db
+        .run_default(r#"?[a] := a = regex_match("abc", "(")"#)
+        .is_err());
+}
+
 #[test]
 fn test_predicates() {
     assert_eq!(
-        op_is_null(&[DataValue::Null]).unwrap(),
+        op_is_null(&[&DataValue::Null]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_is_null(&[DataValue::Bot]).unwrap(),
+        op_is_null(&[&DataValue::Bot]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_int(&[DataValue::from(1)]).unwrap(),
+        op_not_null(&[&DataValue::Null]).unwrap(),
+        DataValue::from(false)
+    );
+    assert_eq!(
+        op_not_null(&[&DataValue::from(0)]).unwrap(),
+        DataValue::from(true)
+    );
+    assert_eq!(
+        op_is_int(&[&DataValue::from(1)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_is_int(&[DataValue::from(1.0)]).unwrap(),
+        op_is_int(&[&DataValue::from(1.0)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_float(&[DataValue::from(1)]).unwrap(),
+        op_is_float(&[&DataValue::from(1)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_float(&[DataValue::from(1.0)]).unwrap(),
+        op_is_float(&[&DataValue::from(1.0)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_is_num(&[DataValue::from(1)]).unwrap(),
+        op_is_num(&[&DataValue::from(1)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_is_num(&[DataValue::from(1.0)]).unwrap(),
+        op_is_num(&[&DataValue::from(1.0)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_is_num(&[DataValue::Null]).unwrap(),
+        op_is_num(&[&DataValue::Null]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_bytes(&[DataValue::Bytes([0b1].into())]).unwrap(),
+        op_is_bytes(&[&DataValue::Bytes([0b1].into())]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_is_bytes(&[DataValue::Null]).unwrap(),
+        op_is_bytes(&[&DataValue::Null]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_list(&[DataValue::List(vec![])]).unwrap(),
+        op_is_list(&[&DataValue::List(vec![])]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_is_list(&[DataValue::Null]).unwrap(),
+        op_is_list(&[&DataValue::Null]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_string(&[DataValue::Str("".into())]).unwrap(),
+        op_is_string(&[&DataValue::Str("".into())]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_is_string(&[DataValue::Null]).unwrap(),
+        op_is_string(&[&DataValue::Null]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_finite(&[DataValue::from(1.0)]).unwrap(),
+        op_is_finite(&[&DataValue::from(1.0)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_is_finite(&[DataValue::from(f64::INFINITY)]).unwrap(),
+        op_is_finite(&[&DataValue::from(f64::INFINITY)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_finite(&[DataValue::from(f64::NAN)]).unwrap(),
+        op_is_finite(&[&DataValue::from(f64::NAN)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_infinite(&[DataValue::from(1.0)]).unwrap(),
+        op_is_infinite(&[&DataValue::from(1.0)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_infinite(&[DataValue::from(f64::INFINITY)]).unwrap(),
+        op_is_infinite(&[&DataValue::from(f64::INFINITY)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_is_infinite(&[DataValue::from(f64::NEG_INFINITY)]).unwrap(),
+        op_is_infinite(&[&DataValue::from(f64::NEG_INFINITY)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_is_infinite(&[DataValue::from(f64::NAN)]).unwrap(),
+        op_is_infinite(&[&DataValue::from(f64::NAN)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_nan(&[DataValue::from(1.0)]).unwrap(),
+        op_is_nan(&[&DataValue::from(1.0)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_nan(&[DataValue::from(f64::INFINITY)]).unwrap(),
+        op_is_nan(&[&DataValue::from(f64::INFINITY)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_nan(&[DataValue::from(f64::NEG_INFINITY)]).unwrap(),
+        op_is_nan(&[&DataValue::from(f64::NEG_INFINITY)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_is_nan(&[DataValue::from(f64::NAN)]).unwrap(),
+        op_is_nan(&[&DataValue::from(f64::NAN)]).unwrap(),
         DataValue::from(true)
     );
 }
@@ -909,8 +1350,8 @@ fn test_predicates() {
 fn test_prepend_append() {
     assert_eq!(
         op_prepend(&[
-            DataValue::List(vec![DataValue::from(1), DataValue::from(2)]),
-            DataValue::Null,
+            &DataValue::List(vec![DataValue::from(1), DataValue::from(2)]),
+            &DataValue::Null,
         ])
         .unwrap(),
         DataValue::List(vec![
@@ -921,8 +1362,8 @@ fn test_prepend_append() {
     );
     assert_eq!(
         op_append(&[
-            DataValue::List(vec![DataValue::from(1), DataValue::from(2)]),
-            DataValue::Null,
+            &DataValue::List(vec![DataValue::from(1), DataValue::from(2)]),
+            &DataValue::Null,
         ])
         .unwrap(),
         DataValue::List(vec![
@@ -936,23 +1377,42 @@ fn test_prepend_append() {
 #[test]
 fn test_length() {
     assert_eq!(
-        op_length(&[DataValue::Str("abc".into())]).unwrap(),
+        op_length(&[&DataValue::Str("abc".into())]).unwrap(),
         DataValue::from(3)
     );
     assert_eq!(
-        op_length(&[DataValue::List(vec![])]).unwrap(),
+        op_length(&[&DataValue::List(vec![])]).unwrap(),
         DataValue::from(0)
     );
     assert_eq!(
-        op_length(&[DataValue::Bytes([].into())]).unwrap(),
+        op_length(&[&DataValue::Bytes([].into())]).unwrap(),
         DataValue::from(0)
     );
+    assert_eq!(
+        op_length(&[&DataValue::Str("héllo".into())]).unwrap(),
+        DataValue::from(5)
+    );
+    assert_eq!(
+        op_length(&[&DataValue::List(vec![DataValue::from(1), DataValue::Null])]).unwrap(),
+        DataValue::from(2)
+    );
+    assert_eq!(
+        op_length(&[&DataValue::Json(JsonData(json!({"a": 1, "b": [1, 2, 3]})))]).unwrap(),
+        DataValue::from(2)
+    );
+    assert_eq!(
+        op_length(&[&DataValue::Json(JsonData(json!([1, 2, 3])))]).unwrap(),
+        DataValue::from(3)
+    );
+    assert_eq!(op_length(&[&DataValue::Null]).unwrap(), DataValue::Null);
+    assert!(op_length(&[&DataValue::from(42)]).is_err());
+    assert!(op_length(&[&DataValue::from(true)]).is_err());
 }
 
 #[test]
 fn test_unicode_normalize() {
     assert_eq!(
-        op_unicode_normalize(&[DataValue::Str("abc".into()), DataValue::Str("nfc".into())])
+        op_unicode_normalize(&[&DataValue::Str("abc".into()), &DataValue::Str("nfc".into())])
             .unwrap(),
         DataValue::Str("abc".into())
     )
@@ -961,7 +1421,7 @@ fn test_unicode_normalize() {
 #[test]
 fn test_sort_reverse() {
     assert_eq!(
-        op_sorted(&[DataValue::List(vec![
+        op_sorted(&[&DataValue::List(vec![
             DataValue::from(2.0),
             DataValue::from(1),
             DataValue::from(2),
@@ -976,7 +1436,7 @@ fn test_sort_reverse() {
         ])
     );
     assert_eq!(
-        op_reverse(&[DataValue::List(vec![
+        op_reverse(&[&DataValue::List(vec![
             DataValue::from(2.0),
             DataValue::from(1),
             DataValue::from(2),
@@ -989,16 +1449,60 @@ fn test_sort_reverse() {
             DataValue::from(1),
             DataValue::from(2.0),
         ])
-    )
+    );
+    let mixed = DataValue::List(vec![
+        DataValue::from("b"),
+        DataValue::from(3),
+        DataValue::Null,
+        DataValue::from(true),
+        DataValue::from("a"),
+        DataValue::from(-1.5),
+    ]);
+    assert_eq!(
+        op_sorted(&[&mixed]).unwrap(),
+        DataValue::List(vec![
+            DataValue::Null,
+            DataValue::from(true),
+            DataValue::from(-1.5),
+            DataValue::from(3),
+            DataValue::from("a"),
+            DataValue::from("b"),
+        ])
+    );
+    assert_eq!(
+        op_sorted(&[&mixed, &DataValue::from(true)]).unwrap(),
+        DataValue::List(vec![
+            DataValue::from("b"),
+            DataValue::from("a"),
+            DataValue::from(3),
+            DataValue::from(-1.5),
+            DataValue::from(true),
+            DataValue::Null,
+        ])
+    );
+    assert_eq!(
+        op_sorted(&[&mixed, &DataValue::from(false)]).unwrap(),
+        op_sorted(&[&mixed]).unwrap()
+    );
+    assert!(op_sorted(&[&DataValue::List(vec![]), &DataValue::from(1)]).is_err());
+
+    let db = DbInstance::default();
+    let res = db
+        .run_default("?[l, a, d] := l = [3, 1, 2], a = sort(l), d = sort(l, true)")
+        .unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([[[3, 1, 2], [1, 2, 3], [3, 2, 1]]])
+    );
 }
 
 #[test]
 fn test_haversine() {
     let d = op_haversine_deg_input(&[
-        DataValue::from(0),
-        DataValue::from(0),
-        DataValue::from(0),
-        DataValue::from(180),
+        &DataValue::from(0),
+        &DataValue::from(0),
+        &DataValue::from(0),
+        &DataValue::from(180),
     ])
     .unwrap()
     .get_float()
@@ -1006,10 +1510,10 @@ fn test_haversine() {
     assert!(d.abs_diff_eq(&f64::PI(), 1e-5));
 
     let d = op_haversine_deg_input(&[
-        DataValue::from(90),
-        DataValue::from(0),
-        DataValue::from(0),
-        DataValue::from(123),
+        &DataValue::from(90),
+        &DataValue::from(0),
+        &DataValue::from(0),
+        &DataValue::from(123),
     ])
     .unwrap()
     .get_float()
@@ -1017,10 +1521,10 @@ fn test_haversine() {
     assert!(d.abs_diff_eq(&(f64::PI() / 2.), 1e-5));
 
     let d = op_haversine(&[
-        DataValue::from(0),
-        DataValue::from(0),
-        DataValue::from(0),
-        DataValue::from(f64::PI()),
+        &DataValue::from(0),
+        &DataValue::from(0),
+        &DataValue::from(0),
+        &DataValue::from(f64::PI()),
     ])
     .unwrap()
     .get_float()
@@ -1031,11 +1535,11 @@ fn test_haversine() {
 #[test]
 fn test_deg_rad() {
     assert_eq!(
-        op_deg_to_rad(&[DataValue::from(180)]).unwrap(),
+        op_deg_to_rad(&[&DataValue::from(180)]).unwrap(),
         DataValue::from(f64::PI())
     );
     assert_eq!(
-        op_rad_to_deg(&[DataValue::from(f64::PI())]).unwrap(),
+        op_rad_to_deg(&[&DataValue::from(f64::PI())]).unwrap(),
         DataValue::from(180.0)
     );
 }
@@ -1043,15 +1547,15 @@ fn test_deg_rad() {
 #[test]
 fn test_first_last() {
     assert_eq!(
-        op_first(&[DataValue::List(vec![])]).unwrap(),
+        op_first(&[&DataValue::List(vec![])]).unwrap(),
         DataValue::Null,
     );
     assert_eq!(
-        op_last(&[DataValue::List(vec![])]).unwrap(),
+        op_last(&[&DataValue::List(vec![])]).unwrap(),
         DataValue::Null,
     );
     assert_eq!(
-        op_first(&[DataValue::List(vec![
+        op_first(&[&DataValue::List(vec![
             DataValue::from(1),
             DataValue::from(2),
         ])])
@@ -1059,7 +1563,7 @@ fn test_first_last() {
         DataValue::from(1),
     );
     assert_eq!(
-        op_last(&[DataValue::List(vec![
+        op_last(&[&DataValue::List(vec![
             DataValue::from(1),
             DataValue::from(2),
         ])])
@@ -1068,18 +1572,57 @@ fn test_first_last() {
     );
 }
 
+#[test]
+fn test_flatten() {
+    let list = DataValue::List;
+    let nested = list(vec![
+        list(vec![DataValue::from(1), DataValue::from(2)]),
+        DataValue::from(3),
+        list(vec![]),
+        list(vec![DataValue::from(4), list(vec![DataValue::from(5)])]),
+    ]);
+    assert_eq!(
+        op_flatten(&[&nested]).unwrap(),
+        list(vec![
+            DataValue::from(1),
+            DataValue::from(2),
+            DataValue::from(3),
+            DataValue::from(4),
+            list(vec![DataValue::from(5)]),
+        ])
+    );
+    assert_eq!(
+        op_flatten_all(&[&nested]).unwrap(),
+        list(vec![
+            DataValue::from(1),
+            DataValue::from(2),
+            DataValue::from(3),
+            DataValue::from(4),
+            DataValue::from(5),
+        ])
+    );
+    assert_eq!(op_flatten(&[&list(vec![])]).unwrap(), list(vec![]));
+    assert_eq!(op_flatten(&[&DataValue::Null]).unwrap(), DataValue::Null);
+    assert_eq!(
+        op_flatten_all(&[&DataValue::Null]).unwrap(),
+        DataValue::Null
+    );
+    assert!(op_flatten(&[&DataValue::from(1)]).is_err());
+    assert!(op_flatten_all(&[&DataValue::from("a")]).is_err());
+}
+
 #[test]
 fn test_chunks() {
     assert_eq!(
         op_chunks(&[
-            DataValue::List(vec![
+            &DataValue::List(vec![
                 DataValue::from(1),
                 DataValue::from(2),
                 DataValue::from(3),
                 DataValue::from(4),
                 DataValue::from(5),
             ]),
-            DataValue::from(2),
+            &DataValue::from(2),
         ])
         .unwrap(),
         DataValue::List(vec![
@@ -1090,14 +1633,14 @@ fn test_chunks() {
     );
     assert_eq!(
         op_chunks_exact(&[
-            DataValue::List(vec![
+            &DataValue::List(vec![
                 DataValue::from(1),
                 DataValue::from(2),
                 DataValue::from(3),
                 DataValue::from(4),
                 DataValue::from(5),
             ]),
-            DataValue::from(2),
+            &DataValue::from(2),
         ])
         .unwrap(),
         DataValue::List(vec![
@@ -1107,14 +1650,14 @@ fn test_chunks() {
     );
     assert_eq!(
         op_windows(&[
-            DataValue::List(vec![
+            &DataValue::List(vec![
                 DataValue::from(1),
                 DataValue::from(2),
                 DataValue::from(3),
                 DataValue::from(4),
                 DataValue::from(5),
             ]),
-            DataValue::from(3),
+            &DataValue::from(3),
         ])
         .unwrap(),
         DataValue::List(vec![
@@ -1139,31 +1682,31 @@ fn test_chunks() {
 
 #[test]
 fn test_get() {
-    assert!(op_get(&[DataValue::List(vec![]), DataValue::from(0)]).is_err());
+    assert!(op_get(&[&DataValue::List(vec![]), &DataValue::from(0)]).is_err());
     assert_eq!(
         op_get(&[
-            DataValue::List(vec![
+            &DataValue::List(vec![
                 DataValue::from(1),
                 DataValue::from(2),
                 DataValue::from(3),
             ]),
-            DataValue::from(1)
+            &DataValue::from(1)
         ])
         .unwrap(),
         DataValue::from(2)
     );
     assert_eq!(
-        op_maybe_get(&[DataValue::List(vec![]), DataValue::from(0)]).unwrap(),
+        op_maybe_get(&[&DataValue::List(vec![]), &DataValue::from(0)]).unwrap(),
         DataValue::Null
     );
     assert_eq!(
         op_maybe_get(&[
-            DataValue::List(vec![
+            &DataValue::List(vec![
                 DataValue::from(1),
                 DataValue::from(2),
                 DataValue::from(3),
             ]),
-            DataValue::from(1)
+            &DataValue::from(1)
         ])
         .unwrap(),
         DataValue::from(2)
@@ -1173,36 +1716,36 @@ fn test_get() {
 #[test]
 fn test_slice() {
     assert!(op_slice(&[
-        DataValue::List(vec![
+        &DataValue::List(vec![
             DataValue::from(1),
             DataValue::from(2),
             DataValue::from(3),
         ]),
-        DataValue::from(1),
-        DataValue::from(4)
+        &DataValue::from(1),
+        &DataValue::from(4)
     ])
     .is_err());
 
     assert!(op_slice(&[
-        DataValue::List(vec![
+        &DataValue::List(vec![
             DataValue::from(1),
             DataValue::from(2),
             DataValue::from(3),
         ]),
-        DataValue::from(1),
-        DataValue::from(3)
+        &DataValue::from(1),
+        &DataValue::from(3)
     ])
     .is_ok());
 
     assert_eq!(
         op_slice(&[
-            DataValue::List(vec![
+            &DataValue::List(vec![
                 DataValue::from(1),
                 DataValue::from(2),
                 DataValue::from(3),
             ]),
-            DataValue::from(1),
-            DataValue::from(-1)
+            &DataValue::from(1),
+            &DataValue::from(-1)
         ])
         .unwrap(),
         DataValue::List(vec![DataValue::from(2)])
@@ -1212,7 +1755,7 @@ fn test_slice() {
 #[test]
 fn test_chars() {
     assert_eq!(
-        op_from_substrings(&[op_chars(&[DataValue::Str("abc".into())]).unwrap()]).unwrap(),
+        op_from_substrings(&[&op_chars(&[&DataValue::Str("abc".into())]).unwrap()]).unwrap(),
         DataValue::Str("abc".into())
     )
 }
@@ -1220,7 +1763,7 @@ fn test_chars() {
 #[test]
 fn test_encode_decode() {
     assert_eq!(
-        op_decode_base64(&[op_encode_base64(&[DataValue::Bytes([1, 2, 3].into())]).unwrap()])
+        op_decode_base64(&[&op_encode_base64(&[&DataValue::Bytes([1, 2, 3].into())]).unwrap()])
             .unwrap(),
         DataValue::Bytes([1, 2, 3].into())
     )
@@ -1229,48 +1772,51 @@ fn test_encode_decode() {
 #[test]
 fn test_to_string() {
     assert_eq!(
-        op_to_string(&[DataValue::from(false)]).unwrap(),
+        op_to_string(&[&DataValue::from(false)]).unwrap(),
         DataValue::Str("false".into())
     );
 }
 
 #[test]
 fn test_to_unity() {
-    assert_eq!(op_to_unity(&[DataValue::Null]).unwrap(), DataValue::from(0));
     assert_eq!(
-        op_to_unity(&[DataValue::from(false)]).unwrap(),
+        op_to_unity(&[&DataValue::Null]).unwrap(),
+        DataValue::from(0)
+    );
+    assert_eq!(
+        op_to_unity(&[&DataValue::from(false)]).unwrap(),
         DataValue::from(0)
     );
     assert_eq!(
-        op_to_unity(&[DataValue::from(true)]).unwrap(),
+        op_to_unity(&[&DataValue::from(true)]).unwrap(),
         DataValue::from(1)
     );
     assert_eq!(
-        op_to_unity(&[DataValue::from(10)]).unwrap(),
+        op_to_unity(&[&DataValue::from(10)]).unwrap(),
         DataValue::from(1)
     );
     assert_eq!(
-        op_to_unity(&[DataValue::from(1.0)]).unwrap(),
+        op_to_unity(&[&DataValue::from(1.0)]).unwrap(),
         DataValue::from(1)
     );
     assert_eq!(
-        op_to_unity(&[DataValue::from(f64::NAN)]).unwrap(),
+        op_to_unity(&[&DataValue::from(f64::NAN)]).unwrap(),
         DataValue::from(1)
     );
     assert_eq!(
-        op_to_unity(&[DataValue::Str("0".into())]).unwrap(),
+        op_to_unity(&[&DataValue::Str("0".into())]).unwrap(),
         DataValue::from(1)
     );
     assert_eq!(
-        op_to_unity(&[DataValue::Str("".into())]).unwrap(),
+        op_to_unity(&[&DataValue::Str("".into())]).unwrap(),
         DataValue::from(0)
     );
     assert_eq!(
-        op_to_unity(&[DataValue::List(vec![])]).unwrap(),
+        op_to_unity(&[&DataValue::List(vec![])]).unwrap(),
         DataValue::from(0)
     );
     assert_eq!(
-        op_to_unity(&[DataValue::List(vec![DataValue::Null])]).unwrap(),
+        op_to_unity(&[&DataValue::List(vec![DataValue::Null])]).unwrap(),
         DataValue::from(1)
     );
 }
@@ -1278,42 +1824,42 @@ fn test_to_unity() {
 #[test]
 fn test_to_float() {
     assert_eq!(
-        op_to_float(&[DataValue::Null]).unwrap(),
+        op_to_float(&[&DataValue::Null]).unwrap(),
         DataValue::from(0.0)
     );
     assert_eq!(
-        op_to_float(&[DataValue::from(false)]).unwrap(),
+        op_to_float(&[&DataValue::from(false)]).unwrap(),
         DataValue::from(0.0)
     );
     assert_eq!(
-        op_to_float(&[DataValue::from(true)]).unwrap(),
+        op_to_float(&[&DataValue::from(true)]).unwrap(),
         DataValue::from(1.0)
     );
     assert_eq!(
-        op_to_float(&[DataValue::from(1)]).unwrap(),
+        op_to_float(&[&DataValue::from(1)]).unwrap(),
         DataValue::from(1.0)
     );
     assert_eq!(
-        op_to_float(&[DataValue::from(1.0)]).unwrap(),
+        op_to_float(&[&DataValue::from(1.0)]).unwrap(),
         DataValue::from(1.0)
     );
-    assert!(op_to_float(&[DataValue::Str("NAN".into())])
+    assert!(op_to_float(&[&DataValue::Str("NAN".into())])
         .unwrap()
         .get_float()
         .unwrap()
         .is_nan());
-    assert!(op_to_float(&[DataValue::Str("INF".into())])
+    assert!(op_to_float(&[&DataValue::Str("INF".into())])
         .unwrap()
         .get_float()
         .unwrap()
         .is_infinite());
-    assert!(op_to_float(&[DataValue::Str("NEG_INF".into())])
+    assert!(op_to_float(&[&DataValue::Str("NEG_INF".into())])
         .unwrap()
         .get_float()
         .unwrap()
         .is_infinite());
     assert_eq!(
-        op_to_float(&[DataValue::Str("3".into())])
+        op_to_float(&[&DataValue::Str("3".into())])
             .unwrap()
             .get_float()
             .unwrap(),
@@ -1327,26 +1873,26 @@ fn test_rand() {
     assert!(n >= 0.);
     assert!(n <= 1.);
     assert_eq!(
-        op_rand_bernoulli(&[DataValue::from(0)]).unwrap(),
+        op_rand_bernoulli(&[&DataValue::from(0)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_rand_bernoulli(&[DataValue::from(1)]).unwrap(),
+        op_rand_bernoulli(&[&DataValue::from(1)]).unwrap(),
         DataValue::from(true)
     );
-    assert!(op_rand_bernoulli(&[DataValue::from(2)]).is_err());
-    let n = op_rand_int(&[DataValue::from(100), DataValue::from(200)])
+    assert!(op_rand_bernoulli(&[&DataValue::from(2)]).is_err());
+    let n = op_rand_int(&[&DataValue::from(100), &DataValue::from(200)])
         .unwrap()
         .get_int()
         .unwrap();
     assert!(n >= 100);
     assert!(n <= 200);
     assert_eq!(
-        op_rand_choose(&[DataValue::List(vec![])]).unwrap(),
+        op_rand_choose(&[&DataValue::List(vec![])]).unwrap(),
         DataValue::Null
     );
     assert_eq!(
-        op_rand_choose(&[DataValue::List(vec![DataValue::from(123)])]).unwrap(),
+        op_rand_choose(&[&DataValue::List(vec![DataValue::from(123)])]).unwrap(),
         DataValue::from(123)
     );
 }
@@ -1355,37 +1901,37 @@ fn test_rand() {
 fn test_set_ops() {
     assert_eq!(
         op_union(&[
-            DataValue::List([1, 2, 3].into_iter().map(DataValue::from).collect()),
-            DataValue::List([2, 3, 4].into_iter().map(DataValue::from).collect()),
-            DataValue::List([3, 4, 5].into_iter().map(DataValue::from).collect())
+            &DataValue::List([1, 2, 3].into_iter().map(DataValue::from).collect()),
+            &DataValue::List([2, 3, 4].into_iter().map(DataValue::from).collect()),
+            &DataValue::List([3, 4, 5].into_iter().map(DataValue::from).collect())
         ])
         .unwrap(),
         DataValue::List([1, 2, 3, 4, 5].into_iter().map(DataValue::from).collect())
     );
     assert_eq!(
         op_intersection(&[
-            DataValue::List(
+            &DataValue::List(
                 [1, 2, 3, 4, 5, 6]
                     .into_iter()
                     .map(DataValue::from)
                     .collect(),
             ),
-            DataValue::List([2, 3, 4].into_iter().map(DataValue::from).collect()),
-            DataValue::List([3, 4, 5].into_iter().map(DataValue::from).collect())
+            &DataValue::List([2, 3, 4].into_iter().map(DataValue::from).collect()),
+            &DataValue::List([3, 4, 5].into_iter().map(DataValue::from).collect())
         ])
         .unwrap(),
         DataValue::List([3, 4].into_iter().map(DataValue::from).collect())
     );
     assert_eq!(
         op_difference(&[
-            DataValue::List(
+            &DataValue::List(
                 [1, 2, 3, 4, 5, 6]
                     .into_iter()
                     .map(DataValue::from)
                     .collect(),
             ),
-            DataValue::List([2, 3, 4].into_iter().map(DataValue::from).collect()),
-            DataValue::List([3, 4, 5].into_iter().map(DataValue::from).collect())
+            &DataValue::List([2, 3, 4].into_iter().map(DataValue::from).collect()),
+            &DataValue::List([3, 4, 5].into_iter().map(DataValue::from).collect())
         ])
         .unwrap(),
         DataValue::List([1, 6].into_iter().map(DataValue::from).collect())
@@ -1396,60 +1942,89 @@ fn test_set_ops() {
 fn test_uuid() {
     let v1 = op_rand_uuid_v1(&[]).unwrap();
     let v4 = op_rand_uuid_v4(&[]).unwrap();
-    assert!(op_is_uuid(&[v4]).unwrap().get_bool().unwrap());
-    assert!(op_uuid_timestamp(&[v1]).unwrap().get_float().is_some());
-    assert!(op_to_uuid(&[DataValue::from("")]).is_err());
-    assert!(op_to_uuid(&[DataValue::from("f3b4958c-52a1-11e7-802a-010203040506")]).is_ok());
+    assert!(op_is_uuid(&[&v4]).unwrap().get_bool().unwrap());
+    assert!(op_uuid_timestamp(&[&v1]).unwrap().get_float().is_some());
+    assert!(op_to_uuid(&[&DataValue::from("")]).is_err());
+    assert!(op_to_uuid(&[&DataValue::from("f3b4958c-52a1-11e7-802a-010203040506")]).is_ok());
 }
 
 #[test]
 fn test_now() {
     let now = op_now(&[]).unwrap();
     assert!(matches!(now, DataValue::Num(_)));
-    let s = op_format_timestamp(&[now]).unwrap();
-    let _dt = op_parse_timestamp(&[s]).unwrap();
+    // some time after 2020-01-01
+    assert!(now.get_float().unwrap() > 1577836800.);
+    let s = op_format_timestamp(&[&now]).unwrap();
+    let _dt = op_parse_timestamp(&[&s]).unwrap();
+
+    let today = op_today(&[]).unwrap().get_float().unwrap();
+    let now = op_now(&[]).unwrap().get_float().unwrap();
+    assert_eq!(today % 86400., 0.);
+    assert!(today <= now && now - today < 86400.);
+}
+
+#[test]
+fn test_date_part() {
+    // 2023-02-14T10:20:30.250Z, a Tuesday
+    let ts = DataValue::from(1676370030.25);
+    let part = |unit: &str| op_date_part(&[&ts, &DataValue::from(unit)]).unwrap();
+    assert_eq!(part("year"), DataValue::from(2023));
+    assert_eq!(part("month"), DataValue::from(2));
+    assert_eq!(part("day"), DataValue::from(14));
+    assert_eq!(part("hour"), DataValue::from(10));
+    assert_eq!(part("minute"), DataValue::from(20));
+    assert_eq!(part("second"), DataValue::from(30));
+    assert_eq!(part("millisecond"), DataValue::from(250));
+    assert_eq!(part("dow"), DataValue::from(2));
+    assert_eq!(part("doy"), DataValue::from(45));
+    assert!(op_date_part(&[&ts, &DataValue::from("fortnight")]).is_err());
+    assert!(op_date_part(&[&DataValue::from("2023"), &DataValue::from("year")]).is_err());
+    assert_eq!(
+        op_date_part(&[&DataValue::Null, &DataValue::from("year")]).unwrap(),
+        DataValue::Null
+    );
 }
 
 #[test]
 fn test_to_bool() {
     assert_eq!(
-        op_to_bool(&[DataValue::Null]).unwrap(),
+        op_to_bool(&[&DataValue::Null]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_to_bool(&[DataValue::from(true)]).unwrap(),
+        op_to_bool(&[&DataValue::from(true)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_to_bool(&[DataValue::from(false)]).unwrap(),
+        op_to_bool(&[&DataValue::from(false)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_to_bool(&[DataValue::from(0)]).unwrap(),
+        op_to_bool(&[&DataValue::from(0)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_to_bool(&[DataValue::from(0.0)]).unwrap(),
+        op_to_bool(&[&DataValue::from(0.0)]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_to_bool(&[DataValue::from(1)]).unwrap(),
+        op_to_bool(&[&DataValue::from(1)]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_to_bool(&[DataValue::from("")]).unwrap(),
+        op_to_bool(&[&DataValue::from("")]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_to_bool(&[DataValue::from("a")]).unwrap(),
+        op_to_bool(&[&DataValue::from("a")]).unwrap(),
         DataValue::from(true)
     );
     assert_eq!(
-        op_to_bool(&[DataValue::List(vec![])]).unwrap(),
+        op_to_bool(&[&DataValue::List(vec![])]).unwrap(),
         DataValue::from(false)
     );
     assert_eq!(
-        op_to_bool(&[DataValue::List(vec![DataValue::from(0)])]).unwrap(),
+        op_to_bool(&[&DataValue::List(vec![DataValue::from(0)])]).unwrap(),
         DataValue::from(true)
     );
 }
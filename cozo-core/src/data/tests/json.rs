@@ -10,7 +10,9 @@
 use serde_json::json;
 
 use crate::data::json::JsonValue;
+use crate::data::memcmp::MemCmpEncoder;
 use crate::data::value::DataValue;
+use crate::evaluate_expressions;
 
 #[test]
 fn bad_values() {
@@ -19,3 +21,27 @@ fn bad_values() {
     println!("{}", JsonValue::from(DataValue::from(f64::NEG_INFINITY)));
     println!("{}", JsonValue::from(DataValue::from(f64::NAN)));
 }
+
+#[test]
+fn object_key_order_is_canonical() {
+    let eval =
+        |src: &str| evaluate_expressions(src, &Default::default(), &Default::default()).unwrap();
+    let a = eval("{'b': 1, 'a': {'y': 2, 'x': 3}}");
+    let b = eval("{'a': {'x': 3, 'y': 2}, 'b': 1}");
+    let c = eval("merge({'b': 1}, {'a': {'y': 2, 'x': 3}})");
+    assert_eq!(a, b);
+    assert_eq!(a, c);
+    assert_eq!(a.cmp(&b), std::cmp::Ordering::Equal);
+
+    let serialized = JsonValue::from(a.clone()).to_string();
+    assert_eq!(serialized, r#"{"a":{"x":3,"y":2},"b":1}"#);
+    assert_eq!(serialized, JsonValue::from(b.clone()).to_string());
+    assert_eq!(serialized, JsonValue::from(c).to_string());
+
+    // stored keys must not depend on the order either
+    let mut ka = vec![];
+    ka.encode_datavalue(&a);
+    let mut kb = vec![];
+    kb.encode_datavalue(&b);
+    assert_eq!(ka, kb);
+}
@@ -47,6 +47,18 @@ fn encode_decode_num() {
     collected.sort();
     collected_copy.sort_by_key(|c| Num::decode_from_key(c).0);
     assert_eq!(collected, collected_copy);
+
+    let encode = |n: Num| {
+        let mut encoder = vec![];
+        encoder.encode_num(n);
+        encoder
+    };
+    assert_eq!(encode(Num::Float(-0.0)), encode(Num::Float(0.0)));
+    assert_eq!(encode(Num::Float(-f64::NAN)), encode(Num::Float(f64::NAN)));
+    assert!(Num::decode_from_key(&encode(Num::Float(-0.0)))
+        .0
+        .get_float()
+        .is_sign_positive());
 }
 
 #[test]
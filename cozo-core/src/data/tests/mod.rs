@@ -11,5 +11,6 @@ mod exprs;
 mod functions;
 mod json;
 mod memcmp;
+mod relation;
 mod validity;
 mod values;
@@ -0,0 +1,124 @@
+/*
+ * Copyright 2023, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use serde_json::json;
+
+use crate::data::functions::current_validity;
+use crate::data::relation::ColType;
+use crate::data::value::{DataValue, JsonData, Vector};
+use crate::parse::parse_type;
+
+#[test]
+fn type_introspection() {
+    let t = parse_type("String?").unwrap();
+    assert!(t.is_nullable());
+    assert_eq!(t.strip_optional(), &ColType::String);
+    assert_eq!(t.element_type(), None);
+
+    let t = parse_type("[Int?]").unwrap();
+    assert!(!t.is_nullable());
+    assert!(matches!(
+        t.strip_optional(),
+        ColType::List { len: None, .. }
+    ));
+    let el = t.element_type().unwrap();
+    assert!(el.is_nullable());
+    assert_eq!(el.strip_optional(), &ColType::Int);
+
+    let t = parse_type("[Float; 3]?").unwrap();
+    assert!(t.is_nullable());
+    assert_eq!(t.element_type().unwrap().strip_optional(), &ColType::Float);
+
+    let t = parse_type("Int").unwrap();
+    assert!(!t.is_nullable());
+    assert_eq!(t.strip_optional(), &ColType::Int);
+    assert_eq!(t.element_type(), None);
+}
+
+#[test]
+fn coercion_by_type() {
+    let coerce =
+        |val: DataValue, typ: &str| val.coerce_to(&parse_type(typ).unwrap(), current_validity());
+
+    // scalars
+    assert_eq!(
+        coerce(DataValue::from(1), "Float").unwrap(),
+        DataValue::from(1.0)
+    );
+    assert_eq!(
+        coerce(DataValue::from(2.0), "Int").unwrap(),
+        DataValue::from(2)
+    );
+    assert!(coerce(DataValue::from(2.5), "Int").is_err());
+    assert!(coerce(DataValue::from(1), "String").is_err());
+    assert_eq!(
+        coerce(DataValue::from("AP8="), "Bytes").unwrap(),
+        DataValue::Bytes(vec![0, 255])
+    );
+    assert_eq!(
+        coerce(DataValue::from(1), "Any").unwrap(),
+        DataValue::from(1)
+    );
+
+    // nullability
+    assert_eq!(coerce(DataValue::Null, "Int?").unwrap(), DataValue::Null);
+    assert_eq!(
+        coerce(DataValue::from(1), "Float?").unwrap(),
+        DataValue::from(1.0)
+    );
+    assert!(coerce(DataValue::Null, "Int").is_err());
+
+    // lists, with their elements coerced
+    assert_eq!(
+        coerce(
+            DataValue::List(vec![DataValue::from(1), DataValue::Null]),
+            "[Float?]"
+        )
+        .unwrap(),
+        DataValue::List(vec![DataValue::from(1.0), DataValue::Null])
+    );
+    assert!(coerce(DataValue::List(vec![DataValue::Null]), "[Float]").is_err());
+    assert!(coerce(DataValue::List(vec![DataValue::from(1)]), "[Int; 2]").is_err());
+    assert!(coerce(DataValue::from(1), "[Int]").is_err());
+
+    // tuples, with each element coerced to its own type
+    assert_eq!(
+        coerce(
+            DataValue::List(vec![DataValue::from(1), DataValue::from("x")]),
+            "(Float, String)"
+        )
+        .unwrap(),
+        DataValue::List(vec![DataValue::from(1.0), DataValue::from("x")])
+    );
+    assert!(coerce(DataValue::List(vec![DataValue::from(1)]), "(Float, String)").is_err());
+
+    // vectors and JSON
+    assert!(matches!(
+        coerce(
+            DataValue::List(vec![DataValue::from(1), DataValue::from(2.5)]),
+            "<F32; 2>"
+        ),
+        Ok(DataValue::Vec(Vector::F32(_)))
+    ));
+    assert!(coerce(DataValue::List(vec![DataValue::from(1)]), "<F32; 2>").is_err());
+    assert_eq!(
+        coerce(
+            DataValue::List(vec![DataValue::from(1), DataValue::from("a")]),
+            "Json"
+        )
+        .unwrap(),
+        DataValue::Json(JsonData(json!([1, "a"])))
+    );
+
+    // validity
+    assert!(matches!(
+        coerce(DataValue::from("ASSERT"), "Validity"),
+        Ok(DataValue::Validity(_))
+    ));
+    assert!(coerce(DataValue::from("yesterday"), "Validity").is_err());
+}
@@ -7,11 +7,16 @@
  *
  */
 
-use std::collections::{BTreeMap, HashMap};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::mem::size_of;
 
+use crate::data::memcmp::MemCmpEncoder;
 use crate::data::symb::Symbol;
-use crate::data::value::DataValue;
+use crate::data::tuple::TupleT;
+use crate::data::value::{DataValue, JsonData};
+use crate::runtime::relation::RelationId;
+use crate::DbInstance;
 
 #[test]
 fn show_size() {
@@ -55,3 +60,193 @@ fn display_datavalues() {
         ])
     );
 }
+
+#[test]
+fn human_readable_display() {
+    let show = |v: DataValue| v.to_display_string();
+    assert_eq!(show(DataValue::Null), "null");
+    assert_eq!(show(DataValue::from(true)), "true");
+    assert_eq!(show(DataValue::from(-12)), "-12");
+    assert_eq!(show(DataValue::from(2.5)), "2.5");
+    assert_eq!(show(DataValue::from(0.1)), "0.1");
+    assert_eq!(show(DataValue::from(3.0)), "3.0");
+    assert_eq!(show(DataValue::from(1e20)), "1e20");
+    assert_eq!(show(DataValue::from(-2.5e-7)), "-2.5e-7");
+    assert_eq!(show(DataValue::from(0.0)), "0.0");
+    assert_eq!(show(DataValue::from(f64::NEG_INFINITY)), "-inf");
+    assert_eq!(show(DataValue::from("say \"hi\"")), r#""say \"hi\"""#);
+    assert_eq!(show(DataValue::Bytes(vec![0, 171, 16])), "0x00ab10");
+    assert_eq!(
+        show(DataValue::List(vec![
+            DataValue::from(1),
+            DataValue::from("你好"),
+            DataValue::List(vec![]),
+            DataValue::Null,
+        ])),
+        r#"[1, "你好", [], null]"#
+    );
+    let json = serde_json::json!({"name": "x", "a b": [1, 2.0], "nested": {"t": null}});
+    assert_eq!(
+        show(DataValue::Json(JsonData(json))),
+        r#"{"a b": [1, 2.0], name: "x", nested: {t: null}}"#
+    );
+}
+
+#[test]
+#[allow(clippy::mutable_key_type)]
+fn hash_consistent_with_eq() {
+    let set: HashSet<DataValue> = [
+        DataValue::from("abc"),
+        DataValue::Str("abc".into()),
+        DataValue::from(String::from("abc")),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(set.len(), 1);
+
+    let set: HashSet<DataValue> = [
+        DataValue::from(f64::NAN),
+        DataValue::from(f64::NAN),
+        DataValue::from(1.5),
+        DataValue::from(1.5),
+        DataValue::List(vec![DataValue::from(1), DataValue::from(f64::NAN)]),
+        DataValue::List(vec![DataValue::from(1), DataValue::from(f64::NAN)]),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(set.len(), 3);
+
+    // the sign of zero and the sign and payload of NaN do not matter
+    let set: HashSet<DataValue> = [
+        DataValue::from(0.0),
+        DataValue::from(-0.0),
+        DataValue::from(f64::NAN),
+        DataValue::from(-f64::NAN),
+        DataValue::from(f64::from_bits(f64::NAN.to_bits() | 1)),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(set.len(), 2);
+    assert_eq!(DataValue::from(-0.0), DataValue::from(0.0));
+    assert_eq!(DataValue::from(-f64::NAN), DataValue::from(f64::NAN));
+
+    // integers and floats never compare equal, so they must stay apart
+    assert_ne!(DataValue::from(1), DataValue::from(1.0));
+    let set: HashSet<DataValue> = [DataValue::from(1), DataValue::from(1.0)]
+        .into_iter()
+        .collect();
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn json_objects_ignore_key_order() {
+    let mut forward = serde_json::Map::new();
+    forward.insert("a".to_string(), serde_json::json!(1));
+    forward.insert("b".to_string(), serde_json::json!([1, 2]));
+    let mut backward = serde_json::Map::new();
+    backward.insert("b".to_string(), serde_json::json!([1, 2]));
+    backward.insert("a".to_string(), serde_json::json!(1));
+    let forward = DataValue::Json(JsonData(forward.into()));
+    let backward = DataValue::Json(JsonData(backward.into()));
+    assert_eq!(forward, backward);
+    assert_eq!(forward.cmp(&backward), Ordering::Equal);
+    assert_eq!(forward.to_string(), backward.to_string());
+    assert_eq!(
+        vec![forward.clone()].encode_as_key(RelationId::SYSTEM),
+        vec![backward.clone()].encode_as_key(RelationId::SYSTEM)
+    );
+    #[allow(clippy::mutable_key_type)]
+    let set: HashSet<DataValue> = [forward, backward].into_iter().collect();
+    assert_eq!(set.len(), 1);
+
+    let json = |v: serde_json::Value| DataValue::Json(JsonData(v));
+    assert_ne!(
+        json(serde_json::json!([1, 2])),
+        json(serde_json::json!([2, 1]))
+    );
+    assert_ne!(
+        DataValue::List(vec![DataValue::from(1), DataValue::from(2)]),
+        DataValue::List(vec![DataValue::from(2), DataValue::from(1)])
+    );
+
+    let db = DbInstance::default();
+    let res = db
+        .run_default(
+            "?[same_obj, same_arr] := same_obj = ({'a': 1, 'b': 2} == {'b': 2, 'a': 1}), \
+             same_arr = ([1, 2] == [2, 1])",
+        )
+        .unwrap();
+    assert_eq!(
+        res.rows,
+        vec![vec![DataValue::from(true), DataValue::from(false)]]
+    );
+}
+
+#[test]
+fn int_float_order_follows_keys() {
+    let big = DataValue::from(9007199254740993i64);
+    let rounded = DataValue::from(9007199254740992.0);
+    // 2^53 + 1 rounds to 2^53 as a float, but the two are still distinct
+    assert_ne!(big, rounded);
+    let values = [
+        DataValue::from(f64::NEG_INFINITY),
+        DataValue::from(i64::MIN),
+        DataValue::from(-1.5),
+        DataValue::from(2),
+        DataValue::from(2.0),
+        DataValue::from(9007199254740992i64),
+        big,
+        rounded,
+        DataValue::from(i64::MAX),
+        DataValue::from(9223372036854775808.0),
+        DataValue::from(f64::NAN),
+    ];
+    // values are ordered as their keys in storage, where integers come first when
+    // their float images tie
+    for a in &values {
+        for b in &values {
+            let mut key_a = vec![];
+            key_a.encode_datavalue(a);
+            let mut key_b = vec![];
+            key_b.encode_datavalue(b);
+            assert_eq!(a.cmp(b), key_a.cmp(&key_b), "{a:?} {b:?}");
+        }
+    }
+    assert!(values.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+fn try_into_rust_values() {
+    assert_eq!(i64::try_from(DataValue::from(42)).unwrap(), 42);
+    assert_eq!(i64::try_from(DataValue::from(3.0)).unwrap(), 3);
+    let err = i64::try_from(DataValue::from(3.5)).unwrap_err();
+    assert_eq!(err.expected, "i64");
+    assert_eq!(err.found, DataValue::from(3.5));
+    assert!(i64::try_from(DataValue::from("42")).is_err());
+
+    assert_eq!(f64::try_from(DataValue::from(1.5)).unwrap(), 1.5);
+    assert_eq!(f64::try_from(DataValue::from(2)).unwrap(), 2.0);
+    let err = f64::try_from(DataValue::Null).unwrap_err();
+    assert_eq!(err.expected, "f64");
+    assert_eq!(err.found, DataValue::Null);
+
+    assert_eq!(String::try_from(DataValue::from("abc")).unwrap(), "abc");
+    let err = String::try_from(DataValue::from(1)).unwrap_err();
+    assert_eq!(err.to_string(), "Cannot convert 1 to String");
+    assert_eq!(err.found, DataValue::from(1));
+
+    assert!(bool::try_from(DataValue::from(true)).unwrap());
+    assert!(!bool::try_from(DataValue::from(false)).unwrap());
+    let err = bool::try_from(DataValue::from(0)).unwrap_err();
+    assert_eq!(err.expected, "bool");
+    assert_eq!(err.found, DataValue::from(0));
+
+    let list = DataValue::List(vec![DataValue::from(1), DataValue::Null]);
+    assert_eq!(
+        Vec::<DataValue>::try_from(list).unwrap(),
+        vec![DataValue::from(1), DataValue::Null]
+    );
+    let err = Vec::<DataValue>::try_from(DataValue::from("[1]")).unwrap_err();
+    assert_eq!(err.expected, "Vec<DataValue>");
+    assert_eq!(err.found, DataValue::from("[1]"));
+}
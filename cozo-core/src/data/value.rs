@@ -8,15 +8,16 @@
 
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
+use miette::Diagnostic;
 use ndarray::Array1;
 use std::cmp::{Ordering, Reverse};
 use std::collections::BTreeSet;
-use std::fmt::{Debug, Display, Formatter};
+use std::fmt::{Debug, Display, Formatter, Write};
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 
 use crate::data::json::JsonValue;
-use crate::data::relation::VecElementType;
+use crate::data::relation::{NullableColType, VecElementType};
 use ordered_float::OrderedFloat;
 use regex::Regex;
 use serde::de::{SeqAccess, Visitor};
@@ -25,6 +26,7 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sha2::digest::FixedOutput;
 use sha2::{Digest, Sha256};
 use smartstring::{LazyCompact, SmartString};
+use thiserror::Error;
 use uuid::Uuid;
 
 /// UUID value in the database
@@ -150,7 +152,7 @@ pub enum DataValue {
     Bool(bool),
     /// number, may be int or float
     Num(Num),
-    /// string
+    /// string, ordered by Unicode code points, which is also the order of their UTF-8 bytes
     Str(SmartString<LazyCompact>),
     /// bytes
     #[serde(with = "serde_bytes")]
@@ -174,6 +176,10 @@ pub enum DataValue {
 }
 
 /// Wrapper for JsonValue
+///
+/// Objects keep their keys sorted (`serde_json` is used without `preserve_order`),
+/// so equality, ordering, hashing and serialization do not depend on insertion order.
+/// Arrays, like [DataValue::List], are compared element by element and are order-sensitive.
 #[derive(Clone, PartialEq, Eq, serde_derive::Deserialize, serde_derive::Serialize)]
 pub struct JsonData(pub JsonValue);
 
@@ -468,6 +474,80 @@ impl From<bool> for DataValue {
     }
 }
 
+/// Error returned when converting a [DataValue] into a Rust value of a different type
+#[derive(Debug, Error, Diagnostic)]
+#[error("Cannot convert {found:?} to {expected}")]
+#[diagnostic(code(eval::type_mismatch))]
+pub struct TypeMismatch {
+    /// The type converted into
+    pub expected: &'static str,
+    /// The value that could not be converted
+    pub found: DataValue,
+}
+
+/// Numbers with an integral value convert, as in [DataValue::get_int].
+impl TryFrom<DataValue> for i64 {
+    type Error = TypeMismatch;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        value.get_int().ok_or(TypeMismatch {
+            expected: "i64",
+            found: value,
+        })
+    }
+}
+
+/// Integers convert too, as in [DataValue::get_float].
+impl TryFrom<DataValue> for f64 {
+    type Error = TypeMismatch;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        value.get_float().ok_or(TypeMismatch {
+            expected: "f64",
+            found: value,
+        })
+    }
+}
+
+impl TryFrom<DataValue> for String {
+    type Error = TypeMismatch;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        match value {
+            DataValue::Str(s) => Ok(s.into()),
+            v => Err(TypeMismatch {
+                expected: "String",
+                found: v,
+            }),
+        }
+    }
+}
+
+impl TryFrom<DataValue> for bool {
+    type Error = TypeMismatch;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        value.get_bool().ok_or(TypeMismatch {
+            expected: "bool",
+            found: value,
+        })
+    }
+}
+
+impl TryFrom<DataValue> for Vec<DataValue> {
+    type Error = TypeMismatch;
+
+    fn try_from(value: DataValue) -> Result<Self, Self::Error> {
+        match value {
+            DataValue::List(l) => Ok(l),
+            v => Err(TypeMismatch {
+                expected: "Vec<DataValue>",
+                found: v,
+            }),
+        }
+    }
+}
+
 /// Representing a number
 #[derive(Copy, Clone, serde_derive::Deserialize, serde_derive::Serialize)]
 pub enum Num {
@@ -477,11 +557,33 @@ pub enum Num {
     Float(f64),
 }
 
+/// The float as it takes part in comparisons: `-0.0` is the same as `0.0`, and all NaNs are
+/// the same regardless of sign and payload. Keys are encoded normalized as well, so that
+/// they are not split by these differences.
+pub(crate) fn normalize_float(f: f64) -> f64 {
+    if f == 0. {
+        0.
+    } else if f.is_nan() {
+        f64::NAN
+    } else {
+        f
+    }
+}
+
+/// Hashing is consistent with the total order of numbers: integers and floats never
+/// compare equal, and normalized floats are compared by `total_cmp`, so hashing their
+/// raw bits is exact.
 impl Hash for Num {
     fn hash<H: Hasher>(&self, state: &mut H) {
         match self {
-            Num::Int(i) => i.hash(state),
-            Num::Float(f) => OrderedFloat(*f).hash(state),
+            Num::Int(i) => {
+                state.write_u8(0);
+                i.hash(state)
+            }
+            Num::Float(f) => {
+                state.write_u8(1);
+                normalize_float(*f).to_bits().hash(state)
+            }
         }
     }
 }
@@ -554,9 +656,12 @@ impl PartialOrd for Num {
 impl Ord for Num {
     fn cmp(&self, other: &Self) -> Ordering {
         match (self, other) {
+            // the order of the keys written by `encode_num`: integers are compared by their
+            // float images, and come first on ties. The comparison operators compare
+            // integers and floats exactly instead, see `cmp_int_float`.
             (Num::Int(i), Num::Float(r)) => {
                 let l = *i as f64;
-                match l.total_cmp(r) {
+                match l.total_cmp(&normalize_float(*r)) {
                     Ordering::Less => Ordering::Less,
                     Ordering::Equal => Ordering::Less,
                     Ordering::Greater => Ordering::Greater,
@@ -564,14 +669,14 @@ impl Ord for Num {
             }
             (Num::Float(l), Num::Int(i)) => {
                 let r = *i as f64;
-                match l.total_cmp(&r) {
+                match normalize_float(*l).total_cmp(&r) {
                     Ordering::Less => Ordering::Less,
                     Ordering::Equal => Ordering::Greater,
                     Ordering::Greater => Ordering::Greater,
                 }
             }
             (Num::Int(l), Num::Int(r)) => l.cmp(r),
-            (Num::Float(l), Num::Float(r)) => l.total_cmp(r),
+            (Num::Float(l), Num::Float(r)) => normalize_float(*l).total_cmp(&normalize_float(*r)),
         }
     }
 }
@@ -627,7 +732,114 @@ impl Display for DataValue {
     }
 }
 
+fn write_display_float(f: f64, out: &mut String) {
+    // very large or small magnitudes use exponent notation, and integral floats keep
+    // a single decimal, so that they do not look like integers
+    if f.is_finite() && f != 0.0 && !(1e-5..1e16).contains(&f.abs()) {
+        write!(out, "{f:e}").unwrap()
+    } else if f.is_finite() && f.fract() == 0.0 {
+        write!(out, "{f:.1}").unwrap()
+    } else {
+        write!(out, "{f}").unwrap()
+    }
+}
+
+fn write_display_seq<T>(
+    items: impl Iterator<Item = T>,
+    out: &mut String,
+    write_item: impl Fn(T, &mut String),
+) {
+    out.push('[');
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_item(item, out);
+    }
+    out.push(']');
+}
+
+fn write_display_json(j: &JsonValue, out: &mut String) {
+    match j {
+        JsonValue::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() => write_display_float(f, out),
+            _ => write!(out, "{n}").unwrap(),
+        },
+        JsonValue::String(s) => write!(out, "{s:?}").unwrap(),
+        JsonValue::Array(a) => write_display_seq(a.iter(), out, write_display_json),
+        JsonValue::Object(o) => {
+            out.push('{');
+            for (i, (k, v)) in o.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                let is_ident = k.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                    && k.chars().all(|c| c.is_alphanumeric() || c == '_');
+                if is_ident {
+                    out.push_str(k);
+                } else {
+                    write!(out, "{k:?}").unwrap();
+                }
+                out.push_str(": ");
+                write_display_json(v, out);
+            }
+            out.push('}');
+        }
+        JsonValue::Null | JsonValue::Bool(_) => write!(out, "{j}").unwrap(),
+    }
+}
+
 impl DataValue {
+    /// Coerce this value into `typing`, exactly as when it is written into a column of that
+    /// type by a mutation or an import: e.g. integers become floats for `Float`, base64
+    /// strings are decoded for `Bytes`, and list and tuple elements are coerced one by one.
+    /// Validity strings such as `"ASSERT"` take the time `cur_vld`.
+    pub fn coerce_to(
+        self,
+        typing: &NullableColType,
+        cur_vld: ValidityTs,
+    ) -> miette::Result<DataValue> {
+        typing.coerce(self, cur_vld)
+    }
+    /// Render this value for people to read, e.g. in a REPL. Unlike [Display], the
+    /// result is not meant to be parsed back: strings are quoted, lists are `[a, b]`,
+    /// JSON objects are `{k: v}`, and floats are written with the fewest digits that
+    /// read back the same value, in exponent notation when very large or small.
+    pub fn to_display_string(&self) -> String {
+        let mut ret = String::new();
+        self.write_display(&mut ret);
+        ret
+    }
+    fn write_display(&self, out: &mut String) {
+        match self {
+            DataValue::Null | DataValue::Bot => out.push_str("null"),
+            DataValue::Bool(b) => write!(out, "{b}").unwrap(),
+            DataValue::Num(Num::Int(i)) => write!(out, "{i}").unwrap(),
+            DataValue::Num(Num::Float(f)) => write_display_float(*f, out),
+            DataValue::Str(s) => write!(out, "{s:?}").unwrap(),
+            DataValue::Bytes(b) => {
+                out.push_str("0x");
+                for byte in b {
+                    write!(out, "{byte:02x}").unwrap();
+                }
+            }
+            DataValue::Uuid(u) => write!(out, "{}", u.0).unwrap(),
+            DataValue::Regex(rx) => write!(out, "/{}/", rx.0.as_str()).unwrap(),
+            DataValue::List(l) => write_display_seq(l.iter(), out, |v, out| v.write_display(out)),
+            DataValue::Set(s) => write_display_seq(s.iter(), out, |v, out| v.write_display(out)),
+            DataValue::Vec(Vector::F32(a)) => {
+                write_display_seq(a.iter(), out, |f, out| write_display_float(*f as f64, out))
+            }
+            DataValue::Vec(Vector::F64(a)) => {
+                write_display_seq(a.iter(), out, |f, out| write_display_float(*f, out))
+            }
+            DataValue::Json(j) => write_display_json(&j.0, out),
+            DataValue::Validity(v) => {
+                let kind = if v.is_assert.0 { "assert" } else { "retract" };
+                write!(out, "{}@{kind}", v.timestamp.0 .0).unwrap()
+            }
+        }
+    }
     /// Returns a slice of bytes if this one is a Bytes
     pub fn get_bytes(&self) -> Option<&[u8]> {
         match self {
@@ -671,6 +883,19 @@ impl DataValue {
             _ => None,
         }
     }
+    /// Whether this one equals `other` up to `epsilon`. Two integers must be equal exactly,
+    /// an integer and a float or two floats are compared as floats, and all other values
+    /// are compared exactly.
+    pub fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        match (self, other) {
+            (DataValue::Num(Num::Int(a)), DataValue::Num(Num::Int(b))) => a == b,
+            (DataValue::Num(a), DataValue::Num(b)) => {
+                let (a, b) = (a.get_float(), b.get_float());
+                a == b || (a - b).abs() <= epsilon
+            }
+            (a, b) => a == b,
+        }
+    }
     /// Returns bool if this one is.
     pub fn get_bool(&self) -> Option<bool> {
         match self {
@@ -678,6 +903,16 @@ impl DataValue {
             _ => None,
         }
     }
+    /// The truth value of this one in conditions and logical operators: `Some` for booleans
+    /// and `None` for null, which propagates as null or counts as not satisfied. Truthiness is
+    /// strict, numbers, strings and all other values are not implicitly boolean but an error.
+    pub(crate) fn is_truthy(&self) -> miette::Result<Option<bool>> {
+        match self {
+            DataValue::Bool(b) => Ok(Some(*b)),
+            DataValue::Null => Ok(None),
+            v => miette::bail!("found {:?} where a boolean value is expected", v),
+        }
+    }
     pub(crate) fn uuid(uuid: Uuid) -> Self {
         Self::Uuid(UuidWrapper(uuid))
     }
@@ -59,7 +59,7 @@ impl FixedRule for CsvReader {
             },
             nullable: false,
         };
-        let types_opts = typing.coerce(types_opts, TERMINAL_VALIDITY.timestamp)?;
+        let types_opts = types_opts.coerce_to(&typing, TERMINAL_VALIDITY.timestamp)?;
         let mut types = vec![];
         for type_str in types_opts.get_slice().unwrap() {
             let type_str = type_str.get_str().unwrap();
@@ -93,7 +93,7 @@ impl FixedRule for CsvReader {
             for (i, typ) in types.iter().enumerate() {
                 match row.get(i) {
                     None => {
-                        if typ.nullable {
+                        if typ.is_nullable() {
                             out_tuple.push(DataValue::Null)
                         } else {
                             bail!(
@@ -105,20 +105,20 @@ impl FixedRule for CsvReader {
                         let dv = DataValue::from(s);
                         match &typ.coltype {
                             ColType::Any | ColType::String => out_tuple.push(dv),
-                            ColType::Uuid => out_tuple.push(match op_to_uuid(&[dv]) {
+                            ColType::Uuid => out_tuple.push(match op_to_uuid(&[&dv]) {
                                 Ok(uuid) => uuid,
                                 Err(err) => {
-                                    if typ.nullable {
+                                    if typ.is_nullable() {
                                         DataValue::Null
                                     } else {
                                         bail!(err)
                                     }
                                 }
                             }),
-                            ColType::Float => out_tuple.push(match op_to_float(&[dv]) {
+                            ColType::Float => out_tuple.push(match op_to_float(&[&dv]) {
                                 Ok(data) => data,
                                 Err(err) => {
-                                    if typ.nullable {
+                                    if typ.is_nullable() {
                                         DataValue::Null
                                     } else {
                                         bail!(err)
@@ -126,10 +126,10 @@ impl FixedRule for CsvReader {
                                 }
                             }),
                             ColType::Int => {
-                                let f = op_to_float(&[dv]).unwrap_or(DataValue::Null);
+                                let f = op_to_float(&[&dv]).unwrap_or(DataValue::Null);
                                 match f.get_int() {
                                     None => {
-                                        if typ.nullable {
+                                        if typ.is_nullable() {
                                             out_tuple.push(DataValue::Null)
                                         } else {
                                             bail!("cannot convert {} to type {}", s, typ)
@@ -32,6 +32,8 @@
 #![allow(clippy::too_many_arguments)]
 
 use std::collections::BTreeMap;
+use std::io::{BufRead, Read, Write};
+use std::ops::Bound;
 use std::path::Path;
 #[allow(unused_imports)]
 use std::time::Instant;
@@ -47,11 +49,14 @@ use miette::{
 };
 use serde_json::json;
 
-pub use data::value::{DataValue, Num, RegexWrapper, UuidWrapper, Validity, ValidityTs};
+pub use data::value::{
+    DataValue, Num, RegexWrapper, TypeMismatch, UuidWrapper, Validity, ValidityTs,
+};
 pub use fixed_rule::{FixedRule, FixedRuleInputRelation, FixedRulePayload};
+pub use query::cost::QueryCost;
 pub use runtime::db::Db;
 pub use runtime::db::NamedRows;
-pub use runtime::relation::decode_tuple_from_kv;
+pub use runtime::relation::{decode_tuple_from_kv, ColumnInfo, RelationDef, RelationStats};
 pub use runtime::temp_store::RegularTempStore;
 pub use storage::mem::{new_cozo_mem, MemStorage};
 #[cfg(feature = "storage-rocksdb")]
@@ -66,13 +71,15 @@ pub use storage::{Storage, StoreTx};
 
 pub use crate::data::expr::Expr;
 use crate::data::json::JsonValue;
+pub use crate::data::relation::{ColType, NullableColType, VecElementType};
 pub use crate::data::symb::Symbol;
 pub use crate::data::value::{JsonData, Vector};
 pub use crate::fixed_rule::SimpleFixedRule;
-pub use crate::parse::SourceSpan;
+pub use crate::parse::{SourceSpan, DEFAULT_MAX_NESTING_DEPTH};
 pub use crate::runtime::callback::CallbackOp;
 pub use crate::runtime::db::evaluate_expressions;
 pub use crate::runtime::db::get_variables;
+pub use crate::runtime::db::ImportOptions;
 pub use crate::runtime::db::Poison;
 pub use crate::runtime::db::ScriptMutability;
 pub use crate::runtime::db::TransactionPayload;
@@ -247,6 +254,290 @@ impl DbInstance {
         )
             .to_string()
     }
+    /// Dispatcher method. See [crate::Db::resolve_relation].
+    pub fn resolve_relation(&self, name: &str) -> Result<Option<RelationDef>> {
+        match self {
+            DbInstance::Mem(db) => db.resolve_relation(name),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.resolve_relation(name),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.resolve_relation(name),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.resolve_relation(name),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.resolve_relation(name),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::exists].
+    pub fn exists(&self, relation: &str, key: &[DataValue]) -> Result<bool> {
+        match self {
+            DbInstance::Mem(db) => db.exists(relation, key),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.exists(relation, key),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.exists(relation, key),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.exists(relation, key),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.exists(relation, key),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::get_row].
+    pub fn get_row(
+        &self,
+        relation: &str,
+        key: &[DataValue],
+    ) -> Result<Option<BTreeMap<String, DataValue>>> {
+        match self {
+            DbInstance::Mem(db) => db.get_row(relation, key),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.get_row(relation, key),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.get_row(relation, key),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.get_row(relation, key),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.get_row(relation, key),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::lookup_by_index].
+    pub fn lookup_by_index(&self, index: &str, value: &[DataValue]) -> Result<Vec<Vec<DataValue>>> {
+        match self {
+            DbInstance::Mem(db) => db.lookup_by_index(index, value),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.lookup_by_index(index, value),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.lookup_by_index(index, value),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.lookup_by_index(index, value),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.lookup_by_index(index, value),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::stats].
+    pub fn stats(&self, relation: &str) -> Result<RelationStats> {
+        match self {
+            DbInstance::Mem(db) => db.stats(relation),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.stats(relation),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.stats(relation),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.stats(relation),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.stats(relation),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::explain_cost].
+    pub fn explain_cost(
+        &self,
+        payload: &str,
+        params: BTreeMap<String, DataValue>,
+    ) -> Result<QueryCost> {
+        match self {
+            DbInstance::Mem(db) => db.explain_cost(payload, params),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.explain_cost(payload, params),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.explain_cost(payload, params),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.explain_cost(payload, params),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.explain_cost(payload, params),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::count_distinct].
+    pub fn count_distinct(&self, relation: &str, column: &str, approximate: bool) -> Result<u64> {
+        match self {
+            DbInstance::Mem(db) => db.count_distinct(relation, column, approximate),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.count_distinct(relation, column, approximate),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.count_distinct(relation, column, approximate),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.count_distinct(relation, column, approximate),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.count_distinct(relation, column, approximate),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::sample].
+    pub fn sample(&self, relation: &str, n: usize, seed: Option<u64>) -> Result<NamedRows> {
+        match self {
+            DbInstance::Mem(db) => db.sample(relation, n, seed),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.sample(relation, n, seed),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.sample(relation, n, seed),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.sample(relation, n, seed),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.sample(relation, n, seed),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::vacuum].
+    pub fn vacuum(&self) -> Result<usize> {
+        match self {
+            DbInstance::Mem(db) => db.vacuum(),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.vacuum(),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.vacuum(),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.vacuum(),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.vacuum(),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::in_edges].
+    pub fn in_edges(&self, relation: &str, dst: &[DataValue]) -> Result<Vec<Vec<DataValue>>> {
+        match self {
+            DbInstance::Mem(db) => db.in_edges(relation, dst),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.in_edges(relation, dst),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.in_edges(relation, dst),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.in_edges(relation, dst),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.in_edges(relation, dst),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::out_edges].
+    pub fn out_edges(
+        &self,
+        relation: &str,
+        src: &[DataValue],
+    ) -> Result<Vec<(Vec<DataValue>, Vec<DataValue>)>> {
+        match self {
+            DbInstance::Mem(db) => db.out_edges(relation, src),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.out_edges(relation, src),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.out_edges(relation, src),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.out_edges(relation, src),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.out_edges(relation, src),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::traverse].
+    pub fn traverse(
+        &self,
+        relation: &str,
+        start: &[DataValue],
+        max_depth: usize,
+    ) -> Result<Vec<(Vec<DataValue>, usize)>> {
+        match self {
+            DbInstance::Mem(db) => db.traverse(relation, start, max_depth),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.traverse(relation, start, max_depth),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.traverse(relation, start, max_depth),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.traverse(relation, start, max_depth),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.traverse(relation, start, max_depth),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::paths].
+    pub fn paths(
+        &self,
+        relation: &str,
+        start: &[DataValue],
+        max_depth: usize,
+    ) -> Result<Vec<Vec<Vec<DataValue>>>> {
+        match self {
+            DbInstance::Mem(db) => db.paths(relation, start, max_depth),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.paths(relation, start, max_depth),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.paths(relation, start, max_depth),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.paths(relation, start, max_depth),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.paths(relation, start, max_depth),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::shortest_path].
+    pub fn shortest_path(
+        &self,
+        relation: &str,
+        src: &[DataValue],
+        dst: &[DataValue],
+    ) -> Result<Option<Vec<Vec<DataValue>>>> {
+        match self {
+            DbInstance::Mem(db) => db.shortest_path(relation, src, dst),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.shortest_path(relation, src, dst),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.shortest_path(relation, src, dst),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.shortest_path(relation, src, dst),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.shortest_path(relation, src, dst),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::storage_version].
+    pub fn storage_version(&self) -> Result<u8> {
+        match self {
+            DbInstance::Mem(db) => db.storage_version(),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.storage_version(),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.storage_version(),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.storage_version(),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.storage_version(),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::edges].
+    pub fn edges(&self, relation: &str, node_arity: usize) -> Result<NamedRows> {
+        match self {
+            DbInstance::Mem(db) => db.edges(relation, node_arity),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.edges(relation, node_arity),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.edges(relation, node_arity),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.edges(relation, node_arity),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.edges(relation, node_arity),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::degree].
+    pub fn degree(&self, relation: &str, node: &[DataValue]) -> Result<(usize, usize)> {
+        match self {
+            DbInstance::Mem(db) => db.degree(relation, node),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.degree(relation, node),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.degree(relation, node),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.degree(relation, node),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.degree(relation, node),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::count_edges_between].
+    pub fn count_edges_between(
+        &self,
+        relation: &str,
+        src: &[DataValue],
+        dst: &[DataValue],
+    ) -> Result<usize> {
+        match self {
+            DbInstance::Mem(db) => db.count_edges_between(relation, src, dst),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.count_edges_between(relation, src, dst),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.count_edges_between(relation, src, dst),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.count_edges_between(relation, src, dst),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.count_edges_between(relation, src, dst),
+        }
+    }
     /// Dispatcher method. See [crate::Db::export_relations].
     pub fn export_relations<I, T>(&self, relations: I) -> Result<BTreeMap<String, NamedRows>>
         where
@@ -265,6 +556,86 @@ impl DbInstance {
             DbInstance::TiKv(db) => db.export_relations(relations),
         }
     }
+    /// Dispatcher method. See [crate::Db::first_row].
+    pub fn first_row(&self, relation: &str) -> Result<Option<Vec<DataValue>>> {
+        match self {
+            DbInstance::Mem(db) => db.first_row(relation),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.first_row(relation),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.first_row(relation),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.first_row(relation),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.first_row(relation),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::last_row].
+    pub fn last_row(&self, relation: &str) -> Result<Option<Vec<DataValue>>> {
+        match self {
+            DbInstance::Mem(db) => db.last_row(relation),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.last_row(relation),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.last_row(relation),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.last_row(relation),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.last_row(relation),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::scan_range].
+    pub fn scan_range(
+        &self,
+        relation: &str,
+        lower: Bound<DataValue>,
+        upper: Bound<DataValue>,
+    ) -> Result<NamedRows> {
+        match self {
+            DbInstance::Mem(db) => db.scan_range(relation, lower, upper),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.scan_range(relation, lower, upper),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.scan_range(relation, lower, upper),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.scan_range(relation, lower, upper),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.scan_range(relation, lower, upper),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::keys].
+    pub fn keys(&self, relation: &str) -> Result<Vec<Vec<DataValue>>> {
+        match self {
+            DbInstance::Mem(db) => db.keys(relation),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.keys(relation),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.keys(relation),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.keys(relation),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.keys(relation),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::join_on_keys].
+    pub fn join_on_keys(
+        &self,
+        relation: &str,
+        others: &[&str],
+        left_join: bool,
+    ) -> Result<NamedRows> {
+        match self {
+            DbInstance::Mem(db) => db.join_on_keys(relation, others, left_join),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.join_on_keys(relation, others, left_join),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.join_on_keys(relation, others, left_join),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.join_on_keys(relation, others, left_join),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.join_on_keys(relation, others, left_join),
+        }
+    }
     /// Export relations to JSON-encoded string.
     /// See [crate::Db::export_relations]
     pub fn export_relations_str(&self, data: &str) -> String {
@@ -305,6 +676,103 @@ impl DbInstance {
             DbInstance::TiKv(db) => db.import_relations(data),
         }
     }
+    /// Dispatcher method. See [crate::Db::update_where].
+    pub fn update_where(
+        &self,
+        relation: &str,
+        assignments: BTreeMap<String, String>,
+        filter: &str,
+        params: BTreeMap<String, DataValue>,
+    ) -> Result<usize> {
+        match self {
+            DbInstance::Mem(db) => db.update_where(relation, assignments, filter, params),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.update_where(relation, assignments, filter, params),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.update_where(relation, assignments, filter, params),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.update_where(relation, assignments, filter, params),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.update_where(relation, assignments, filter, params),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::delete_where].
+    pub fn delete_where(
+        &self,
+        relation: &str,
+        filter: &str,
+        params: BTreeMap<String, DataValue>,
+    ) -> Result<usize> {
+        match self {
+            DbInstance::Mem(db) => db.delete_where(relation, filter, params),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.delete_where(relation, filter, params),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.delete_where(relation, filter, params),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.delete_where(relation, filter, params),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.delete_where(relation, filter, params),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::import_rows].
+    pub fn import_rows(
+        &self,
+        relation: &str,
+        rows: impl IntoIterator<Item = BTreeMap<String, DataValue>>,
+        options: ImportOptions,
+    ) -> Result<usize> {
+        match self {
+            DbInstance::Mem(db) => db.import_rows(relation, rows, options),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.import_rows(relation, rows, options),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.import_rows(relation, rows, options),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.import_rows(relation, rows, options),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.import_rows(relation, rows, options),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::import_jsonl].
+    pub fn import_jsonl(
+        &self,
+        relation: &str,
+        reader: impl BufRead,
+        strict: bool,
+        options: ImportOptions,
+    ) -> Result<usize> {
+        match self {
+            DbInstance::Mem(db) => db.import_jsonl(relation, reader, strict, options),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.import_jsonl(relation, reader, strict, options),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.import_jsonl(relation, reader, strict, options),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.import_jsonl(relation, reader, strict, options),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.import_jsonl(relation, reader, strict, options),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::import_csv].
+    pub fn import_csv(
+        &self,
+        relation: &str,
+        reader: impl Read,
+        options: ImportOptions,
+    ) -> Result<usize> {
+        match self {
+            DbInstance::Mem(db) => db.import_csv(relation, reader, options),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.import_csv(relation, reader, options),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.import_csv(relation, reader, options),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.import_csv(relation, reader, options),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.import_csv(relation, reader, options),
+        }
+    }
     /// Import a relation, the data is given as a JSON string, and the returned result is converted into a string.
     /// See [crate::Db::import_relations].
     pub fn import_relations_str(&self, data: &str) -> String {
@@ -376,6 +844,34 @@ impl DbInstance {
             Err(err) => json!({"ok": false, "message": err.to_string()}).to_string(),
         }
     }
+    /// Dispatcher method. See [crate::Db::snapshot].
+    pub fn snapshot(&self, writer: impl Write) -> Result<()> {
+        match self {
+            DbInstance::Mem(db) => db.snapshot(writer),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.snapshot(writer),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.snapshot(writer),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.snapshot(writer),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.snapshot(writer),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::restore].
+    pub fn restore(&self, reader: impl Read) -> Result<()> {
+        match self {
+            DbInstance::Mem(db) => db.restore(reader),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.restore(reader),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.restore(reader),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.restore(reader),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.restore(reader),
+        }
+    }
     /// Dispatcher method. See [crate::Db::import_from_backup].
     pub fn import_from_backup(
         &self,
@@ -448,6 +944,48 @@ impl DbInstance {
             DbInstance::TiKv(db) => db.unregister_callback(id),
         }
     }
+    /// Dispatcher method. See [crate::Db::set_case_insensitive_names].
+    pub fn set_case_insensitive_names(&self, enabled: bool) {
+        match self {
+            DbInstance::Mem(db) => db.set_case_insensitive_names(enabled),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.set_case_insensitive_names(enabled),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.set_case_insensitive_names(enabled),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.set_case_insensitive_names(enabled),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.set_case_insensitive_names(enabled),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::set_max_nesting_depth].
+    pub fn set_max_nesting_depth(&self, depth: usize) {
+        match self {
+            DbInstance::Mem(db) => db.set_max_nesting_depth(depth),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.set_max_nesting_depth(depth),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.set_max_nesting_depth(depth),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.set_max_nesting_depth(depth),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.set_max_nesting_depth(depth),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::read_only].
+    pub fn read_only(&self) -> Self {
+        match self {
+            DbInstance::Mem(db) => DbInstance::Mem(db.read_only()),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => DbInstance::Sqlite(db.read_only()),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => DbInstance::RocksDb(db.read_only()),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => DbInstance::Sled(db.read_only()),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => DbInstance::TiKv(db.read_only()),
+        }
+    }
     /// Dispatcher method. See [crate::Db::register_fixed_rule].
     pub fn register_fixed_rule<R>(&self, name: String, rule_impl: R) -> Result<()>
         where
@@ -480,6 +1018,24 @@ impl DbInstance {
         }
     }
 
+    /// Dispatcher method. See [crate::Db::batch].
+    pub fn batch<I, T>(&self, queries: I, params: BTreeMap<String, DataValue>) -> Result<()>
+    where
+        T: AsRef<str>,
+        I: IntoIterator<Item = T>,
+    {
+        match self {
+            DbInstance::Mem(db) => db.batch(queries, params),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.batch(queries, params),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.batch(queries, params),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.batch(queries, params),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.batch(queries, params),
+        }
+    }
     /// Dispatcher method. See [crate::Db::run_multi_transaction]
     pub fn run_multi_transaction(
         &self,
@@ -6,7 +6,7 @@
  * You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use itertools::Itertools;
 use lazy_static::lazy_static;
@@ -17,9 +17,9 @@ use thiserror::Error;
 
 use crate::data::expr::{get_op, Bytecode, Expr, NoImplementationError};
 use crate::data::functions::{
-    OP_ADD, OP_AND, OP_COALESCE, OP_CONCAT, OP_DIV, OP_EQ, OP_GE, OP_GT, OP_JSON_OBJECT, OP_LE,
-    OP_LIST, OP_LT, OP_MAYBE_GET, OP_MINUS, OP_MOD, OP_MUL, OP_NEGATE, OP_NEQ, OP_OR, OP_POW,
-    OP_SUB,
+    OP_ADD, OP_AND, OP_CHAINED_CMP, OP_COALESCE, OP_CONCAT, OP_DIV, OP_EQ, OP_GE, OP_GT,
+    OP_JSON_OBJECT, OP_LE, OP_LIST, OP_LT, OP_MAYBE_GET, OP_MINUS, OP_MOD, OP_MUL, OP_NEGATE,
+    OP_NEQ, OP_OR, OP_POW, OP_SUB,
 };
 use crate::data::symb::Symbol;
 use crate::data::value::DataValue;
@@ -37,11 +37,12 @@ lazy_static! {
                 | Op::infix(Rule::op_ge, Left)
                 | Op::infix(Rule::op_le, Left))
             .op(Op::infix(Rule::op_eq, Left) | Op::infix(Rule::op_ne, Left))
-            .op(Op::infix(Rule::op_mod, Left))
             .op(Op::infix(Rule::op_add, Left)
                 | Op::infix(Rule::op_sub, Left)
                 | Op::infix(Rule::op_concat, Left))
-            .op(Op::infix(Rule::op_mul, Left) | Op::infix(Rule::op_div, Left))
+            .op(Op::infix(Rule::op_mul, Left)
+                | Op::infix(Rule::op_div, Left)
+                | Op::infix(Rule::op_mod, Left))
             .op(Op::infix(Rule::op_pow, Right))
             .op(Op::infix(Rule::op_coalesce, Left))
             .op(Op::prefix(Rule::minus))
@@ -65,6 +66,29 @@ pub(crate) fn expr2bytecode(expr: &Expr, collector: &mut Vec<Bytecode>) -> Resul
             val: val.clone(),
             span: *span,
         }),
+        Expr::Apply { op, args, span } if op.name == OP_COALESCE.name => {
+            // the arguments after the first non-null one are not evaluated
+            let mut jump_pos = vec![];
+            for arg in args.iter() {
+                expr2bytecode(arg, collector)?;
+                collector.push(Bytecode::JumpIfNotNull {
+                    jump_to: 0,
+                    span: *span,
+                });
+                jump_pos.push(collector.len() - 1);
+            }
+            collector.push(Bytecode::Const {
+                val: DataValue::Null,
+                span: *span,
+            });
+            let total_len = collector.len();
+            for pos in jump_pos {
+                collector[pos] = Bytecode::JumpIfNotNull {
+                    jump_to: total_len,
+                    span: *span,
+                }
+            }
+        }
         Expr::Apply { op, args, span } => {
             let arity = args.len();
             for arg in args.iter() {
@@ -115,36 +139,69 @@ pub(crate) fn expr2bytecode(expr: &Expr, collector: &mut Vec<Bytecode>) -> Resul
 }
 
 pub(crate) fn build_expr(pair: Pair<'_>, param_pool: &BTreeMap<String, DataValue>) -> Result<Expr> {
+    build_expr_in(pair, param_pool, None)
+}
+
+/// Build the expression of a `:having` option. Applications written as one of the
+/// `aggregated` columns of the output, e.g. `count(id)`, refer to that column.
+pub(crate) fn build_having_expr(
+    pair: Pair<'_>,
+    param_pool: &BTreeMap<String, DataValue>,
+    aggregated: &BTreeSet<String>,
+) -> Result<Expr> {
+    build_expr_in(pair, param_pool, Some(aggregated))
+}
+
+fn build_expr_in(
+    pair: Pair<'_>,
+    param_pool: &BTreeMap<String, DataValue>,
+    aggregated: Option<&BTreeSet<String>>,
+) -> Result<Expr> {
     ensure!(
         pair.as_rule() == Rule::expr,
         InvalidExpression(pair.extract_span())
     );
 
+    // the flag marks comparisons not enclosed in parentheses, which can be chained
     PRATT_PARSER
-        .map_primary(|v| build_term(v, param_pool))
+        .map_primary(|v| Ok((build_term(v, param_pool, aggregated)?, false)))
         .map_infix(build_expr_infix)
         .map_prefix(|op, rhs| {
-            let rhs = rhs?;
+            let (rhs, _) = rhs?;
             let rhs_span = rhs.span();
-            Ok(match op.as_rule() {
-                Rule::minus => Expr::Apply {
-                    op: &OP_MINUS,
-                    args: [rhs].into(),
-                    span: op.extract_span().merge(rhs_span),
-                },
-                Rule::negate => Expr::Apply {
-                    op: &OP_NEGATE,
-                    args: [rhs].into(),
-                    span: op.extract_span().merge(rhs_span),
+            Ok((
+                match op.as_rule() {
+                    Rule::minus => Expr::Apply {
+                        op: &OP_MINUS,
+                        args: [rhs].into(),
+                        span: op.extract_span().merge(rhs_span),
+                    },
+                    Rule::negate => Expr::Apply {
+                        op: &OP_NEGATE,
+                        args: [rhs].into(),
+                        span: op.extract_span().merge(rhs_span),
+                    },
+                    _ => unreachable!(),
                 },
-                _ => unreachable!(),
-            })
+                false,
+            ))
         })
         .parse(pair.into_inner())
+        .map(|(expr, _)| expr)
 }
 
-fn build_expr_infix(lhs: Result<Expr>, op: Pair<'_>, rhs: Result<Expr>) -> Result<Expr> {
-    let args = vec![lhs?, rhs?];
+fn build_expr_infix(
+    lhs: Result<(Expr, bool)>,
+    op: Pair<'_>,
+    rhs: Result<(Expr, bool)>,
+) -> Result<(Expr, bool)> {
+    let (lhs, lhs_is_comparison) = lhs?;
+    let (rhs, _) = rhs?;
+    let is_comparison = matches!(
+        op.as_rule(),
+        Rule::op_gt | Rule::op_ge | Rule::op_lt | Rule::op_le
+    );
+    let args = vec![lhs, rhs];
     let op = match op.as_rule() {
         Rule::op_add => &OP_ADD,
         Rule::op_sub => &OP_SUB,
@@ -168,14 +225,111 @@ fn build_expr_infix(lhs: Result<Expr>, op: Pair<'_>, rhs: Result<Expr>) -> Resul
     let start = args[0].span().0;
     let end = args[1].span().0 + args[1].span().1;
     let length = end - start;
-    Ok(Expr::Apply {
-        op,
-        args: args.into(),
-        span: SourceSpan(start, length),
-    })
+    let span = SourceSpan(start, length);
+    if is_comparison && lhs_is_comparison {
+        let [lhs, rhs]: [Expr; 2] = args.try_into().unwrap();
+        let (mut operands, mut ops) = unchain_comparisons(lhs);
+        operands.push(rhs);
+        ops.push(op);
+        return Ok((chain_comparisons(operands, ops, span), true));
+    }
+    Ok((
+        Expr::Apply {
+            op,
+            args: args.into(),
+            span,
+        },
+        is_comparison,
+    ))
+}
+
+/// Build `a < b <= c` from its operands and comparisons. If the inner operands are
+/// variables or constants, this is the conjunction `a < b && b <= c`, otherwise the
+/// operands are evaluated only once by `chained_cmp`.
+fn chain_comparisons(
+    operands: Vec<Expr>,
+    ops: Vec<&'static crate::data::expr::Op>,
+    span: SourceSpan,
+) -> Expr {
+    let inner_is_simple = operands[1..operands.len() - 1]
+        .iter()
+        .all(|e| matches!(e, Expr::Binding { .. } | Expr::Const { .. }));
+    if inner_is_simple {
+        let comparisons = ops
+            .into_iter()
+            .zip(operands.iter().tuple_windows())
+            .map(|(op, (l, r))| Expr::Apply {
+                op,
+                args: [l.clone(), r.clone()].into(),
+                span: l.span().merge(r.span()),
+            })
+            .collect_vec();
+        Expr::Apply {
+            op: &OP_AND,
+            args: comparisons.into(),
+            span,
+        }
+    } else {
+        let mut args = vec![];
+        let mut operands = operands.into_iter();
+        args.push(operands.next().unwrap());
+        for (op, operand) in ops.into_iter().zip(operands) {
+            let name = op.name.strip_prefix("OP_").unwrap().to_ascii_lowercase();
+            args.push(Expr::Const {
+                val: DataValue::from(name),
+                span,
+            });
+            args.push(operand);
+        }
+        Expr::Apply {
+            op: &OP_CHAINED_CMP,
+            args: args.into(),
+            span,
+        }
+    }
+}
+
+/// The inverse of [chain_comparisons], also accepting a single comparison.
+fn unchain_comparisons(expr: Expr) -> (Vec<Expr>, Vec<&'static crate::data::expr::Op>) {
+    match expr {
+        Expr::Apply { op, args, .. } if *op == OP_AND => {
+            let mut operands = vec![];
+            let mut ops = vec![];
+            for comparison in args.into_vec() {
+                let (mut pair, mut op) = unchain_comparisons(comparison);
+                if operands.is_empty() {
+                    operands.push(pair.swap_remove(0));
+                    operands.push(pair.pop().unwrap());
+                } else {
+                    operands.push(pair.pop().unwrap());
+                }
+                ops.append(&mut op);
+            }
+            (operands, ops)
+        }
+        Expr::Apply { op, args, .. } if *op == OP_CHAINED_CMP => {
+            let mut operands = vec![];
+            let mut ops = vec![];
+            for (i, arg) in args.into_vec().into_iter().enumerate() {
+                if i % 2 == 0 {
+                    operands.push(arg);
+                } else {
+                    let name = arg.get_const().and_then(|v| v.get_str()).unwrap();
+                    ops.push(get_op(name).unwrap());
+                }
+            }
+            (operands, ops)
+        }
+        Expr::Apply { op, args, .. } => (args.into_vec(), vec![op]),
+        _ => unreachable!(),
+    }
 }
 
-fn build_term(pair: Pair<'_>, param_pool: &BTreeMap<String, DataValue>) -> Result<Expr> {
+fn build_term(
+    pair: Pair<'_>,
+    param_pool: &BTreeMap<String, DataValue>,
+    aggregated: Option<&BTreeSet<String>>,
+) -> Result<Expr> {
     let span = pair.extract_span();
     let op = pair.as_rule();
     Ok(match op {
@@ -199,11 +353,6 @@ fn build_term(pair: Pair<'_>, param_pool: &BTreeMap<String, DataValue>) -> Resul
             }
         }
         Rule::pos_int => {
-            #[derive(Error, Diagnostic, Debug)]
-            #[error("Cannot parse integer")]
-            #[diagnostic(code(parser::bad_pos_int))]
-            struct BadIntError(#[label] SourceSpan);
-
             let i = pair
                 .as_str()
                 .replace('_', "")
@@ -215,21 +364,21 @@ fn build_term(pair: Pair<'_>, param_pool: &BTreeMap<String, DataValue>) -> Resul
             }
         }
         Rule::hex_pos_int => {
-            let i = parse_int(pair.as_str(), 16);
+            let i = parse_int(pair.as_str(), 16, span)?;
             Expr::Const {
                 val: DataValue::from(i),
                 span,
             }
         }
         Rule::octo_pos_int => {
-            let i = parse_int(pair.as_str(), 8);
+            let i = parse_int(pair.as_str(), 8, span)?;
             Expr::Const {
                 val: DataValue::from(i),
                 span,
             }
         }
         Rule::bin_pos_int => {
-            let i = parse_int(pair.as_str(), 2);
+            let i = parse_int(pair.as_str(), 2, span)?;
             Expr::Const {
                 val: DataValue::from(i),
                 span,
@@ -269,7 +418,7 @@ fn build_term(pair: Pair<'_>, param_pool: &BTreeMap<String, DataValue>) -> Resul
         Rule::list => {
             let mut collected = vec![];
             for p in pair.into_inner() {
-                collected.push(build_expr(p, param_pool)?)
+                collected.push(build_expr_in(p, param_pool, aggregated)?)
             }
             Expr::Apply {
                 op: &OP_LIST,
@@ -280,11 +429,20 @@ fn build_term(pair: Pair<'_>, param_pool: &BTreeMap<String, DataValue>) -> Resul
         Rule::object => {
             let mut args = vec![];
             for p in pair.into_inner() {
+                if p.as_rule() == Rule::var {
+                    // shorthand `{a}` for `{"a": a}`
+                    args.push(Expr::Const {
+                        val: DataValue::from(p.as_str()),
+                        span: p.extract_span(),
+                    });
+                    args.push(build_term(p, param_pool, aggregated)?);
+                    continue;
+                }
                 let mut p = p.into_inner();
                 let k = p.next().unwrap();
                 let v = p.next().unwrap();
-                let k = build_expr(k, param_pool)?;
-                let v = build_expr(v, param_pool)?;
+                let k = build_expr_in(k, param_pool, aggregated)?;
+                let v = build_expr_in(v, param_pool, aggregated)?;
                 args.push(k);
                 args.push(v);
             }
@@ -295,6 +453,15 @@ fn build_term(pair: Pair<'_>, param_pool: &BTreeMap<String, DataValue>) -> Resul
             }
         }
         Rule::apply => {
+            if let Some(aggregated) = aggregated {
+                let written: String = pair.as_str().split_whitespace().collect();
+                if aggregated.contains(&written) {
+                    return Ok(Expr::Binding {
+                        var: Symbol::new(written, span),
+                        tuple_pos: None,
+                    });
+                }
+            }
             let mut p = pair.into_inner();
             let ident_p = p.next().unwrap();
             let ident = ident_p.as_str();
@@ -302,7 +469,7 @@ fn build_term(pair: Pair<'_>, param_pool: &BTreeMap<String, DataValue>) -> Resul
                 .next()
                 .unwrap()
                 .into_inner()
-                .map(|v| build_expr(v, param_pool))
+                .map(|v| build_expr_in(v, param_pool, aggregated))
                 .try_collect()?;
             #[derive(Error, Diagnostic, Debug)]
             #[error("Named function '{0}' not found")]
@@ -419,13 +586,18 @@ fn build_term(pair: Pair<'_>, param_pool: &BTreeMap<String, DataValue>) -> Resul
                 },
             }
         }
-        Rule::grouping => build_expr(pair.into_inner().next().unwrap(), param_pool)?,
+        Rule::grouping => build_expr_in(pair.into_inner().next().unwrap(), param_pool, aggregated)?,
         r => unreachable!("Encountered unknown op {:?}", r),
     })
 }
 
-pub(crate) fn parse_int(s: &str, radix: u32) -> i64 {
-    i64::from_str_radix(&s[2..].replace('_', ""), radix).unwrap()
+#[derive(Error, Diagnostic, Debug)]
+#[error("Cannot parse integer")]
+#[diagnostic(code(parser::bad_pos_int))]
+struct BadIntError(#[label] SourceSpan);
+
+pub(crate) fn parse_int(s: &str, radix: u32, span: SourceSpan) -> Result<i64> {
+    Ok(i64::from_str_radix(&s[2..].replace('_', ""), radix).map_err(|_| BadIntError(span))?)
 }
 
 pub(crate) fn parse_string(pair: Pair<'_>) -> Result<SmartString<LazyCompact>> {
@@ -463,7 +635,7 @@ fn parse_quoted_string(pair: Pair<'_>) -> Result<SmartString<LazyCompact>> {
             r"\r" => ret.push('\r'),
             r"\t" => ret.push('\t'),
             s if s.starts_with(r"\u") => {
-                let code = parse_int(s, 16) as u32;
+                let code = parse_int(s, 16, pair.extract_span())? as u32;
                 let ch = char::from_u32(code)
                     .ok_or_else(|| InvalidUtf8Error(code, pair.extract_span()))?;
                 ret.push(ch);
@@ -492,7 +664,7 @@ fn parse_s_quoted_string(pair: Pair<'_>) -> Result<SmartString<LazyCompact>> {
             r"\r" => ret.push('\r'),
             r"\t" => ret.push('\t'),
             s if s.starts_with(r"\u") => {
-                let code = parse_int(s, 16) as u32;
+                let code = parse_int(s, 16, pair.extract_span())? as u32;
                 let ch = char::from_u32(code)
                     .ok_or_else(|| InvalidUtf8Error(code, pair.extract_span()))?;
                 ret.push(ch);
@@ -11,7 +11,7 @@ use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Display, Formatter};
 use std::sync::Arc;
 
-use either::{Either, Left};
+use either::{Either, Left, Right};
 use miette::{bail, Diagnostic, IntoDiagnostic, Result};
 use pest::error::InputLocation;
 use pest::Parser;
@@ -20,6 +20,7 @@ use thiserror::Error;
 
 use crate::data::program::InputProgram;
 use crate::data::relation::NullableColType;
+use crate::data::symb::fold_name;
 use crate::data::value::{DataValue, ValidityTs};
 use crate::parse::expr::build_expr;
 use crate::parse::imperative::parse_imperative_block;
@@ -106,7 +107,62 @@ pub(crate) type ImperativeCondition = Either<SmartString<LazyCompact>, Imperativ
 
 pub(crate) type ImperativeProgram = Vec<ImperativeStmt>;
 
+impl ImperativeStmtClause {
+    fn fold_case(&mut self) {
+        self.prog.fold_case();
+        if let Some(name) = &mut self.store_as {
+            *name = fold_name(name);
+        }
+    }
+}
+
 impl ImperativeStmt {
+    fn fold_case(&mut self) {
+        match self {
+            ImperativeStmt::Break { .. } | ImperativeStmt::Continue { .. } => {}
+            ImperativeStmt::Return { returns } => {
+                for ret in returns {
+                    match ret {
+                        Left(prog) => prog.fold_case(),
+                        Right(name) => *name = fold_name(name),
+                    }
+                }
+            }
+            ImperativeStmt::Program { prog } | ImperativeStmt::IgnoreErrorProgram { prog } => {
+                prog.fold_case()
+            }
+            ImperativeStmt::SysOp { sysop } => {
+                sysop.sysop.fold_case();
+                if let Some(name) = &mut sysop.store_as {
+                    *name = fold_name(name);
+                }
+            }
+            ImperativeStmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                match condition {
+                    Left(name) => *name = fold_name(name),
+                    Right(prog) => prog.fold_case(),
+                }
+                for stmt in then_branch.iter_mut().chain(else_branch.iter_mut()) {
+                    stmt.fold_case();
+                }
+            }
+            ImperativeStmt::Loop { body, .. } => {
+                for stmt in body {
+                    stmt.fold_case();
+                }
+            }
+            ImperativeStmt::TempSwap { left, right } => {
+                *left = fold_name(left);
+                *right = fold_name(right);
+            }
+            ImperativeStmt::TempDebug { temp } => *temp = fold_name(temp),
+        }
+    }
     pub(crate) fn needs_write_locks(&self, collector: &mut BTreeSet<SmartString<LazyCompact>>) {
         match self {
             ImperativeStmt::Program { prog, .. }
@@ -161,7 +217,12 @@ impl ImperativeStmt {
                             collector.insert(new.name.clone());
                         }
                     }
-                    SysOp::CreateIndex(symb, subs, _) => {
+                    SysOp::AddColumn(rel, _)
+                    | SysOp::DropColumn(rel, _)
+                    | SysOp::TruncateRelation(rel) => {
+                        collector.insert(rel.name.clone());
+                    }
+                    SysOp::CreateIndex(symb, subs, ..) => {
                         collector.insert(symb.name.clone());
                         collector.insert(SmartString::from(format!("{}:{}", symb.name, subs.name)));
                     }
@@ -188,6 +249,19 @@ impl ImperativeStmt {
 }
 
 impl CozoScript {
+    /// Lowercase the names of the stored relations and columns used by the script,
+    /// see [crate::Db::set_case_insensitive_names].
+    pub(crate) fn fold_case(&mut self) {
+        match self {
+            CozoScript::Single(prog) => prog.fold_case(),
+            CozoScript::Imperative(stmts) => {
+                for stmt in stmts {
+                    stmt.fold_case();
+                }
+            }
+            CozoScript::Sys(op) => op.fold_case(),
+        }
+    }
     pub(crate) fn get_single_program(self) -> Result<InputProgram> {
         #[derive(Debug, Error, Diagnostic)]
         #[error("expect script to contain only a single program")]
@@ -246,6 +320,87 @@ pub(crate) struct ParseError {
     pub(crate) span: SourceSpan,
 }
 
+/// Default value for [crate::Db::set_max_nesting_depth].
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 100;
+
+#[derive(thiserror::Error, Diagnostic, Debug)]
+#[error("Nesting too deep: at most {0} levels are allowed")]
+#[diagnostic(code(parser::nesting_too_deep))]
+pub(crate) struct NestingTooDeep(usize, #[label] SourceSpan);
+
+/// Reject scripts nested deeper than the limit, before handing them to the recursive parser.
+/// String literals and comments are skipped, except that nested block comments count.
+pub(crate) fn check_nesting_depth(src: &str, max_depth: usize) -> Result<()> {
+    let bytes = src.as_bytes();
+    let mut depth = 0usize;
+    let mut comment_depth = 0usize;
+    let mut i = 0;
+    while i < bytes.len() {
+        let next = bytes.get(i + 1).copied();
+        if comment_depth > 0 {
+            match (bytes[i], next) {
+                (b'/', Some(b'*')) => {
+                    comment_depth += 1;
+                    i += 1;
+                }
+                (b'*', Some(b'/')) => {
+                    comment_depth -= 1;
+                    i += 1;
+                }
+                _ => {}
+            }
+        } else {
+            match (bytes[i], next) {
+                (b'(' | b'[' | b'{', _) => depth += 1,
+                (b')' | b']' | b'}', _) => depth = depth.saturating_sub(1),
+                (b'/', Some(b'*')) => {
+                    comment_depth += 1;
+                    i += 1;
+                }
+                (b'#', _) => {
+                    while i < bytes.len() && bytes[i] != b'\n' {
+                        i += 1;
+                    }
+                }
+                (quote @ (b'"' | b'\''), _) => {
+                    i += 1;
+                    while i < bytes.len() && bytes[i] != quote {
+                        if bytes[i] == b'\\' {
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                }
+                (b'_', _) => {
+                    // a raw string `_"..."_` has no escapes, and ends at a quotation mark
+                    // followed by as many underscores as it starts with
+                    let start = i;
+                    while bytes.get(i) == Some(&b'_') {
+                        i += 1;
+                    }
+                    if bytes.get(i) == Some(&b'"') {
+                        let mut closing = vec![b'"'];
+                        closing.extend_from_slice(&bytes[start..i]);
+                        i += 1;
+                        while i < bytes.len() && !bytes[i..].starts_with(&closing) {
+                            i += 1;
+                        }
+                        i += closing.len() - 1;
+                    } else {
+                        i -= 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if depth + comment_depth > max_depth {
+            bail!(NestingTooDeep(max_depth, SourceSpan(i, 1)))
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
 pub(crate) fn parse_type(src: &str) -> Result<NullableColType> {
     let parsed = CozoScriptParser::parse(Rule::col_type_with_term, src)
         .into_diagnostic()?
@@ -26,14 +26,15 @@ use crate::data::functions::{str2vld, MAX_VALIDITY_TS};
 use crate::data::program::{
     FixedRuleApply, FixedRuleArg, InputAtom, InputInlineRule, InputInlineRulesOrFixed,
     InputNamedFieldRelationApplyAtom, InputProgram, InputRelationApplyAtom, InputRuleApplyAtom,
-    QueryAssertion, QueryOutOptions, RelationOp, ReturnMutation, SearchInput, SortDir, Unification,
+    NullsOrder, QueryAssertion, QueryOutOptions, RelationOp, ReturnMutation, SearchInput, SortDir,
+    Unification,
 };
 use crate::data::relation::{ColType, ColumnDef, NullableColType, StoredRelationMetadata};
 use crate::data::symb::{Symbol, PROG_ENTRY};
 use crate::data::value::{DataValue, ValidityTs};
 use crate::fixed_rule::utilities::constant::Constant;
 use crate::fixed_rule::{FixedRuleHandle, FixedRuleNotFoundError};
-use crate::parse::expr::build_expr;
+use crate::parse::expr::{build_expr, build_having_expr};
 use crate::parse::schema::parse_schema;
 use crate::parse::{CozoScriptParser, ExtractSpan, Pair, Pairs, Rule, SourceSpan};
 use crate::runtime::relation::InputRelationHandle;
@@ -115,6 +116,7 @@ pub(crate) fn parse_query(
 
     let mut stored_relation = None;
     let mut returning_mutation = ReturnMutation::NotReturning;
+    let mut having = None;
 
     for pair in src {
         match pair.as_rule() {
@@ -327,6 +329,17 @@ pub(crate) fn parse_query(
                     out_opts.sorters.push((Symbol::new(var, span), dir));
                 }
             }
+            Rule::nulls_option => {
+                out_opts.nulls = Some(match pair.into_inner().next().unwrap().as_rule() {
+                    Rule::nulls_first => NullsOrder::First,
+                    Rule::nulls_last => NullsOrder::Last,
+                    _ => unreachable!(),
+                });
+            }
+            Rule::having_option => {
+                // built once the output columns are known
+                having = Some(pair.into_inner().next().unwrap());
+            }
             Rule::returning_option => {
                 returning_mutation = ReturnMutation::Returning;
             }
@@ -485,6 +498,24 @@ pub(crate) fn parse_query(
         }
     }
 
+    if let Some(pair) = having {
+        // aggregated columns are referred to as they are written in the head, e.g. `count(id)`
+        let head = prog.get_entry_out_head_or_default()?;
+        let aggregated = head
+            .iter()
+            .filter(|symb| symb.name.contains('('))
+            .map(|symb| symb.name.to_string())
+            .collect();
+        let mut expr = build_having_expr(pair, param_pool, &aggregated)?;
+        let binding_map = head
+            .into_iter()
+            .enumerate()
+            .map(|(i, symb)| (symb, i))
+            .collect();
+        expr.fill_binding_indices(&binding_map)?;
+        prog.out_opts.having = Some(expr);
+    }
+
     #[derive(Debug, Error, Diagnostic)]
     #[error("Input relation '{0}' has no keys")]
     #[diagnostic(code(parser::relation_has_no_keys))]
@@ -829,18 +860,22 @@ fn parse_rule_head_arg(
             let mut inner = src.into_inner();
             let aggr_p = inner.next().unwrap();
             let aggr_name = aggr_p.as_str();
-            let var = inner.next().unwrap();
+            let mut aggr = parse_aggr(aggr_name)
+                .ok_or_else(|| AggrNotFound(aggr_name.to_string(), aggr_p.extract_span()))?
+                .clone();
+            let mut var = inner.next().unwrap();
+            if var.as_rule() == Rule::aggr_distinct {
+                aggr.distinct = true;
+                // skipping repeats is not compatible with the incremental evaluation of meets
+                aggr.is_meet = false;
+                var = inner.next().unwrap();
+            }
             let args: Vec<_> = inner
                 .map(|v| -> Result<DataValue> { build_expr(v, param_pool)?.eval_to_const() })
                 .try_collect()?;
             (
                 Symbol::new(var.as_str(), var.extract_span()),
-                Some((
-                    parse_aggr(aggr_name)
-                        .ok_or_else(|| AggrNotFound(aggr_name.to_string(), aggr_p.extract_span()))?
-                        .clone(),
-                    args,
-                )),
+                Some((aggr, args)),
             )
         }
         _ => unreachable!(),
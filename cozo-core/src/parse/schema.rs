@@ -13,6 +13,7 @@ use miette::{bail, ensure, Diagnostic, Result, IntoDiagnostic};
 use smartstring::SmartString;
 use thiserror::Error;
 
+use crate::data::functions::current_validity;
 use crate::data::relation::{VecElementType, ColType, ColumnDef, NullableColType, StoredRelationMetadata};
 use crate::data::symb::Symbol;
 use crate::data::value::DataValue;
@@ -64,7 +65,7 @@ pub(crate) fn parse_schema(
     ))
 }
 
-fn parse_col(pair: Pair<'_>) -> Result<(ColumnDef, Symbol)> {
+pub(crate) fn parse_col(pair: Pair<'_>) -> Result<(ColumnDef, Symbol)> {
     let mut src = pair.into_inner();
     let name_p = src.next().unwrap();
     let name = SmartString::from(name_p.as_str());
@@ -84,6 +85,26 @@ fn parse_col(pair: Pair<'_>) -> Result<(ColumnDef, Symbol)> {
             r => unreachable!("{:?}", r),
         }
     }
+    if let Some(default) = &default_gen {
+        // constant defaults must be acceptable to the column, e.g. no null for non-nullable columns
+        let mut folded = default.clone();
+        folded.partial_eval()?;
+        if let Some(val) = folded.get_const() {
+            #[derive(Debug, Error, Diagnostic)]
+            #[error("Default value {0} of column {1} does not fit its type {2}")]
+            #[diagnostic(code(parser::bad_default_for_col))]
+            struct BadDefaultForColumn(DataValue, String, NullableColType, #[label] SourceSpan);
+
+            if val.clone().coerce_to(&typing, current_validity()).is_err() {
+                bail!(BadDefaultForColumn(
+                    val.clone(),
+                    name.to_string(),
+                    typing,
+                    default.span()
+                ))
+            }
+        }
+    }
     let binding =
         binding_candidate.unwrap_or_else(|| Symbol::new(&name as &str, name_p.extract_span()));
     Ok((
@@ -16,12 +16,13 @@ use smartstring::{LazyCompact, SmartString};
 use thiserror::Error;
 
 use crate::data::program::InputProgram;
-use crate::data::relation::VecElementType;
-use crate::data::symb::Symbol;
+use crate::data::relation::{ColumnDef, VecElementType};
+use crate::data::symb::{fold_name, Symbol};
 use crate::data::value::{DataValue, ValidityTs};
 use crate::fts::TokenizerConfig;
 use crate::parse::expr::{build_expr, parse_string};
 use crate::parse::query::parse_query;
+use crate::parse::schema::parse_col;
 use crate::parse::{ExtractSpan, Pairs, Rule, SourceSpan};
 use crate::runtime::relation::AccessLevel;
 use crate::{Expr, FixedRule};
@@ -38,15 +39,84 @@ pub(crate) enum SysOp {
     Explain(Box<InputProgram>),
     RemoveRelation(Vec<Symbol>),
     RenameRelation(Vec<(Symbol, Symbol)>),
+    AddColumn(Symbol, ColumnDef),
+    DropColumn(Symbol, Symbol),
+    TruncateRelation(Symbol),
     ShowTrigger(Symbol),
     SetTriggers(Symbol, Vec<String>, Vec<String>, Vec<String>),
     SetAccessLevel(Vec<Symbol>, AccessLevel),
-    CreateIndex(Symbol, Symbol, Vec<Symbol>),
+    CreateIndex(Symbol, Symbol, Vec<Symbol>, bool),
     CreateVectorIndex(HnswIndexConfig),
     CreateFtsIndex(FtsIndexConfig),
     CreateMinHashLshIndex(MinHashLshConfig),
     RemoveIndex(Symbol, Symbol),
-    DescribeRelation(Symbol, SmartString<LazyCompact>)
+    DescribeRelation(Symbol, SmartString<LazyCompact>),
+    CreateView(Symbol, String),
+    RemoveView(Symbol),
+}
+
+impl SysOp {
+    /// Lowercase the names of the stored relations, indices and columns,
+    /// see [crate::Db::set_case_insensitive_names].
+    pub(crate) fn fold_case(&mut self) {
+        match self {
+            SysOp::Compact
+            | SysOp::ListRelations
+            | SysOp::ListRunning
+            | SysOp::ListFixedRules
+            | SysOp::KillRunning(_) => {}
+            SysOp::Explain(prog) => prog.fold_case(),
+            SysOp::ListColumns(rel)
+            | SysOp::ListIndices(rel)
+            | SysOp::TruncateRelation(rel)
+            | SysOp::ShowTrigger(rel)
+            | SysOp::SetTriggers(rel, ..)
+            | SysOp::DescribeRelation(rel, _)
+            | SysOp::CreateView(rel, _)
+            | SysOp::RemoveView(rel) => rel.fold_case(),
+            SysOp::RemoveRelation(rels) | SysOp::SetAccessLevel(rels, _) => {
+                for rel in rels {
+                    rel.fold_case();
+                }
+            }
+            SysOp::RenameRelation(pairs) => {
+                for (old, new) in pairs {
+                    old.fold_case();
+                    new.fold_case();
+                }
+            }
+            SysOp::AddColumn(rel, col) => {
+                rel.fold_case();
+                col.name = fold_name(&col.name);
+            }
+            SysOp::DropColumn(rel, col) | SysOp::RemoveIndex(rel, col) => {
+                rel.fold_case();
+                col.fold_case();
+            }
+            SysOp::CreateIndex(rel, idx, cols, _) => {
+                rel.fold_case();
+                idx.fold_case();
+                for col in cols {
+                    col.fold_case();
+                }
+            }
+            SysOp::CreateVectorIndex(config) => {
+                config.base_relation = fold_name(&config.base_relation);
+                config.index_name = fold_name(&config.index_name);
+                for field in config.vec_fields.iter_mut() {
+                    *field = fold_name(field);
+                }
+            }
+            SysOp::CreateFtsIndex(config) => {
+                config.base_relation = fold_name(&config.base_relation);
+                config.index_name = fold_name(&config.index_name);
+            }
+            SysOp::CreateMinHashLshIndex(config) => {
+                config.base_relation = fold_name(&config.base_relation);
+                config.index_name = fold_name(&config.index_name);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -96,6 +166,17 @@ pub(crate) enum HnswDistance {
     Cosine,
 }
 
+#[derive(Debug, Diagnostic, Error)]
+#[error("Column {0} cannot be bound to a variable when altering a relation")]
+#[diagnostic(code(parser::binding_in_alter))]
+struct ColumnBindingInAlter(String, #[label] SourceSpan);
+
+#[derive(Debug, Diagnostic, Error)]
+#[error("The query defining view {0} cannot have options or store its results")]
+#[diagnostic(code(parser::options_in_view))]
+#[diagnostic(help("Apply options such as ':limit' when querying the view instead"))]
+struct OptionsInView(String, #[label] SourceSpan);
+
 #[derive(Debug, Diagnostic, Error)]
 #[error("Cannot interpret {0} as process ID")]
 #[diagnostic(code(parser::not_proc_id))]
@@ -172,6 +253,33 @@ pub(crate) fn parse_sys(
                 .collect_vec();
             SysOp::RenameRelation(rename_pairs)
         }
+        Rule::alter_relation_op => {
+            let mut src = inner.into_inner();
+            let rel_p = src.next().unwrap();
+            let rel = Symbol::new(rel_p.as_str(), rel_p.extract_span());
+            let action = src.next().unwrap();
+            match action.as_rule() {
+                Rule::alter_add_col => {
+                    let col_p = action.into_inner().next().unwrap();
+                    let span = col_p.extract_span();
+                    let (col, binding) = parse_col(col_p)?;
+                    ensure!(
+                        binding.name == col.name,
+                        ColumnBindingInAlter(col.name.to_string(), span)
+                    );
+                    SysOp::AddColumn(rel, col)
+                }
+                Rule::alter_drop_col => {
+                    let col_p = action.into_inner().next().unwrap();
+                    SysOp::DropColumn(rel, Symbol::new(col_p.as_str(), col_p.extract_span()))
+                }
+                r => unreachable!("{:?}", r),
+            }
+        }
+        Rule::truncate_relation_op => {
+            let rel_p = inner.into_inner().next().unwrap();
+            SysOp::TruncateRelation(Symbol::new(rel_p.as_str(), rel_p.extract_span()))
+        }
         Rule::access_level_op => {
             let mut ps = inner.into_inner();
             let access_level = match ps.next().unwrap().as_str() {
@@ -220,6 +328,43 @@ pub(crate) fn parse_sys(
             }
             SysOp::SetTriggers(rel, puts, rms, replaces)
         }
+        Rule::view_op => {
+            let inner = inner.into_inner().next().unwrap();
+            match inner.as_rule() {
+                Rule::view_create => {
+                    let mut inner = inner.into_inner();
+                    let name_p = inner.next().unwrap();
+                    let name = Symbol::new(name_p.as_str(), name_p.extract_span());
+                    let script = inner.next().unwrap();
+                    let span = script.extract_span();
+                    let script_str = script.as_str();
+                    let prog = parse_query(
+                        script.into_inner(),
+                        &Default::default(),
+                        algorithms,
+                        cur_vld,
+                    )?;
+                    let opts = &prog.out_opts;
+                    ensure!(
+                        opts.limit.is_none()
+                            && opts.offset.is_none()
+                            && opts.timeout.is_none()
+                            && opts.sleep.is_none()
+                            && opts.sorters.is_empty()
+                            && opts.having.is_none()
+                            && opts.store_relation.is_none()
+                            && opts.assertion.is_none(),
+                        OptionsInView(name.to_string(), span)
+                    );
+                    SysOp::CreateView(name, script_str.to_string())
+                }
+                Rule::view_drop => {
+                    let name_p = inner.into_inner().next().unwrap();
+                    SysOp::RemoveView(Symbol::new(name_p.as_str(), name_p.extract_span()))
+                }
+                r => unreachable!("{:?}", r),
+            }
+        }
         Rule::lsh_idx_op => {
             let inner = inner.into_inner().next().unwrap();
             match inner.as_rule() {
@@ -638,7 +783,10 @@ pub(crate) fn parse_sys(
             match inner.as_rule() {
                 Rule::index_create => {
                     let span = inner.extract_span();
-                    let mut inner = inner.into_inner();
+                    let mut inner = inner.into_inner().peekable();
+                    let unique = inner
+                        .next_if(|p| p.as_rule() == Rule::index_unique)
+                        .is_some();
                     let rel = inner.next().unwrap();
                     let name = inner.next().unwrap();
                     let cols = inner
@@ -655,6 +803,7 @@ pub(crate) fn parse_sys(
                         Symbol::new(rel.as_str(), rel.extract_span()),
                         Symbol::new(name.as_str(), name.extract_span()),
                         cols,
+                        unique,
                     )
                 }
                 Rule::index_drop => {
@@ -0,0 +1,203 @@
+/*
+ * Copyright 2022, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::collections::BTreeMap;
+
+use miette::Result;
+
+use crate::data::program::{MagicFixedRuleRuleArg, MagicSymbol};
+use crate::data::tuple::{Tuple, TupleT};
+use crate::query::compile::{CompiledProgram, CompiledRuleSet};
+use crate::query::ra::{RelAlgebra, StoredRA, StoredWithValidityRA};
+use crate::runtime::relation::RelationHandle;
+use crate::runtime::transact::SessionTx;
+use crate::storage::StoreTx;
+
+/// Fraction of the rows that each filter is guessed to let through.
+const FILTER_SELECTIVITY: f64 = 0.5;
+
+/// Estimated cost of running a query, see [crate::Db::explain_cost].
+///
+/// The estimate is made from the row counts kept for stored relations. A join binding `k`
+/// of the `n` key columns of a relation (or of an index of it) is guessed to match
+/// `rows^(1 - k/n)` rows for each row joined, and every filter is guessed to keep half
+/// of the rows. Rules that are not yet estimated when used, such as recursive ones,
+/// are taken to hold a single row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryCost {
+    /// Estimated number of rows read from stored relations and indices
+    pub rows_scanned: u64,
+    /// Estimated number of rows returned
+    pub rows_returned: u64,
+    /// Estimated number of rows read from each stored relation or index, by name
+    pub scans: BTreeMap<String, u64>,
+}
+
+struct CostEstimator<'a, 'b> {
+    tx: &'a SessionTx<'b>,
+    rule_rows: BTreeMap<MagicSymbol, f64>,
+    scans: BTreeMap<String, f64>,
+}
+
+impl<'a> SessionTx<'a> {
+    pub(crate) fn estimate_cost(
+        &self,
+        strata: &[CompiledProgram],
+        limit: Option<usize>,
+    ) -> Result<QueryCost> {
+        let mut estimator = CostEstimator {
+            tx: self,
+            rule_rows: Default::default(),
+            scans: Default::default(),
+        };
+        let mut returned = 0.;
+        for stratum in strata {
+            for (name, ruleset) in stratum {
+                let rows = match ruleset {
+                    CompiledRuleSet::Rules(rules) => {
+                        let mut rows = 0.;
+                        for rule in rules {
+                            rows += estimator.estimate(&rule.relation)?;
+                        }
+                        rows
+                    }
+                    // the output of a fixed rule is guessed to be as large as its inputs
+                    CompiledRuleSet::Fixed(fixed) => {
+                        let mut rows = 0.;
+                        for arg in &fixed.rule_args {
+                            rows += match arg {
+                                MagicFixedRuleRuleArg::InMem { name, .. } => {
+                                    estimator.rule_rows(name)
+                                }
+                                MagicFixedRuleRuleArg::Stored { name, .. } => {
+                                    let handle = self.get_relation(name, false)?;
+                                    let n = estimator.stored_rows(&handle)?;
+                                    estimator.record_scan(&handle, n);
+                                    n
+                                }
+                            }
+                        }
+                        rows
+                    }
+                };
+                if name.is_prog_entry() {
+                    returned = rows;
+                }
+                estimator.rule_rows.insert(name.clone(), rows);
+            }
+        }
+        if let Some(limit) = limit {
+            returned = returned.min(limit as f64);
+        }
+        let scans: BTreeMap<_, _> = estimator
+            .scans
+            .into_iter()
+            .map(|(name, n)| (name, n.ceil() as u64))
+            .collect();
+        Ok(QueryCost {
+            rows_scanned: scans.values().sum(),
+            rows_returned: returned.ceil() as u64,
+            scans,
+        })
+    }
+}
+
+impl CostEstimator<'_, '_> {
+    fn rule_rows(&self, name: &MagicSymbol) -> f64 {
+        self.rule_rows.get(name).copied().unwrap_or(1.)
+    }
+    fn stored_rows(&self, handle: &RelationHandle) -> Result<f64> {
+        if handle.is_temp {
+            let lower = Tuple::default().encode_as_key(handle.id);
+            let upper = Tuple::default().encode_as_key(handle.id.next());
+            return Ok(self.tx.temp_store_tx.range_count(&lower, &upper)? as f64);
+        }
+        // counts are kept for relations only, and a regular index
+        // has one row for each row of its relation
+        let rows = match handle.name.split_once(':') {
+            Some((base, _)) => self.tx.row_count(&self.tx.get_relation(base, false)?)?,
+            None => self.tx.row_count(handle)?,
+        };
+        Ok(rows as f64)
+    }
+    fn record_scan(&mut self, handle: &RelationHandle, rows: f64) {
+        *self.scans.entry(handle.name.to_string()).or_default() += rows;
+    }
+    /// Estimate the number of rows produced by `ra`, recording the rows it reads.
+    fn estimate(&mut self, ra: &RelAlgebra) -> Result<f64> {
+        Ok(match ra {
+            RelAlgebra::Fixed(fixed) => fixed.data.len() as f64,
+            RelAlgebra::TempStore(store) => {
+                self.rule_rows(&store.storage_key) * filtered(store.filters.len())
+            }
+            RelAlgebra::Stored(StoredRA {
+                storage, filters, ..
+            })
+            | RelAlgebra::StoredWithValidity(StoredWithValidityRA {
+                storage, filters, ..
+            }) => {
+                let n = self.stored_rows(storage)?;
+                self.record_scan(storage, n);
+                n * filtered(filters.len())
+            }
+            RelAlgebra::Join(join) => {
+                let left = self.estimate(&join.left)?;
+                let bound = join.joiner.left_keys.len();
+                match &join.right {
+                    RelAlgebra::Stored(StoredRA {
+                        storage, filters, ..
+                    })
+                    | RelAlgebra::StoredWithValidity(StoredWithValidityRA {
+                        storage,
+                        filters,
+                        ..
+                    }) => {
+                        let n = self.stored_rows(storage)?;
+                        let per_row = matches_per_row(n, bound, storage.metadata.keys.len());
+                        // only a join on a prefix of the keys avoids reading every row
+                        let scanned = if join.join_type() == "stored_prefix_join" {
+                            left * per_row
+                        } else {
+                            n
+                        };
+                        self.record_scan(storage, scanned);
+                        left * per_row * filtered(filters.len())
+                    }
+                    right => {
+                        let width = right.bindings_after_eliminate().len();
+                        left * matches_per_row(self.estimate(right)?, bound, width)
+                    }
+                }
+            }
+            RelAlgebra::NegJoin(join) => {
+                self.estimate(&join.right)?;
+                self.estimate(&join.left)?
+            }
+            RelAlgebra::Reorder(reorder) => self.estimate(&reorder.relation)?,
+            RelAlgebra::Filter(filter) => {
+                self.estimate(&filter.parent)? * filtered(filter.filters.len())
+            }
+            RelAlgebra::Unification(unification) => self.estimate(&unification.parent)?,
+            RelAlgebra::HnswSearch(search) => self.estimate(&search.parent)?,
+            RelAlgebra::FtsSearch(search) => self.estimate(&search.parent)?,
+            RelAlgebra::LshSearch(search) => self.estimate(&search.parent)?,
+        })
+    }
+}
+
+fn filtered(n_filters: usize) -> f64 {
+    FILTER_SELECTIVITY.powi(n_filters as i32)
+}
+
+fn matches_per_row(rows: f64, bound: usize, width: usize) -> f64 {
+    if width == 0 {
+        return rows;
+    }
+    let bound = bound.min(width) as f64;
+    rows.powf(1. - bound / width as f64)
+}
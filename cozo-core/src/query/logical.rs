@@ -215,6 +215,7 @@ impl InputAtom {
             InputAtom::Relation { inner: v } => v.normalize(false, gen),
             InputAtom::Predicate { inner: mut p } => {
                 p.partial_eval()?;
+                p.simplify();
                 Disjunction::singlet(NormalFormAtom::Predicate(p))
             }
             InputAtom::Negation { inner: n, .. } => match *n {
@@ -7,6 +7,7 @@
  */
 
 pub(crate) mod compile;
+pub(crate) mod cost;
 pub(crate) mod eval;
 pub(crate) mod graph;
 pub(crate) mod logical;
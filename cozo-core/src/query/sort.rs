@@ -12,9 +12,11 @@ use std::collections::BTreeMap;
 use itertools::Itertools;
 use miette::Result;
 
-use crate::data::program::SortDir;
+use crate::data::functions::compare_values;
+use crate::data::program::{NullsOrder, SortDir};
 use crate::data::symb::Symbol;
 use crate::data::tuple::Tuple;
+use crate::data::value::DataValue;
 use crate::runtime::temp_store::EpochStore;
 use crate::runtime::transact::SessionTx;
 
@@ -23,6 +25,7 @@ impl<'a> SessionTx<'a> {
         &mut self,
         original: EpochStore,
         sorters: &[(Symbol, SortDir)],
+        nulls: Option<NullsOrder>,
         head: &[Symbol],
     ) -> Result<Vec<Tuple>> {
         let head_indices: BTreeMap<_, _> = head.iter().enumerate().map(|(i, k)| (k, i)).collect();
@@ -34,7 +37,22 @@ impl<'a> SessionTx<'a> {
         let mut all_data: Vec<_> = original.all_iter().map(|v| v.into_tuple()).collect_vec();
         all_data.sort_by(|a, b| {
             for (idx, dir) in &idx_sorters {
-                match a[*idx].cmp(&b[*idx]) {
+                let (x, y) = (&a[*idx], &b[*idx]);
+                // nulls are placed according to `nulls` independent of the direction if given,
+                // and otherwise sort as the smallest values
+                let null_order = match (x, y) {
+                    (DataValue::Null, DataValue::Null) => Ordering::Equal,
+                    (DataValue::Null, _) => Ordering::Less,
+                    (_, DataValue::Null) => Ordering::Greater,
+                    _ => Ordering::Equal,
+                };
+                if null_order != Ordering::Equal {
+                    return match (nulls, dir) {
+                        (Some(NullsOrder::First), _) | (None, SortDir::Asc) => null_order,
+                        (Some(NullsOrder::Last), _) | (None, SortDir::Dsc) => null_order.reverse(),
+                    };
+                }
+                match compare_values(x, y).unwrap_or_else(|| x.cmp(y)) {
                     Ordering::Equal => {}
                     o => {
                         return match dir {
@@ -25,7 +25,7 @@ use crate::fixed_rule::utilities::constant::Constant;
 use crate::fixed_rule::FixedRuleHandle;
 use crate::fts::tokenizer::TextAnalyzer;
 use crate::parse::expr::build_expr;
-use crate::parse::{parse_script, CozoScriptParser, Rule};
+use crate::parse::{CozoScriptParser, Rule};
 use crate::runtime::callback::{CallbackCollector, CallbackOp};
 use crate::runtime::minhash_lsh::HashPermutations;
 use crate::runtime::relation::{
@@ -83,13 +83,9 @@ impl<'a> SessionTx<'a> {
                     replaced_old_triggers = Some((old_handle.put_triggers, old_handle.rm_triggers))
                 }
                 for trigger in &old_handle.replace_triggers {
-                    let program = parse_script(
-                        trigger,
-                        &Default::default(),
-                        &db.fixed_rules.read().unwrap(),
-                        cur_vld,
-                    )?
-                    .get_single_program()?;
+                    let program = db
+                        .parse_script(trigger, &Default::default(), cur_vld)?
+                        .get_single_program()?;
 
                     let (_, cleanups) = db
                         .run_query(
@@ -272,6 +268,7 @@ impl<'a> SessionTx<'a> {
         let hnsw_filters = Self::make_hnsw_filters(relation_store)?;
         let fts_lsh_processors = self.make_fts_lsh_processors(relation_store)?;
         let lsh_perms = self.make_lsh_hash_perms(relation_store);
+        let mut added_rows = 0;
 
         for tuple in res_iter {
             let extracted: Vec<DataValue> = key_extractors
@@ -299,6 +296,7 @@ impl<'a> SessionTx<'a> {
 
             let val = relation_store.encode_val_for_store(&extracted, span)?;
 
+            let mut is_new = true;
             if need_to_collect
                 || has_indices
                 || has_hnsw_indices
@@ -306,6 +304,7 @@ impl<'a> SessionTx<'a> {
                 || has_lsh_indices
             {
                 if let Some(existing) = self.store_tx.get(&key, false)? {
+                    is_new = false;
                     let mut tup = extracted[0..relation_store.metadata.keys.len()].to_vec();
                     extend_tuple_from_v(&mut tup, &existing);
                     if has_indices && extracted != tup {
@@ -318,11 +317,12 @@ impl<'a> SessionTx<'a> {
                         old_tuples.push(DataValue::List(tup));
                     }
                 } else if has_indices {
-                    for (idx_rel, extractor) in relation_store.indices.values() {
+                    for (name, (idx_rel, extractor)) in relation_store.indices.iter() {
                         let idx_tup_new = extractor
                             .iter()
                             .map(|i| extracted[*i].clone())
                             .collect_vec();
+                        relation_store.ensure_unique_in_index(self, name, &idx_tup_new)?;
                         let encoded_new =
                             idx_rel.encode_key_for_store(&idx_tup_new, Default::default())?;
                         self.store_tx.put(&encoded_new, &[])?;
@@ -342,6 +342,11 @@ impl<'a> SessionTx<'a> {
                 if need_to_collect {
                     new_tuples.push(DataValue::List(extracted));
                 }
+            } else if !relation_store.is_temp && !is_insert {
+                is_new = !self.store_tx.exists(&key, false)?;
+            }
+            if is_new {
+                added_rows += 1;
             }
 
             if relation_store.is_temp {
@@ -350,6 +355,7 @@ impl<'a> SessionTx<'a> {
                 self.store_tx.put(&key, &val)?;
             }
         }
+        self.adjust_row_count(relation_store, added_rows)?;
 
         if need_to_collect && !new_tuples.is_empty() {
             self.collect_mutations(
@@ -695,13 +701,9 @@ impl<'a> SessionTx<'a> {
         let kv_bindings = bindings;
         if propagate_triggers {
             for trigger in &relation_store.put_triggers {
-                let mut program = parse_script(
-                    trigger,
-                    &Default::default(),
-                    &db.fixed_rules.read().unwrap(),
-                    cur_vld,
-                )?
-                .get_single_program()?;
+                let mut program = db
+                    .parse_script(trigger, &Default::default(), cur_vld)?
+                    .get_single_program()?;
 
                 make_const_rule(
                     &mut program,
@@ -777,18 +779,18 @@ impl<'a> SessionTx<'a> {
         new_kv: &[DataValue],
         old_kv: &[DataValue],
     ) -> Result<()> {
-        for (idx_rel, idx_extractor) in relation_store.indices.values() {
+        for (name, (idx_rel, idx_extractor)) in relation_store.indices.iter() {
             let idx_tup_old = idx_extractor
                 .iter()
                 .map(|i| old_kv[*i].clone())
                 .collect_vec();
-            let encoded_old = idx_rel.encode_key_for_store(&idx_tup_old, Default::default())?;
-            self.store_tx.del(&encoded_old)?;
+            relation_store.del_from_index(self, name, &idx_tup_old)?;
 
             let idx_tup_new = idx_extractor
                 .iter()
                 .map(|i| new_kv[*i].clone())
                 .collect_vec();
+            relation_store.ensure_unique_in_index(self, name, &idx_tup_new)?;
             let encoded_new = idx_rel.encode_key_for_store(&idx_tup_new, Default::default())?;
             self.store_tx.put(&encoded_new, &[])?;
         }
@@ -957,6 +959,7 @@ impl<'a> SessionTx<'a> {
         let mut new_tuples: Vec<DataValue> = vec![];
         let mut old_tuples: Vec<DataValue> = vec![];
         let mut stack = vec![];
+        let mut removed_rows = 0;
 
         for tuple in res_iter {
             let extracted: Vec<DataValue> = key_extractors
@@ -964,6 +967,7 @@ impl<'a> SessionTx<'a> {
                 .map(|ex| ex.extract_data(&tuple, cur_vld))
                 .try_collect()?;
             let key = relation_store.encode_key_for_store(&extracted, span)?;
+            let mut existed = check_exists;
             if check_exists {
                 let exists = if relation_store.is_temp {
                     self.temp_store_tx.exists(&key, false)?
@@ -980,16 +984,15 @@ impl<'a> SessionTx<'a> {
             }
             if need_to_collect || has_indices || has_hnsw_indices || has_fts_indices || has_lsh_indices {
                 if let Some(existing) = self.store_tx.get(&key, false)? {
+                    existed = true;
                     let mut tup = extracted.clone();
                     extend_tuple_from_v(&mut tup, &existing);
                     self.del_in_fts(relation_store, &mut stack, &fts_processors, &tup)?;
                     self.del_in_lsh(relation_store, &tup)?;
                     if has_indices {
-                        for (idx_rel, extractor) in relation_store.indices.values() {
+                        for (name, (_, extractor)) in relation_store.indices.iter() {
                             let idx_tup = extractor.iter().map(|i| tup[*i].clone()).collect_vec();
-                            let encoded =
-                                idx_rel.encode_key_for_store(&idx_tup, Default::default())?;
-                            self.store_tx.del(&encoded)?;
+                            relation_store.del_from_index(self, name, &idx_tup)?;
                         }
                     }
                     if has_hnsw_indices {
@@ -1004,6 +1007,11 @@ impl<'a> SessionTx<'a> {
                 if need_to_collect {
                     new_tuples.push(DataValue::List(extracted.clone()));
                 }
+            } else if !relation_store.is_temp && !existed {
+                existed = self.store_tx.exists(&key, false)?;
+            }
+            if existed {
+                removed_rows += 1;
             }
             if relation_store.is_temp {
                 self.temp_store_tx.del(&key)?;
@@ -1011,6 +1019,7 @@ impl<'a> SessionTx<'a> {
                 self.store_tx.del(&key)?;
             }
         }
+        self.adjust_row_count(relation_store, -removed_rows)?;
 
         // triggers and callbacks
         if need_to_collect && !new_tuples.is_empty() {
@@ -1032,13 +1041,9 @@ impl<'a> SessionTx<'a> {
 
             if propagate_triggers {
                 for trigger in &relation_store.rm_triggers {
-                    let mut program = parse_script(
-                        trigger,
-                        &Default::default(),
-                        &db.fixed_rules.read().unwrap(),
-                        cur_vld,
-                    )?
-                    .get_single_program()?;
+                    let mut program = db
+                        .parse_script(trigger, &Default::default(), cur_vld)?
+                        .get_single_program()?;
 
                     make_const_rule(&mut program, "_new", k_bindings.clone(), new_tuples.clone());
 
@@ -1125,11 +1130,14 @@ enum DataExtractor {
 impl DataExtractor {
     fn extract_data(&self, tuple: &Tuple, cur_vld: ValidityTs) -> Result<DataValue> {
         Ok(match self {
-            DataExtractor::DefaultExtractor(expr, typ) => typ
-                .coerce(expr.clone().eval_to_const()?, cur_vld)
+            DataExtractor::DefaultExtractor(expr, typ) => expr
+                .clone()
+                .eval_to_const()?
+                .coerce_to(typ, cur_vld)
                 .wrap_err_with(|| format!("when processing tuple {tuple:?}"))?,
-            DataExtractor::IndexExtractor(i, typ) => typ
-                .coerce(tuple[*i].clone(), cur_vld)
+            DataExtractor::IndexExtractor(i, typ) => tuple[*i]
+                .clone()
+                .coerce_to(typ, cur_vld)
                 .wrap_err_with(|| format!("when processing tuple {tuple:?}"))?,
         })
     }
@@ -1185,6 +1193,14 @@ fn make_extractor(
             expr.clone(),
             stored.typing.clone(),
         ))
+    } else if stored.typing.is_nullable() {
+        Ok(DataExtractor::DefaultExtractor(
+            Expr::Const {
+                val: DataValue::Null,
+                span: Default::default(),
+            },
+            stored.typing.clone(),
+        ))
     } else {
         #[derive(Debug, Error, Diagnostic)]
         #[error("cannot make extractor for column {0}")]
@@ -10,10 +10,12 @@ use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet};
 use std::default::Default;
 use std::fmt::{Debug, Formatter};
+use std::io::{BufRead, Read, Write};
 use std::iter;
+use std::ops::Bound;
 use std::path::Path;
 #[allow(unused_imports)]
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 #[allow(unused_imports)]
 use std::thread;
@@ -25,24 +27,34 @@ use crossbeam::channel::{bounded, unbounded, Receiver, Sender};
 use crossbeam::sync::ShardedLock;
 use either::{Left, Right};
 use itertools::Itertools;
+use log::error;
 use miette::Report;
 #[allow(unused_imports)]
 use miette::{bail, ensure, miette, Diagnostic, IntoDiagnostic, Result, WrapErr};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde_json::json;
 use smartstring::{LazyCompact, SmartString};
 use thiserror::Error;
+use uuid::Uuid;
 
+use crate::data::expr::{eval_bytecode, eval_bytecode_pred, Expr};
 use crate::data::functions::current_validity;
 use crate::data::json::JsonValue;
 use crate::data::program::{InputProgram, QueryAssertion, RelationOp, ReturnMutation};
-use crate::data::relation::ColumnDef;
+use crate::data::relation::{ColType, ColumnDef, NullableColType, StoredRelationMetadata};
+use crate::data::symb::PROG_ENTRY;
 use crate::data::tuple::{Tuple, TupleT};
 use crate::data::value::{DataValue, ValidityTs, LARGEST_UTF_CHAR};
 use crate::fixed_rule::DEFAULT_FIXED_RULES;
 use crate::fts::TokenizerCache;
 use crate::parse::sys::SysOp;
-use crate::parse::{parse_expressions, parse_script, CozoScript, SourceSpan};
+use crate::parse::{
+    check_nesting_depth, parse_expressions, parse_script, CozoScript, SourceSpan,
+    DEFAULT_MAX_NESTING_DEPTH,
+};
 use crate::query::compile::{CompiledProgram, CompiledRule, CompiledRuleSet};
+use crate::query::cost::QueryCost;
 use crate::query::ra::{
     FilteredRA, FtsSearchRA, HnswSearchRA, InnerJoin, LshSearchRA, NegJoin, RelAlgebra, ReorderRA,
     StoredRA, StoredWithValidityRA, TempStoreRA, UnificationRA,
@@ -52,11 +64,14 @@ use crate::runtime::callback::{
     CallbackCollector, CallbackDeclaration, CallbackOp, EventCallbackRegistry,
 };
 use crate::runtime::relation::{
-    extend_tuple_from_v, AccessLevel, InsufficientAccessLevel, RelationHandle, RelationId,
+    extend_tuple_from_v, AccessLevel, IndexNotFound, InputRelationHandle, InsufficientAccessLevel,
+    RelationDef, RelationHandle, RelationId, RelationStats,
 };
+use crate::runtime::temp_store::{EpochStore, RegularTempStore};
 use crate::runtime::transact::SessionTx;
 use crate::storage::temp::TempStorage;
 use crate::storage::Storage;
+use crate::utils::HyperLogLog;
 use crate::{decode_tuple_from_kv, FixedRule, Symbol};
 
 pub(crate) struct RunningQueryHandle {
@@ -92,6 +107,49 @@ pub enum ScriptMutability {
     Immutable,
 }
 
+/// Options for [Db::import_rows].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ImportOptions {
+    /// Number of rows written per transaction. Each batch is committed on its own,
+    /// so if the import fails, the batches before the failing one stay imported.
+    pub batch_size: usize,
+    /// Do not look up existing rows before writing. Only safe if none of the imported keys
+    /// is already present: index entries of overwritten rows are not removed, and the rows
+    /// are counted twice in the statistics of the relation.
+    pub assume_new_keys: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 10000,
+            assume_new_keys: false,
+        }
+    }
+}
+
+fn parse_csv_cell(cell: &str, typing: &NullableColType) -> Result<DataValue> {
+    Ok(match typing.coltype {
+        ColType::Int => DataValue::from(
+            cell.trim()
+                .parse::<i64>()
+                .map_err(|_| miette!("cannot parse '{}' as an integer", cell))?,
+        ),
+        ColType::Float => DataValue::from(
+            cell.trim()
+                .parse::<f64>()
+                .map_err(|_| miette!("cannot parse '{}' as a float", cell))?,
+        ),
+        ColType::Bool => match cell.trim() {
+            "true" => DataValue::from(true),
+            "false" => DataValue::from(false),
+            _ => bail!("cannot parse '{}' as a boolean", cell),
+        },
+        // other types are coerced from the string
+        _ => DataValue::from(cell),
+    })
+}
+
 /// The database object of Cozo.
 #[derive(Clone)]
 pub struct Db<S> {
@@ -107,6 +165,9 @@ pub struct Db<S> {
     #[cfg(not(target_arch = "wasm32"))]
     pub(crate) event_callbacks: Arc<ShardedLock<EventCallbackRegistry>>,
     relation_locks: Arc<ShardedLock<BTreeMap<SmartString<LazyCompact>, Arc<ShardedLock<()>>>>>,
+    case_insensitive_names: Arc<AtomicBool>,
+    max_nesting_depth: Arc<AtomicUsize>,
+    read_only: bool,
 }
 
 impl<S> Debug for Db<S> {
@@ -125,6 +186,35 @@ pub(crate) struct BadDbInit(#[help] pub(crate) String);
 #[diagnostic(code(tx::import_into_index))]
 pub(crate) struct ImportIntoIndex(pub(crate) String);
 
+#[derive(Debug, Error, Diagnostic)]
+#[error("Cannot write to the database through a read-only handle")]
+#[diagnostic(code(db::read_only))]
+struct WriteThroughReadOnly;
+
+/// An entry of the portable content of a database, as written by [Db::snapshot].
+/// Each relation is followed by its rows, and the last entry is [SnapshotEntry::End],
+/// so the content is written and read one entry at a time.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize)]
+enum SnapshotEntry {
+    Relation(RelationSnapshot),
+    Row(Tuple),
+    End,
+}
+
+#[derive(serde_derive::Serialize, serde_derive::Deserialize)]
+struct RelationSnapshot {
+    name: SmartString<LazyCompact>,
+    metadata: StoredRelationMetadata,
+    indices: Vec<SnapshotIndex>,
+}
+
+/// A plain index in a snapshot: its name, the names of the indexed columns and whether it is unique
+type SnapshotIndex = (
+    SmartString<LazyCompact>,
+    Vec<SmartString<LazyCompact>>,
+    bool,
+);
+
 #[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, Default)]
 /// Rows in a relation, together with headers for the fields.
 pub struct NamedRows {
@@ -266,16 +356,32 @@ impl<'s, S: Storage<'s>> Db<S> {
             #[cfg(not(target_arch = "wasm32"))]
             event_callbacks: Default::default(),
             relation_locks: Default::default(),
+            case_insensitive_names: Default::default(),
+            max_nesting_depth: Arc::new(AtomicUsize::new(DEFAULT_MAX_NESTING_DEPTH)),
+            read_only: false,
         };
         Ok(ret)
     }
 
     /// Must be called after creation of the database to initialize the runtime state.
+    ///
+    /// Fails if the storage was written with a storage format other than the one
+    /// this version of Cozo uses, see [Self::storage_version].
     pub fn initialize(&'s self) -> Result<()> {
         self.load_last_ids()?;
         Ok(())
     }
 
+    /// The version of the storage format, persisted when the storage is first used
+    /// and checked every time it is opened.
+    pub fn storage_version(&'s self) -> Result<u8> {
+        let tx = self.transact()?;
+        match tx.storage_version()?.as_deref() {
+            Some([v]) => Ok(*v),
+            v => bail!("Storage has no valid version: {:?}", v),
+        }
+    }
+
     /// Run a multi-transaction. A command should be sent to `payloads`, and the result should be
     /// retrieved from `results`. A transaction ends when it receives a `Commit` or `Abort`,
     /// or when a query is not successful. After a transaction ends, sending / receiving from
@@ -313,7 +419,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                 TransactionPayload::Commit => {
                     for (lower, upper) in cleanups {
                         if let Err(err) = tx.store_tx.del_range_from_persisted(&lower, &upper) {
-                            eprintln!("{err:?}")
+                            error!("{err:?}")
                         }
                     }
 
@@ -330,18 +436,16 @@ impl<'s, S: Storage<'s>> Db<S> {
                     break;
                 }
                 TransactionPayload::Query((script, params)) => {
-                    let p =
-                        match parse_script(&script, &params, &self.fixed_rules.read().unwrap(), ts)
-                        {
-                            Ok(p) => p,
-                            Err(err) => {
-                                if results.send(Err(err)).is_err() {
-                                    break;
-                                } else {
-                                    continue;
-                                }
+                    let p = match self.parse_script(&script, &params, ts) {
+                        Ok(p) => p,
+                        Err(err) => {
+                            if results.send(Err(err)).is_err() {
+                                break;
+                            } else {
+                                continue;
                             }
-                        };
+                        }
+                    };
 
                     let p = match p.get_single_program() {
                         Ok(p) => p,
@@ -382,6 +486,60 @@ impl<'s, S: Storage<'s>> Db<S> {
         }
     }
 
+    /// Run several queries, usually mutations, in a single write transaction.
+    ///
+    /// The queries are run in order and each sees the changes of the ones before it. If
+    /// any of them fails, nothing is committed and the error is returned. All queries
+    /// share the parameters given in `params`.
+    pub fn batch<I, T>(&'s self, queries: I, params: BTreeMap<String, DataValue>) -> Result<()>
+    where
+        T: AsRef<str>,
+        I: IntoIterator<Item = T>,
+    {
+        let cur_vld = current_validity();
+        let programs: Vec<_> = queries
+            .into_iter()
+            .map(|q| {
+                self.parse_script(q.as_ref(), &params, cur_vld)?
+                    .get_single_program()
+            })
+            .try_collect()?;
+        let write_lock_names = programs
+            .iter()
+            .filter_map(|p| p.needs_write_lock())
+            .collect::<BTreeSet<_>>();
+        let write_locks = self.obtain_relation_locks(write_lock_names.iter());
+        let _write_lock_guards = write_locks
+            .iter()
+            .map(|lock| lock.read().unwrap())
+            .collect_vec();
+        let callback_targets = self.current_callback_targets();
+        let mut callback_collector = BTreeMap::new();
+        let mut cleanups = vec![];
+        {
+            let mut tx = self.transact_write()?;
+            for p in programs {
+                self.execute_single_program(
+                    p,
+                    &mut tx,
+                    &mut cleanups,
+                    cur_vld,
+                    &callback_targets,
+                    &mut callback_collector,
+                )?;
+            }
+            for (lower, upper) in cleanups {
+                tx.store_tx.del_range_from_persisted(&lower, &upper)?;
+            }
+            tx.commit_tx()?;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if !callback_collector.is_empty() {
+            self.send_callbacks(callback_collector)
+        }
+        Ok(())
+    }
+
     /// Run the CozoScript passed in. The `params` argument is a map of parameters.
     pub fn run_script(
         &'s self,
@@ -407,6 +565,286 @@ impl<'s, S: Storage<'s>> Db<S> {
         self.do_run_script(payload, &params, cur_vld, true)
     }
 
+    /// Look up the definition of a stored relation.
+    ///
+    /// Returns `None` if no relation with the given name exists.
+    pub fn resolve_relation(&'s self, name: &str) -> Result<Option<RelationDef>> {
+        let tx = self.transact()?;
+        let found = tx.resolve_relation(name, false)?;
+        Ok(found.as_ref().map(RelationDef::from))
+    }
+
+    /// Check whether a row with the given key exists in a stored relation,
+    /// without decoding the rest of the row.
+    ///
+    /// `key` must contain one value for each key column, in order.
+    pub fn exists(&'s self, relation: &str, key: &[DataValue]) -> Result<bool> {
+        let tx = self.transact()?;
+        let handle = tx.get_relation(relation, false)?;
+        require_access(&handle, AccessLevel::ReadOnly, "key lookup")?;
+        ensure!(
+            key.len() == handle.metadata.keys.len(),
+            "relation '{}' has {} key columns, but {} key values were given",
+            handle.name,
+            handle.metadata.keys.len(),
+            key.len()
+        );
+        let cur_vld = current_validity();
+        let key: Vec<_> = handle
+            .metadata
+            .keys
+            .iter()
+            .zip(key)
+            .map(|(col, val)| val.clone().coerce_to(&col.typing, cur_vld))
+            .try_collect()?;
+        handle.exists(&tx, &key)
+    }
+
+    /// Read the row with the given key from a stored relation, or `None` if there is none.
+    ///
+    /// `key` must contain one value for each key column, in order. The row is returned
+    /// as a map from column names to values, covering both key and non-key columns.
+    pub fn get_row(
+        &'s self,
+        relation: &str,
+        key: &[DataValue],
+    ) -> Result<Option<BTreeMap<String, DataValue>>> {
+        let tx = self.transact()?;
+        let handle = tx.get_relation(relation, false)?;
+        require_access(&handle, AccessLevel::ReadOnly, "data read")?;
+        ensure!(
+            key.len() == handle.metadata.keys.len(),
+            "relation '{}' has {} key columns, but {} key values were given",
+            handle.name,
+            handle.metadata.keys.len(),
+            key.len()
+        );
+        let cur_vld = current_validity();
+        let key: Vec<_> = handle
+            .metadata
+            .keys
+            .iter()
+            .zip(key)
+            .map(|(col, val)| val.clone().coerce_to(&col.typing, cur_vld))
+            .try_collect()?;
+        let found = handle.get(&tx, &key)?;
+        Ok(found.map(|row| {
+            handle
+                .metadata
+                .keys
+                .iter()
+                .chain(handle.metadata.non_keys.iter())
+                .map(|col| col.name.to_string())
+                .zip(row)
+                .collect()
+        }))
+    }
+
+    /// Find the keys of the rows of a stored relation through one of its regular indices.
+    ///
+    /// `index` is given as `relation:index`, and `value` holds the values of the leading
+    /// columns of the index. Returns the keys of all matching rows, in index order.
+    pub fn lookup_by_index(
+        &'s self,
+        index: &str,
+        value: &[DataValue],
+    ) -> Result<Vec<Vec<DataValue>>> {
+        let tx = self.transact()?;
+        let (rel_name, idx_name) = index
+            .split_once(':')
+            .ok_or_else(|| miette!("index must be given as 'relation:index', got '{}'", index))?;
+        let handle = tx.get_relation(rel_name, false)?;
+        require_access(&handle, AccessLevel::ReadOnly, "index lookup")?;
+        let (idx_handle, mapping) = handle
+            .indices
+            .get(idx_name)
+            .ok_or_else(|| IndexNotFound(idx_name.to_string(), rel_name.to_string()))?;
+        ensure!(
+            value.len() <= idx_handle.metadata.keys.len(),
+            "index '{}' has {} columns, but {} values were given",
+            index,
+            idx_handle.metadata.keys.len(),
+            value.len()
+        );
+        let cur_vld = current_validity();
+        let prefix: Vec<_> = idx_handle
+            .metadata
+            .keys
+            .iter()
+            .zip(value)
+            .map(|(col, val)| val.clone().coerce_to(&col.typing, cur_vld))
+            .try_collect()?;
+        // the index holds all key columns of the relation, possibly in a different order
+        let key_positions = (0..handle.metadata.keys.len())
+            .map(|i| mapping.iter().position(|m| *m == i).unwrap())
+            .collect_vec();
+        idx_handle
+            .scan_prefix(&tx, &prefix)
+            .map_ok(|tuple| key_positions.iter().map(|i| tuple[*i].clone()).collect())
+            .collect()
+    }
+
+    /// Get the statistics of a stored relation. These are kept up to date as the relation
+    /// is written to, so no scan of the relation is needed.
+    pub fn stats(&'s self, relation: &str) -> Result<RelationStats> {
+        let tx = self.transact()?;
+        let handle = tx.get_relation(relation, false)?;
+        Ok(RelationStats {
+            rows: tx.row_count(&handle)?,
+        })
+    }
+
+    /// Estimate how many rows a query reads and returns, without running it.
+    ///
+    /// The estimate uses the row counts kept for stored relations, so it is cheap to make
+    /// and helps spotting queries that scan whole relations. See [QueryCost] for how
+    /// the numbers are guessed.
+    pub fn explain_cost(
+        &'s self,
+        payload: &str,
+        params: BTreeMap<String, DataValue>,
+    ) -> Result<QueryCost> {
+        let prog = self
+            .parse_script(payload, &params, current_validity())?
+            .get_single_program()?;
+        let mut tx = self.transact()?;
+        let mut prog = prog;
+        self.inline_views(&tx, &mut prog, current_validity())?;
+        let (normalized_program, out_opts) = prog.into_normalized_program(&tx)?;
+        let (stratified_program, _) = normalized_program.into_stratified_program()?;
+        let program = stratified_program.magic_sets_rewrite(&tx)?;
+        let compiled = tx.stratified_magic_compile(program)?;
+        tx.estimate_cost(&compiled, out_opts.limit)
+    }
+
+    /// Count the distinct values in a column of a stored relation, null included.
+    ///
+    /// The exact count keeps every distinct value in memory. With `approximate`, the count
+    /// is instead estimated with HyperLogLog in constant memory, to within a few percent.
+    #[allow(clippy::mutable_key_type)]
+    pub fn count_distinct(
+        &'s self,
+        relation: &str,
+        column: &str,
+        approximate: bool,
+    ) -> Result<u64> {
+        let tx = self.transact()?;
+        let handle = tx.get_relation(relation, false)?;
+        require_access(&handle, AccessLevel::ReadOnly, "data read")?;
+        let Some(pos) = handle
+            .metadata
+            .keys
+            .iter()
+            .chain(handle.metadata.non_keys.iter())
+            .position(|col| col.name == column)
+        else {
+            bail!("relation {} has no column named {}", relation, column)
+        };
+        if approximate {
+            let mut hll = HyperLogLog::new();
+            for tuple in handle.scan_all(&tx) {
+                hll.insert(&tuple?[pos]);
+            }
+            Ok(hll.estimate().round() as u64)
+        } else {
+            let mut seen = BTreeSet::new();
+            for tuple in handle.scan_all(&tx) {
+                let mut tuple = tuple?;
+                seen.insert(tuple.swap_remove(pos));
+            }
+            Ok(seen.len() as u64)
+        }
+    }
+
+    /// Draw up to `n` rows uniformly at random from a stored relation.
+    ///
+    /// The relation is scanned once with reservoir sampling, so its size need not be known
+    /// in advance. Passing a `seed` makes the sample reproducible. The rows are returned in
+    /// key order.
+    pub fn sample(&'s self, relation: &str, n: usize, seed: Option<u64>) -> Result<NamedRows> {
+        let tx = self.transact()?;
+        let handle = tx.get_relation(relation, false)?;
+        require_access(&handle, AccessLevel::ReadOnly, "sampling")?;
+        let headers = handle
+            .metadata
+            .keys
+            .iter()
+            .chain(handle.metadata.non_keys.iter())
+            .map(|col| col.name.to_string())
+            .collect_vec();
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        // holds the sampled rows together with their positions in the scan
+        let mut reservoir: Vec<(usize, Tuple)> = Vec::with_capacity(n);
+        for (i, tuple) in handle.scan_all(&tx).enumerate() {
+            let tuple = tuple?;
+            if reservoir.len() < n {
+                reservoir.push((i, tuple));
+            } else {
+                let j = rng.gen_range(0..=i);
+                if j < n {
+                    reservoir[j] = (i, tuple);
+                }
+            }
+        }
+        reservoir.sort_by_key(|(i, _)| *i);
+        Ok(NamedRows::new(
+            headers,
+            reservoir.into_iter().map(|(_, tuple)| tuple).collect(),
+        ))
+    }
+
+    /// Delete data left behind by stored relations that no longer exist.
+    ///
+    /// Relations removed by `::remove` have their data deleted when the transaction
+    /// commits, but data may linger e.g. if that failed. This scans the store for key
+    /// ranges belonging to relation ids without metadata and deletes them, returning the
+    /// number of keys reclaimed.
+    pub fn vacuum(&'s self) -> Result<usize> {
+        let mut tx = self.transact_write()?;
+        let mut live = BTreeSet::new();
+        let lower = vec![DataValue::from("")].encode_as_key(RelationId::SYSTEM);
+        let upper =
+            vec![DataValue::from(String::from(LARGEST_UTF_CHAR))].encode_as_key(RelationId::SYSTEM);
+        for kv in tx.store_tx.range_scan(&lower, &upper) {
+            let (_, v) = kv?;
+            let handle = RelationHandle::decode(&v)?;
+            live.insert(handle.id);
+            live.extend(handle.indices.values().map(|(idx, _)| idx.id));
+            live.extend(handle.hnsw_indices.values().map(|(idx, _)| idx.id));
+            live.extend(handle.fts_indices.values().map(|(idx, _)| idx.id));
+            live.extend(
+                handle
+                    .lsh_indices
+                    .values()
+                    .flat_map(|(idx, inv_idx, _)| [idx.id, inv_idx.id]),
+            );
+        }
+        let end = Tuple::default().encode_as_key(RelationId(u64::MAX));
+        let mut cur = RelationId::SYSTEM.next();
+        let mut reclaimed = 0;
+        // jump from one relation id present in the store to the next
+        loop {
+            let start = Tuple::default().encode_as_key(cur);
+            let first = tx.store_tx.range_scan(&start, &end).next().transpose()?;
+            let Some((k, _)) = first else {
+                break;
+            };
+            let id = RelationId::raw_decode(&k);
+            let lower = Tuple::default().encode_as_key(id);
+            let upper = Tuple::default().encode_as_key(id.next());
+            if !live.contains(&id) {
+                reclaimed += tx.store_tx.range_count(&lower, &upper)?;
+                tx.store_tx.del_range_from_persisted(&lower, &upper)?;
+            }
+            cur = id.next();
+        }
+        tx.commit_tx()?;
+        Ok(reclaimed)
+    }
+
     /// Export relations to JSON data.
     ///
     /// `relations` contains names of the stored relations to export.
@@ -421,13 +859,7 @@ impl<'s, S: Storage<'s>> Db<S> {
             let handle = tx.get_relation(rel.as_ref(), false)?;
             let size_hint = handle.metadata.keys.len() + handle.metadata.non_keys.len();
 
-            if handle.access_level < AccessLevel::ReadOnly {
-                bail!(InsufficientAccessLevel(
-                    handle.name.to_string(),
-                    "data export".to_string(),
-                    handle.access_level
-                ));
-            }
+            require_access(&handle, AccessLevel::ReadOnly, "data export")?;
 
             let mut cols = handle
                 .metadata
@@ -458,11 +890,140 @@ impl<'s, S: Storage<'s>> Db<S> {
         }
         Ok(ret)
     }
+    /// Get the row with the smallest key in a stored relation, or `None` if it is empty.
+    /// Only one row is read from the storage.
+    pub fn first_row(&'s self, relation: &str) -> Result<Option<Tuple>> {
+        self.seek_row(relation, false)
+    }
+    /// Get the row with the largest key in a stored relation, or `None` if it is empty.
+    /// Only one row is read with the in-memory and SQLite engines, the others cannot seek
+    /// backwards and scan the whole relation.
+    pub fn last_row(&'s self, relation: &str) -> Result<Option<Tuple>> {
+        self.seek_row(relation, true)
+    }
+    /// Get the rows of a stored relation whose first key column lies within the given bounds,
+    /// in key order. Only the matching slice of the relation is read from the storage.
+    pub fn scan_range(
+        &'s self,
+        relation: &str,
+        lower: Bound<DataValue>,
+        upper: Bound<DataValue>,
+    ) -> Result<NamedRows> {
+        let tx = self.transact()?;
+        let handle = tx.get_relation(relation, false)?;
+        require_access(&handle, AccessLevel::ReadOnly, "data read")?;
+        let headers = handle
+            .metadata
+            .keys
+            .iter()
+            .chain(handle.metadata.non_keys.iter())
+            .map(|col| col.name.to_string())
+            .collect_vec();
+        let rows: Vec<_> = handle
+            .scan_key_range(&tx, lower.as_ref(), upper.as_ref())
+            .try_collect()?;
+        Ok(NamedRows::new(headers, rows))
+    }
+    /// Get the keys of all rows of a stored relation, in key order. Cheaper than reading
+    /// the whole rows, as the non-key columns are not decoded.
+    pub fn keys(&'s self, relation: &str) -> Result<Vec<Tuple>> {
+        let tx = self.transact()?;
+        let handle = tx.get_relation(relation, false)?;
+        require_access(&handle, AccessLevel::ReadOnly, "data read")?;
+        handle.scan_keys(&tx).try_collect()
+    }
+    /// Join a stored relation with other stored relations keyed the same way, such as
+    /// relations holding extra columns for some of the rows of `relation`.
+    ///
+    /// The rows of `relation` are returned in key order, each followed by the non-key
+    /// columns of the rows with the same key in each of `others`, in the given order.
+    /// A row with no counterpart in one of `others` is skipped, or with `left_join`,
+    /// kept with nulls in place of the missing columns.
+    pub fn join_on_keys(
+        &'s self,
+        relation: &str,
+        others: &[&str],
+        left_join: bool,
+    ) -> Result<NamedRows> {
+        let tx = self.transact()?;
+        let handle = tx.get_relation(relation, false)?;
+        let other_handles: Vec<_> = others
+            .iter()
+            .map(|name| tx.get_relation(name, false))
+            .try_collect()?;
+        let mut headers = handle
+            .metadata
+            .keys
+            .iter()
+            .chain(handle.metadata.non_keys.iter())
+            .map(|col| col.name.to_string())
+            .collect_vec();
+        for h in iter::once(&handle).chain(other_handles.iter()) {
+            require_access(h, AccessLevel::ReadOnly, "data read")?;
+        }
+        for other in &other_handles {
+            ensure!(
+                other.metadata.keys.len() == handle.metadata.keys.len(),
+                "relation '{}' has {} key columns, but '{}' has {}",
+                other.name,
+                other.metadata.keys.len(),
+                handle.name,
+                handle.metadata.keys.len()
+            );
+            for col in &other.metadata.non_keys {
+                ensure!(
+                    !headers.iter().any(|h| *h == col.name),
+                    "column '{}' of relation '{}' clashes with a column joined before it",
+                    col.name,
+                    other.name
+                );
+                headers.push(col.name.to_string());
+            }
+        }
+        let cur_vld = current_validity();
+        let num_keys = handle.metadata.keys.len();
+        let mut rows = vec![];
+        'outer: for row in handle.scan_all(&tx) {
+            let mut row = row?;
+            for other in &other_handles {
+                let key: Vec<_> = other
+                    .metadata
+                    .keys
+                    .iter()
+                    .zip(&row[..num_keys])
+                    .map(|(col, val)| val.clone().coerce_to(&col.typing, cur_vld))
+                    .try_collect()?;
+                match other.get(&tx, &key)? {
+                    Some(found) => row.extend(found.into_iter().skip(num_keys)),
+                    None if left_join => row
+                        .extend(iter::repeat(DataValue::Null).take(other.metadata.non_keys.len())),
+                    None => continue 'outer,
+                }
+            }
+            rows.push(row);
+        }
+        Ok(NamedRows::new(headers, rows))
+    }
+    fn seek_row(&'s self, relation: &str, last: bool) -> Result<Option<Tuple>> {
+        let tx = self.transact()?;
+        let handle = tx.get_relation(relation, false)?;
+        require_access(&handle, AccessLevel::ReadOnly, "data read")?;
+        if last {
+            handle.last_row(&tx)
+        } else {
+            handle.first_row(&tx)
+        }
+    }
     /// Import relations. The argument `data` accepts data in the shape of
     /// what was returned by [Self::export_relations].
     /// The target stored relations must already exist in the database.
     /// Any associated indices will be updated.
     ///
+    /// Each relation's rows are checked against its own schema: every header must name
+    /// a column of the relation and every value must fit the type of its column.
+    /// All relations are imported in a single transaction, so a bad row anywhere
+    /// leaves the database unchanged.
+    ///
     /// Note that triggers and callbacks are _not_ run for the relations, if any exists.
     /// If you need to activate triggers or callbacks, use queries with parameters.
     pub fn import_relations(&'s self, data: BTreeMap<String, NamedRows>) -> Result<()> {
@@ -497,13 +1058,7 @@ impl<'s, S: Storage<'s>> Db<S> {
             let handle = tx.get_relation(relation, false)?;
             let has_indices = !handle.indices.is_empty();
 
-            if handle.access_level < AccessLevel::Protected {
-                bail!(InsufficientAccessLevel(
-                    handle.name.to_string(),
-                    "data import".to_string(),
-                    handle.access_level
-                ));
-            }
+            require_access(&handle, AccessLevel::Protected, "data import")?;
 
             let header2idx: BTreeMap<_, _> = in_data
                 .headers
@@ -511,6 +1066,16 @@ impl<'s, S: Storage<'s>> Db<S> {
                 .enumerate()
                 .map(|(i, k)| -> Result<(&str, usize)> { Ok((k as &str, i)) })
                 .try_collect()?;
+            if let Some(extra) = in_data.headers.iter().find(|h| {
+                !handle
+                    .metadata
+                    .keys
+                    .iter()
+                    .chain(handle.metadata.non_keys.iter())
+                    .any(|col| col.name == h.as_str())
+            }) {
+                bail!("relation {} has no column named {}", relation, extra)
+            }
 
             let key_indices: Vec<_> = handle
                 .metadata
@@ -548,6 +1113,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                     .try_collect()?
             };
 
+            let mut added_rows = 0;
             for row in in_data.rows {
                 let keys: Vec<_> = key_indices
                     .iter()
@@ -555,21 +1121,38 @@ impl<'s, S: Storage<'s>> Db<S> {
                         let v = row
                             .get(*i)
                             .ok_or_else(|| miette!("row too short: {:?}", row))?;
-                        col.typing.coerce(v.clone(), cur_vld)
+                        v.clone().coerce_to(&col.typing, cur_vld).wrap_err_with(|| {
+                            format!("when importing column {} of {}", col.name, relation)
+                        })
+                    })
+                    .try_collect()?;
+                let vals: Vec<_> = val_indices
+                    .iter()
+                    .map(|(i, col)| -> Result<DataValue> {
+                        let v = row
+                            .get(*i)
+                            .ok_or_else(|| miette!("row too short: {:?}", row))?;
+                        v.clone().coerce_to(&col.typing, cur_vld).wrap_err_with(|| {
+                            format!("when importing column {} of {}", col.name, relation)
+                        })
                     })
                     .try_collect()?;
                 let k_store = handle.encode_key_for_store(&keys, Default::default())?;
+                let existing = tx.store_tx.get(&k_store, false)?;
+                match (&existing, is_delete) {
+                    (Some(_), true) => added_rows -= 1,
+                    (None, false) => added_rows += 1,
+                    _ => {}
+                }
                 if has_indices {
-                    if let Some(existing) = tx.store_tx.get(&k_store, false)? {
+                    if let Some(existing) = existing {
                         let mut old = keys.clone();
                         extend_tuple_from_v(&mut old, &existing);
-                        if is_delete || old != row {
-                            for (idx_rel, extractor) in handle.indices.values() {
+                        if is_delete || old[keys.len()..] != vals[..] {
+                            for (name, (_, extractor)) in handle.indices.iter() {
                                 let idx_tup =
                                     extractor.iter().map(|i| old[*i].clone()).collect_vec();
-                                let encoded =
-                                    idx_rel.encode_key_for_store(&idx_tup, Default::default())?;
-                                tx.store_tx.del(&encoded)?;
+                                handle.del_from_index(&mut tx, name, &idx_tup)?;
                             }
                         }
                     }
@@ -577,22 +1160,14 @@ impl<'s, S: Storage<'s>> Db<S> {
                 if is_delete {
                     tx.store_tx.del(&k_store)?;
                 } else {
-                    let vals: Vec<_> = val_indices
-                        .iter()
-                        .map(|(i, col)| -> Result<DataValue> {
-                            let v = row
-                                .get(*i)
-                                .ok_or_else(|| miette!("row too short: {:?}", row))?;
-                            col.typing.coerce(v.clone(), cur_vld)
-                        })
-                        .try_collect()?;
                     let v_store = handle.encode_val_only_for_store(&vals, Default::default())?;
                     tx.store_tx.put(&k_store, &v_store)?;
                     if has_indices {
                         let mut kv = keys;
                         kv.extend(vals);
-                        for (idx_rel, extractor) in handle.indices.values() {
+                        for (name, (idx_rel, extractor)) in handle.indices.iter() {
                             let idx_tup = extractor.iter().map(|i| kv[*i].clone()).collect_vec();
+                            handle.ensure_unique_in_index(&mut tx, name, &idx_tup)?;
                             let encoded =
                                 idx_rel.encode_key_for_store(&idx_tup, Default::default())?;
                             tx.store_tx.put(&encoded, &[])?;
@@ -600,10 +1175,379 @@ impl<'s, S: Storage<'s>> Db<S> {
                     }
                 }
             }
+            tx.adjust_row_count(&handle, added_rows)?;
         }
         tx.commit_tx()?;
         Ok(())
     }
+    /// Update the rows of a stored relation for which `filter` holds, returning the
+    /// number of rows updated.
+    ///
+    /// `assignments` maps non-key columns to the expressions computing their new values.
+    /// These and `filter` may refer to the columns of the relation by name and to `params`,
+    /// e.g. the filter `id > 5` with the assignment `count + 1` for the column `count`.
+    /// All expressions see the row as it was before the update. The new values are then
+    /// written in a single transaction as by an `:update` query, so all indices are
+    /// maintained and triggers and callbacks are run.
+    pub fn update_where(
+        &'s self,
+        relation: &str,
+        assignments: BTreeMap<String, String>,
+        filter: &str,
+        params: BTreeMap<String, DataValue>,
+    ) -> Result<usize> {
+        let rel_name = SmartString::from(relation);
+        let locks = self.obtain_relation_locks(iter::once(&rel_name));
+        let _guards = locks.iter().map(|l| l.read().unwrap()).collect_vec();
+
+        let cur_vld = current_validity();
+        let tx = self.transact_write()?;
+        if relation.contains(':') {
+            bail!("cannot update index {} directly", relation)
+        }
+        let handle = tx.get_relation(relation, false)?;
+        require_access(&handle, AccessLevel::Protected, "data update")?;
+        let n_keys = handle.metadata.keys.len();
+        let binding_map = column_bindings(&handle);
+        let mut filter = self.parse_expressions(filter, &params)?;
+        filter.fill_binding_indices(&binding_map)?;
+        let filter_bytecode = filter.compile()?;
+        let mut cols = handle.metadata.keys.clone();
+        let mut compiled = vec![];
+        for (name, src) in &assignments {
+            let Some(col) = handle
+                .metadata
+                .non_keys
+                .iter()
+                .find(|col| col.name == name.as_str())
+            else {
+                bail!("relation {} has no non-key column named {}", relation, name)
+            };
+            let mut expr = self.parse_expressions(src, &params)?;
+            expr.fill_binding_indices(&binding_map)?;
+            cols.push(col.clone());
+            compiled.push(expr.compile()?);
+        }
+
+        // the store cannot be written while being scanned, so the updates are collected first
+        let mut updates = vec![];
+        let mut stack = vec![];
+        for tuple in handle.scan_all(&tx) {
+            let tuple = tuple?;
+            if !eval_bytecode_pred(&filter_bytecode, &tuple, &mut stack, filter.span())? {
+                continue;
+            }
+            let mut row = tuple[..n_keys].to_vec();
+            for bytecode in &compiled {
+                row.push(eval_bytecode(bytecode, &tuple, &mut stack)?);
+            }
+            updates.push(row);
+        }
+        let n_updated = updates.len();
+        self.mutate_relation(tx, &handle, RelationOp::Update, cols, updates, cur_vld)
+            .wrap_err_with(|| format!("when updating relation {relation}"))?;
+        Ok(n_updated)
+    }
+    /// Apply `op` to a stored relation with `rows` holding the columns `cols`, then commit
+    /// `tx`. This is what a query ending in the op does, so indices are maintained and
+    /// triggers and callbacks are run.
+    fn mutate_relation(
+        &'s self,
+        mut tx: SessionTx<'_>,
+        handle: &RelationHandle,
+        op: RelationOp,
+        cols: Vec<ColumnDef>,
+        rows: Vec<Tuple>,
+        cur_vld: ValidityTs,
+    ) -> Result<()> {
+        let bindings = cols
+            .iter()
+            .map(|col| Symbol::new(col.name.clone(), Default::default()))
+            .collect_vec();
+        let meta = InputRelationHandle {
+            name: Symbol::new(handle.name.clone(), Default::default()),
+            metadata: StoredRelationMetadata {
+                keys: cols,
+                non_keys: vec![],
+            },
+            key_bindings: bindings.clone(),
+            dep_bindings: vec![],
+            span: Default::default(),
+        };
+        let callback_targets = self.current_callback_targets();
+        let mut callback_collector = BTreeMap::new();
+        let cleanups = tx.execute_relation(
+            self,
+            rows.into_iter(),
+            op,
+            &meta,
+            &bindings,
+            cur_vld,
+            &callback_targets,
+            &mut callback_collector,
+            true,
+            "",
+        )?;
+        for (lower, upper) in cleanups {
+            tx.store_tx.del_range_from_persisted(&lower, &upper)?;
+        }
+        tx.commit_tx()?;
+        #[cfg(not(target_arch = "wasm32"))]
+        if !callback_collector.is_empty() {
+            self.send_callbacks(callback_collector)
+        }
+        Ok(())
+    }
+    /// Delete the rows of a stored relation for which `filter` holds, returning the
+    /// number of rows deleted.
+    ///
+    /// `filter` may refer to the columns of the relation by name and to `params`, as in
+    /// [Self::update_where]. The rows are deleted in a single transaction as by an `:rm`
+    /// query, so all indices are maintained and triggers and callbacks are run.
+    pub fn delete_where(
+        &'s self,
+        relation: &str,
+        filter: &str,
+        params: BTreeMap<String, DataValue>,
+    ) -> Result<usize> {
+        let rel_name = SmartString::from(relation);
+        let locks = self.obtain_relation_locks(iter::once(&rel_name));
+        let _guards = locks.iter().map(|l| l.read().unwrap()).collect_vec();
+
+        let cur_vld = current_validity();
+        let tx = self.transact_write()?;
+        if relation.contains(':') {
+            bail!("cannot delete from index {} directly", relation)
+        }
+        let handle = tx.get_relation(relation, false)?;
+        require_access(&handle, AccessLevel::Protected, "data deletion")?;
+        let n_keys = handle.metadata.keys.len();
+        let mut filter = self.parse_expressions(filter, &params)?;
+        filter.fill_binding_indices(&column_bindings(&handle))?;
+        let filter_bytecode = filter.compile()?;
+
+        let mut deleted = vec![];
+        let mut stack = vec![];
+        for tuple in handle.scan_all(&tx) {
+            let mut tuple = tuple?;
+            if eval_bytecode_pred(&filter_bytecode, &tuple, &mut stack, filter.span())? {
+                tuple.truncate(n_keys);
+                deleted.push(tuple);
+            }
+        }
+        let n_deleted = deleted.len();
+        let cols = handle.metadata.keys.clone();
+        self.mutate_relation(tx, &handle, RelationOp::Rm, cols, deleted, cur_vld)
+            .wrap_err_with(|| format!("when deleting from relation {relation}"))?;
+        Ok(n_deleted)
+    }
+    /// Bulk-load rows into a stored relation, faster than importing them one by one
+    /// through queries. Each row maps column names to values: missing columns take
+    /// their default values, and unknown columns are an error.
+    /// Returns the number of rows imported.
+    ///
+    /// The relation is resolved once for the whole import and the rows are written
+    /// in batches according to `options`. As with [Self::import_relations],
+    /// triggers and callbacks are _not_ run.
+    pub fn import_rows(
+        &'s self,
+        relation: &str,
+        rows: impl IntoIterator<Item = BTreeMap<String, DataValue>>,
+        options: ImportOptions,
+    ) -> Result<usize> {
+        self.import_fallible_rows(relation, rows.into_iter().map(Ok), options)
+    }
+    /// Bulk-load newline-delimited JSON objects into a stored relation, see [Self::import_rows].
+    /// Empty lines are ignored. If `strict` is true, a malformed line aborts the import
+    /// with an error pointing at the line, otherwise such lines are skipped.
+    /// Returns the number of rows imported.
+    pub fn import_jsonl(
+        &'s self,
+        relation: &str,
+        reader: impl BufRead,
+        strict: bool,
+        options: ImportOptions,
+    ) -> Result<usize> {
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("malformed JSON line {0}: {1}")]
+        #[diagnostic(code(import::bad_jsonl))]
+        struct BadJsonLine(usize, String);
+
+        let rows = reader.lines().enumerate().filter_map(
+            move |(i, line)| -> Option<Result<BTreeMap<String, DataValue>>> {
+                let line = match line {
+                    Ok(l) => l,
+                    Err(err) => return Some(Err(err).into_diagnostic()),
+                };
+                if line.trim().is_empty() {
+                    return None;
+                }
+                let parsed = match serde_json::from_str::<JsonValue>(&line) {
+                    Ok(JsonValue::Object(obj)) => Ok(obj
+                        .into_iter()
+                        .map(|(k, v)| (k, DataValue::from(v)))
+                        .collect()),
+                    Ok(_) => Err(BadJsonLine(i + 1, "expected an object".to_string())),
+                    Err(err) => Err(BadJsonLine(i + 1, err.to_string())),
+                };
+                match parsed {
+                    Ok(row) => Some(Ok(row)),
+                    Err(err) if strict => Some(Err(err.into())),
+                    Err(_) => None,
+                }
+            },
+        );
+        self.import_fallible_rows(relation, rows, options)
+    }
+    /// Bulk-load CSV data into a stored relation, see [Self::import_rows].
+    /// The first row is the header naming the columns. Cells are parsed according to
+    /// the declared types of the columns, and empty cells are treated as missing,
+    /// so they take the default value of the column, or null for nullable columns.
+    /// Returns the number of rows imported.
+    pub fn import_csv(
+        &'s self,
+        relation: &str,
+        reader: impl Read,
+        options: ImportOptions,
+    ) -> Result<usize> {
+        let typings: BTreeMap<_, _> = {
+            let tx = self.transact()?;
+            let handle = tx.get_relation(relation, false)?;
+            handle
+                .metadata
+                .keys
+                .iter()
+                .chain(handle.metadata.non_keys.iter())
+                .map(|col| (col.name.to_string(), col.typing.clone()))
+                .collect()
+        };
+        let mut rdr = csv::Reader::from_reader(reader);
+        let headers: Vec<_> = rdr
+            .headers()
+            .into_diagnostic()?
+            .iter()
+            .map(|h| -> Result<(String, NullableColType)> {
+                let typing = typings
+                    .get(h)
+                    .ok_or_else(|| miette!("relation {} has no column named {}", relation, h))?;
+                Ok((h.to_string(), typing.clone()))
+            })
+            .try_collect()?;
+        let rows = rdr.into_records().map(move |record| {
+            let record = record.into_diagnostic()?;
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            let mut row = BTreeMap::new();
+            for ((name, typing), cell) in headers.iter().zip(record.iter()) {
+                if cell.is_empty() {
+                    continue;
+                }
+                let val = parse_csv_cell(cell, typing)
+                    .wrap_err_with(|| format!("on line {line} of CSV for column {name}"))?;
+                row.insert(name.clone(), val);
+            }
+            Ok(row)
+        });
+        self.import_fallible_rows(relation, rows, options)
+    }
+    fn import_fallible_rows(
+        &'s self,
+        relation: &str,
+        rows: impl Iterator<Item = Result<BTreeMap<String, DataValue>>>,
+        options: ImportOptions,
+    ) -> Result<usize> {
+        ensure!(
+            options.batch_size > 0,
+            "batch size for import must be positive"
+        );
+        if relation.contains(':') {
+            bail!(ImportIntoIndex(relation.to_string()))
+        }
+        let rel_name = SmartString::from(relation);
+        let locks = self.obtain_relation_locks(iter::once(&rel_name));
+        let _guards = locks.iter().map(|l| l.read().unwrap()).collect_vec();
+
+        let cur_vld = current_validity();
+        let mut tx = self.transact_write()?;
+        let handle = tx.get_relation(relation, false)?;
+        require_access(&handle, AccessLevel::Protected, "data import")?;
+        ensure!(
+            handle.hnsw_indices.is_empty()
+                && handle.fts_indices.is_empty()
+                && handle.lsh_indices.is_empty(),
+            "cannot bulk import into relation {} as it has search indices",
+            relation
+        );
+        let n_keys = handle.metadata.keys.len();
+        let cols = handle
+            .metadata
+            .keys
+            .iter()
+            .chain(handle.metadata.non_keys.iter())
+            .collect_vec();
+
+        let mut count = 0;
+        let mut added_rows = 0;
+        let mut tuple = Vec::with_capacity(cols.len());
+        for row in rows {
+            let mut row = row?;
+            tuple.clear();
+            for col in &cols {
+                let val = match row.remove(&col.name as &str) {
+                    Some(v) => v,
+                    None => match &col.default_gen {
+                        Some(gen) => gen.clone().eval_to_const()?,
+                        None => DataValue::Null,
+                    },
+                };
+                tuple.push(
+                    val.coerce_to(&col.typing, cur_vld)
+                        .wrap_err_with(|| format!("when importing column {}", col.name))?,
+                );
+            }
+            if let Some(extra) = row.keys().next() {
+                bail!("relation {} has no column named {}", relation, extra)
+            }
+
+            let k_store = handle.encode_key_for_store(&tuple[..n_keys], Default::default())?;
+            let existing = if options.assume_new_keys {
+                None
+            } else {
+                tx.store_tx.get(&k_store, false)?
+            };
+            match existing {
+                Some(existing) => {
+                    let mut old = tuple[..n_keys].to_vec();
+                    extend_tuple_from_v(&mut old, &existing);
+                    for (name, (_, extractor)) in handle.indices.iter() {
+                        let idx_tup = extractor.iter().map(|i| old[*i].clone()).collect_vec();
+                        handle.del_from_index(&mut tx, name, &idx_tup)?;
+                    }
+                }
+                None => added_rows += 1,
+            }
+            let v_store = handle.encode_val_only_for_store(&tuple[n_keys..], Default::default())?;
+            tx.store_tx.put(&k_store, &v_store)?;
+            for (name, (idx_rel, extractor)) in handle.indices.iter() {
+                let idx_tup = extractor.iter().map(|i| tuple[*i].clone()).collect_vec();
+                handle.ensure_unique_in_index(&mut tx, name, &idx_tup)?;
+                let encoded = idx_rel.encode_key_for_store(&idx_tup, Default::default())?;
+                tx.store_tx.put(&encoded, &[])?;
+            }
+
+            count += 1;
+            if count % options.batch_size == 0 {
+                tx.adjust_row_count(&handle, added_rows)?;
+                added_rows = 0;
+                tx.commit_tx()?;
+                // the old transaction must be gone before a new one can be started
+                drop(tx);
+                tx = self.transact_write()?;
+            }
+        }
+        tx.adjust_row_count(&handle, added_rows)?;
+        tx.commit_tx()?;
+        Ok(count)
+    }
     /// Backup the running database into an Sqlite file
     #[allow(unused_variables)]
     pub fn backup_db(&'s self, out_file: impl AsRef<Path>) -> Result<()> {
@@ -625,6 +1569,10 @@ impl<'s, S: Storage<'s>> Db<S> {
     /// Restore from an Sqlite backup
     #[allow(unused_variables)]
     pub fn restore_backup(&'s self, in_file: impl AsRef<Path>) -> Result<()> {
+        // the rows are written to the storage directly, not through a write transaction
+        if self.read_only {
+            bail!(WriteThroughReadOnly)
+        }
         #[cfg(feature = "storage-sqlite")]
         {
             let sqlite_db = crate::new_cozo_sqlite(in_file)?;
@@ -649,6 +1597,132 @@ impl<'s, S: Storage<'s>> Db<S> {
         #[cfg(not(feature = "storage-sqlite"))]
         bail!("backup requires the 'storage-sqlite' feature to be enabled")
     }
+    /// Write the stored relations of the database, their rows and their plain indices
+    /// to `writer`, in a format independent of the storage engine and of the way
+    /// it encodes data. Use [Db::restore] to load the result into a fresh database.
+    ///
+    /// Search indices (HNSW, FTS and LSH), triggers and access levels are not included.
+    /// Rows are written as they are scanned, so `writer` should be buffered.
+    pub fn snapshot(&'s self, mut writer: impl Write) -> Result<()> {
+        let tx = self.transact()?;
+        let lower = vec![DataValue::from("")].encode_as_key(RelationId::SYSTEM);
+        let upper =
+            vec![DataValue::from(String::from(LARGEST_UTF_CHAR))].encode_as_key(RelationId::SYSTEM);
+        let mut write_entry = |entry: &SnapshotEntry| {
+            rmp_serde::encode::write_named(&mut writer, entry).into_diagnostic()
+        };
+        for kv in tx.store_tx.range_scan(&lower, &upper) {
+            let (_, v) = kv?;
+            let handle = RelationHandle::decode(&v)?;
+            if handle.name.contains(':') {
+                continue;
+            }
+            let columns = handle
+                .metadata
+                .keys
+                .iter()
+                .chain(handle.metadata.non_keys.iter())
+                .collect_vec();
+            let indices = handle
+                .indices
+                .iter()
+                .map(|(name, (_, mapping))| {
+                    let unique = handle.unique_indices.get(name).copied();
+                    // the indexed columns are followed by the key columns not among them,
+                    // so the shortest prefix consistent with that defines an equivalent index
+                    let n_cols = unique.unwrap_or_else(|| {
+                        (1..mapping.len())
+                            .find(|n| {
+                                let prefix = &mapping[..*n];
+                                (0..handle.metadata.keys.len())
+                                    .filter(|i| !prefix.contains(i))
+                                    .eq(mapping[*n..].iter().copied())
+                            })
+                            .unwrap_or(mapping.len())
+                    });
+                    let cols = mapping[..n_cols]
+                        .iter()
+                        .map(|i| columns[*i].name.clone())
+                        .collect_vec();
+                    (name.clone(), cols, unique.is_some())
+                })
+                .collect_vec();
+            write_entry(&SnapshotEntry::Relation(RelationSnapshot {
+                name: handle.name.clone(),
+                metadata: handle.metadata.clone(),
+                indices,
+            }))?;
+            for tuple in handle.scan_all(&tx) {
+                write_entry(&SnapshotEntry::Row(tuple?))?;
+            }
+        }
+        write_entry(&SnapshotEntry::End)
+    }
+    /// Load what was written by [Db::snapshot] from `reader`, one row at a time,
+    /// so `reader` should be buffered. The database must not contain any stored relations.
+    pub fn restore(&'s self, mut reader: impl Read) -> Result<()> {
+        let mut tx = self.transact_write()?;
+        let lower = vec![DataValue::from("")].encode_as_key(RelationId::SYSTEM);
+        let upper =
+            vec![DataValue::from(String::from(LARGEST_UTF_CHAR))].encode_as_key(RelationId::SYSTEM);
+        ensure!(
+            tx.store_tx.range_scan(&lower, &upper).next().is_none(),
+            "Cannot restore snapshot: stored relations exist in the current database"
+        );
+        // indices are built once the rows of their relation are all in
+        let mut current: Option<(RelationHandle, Vec<SnapshotIndex>, i64)> = None;
+        loop {
+            let entry: SnapshotEntry = rmp_serde::from_read(&mut reader)
+                .into_diagnostic()
+                .wrap_err("bad snapshot data")?;
+            let rel = match entry {
+                SnapshotEntry::Row(tuple) => {
+                    let Some((handle, _, n_rows)) = &mut current else {
+                        bail!("bad snapshot data: rows before any relation")
+                    };
+                    let key = handle.encode_key_for_store(&tuple, Default::default())?;
+                    let val = handle.encode_val_for_store(&tuple, Default::default())?;
+                    tx.store_tx.put(&key, &val)?;
+                    *n_rows += 1;
+                    continue;
+                }
+                SnapshotEntry::Relation(rel) => Some(rel),
+                SnapshotEntry::End => None,
+            };
+            if let Some((handle, indices, n_rows)) = current.take() {
+                tx.adjust_row_count(&handle, n_rows)?;
+                let name = Symbol::new(handle.name.clone(), Default::default());
+                for (idx_name, cols, unique) in indices {
+                    let idx_name = Symbol::new(idx_name, Default::default());
+                    let cols = cols
+                        .into_iter()
+                        .map(|col| Symbol::new(col, Default::default()))
+                        .collect_vec();
+                    tx.create_index(&name, &idx_name, &cols, unique)?;
+                }
+            }
+            let Some(rel) = rel else { break };
+            let handle = tx.create_relation(InputRelationHandle {
+                name: Symbol::new(rel.name, Default::default()),
+                key_bindings: rel
+                    .metadata
+                    .keys
+                    .iter()
+                    .map(|col| Symbol::new(col.name.clone(), Default::default()))
+                    .collect(),
+                dep_bindings: rel
+                    .metadata
+                    .non_keys
+                    .iter()
+                    .map(|col| Symbol::new(col.name.clone(), Default::default()))
+                    .collect(),
+                metadata: rel.metadata,
+                span: Default::default(),
+            })?;
+            current = Some((handle, rel.indices, 0));
+        }
+        tx.commit_tx()
+    }
     /// Import data from relations in a backup file.
     /// The target stored relations must already exist in the database, and it must not
     /// have any associated indices. If you want to import into relations with indices,
@@ -692,13 +1766,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                     bail!(RestoreIntoRelWithIndices(dst_handle.name.to_string()))
                 }
 
-                if dst_handle.access_level < AccessLevel::Protected {
-                    bail!(InsufficientAccessLevel(
-                        dst_handle.name.to_string(),
-                        "data import".to_string(),
-                        dst_handle.access_level
-                    ));
-                }
+                require_access(&dst_handle, AccessLevel::Protected, "data import")?;
 
                 let src_lower = Tuple::default().encode_as_key(src_handle.id);
                 let src_upper = Tuple::default().encode_as_key(src_handle.id.next());
@@ -715,12 +1783,54 @@ impl<'s, S: Storage<'s>> Db<S> {
                     let (key, val) = result?;
                     dst_tx.store_tx.put(&key, &val)?;
                 }
+                dst_tx.recount_rows(&dst_handle)?;
             }
 
             src_tx.commit_tx()?;
             dst_tx.commit_tx()
         }
     }
+    /// Make the names of stored relations, indices and columns case-insensitive.
+    ///
+    /// When enabled, names in scripts are lowercased before they are resolved, as are the
+    /// relation names passed to methods such as [Self::stats], so `Person` and `person`
+    /// refer to the same relation. Relations created while enabled are stored under their
+    /// lowercased names; relations with uppercase letters in their names that were created
+    /// before cannot be reached while it is enabled. Expressions inside index definitions
+    /// must use the lowercased column names. The setting is not persisted and is
+    /// disabled by default.
+    pub fn set_case_insensitive_names(&self, enabled: bool) {
+        self.case_insensitive_names
+            .store(enabled, Ordering::Relaxed);
+    }
+    /// Set the maximum nesting depth of brackets (and block comments) allowed in scripts
+    /// and expressions run against this database, [DEFAULT_MAX_NESTING_DEPTH] by default.
+    /// Parsing is recursive, and deeper scripts are rejected with an error
+    /// instead of risking a stack overflow.
+    pub fn set_max_nesting_depth(&self, depth: usize) {
+        self.max_nesting_depth.store(depth, Ordering::Relaxed);
+    }
+
+    /// Get a handle to the same database that cannot write to it, e.g. for serving queries.
+    ///
+    /// Scripts run through the handle are always run as [ScriptMutability::Immutable], and
+    /// methods writing data, such as [Self::import_relations], return errors. Queries and
+    /// scans work as usual, and see the writes made through other handles.
+    ///
+    /// The handle starts with the settings of this one, such as
+    /// [Self::set_case_insensitive_names], but changing them through either handle
+    /// leaves the other as it is.
+    pub fn read_only(&self) -> Self {
+        let case_insensitive_names = self.case_insensitive_names.load(Ordering::Relaxed);
+        let max_nesting_depth = self.max_nesting_depth.load(Ordering::Relaxed);
+        Self {
+            case_insensitive_names: Arc::new(AtomicBool::new(case_insensitive_names)),
+            max_nesting_depth: Arc::new(AtomicUsize::new(max_nesting_depth)),
+            read_only: true,
+            ..self.clone()
+        }
+    }
+
     /// Register a custom fixed rule implementation.
     pub fn register_fixed_rule<R>(&self, name: String, rule_impl: R) -> Result<()>
     where
@@ -821,6 +1931,9 @@ impl<'s, S: Storage<'s>> Db<S> {
     }
 
     fn compact_relation(&'s self) -> Result<()> {
+        if self.read_only {
+            bail!(WriteThroughReadOnly)
+        }
         let l = Tuple::default().encode_as_key(RelationId(0));
         let u = vec![DataValue::Bot].encode_as_key(RelationId(u64::MAX));
         self.db.range_compact(&l, &u)?;
@@ -834,6 +1947,61 @@ impl<'s, S: Storage<'s>> Db<S> {
         tx.commit_tx()?;
         Ok(())
     }
+    /// Parse a script, folding the case of names if [Self::set_case_insensitive_names]
+    /// is in effect.
+    pub(crate) fn parse_script(
+        &self,
+        payload: &str,
+        param_pool: &BTreeMap<String, DataValue>,
+        cur_vld: ValidityTs,
+    ) -> Result<CozoScript> {
+        check_nesting_depth(payload, self.max_nesting_depth.load(Ordering::Relaxed))?;
+        let mut script = parse_script(
+            payload,
+            param_pool,
+            &self.fixed_rules.read().unwrap(),
+            cur_vld,
+        )?;
+        if self.case_insensitive_names.load(Ordering::Relaxed) {
+            script.fold_case();
+        }
+        Ok(script)
+    }
+    /// Parse an expression, such as a filter given to [Self::update_where].
+    fn parse_expressions(&self, src: &str, params: &BTreeMap<String, DataValue>) -> Result<Expr> {
+        check_nesting_depth(src, self.max_nesting_depth.load(Ordering::Relaxed))?;
+        parse_expressions(src, params)
+    }
+    /// Replace the reads of views in `prog` by the rules of the queries defining them,
+    /// including the views read by those queries in turn.
+    fn inline_views(
+        &self,
+        tx: &SessionTx<'_>,
+        prog: &mut InputProgram,
+        cur_vld: ValidityTs,
+    ) -> Result<()> {
+        let mut inlined: BTreeMap<Symbol, (Symbol, Vec<Symbol>)> = BTreeMap::new();
+        loop {
+            let mut changed = false;
+            for name in prog.stored_relations_used() {
+                if let Some((rule, head)) = inlined.get(&name) {
+                    prog.read_from_rule(&name, rule, head)?;
+                    changed = true;
+                } else if let Some(script) = tx.view_definition(&name)? {
+                    let view = self
+                        .parse_script(&script, &Default::default(), cur_vld)?
+                        .get_single_program()?;
+                    let rule = Symbol::new(format!("*{}", name), name.span);
+                    let head = prog.add_view_rules(&rule, view)?;
+                    inlined.insert(name, (rule, head));
+                    changed = true;
+                }
+            }
+            if !changed {
+                return Ok(());
+            }
+        }
+    }
     pub(crate) fn transact(&'s self) -> Result<SessionTx<'_>> {
         let ret = SessionTx {
             store_tx: Box::new(self.db.transact(false)?),
@@ -841,16 +2009,23 @@ impl<'s, S: Storage<'s>> Db<S> {
             relation_store_id: self.relation_store_id.clone(),
             temp_store_id: Default::default(),
             tokenizers: self.tokenizers.clone(),
+            fold_case: self.case_insensitive_names.load(Ordering::Relaxed),
+            row_count_part: Uuid::new_v4(),
         };
         Ok(ret)
     }
     pub(crate) fn transact_write(&'s self) -> Result<SessionTx<'_>> {
+        if self.read_only {
+            bail!(WriteThroughReadOnly)
+        }
         let ret = SessionTx {
             store_tx: Box::new(self.db.transact(true)?),
             temp_store_tx: self.temp_db.transact(true)?,
             relation_store_id: self.relation_store_id.clone(),
             temp_store_id: Default::default(),
             tokenizers: self.tokenizers.clone(),
+            fold_case: self.case_insensitive_names.load(Ordering::Relaxed),
+            row_count_part: Uuid::new_v4(),
         };
         Ok(ret)
     }
@@ -883,12 +2058,8 @@ impl<'s, S: Storage<'s>> Db<S> {
         cur_vld: ValidityTs,
         read_only: bool,
     ) -> Result<NamedRows> {
-        match parse_script(
-            payload,
-            param_pool,
-            &self.fixed_rules.read().unwrap(),
-            cur_vld,
-        )? {
+        let read_only = read_only || self.read_only;
+        match self.parse_script(payload, param_pool, cur_vld)? {
             CozoScript::Single(p) => self.execute_single(cur_vld, p, read_only),
             CozoScript::Imperative(ps) => self.execute_imperative(cur_vld, &ps, read_only),
             CozoScript::Sys(op) => self.run_sys_op(op, read_only),
@@ -949,7 +2120,12 @@ impl<'s, S: Storage<'s>> Db<S> {
 
         Ok(res)
     }
-    fn explain_compiled(&self, strata: &[CompiledProgram]) -> Result<NamedRows> {
+    fn explain_compiled(
+        &self,
+        tx: &SessionTx<'_>,
+        strata: &[CompiledProgram],
+        store_relation: Option<&(InputRelationHandle, RelationOp, ReturnMutation)>,
+    ) -> Result<NamedRows> {
         let mut ret: Vec<JsonValue> = vec![];
         const STRATUM: &str = "stratum";
         const ATOM_IDX: &str = "atom_idx";
@@ -960,6 +2136,8 @@ impl<'s, S: Storage<'s>> Db<S> {
         const OUT_BINDINGS: &str = "out_relation";
         const JOINS_ON: &str = "joins_on";
         const FILTERS: &str = "filters/expr";
+        // only for the row of the mutation of a stored relation
+        const KEY_COLS: &str = "key_cols";
 
         let headers = vec![
             STRATUM.to_string(),
@@ -971,6 +2149,7 @@ impl<'s, S: Storage<'s>> Db<S> {
             JOINS_ON.to_string(),
             FILTERS.to_string(),
             OUT_BINDINGS.to_string(),
+            KEY_COLS.to_string(),
         ];
 
         for (stratum, p) in strata.iter().enumerate() {
@@ -1161,6 +2340,59 @@ impl<'s, S: Storage<'s>> Db<S> {
             }
         }
 
+        if let Some((handle, op, _)) = store_relation {
+            let op_name = match op {
+                RelationOp::Create => "create",
+                RelationOp::Replace => "replace",
+                RelationOp::Put => "put",
+                RelationOp::Insert => "insert",
+                RelationOp::Update => "update",
+                RelationOp::Rm => "rm",
+                RelationOp::Delete => "delete",
+                RelationOp::Ensure => "ensure",
+                RelationOp::EnsureNot => "ensure_not",
+            };
+            let bindings: BTreeMap<_, _> = handle
+                .metadata
+                .keys
+                .iter()
+                .chain(handle.metadata.non_keys.iter())
+                .zip(handle.key_bindings.iter().chain(handle.dep_bindings.iter()))
+                .map(|(col, binding)| (col.name.to_string(), binding.name.to_string()))
+                .collect();
+            // Without an explicit spec, an existing relation decides which columns are keys
+            let key_cols = match op {
+                RelationOp::Create | RelationOp::Replace => handle.metadata.keys.clone(),
+                _ => match tx.get_relation(&handle.name, false) {
+                    Ok(existing) => existing.metadata.keys,
+                    Err(_) => handle.metadata.keys.clone(),
+                },
+            };
+            let keys: serde_json::Map<String, JsonValue> = key_cols
+                .iter()
+                .map(|col| {
+                    let name = col.name.to_string();
+                    let binding = bindings.get(&name).map_or(JsonValue::Null, |b| json!(b));
+                    (name, binding)
+                })
+                .collect();
+            ret.push(json!({
+                STRATUM: strata.len(),
+                ATOM_IDX: 0,
+                OP: op_name,
+                RULE_IDX: 0,
+                RULE_NAME: PROG_ENTRY,
+                REF_NAME: format!(":{}", handle.name),
+                KEY_COLS: keys,
+                OUT_BINDINGS: handle
+                    .key_bindings
+                    .iter()
+                    .chain(handle.dep_bindings.iter())
+                    .map(|b| b.to_string())
+                    .collect_vec(),
+            }));
+        }
+
         let rows = ret
             .into_iter()
             .map(|m| {
@@ -1182,11 +2414,13 @@ impl<'s, S: Storage<'s>> Db<S> {
     ) -> Result<NamedRows> {
         match op {
             SysOp::Explain(prog) => {
-                let (normalized_program, _) = prog.clone().into_normalized_program(tx)?;
+                let mut inlined = prog.clone();
+                self.inline_views(tx, &mut inlined, current_validity())?;
+                let (normalized_program, _) = inlined.into_normalized_program(tx)?;
                 let (stratified_program, _) = normalized_program.into_stratified_program()?;
                 let program = stratified_program.magic_sets_rewrite(tx)?;
                 let compiled = tx.stratified_magic_compile(program)?;
-                self.explain_compiled(&compiled)
+                self.explain_compiled(tx, &compiled, prog.out_opts.store_relation.as_ref())
             }
             SysOp::Compact => {
                 if read_only {
@@ -1235,6 +2469,33 @@ impl<'s, S: Storage<'s>> Db<S> {
                     vec![vec![DataValue::from(OK_STR)]],
                 ))
             }
+            SysOp::CreateView(name, script) => {
+                if read_only {
+                    bail!("Cannot create views in read-only mode");
+                }
+                // check that the view can be read before persisting it
+                let mut prog = self
+                    .parse_script(script, &Default::default(), current_validity())?
+                    .get_single_program()?;
+                self.inline_views(tx, &mut prog, current_validity())?;
+                let (normalized_program, _) = prog.into_normalized_program(tx)?;
+                normalized_program.into_stratified_program()?;
+                tx.create_view(name, script)?;
+                Ok(NamedRows::new(
+                    vec![STATUS_STR.to_string()],
+                    vec![vec![DataValue::from(OK_STR)]],
+                ))
+            }
+            SysOp::RemoveView(name) => {
+                if read_only {
+                    bail!("Cannot remove views in read-only mode");
+                }
+                tx.remove_view(name)?;
+                Ok(NamedRows::new(
+                    vec![STATUS_STR.to_string()],
+                    vec![vec![DataValue::from(OK_STR)]],
+                ))
+            }
             SysOp::DescribeRelation(rel_name, description) => {
                 tx.describe_relation(rel_name, description)?;
                 Ok(NamedRows::new(
@@ -1242,19 +2503,19 @@ impl<'s, S: Storage<'s>> Db<S> {
                     vec![vec![DataValue::from(OK_STR)]],
                 ))
             }
-            SysOp::CreateIndex(rel_name, idx_name, cols) => {
+            SysOp::CreateIndex(rel_name, idx_name, cols, unique) => {
                 if read_only {
                     bail!("Cannot create index in read-only mode");
                 }
                 if skip_locking {
-                    tx.create_index(rel_name, idx_name, cols)?;
+                    tx.create_index(rel_name, idx_name, cols, *unique)?;
                 } else {
                     let lock = self
                         .obtain_relation_locks(iter::once(&rel_name.name))
                         .pop()
                         .unwrap();
                     let _guard = lock.write().unwrap();
-                    tx.create_index(rel_name, idx_name, cols)?;
+                    tx.create_index(rel_name, idx_name, cols, *unique)?;
                 }
                 Ok(NamedRows::new(
                     vec![STATUS_STR.to_string()],
@@ -1363,6 +2624,63 @@ impl<'s, S: Storage<'s>> Db<S> {
                     vec![vec![DataValue::from(OK_STR)]],
                 ))
             }
+            SysOp::AddColumn(rel_name, col) => {
+                if read_only {
+                    bail!("Cannot alter relations in read-only mode");
+                }
+                if skip_locking {
+                    tx.add_column(rel_name, col)?;
+                } else {
+                    let lock = self
+                        .obtain_relation_locks(iter::once(&rel_name.name))
+                        .pop()
+                        .unwrap();
+                    let _guard = lock.write().unwrap();
+                    tx.add_column(rel_name, col)?;
+                }
+                Ok(NamedRows::new(
+                    vec![STATUS_STR.to_string()],
+                    vec![vec![DataValue::from(OK_STR)]],
+                ))
+            }
+            SysOp::DropColumn(rel_name, col) => {
+                if read_only {
+                    bail!("Cannot alter relations in read-only mode");
+                }
+                if skip_locking {
+                    tx.drop_column(rel_name, col)?;
+                } else {
+                    let lock = self
+                        .obtain_relation_locks(iter::once(&rel_name.name))
+                        .pop()
+                        .unwrap();
+                    let _guard = lock.write().unwrap();
+                    tx.drop_column(rel_name, col)?;
+                }
+                Ok(NamedRows::new(
+                    vec![STATUS_STR.to_string()],
+                    vec![vec![DataValue::from(OK_STR)]],
+                ))
+            }
+            SysOp::TruncateRelation(rel_name) => {
+                if read_only {
+                    bail!("Cannot truncate relations in read-only mode");
+                }
+                let removed = if skip_locking {
+                    tx.truncate_relation(rel_name)?
+                } else {
+                    let lock = self
+                        .obtain_relation_locks(iter::once(&rel_name.name))
+                        .pop()
+                        .unwrap();
+                    let _guard = lock.write().unwrap();
+                    tx.truncate_relation(rel_name)?
+                };
+                Ok(NamedRows::new(
+                    vec!["removed".to_string()],
+                    vec![vec![DataValue::from(removed as i64)]],
+                ))
+            }
             SysOp::ListRunning => self.list_running(),
             SysOp::KillRunning(id) => {
                 let queries = self.running_queries.lock().unwrap();
@@ -1439,7 +2757,7 @@ impl<'s, S: Storage<'s>> Db<S> {
     pub(crate) fn run_query(
         &self,
         tx: &mut SessionTx<'_>,
-        input_program: InputProgram,
+        mut input_program: InputProgram,
         cur_vld: ValidityTs,
         callback_targets: &BTreeSet<SmartString<LazyCompact>>,
         callback_collector: &mut CallbackCollector,
@@ -1481,6 +2799,7 @@ impl<'s, S: Storage<'s>> Db<S> {
         };
 
         // query compilation
+        self.inline_views(tx, &mut input_program, cur_vld)?;
         let entry_head_or_default = input_program.get_entry_out_head_or_default()?;
         let (normalized_program, out_opts) = input_program.into_normalized_program(tx)?;
         let (stratified_program, store_lifetimes) = normalized_program.into_stratified_program()?;
@@ -1510,20 +2829,22 @@ impl<'s, S: Storage<'s>> Db<S> {
             running_queries: self.running_queries.clone(),
         };
 
-        let total_num_to_take = if out_opts.sorters.is_empty() {
-            out_opts.num_to_take()
-        } else {
+        // the rows to take are only known after sorting or filtering
+        let post_processed = !out_opts.sorters.is_empty() || out_opts.having.is_some();
+        let total_num_to_take = if post_processed {
             None
+        } else {
+            out_opts.num_to_take()
         };
 
-        let num_to_skip = if out_opts.sorters.is_empty() {
-            out_opts.offset
-        } else {
+        let num_to_skip = if post_processed {
             None
+        } else {
+            out_opts.offset
         };
 
         // the real evaluation
-        let (result_store, early_return) = tx.stratified_magic_evaluate(
+        let (mut result_store, early_return) = tx.stratified_magic_evaluate(
             &compiled,
             store_lifetimes,
             total_num_to_take,
@@ -1531,6 +2852,21 @@ impl<'s, S: Storage<'s>> Db<S> {
             poison,
         )?;
 
+        if let Some(having) = &out_opts.having {
+            let bytecode = having.compile()?;
+            let mut stack = vec![];
+            let mut kept = RegularTempStore::default();
+            for tuple in result_store.all_iter() {
+                let tuple = tuple.into_tuple();
+                if eval_bytecode_pred(&bytecode, &tuple, &mut stack, having.span())? {
+                    kept.put(tuple);
+                }
+            }
+            let mut filtered = EpochStore::new_normal(result_store.arity);
+            filtered.merge_in(kept.wrap())?;
+            result_store = filtered;
+        }
+
         // deal with assertions
         if let Some(assertion) = &out_opts.assertion {
             match assertion {
@@ -1559,8 +2895,12 @@ impl<'s, S: Storage<'s>> Db<S> {
 
         if !out_opts.sorters.is_empty() {
             // sort outputs if required
-            let sorted_result =
-                tx.sort_and_collect(result_store, &out_opts.sorters, &entry_head_or_default)?;
+            let sorted_result = tx.sort_and_collect(
+                result_store,
+                &out_opts.sorters,
+                out_opts.nulls,
+                &entry_head_or_default,
+            )?;
             let sorted_iter = if let Some(offset) = out_opts.offset {
                 Left(sorted_result.into_iter().skip(offset))
             } else {
@@ -1689,9 +3029,14 @@ impl<'s, S: Storage<'s>> Db<S> {
         let handle = tx.get_relation(name, false)?;
         let mut rows = vec![];
         for (name, (rel, cols)) in &handle.indices {
+            let kind = if handle.unique_indices.contains_key(name) {
+                "unique"
+            } else {
+                "normal"
+            };
             rows.push(vec![
                 json!(name),
-                json!("normal"),
+                json!(kind),
                 json!([rel.name]),
                 json!({ "indices": cols }),
             ]);
@@ -1851,6 +3196,34 @@ impl<'s, S: Storage<'s>> Db<S> {
     }
 }
 
+/// Fail unless the stored relation allows operations needing the given access level.
+pub(crate) fn require_access(
+    handle: &RelationHandle,
+    level: AccessLevel,
+    what: &str,
+) -> Result<()> {
+    if handle.access_level < level {
+        bail!(InsufficientAccessLevel(
+            handle.name.to_string(),
+            what.to_string(),
+            handle.access_level
+        ));
+    }
+    Ok(())
+}
+
+/// Bindings of the columns of a stored relation by name, to their positions in its rows.
+fn column_bindings(handle: &RelationHandle) -> BTreeMap<Symbol, usize> {
+    handle
+        .metadata
+        .keys
+        .iter()
+        .chain(handle.metadata.non_keys.iter())
+        .enumerate()
+        .map(|(i, col)| (Symbol::new(col.name.clone(), Default::default()), i))
+        .collect()
+}
+
 /// Evaluate a string expression in the context of a set of parameters and variables
 pub fn evaluate_expressions(
     src: &str,
@@ -1882,6 +3255,7 @@ fn _evaluate_expressions(
     params: &BTreeMap<String, DataValue>,
     vars: &BTreeMap<String, DataValue>,
 ) -> Result<DataValue> {
+    check_nesting_depth(src, DEFAULT_MAX_NESTING_DEPTH)?;
     let mut expr = parse_expressions(src, params)?;
     let mut ctx = vec![];
     let mut binding_map = BTreeMap::new();
@@ -1894,6 +3268,7 @@ fn _evaluate_expressions(
 }
 
 fn _get_variables(src: &str, params: &BTreeMap<String, DataValue>) -> Result<BTreeSet<String>> {
+    check_nesting_depth(src, DEFAULT_MAX_NESTING_DEPTH)?;
     let expr = parse_expressions(src, params)?;
     expr.get_variables()
 }
@@ -0,0 +1,349 @@
+/*
+ * Copyright 2023, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use itertools::Itertools;
+use miette::{bail, ensure, Diagnostic, Result};
+use thiserror::Error;
+
+use crate::data::functions::current_validity;
+use crate::data::value::DataValue;
+use crate::runtime::db::require_access;
+use crate::runtime::relation::{AccessLevel, RelationHandle};
+use crate::runtime::transact::SessionTx;
+use crate::{Db, NamedRows, Storage};
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Stored relation '{0}' cannot hold edges between nodes with {1} key columns")]
+#[diagnostic(code(graph::bad_edge_relation))]
+#[diagnostic(help(
+    "The leading key columns of an edge relation hold the source node, \
+followed by the same number of columns holding the destination node"
+))]
+struct BadEdgeRelation(String, usize);
+
+/// Get the handle of an edge relation between nodes with `node_arity` key columns.
+fn edge_relation(tx: &SessionTx<'_>, name: &str, node_arity: usize) -> Result<RelationHandle> {
+    let handle = tx.get_relation(name, false)?;
+    require_access(&handle, AccessLevel::ReadOnly, "graph traversal")?;
+    if node_arity == 0 || handle.metadata.keys.len() < 2 * node_arity {
+        bail!(BadEdgeRelation(handle.name.to_string(), node_arity))
+    }
+    Ok(handle)
+}
+
+/// Coerce the key of a node to the types of the key columns starting at `offset`.
+fn coerce_node(
+    handle: &RelationHandle,
+    offset: usize,
+    node: &[DataValue],
+) -> Result<Vec<DataValue>> {
+    let cur_vld = current_validity();
+    handle.metadata.keys[offset..offset + node.len()]
+        .iter()
+        .zip(node)
+        .map(|(col, val)| val.clone().coerce_to(&col.typing, cur_vld))
+        .try_collect()
+}
+
+/// The distinct destinations of the edges starting at `node`, in key order.
+fn successors(
+    tx: &SessionTx<'_>,
+    handle: &RelationHandle,
+    node: &[DataValue],
+) -> Result<Vec<Vec<DataValue>>> {
+    let n = node.len();
+    let mut ret: Vec<_> = handle
+        .scan_prefix(tx, &node.to_vec())
+        .map_ok(|tuple| tuple[n..2 * n].to_vec())
+        .try_collect()?;
+    ret.dedup();
+    Ok(ret)
+}
+
+/// A regular index of the edge relation starting with the destination columns, if any.
+fn reverse_index(handle: &RelationHandle, n: usize) -> Option<&(RelationHandle, Vec<usize>)> {
+    handle
+        .indices
+        .values()
+        .find(|(_, mapping)| mapping.len() >= n && mapping[..n].iter().copied().eq(n..2 * n))
+}
+
+impl<'s, S: Storage<'s>> Db<S> {
+    /// Find the sources of all edges pointing at the node `dst`.
+    ///
+    /// The leading key columns of the edge relation hold the source node, followed by
+    /// the same number of columns holding the destination node, `dst` gives the values
+    /// of the latter. A regular index starting with the destination columns is used
+    /// if there is one, otherwise the whole relation is scanned. The distinct sources
+    /// are returned in key order either way.
+    pub fn in_edges(&'s self, relation: &str, dst: &[DataValue]) -> Result<Vec<Vec<DataValue>>> {
+        let tx = self.transact()?;
+        let n = dst.len();
+        let handle = edge_relation(&tx, relation, n)?;
+        let dst = coerce_node(&handle, n, dst)?;
+        // several edges may connect the same nodes if there are more key columns,
+        // and the index may hold them apart
+        let mut ret = BTreeSet::new();
+        if let Some((index, mapping)) = reverse_index(&handle, n) {
+            // the index holds all key columns of the relation, the source among them
+            let src_positions = (0..n)
+                .map(|i| mapping.iter().position(|m| *m == i).unwrap())
+                .collect_vec();
+            for tuple in index.scan_prefix(&tx, &dst) {
+                let tuple = tuple?;
+                ret.insert(
+                    src_positions
+                        .iter()
+                        .map(|i| tuple[*i].clone())
+                        .collect_vec(),
+                );
+            }
+        } else {
+            for tuple in handle.scan_all(&tx) {
+                let tuple = tuple?;
+                if tuple[n..2 * n] == dst[..] {
+                    ret.insert(tuple[..n].to_vec());
+                }
+            }
+        }
+        Ok(ret.into_iter().collect())
+    }
+
+    /// Find all edges starting at the node `src`.
+    ///
+    /// See [`in_edges`](Self::in_edges) for the layout of edge relations. Each edge is
+    /// returned as its destination node together with the remaining columns of the edge,
+    /// i.e. any further key columns followed by the non-key columns.
+    pub fn out_edges(
+        &'s self,
+        relation: &str,
+        src: &[DataValue],
+    ) -> Result<Vec<(Vec<DataValue>, Vec<DataValue>)>> {
+        let tx = self.transact()?;
+        let n = src.len();
+        let handle = edge_relation(&tx, relation, n)?;
+        let src = coerce_node(&handle, 0, src)?;
+        handle
+            .scan_prefix(&tx, &src)
+            .map_ok(|mut tuple| {
+                let data = tuple.split_off(2 * n);
+                let dst = tuple.split_off(n);
+                (dst, data)
+            })
+            .collect()
+    }
+
+    /// List all edges of the relation as rows, with the source and destination nodes
+    /// under the headers `_src` and `_dst`, followed by the remaining columns of the edge.
+    ///
+    /// See [`in_edges`](Self::in_edges) for the layout of edge relations, `node_arity`
+    /// is the number of key columns holding each node. Nodes held in a single column
+    /// are given as is, otherwise as lists. Only the relation itself is scanned, not
+    /// its indices, so every edge appears once, ordered by source.
+    pub fn edges(&'s self, relation: &str, node_arity: usize) -> Result<NamedRows> {
+        let tx = self.transact()?;
+        let n = node_arity;
+        let handle = edge_relation(&tx, relation, n)?;
+        let node = |mut cols: Vec<DataValue>| {
+            if n == 1 {
+                cols.pop().unwrap()
+            } else {
+                DataValue::List(cols)
+            }
+        };
+        let headers = ["_src", "_dst"]
+            .into_iter()
+            .map(str::to_string)
+            .chain(
+                handle
+                    .metadata
+                    .keys
+                    .iter()
+                    .chain(handle.metadata.non_keys.iter())
+                    .skip(2 * n)
+                    .map(|col| col.name.to_string()),
+            )
+            .collect_vec();
+        let rows = handle
+            .scan_all(&tx)
+            .map_ok(|mut tuple| {
+                let data = tuple.split_off(2 * n);
+                let dst = tuple.split_off(n);
+                let mut row = vec![node(tuple), node(dst)];
+                row.extend(data);
+                row
+            })
+            .try_collect()?;
+        Ok(NamedRows::new(headers, rows))
+    }
+
+    /// Count the edges starting at and pointing at the node `node`, in this order.
+    ///
+    /// See [`in_edges`](Self::in_edges) for the layout of edge relations. Edges are
+    /// counted without decoding them, but counting the edges pointing at the node
+    /// still requires a full scan unless there is a reverse index.
+    pub fn degree(&'s self, relation: &str, node: &[DataValue]) -> Result<(usize, usize)> {
+        let tx = self.transact()?;
+        let n = node.len();
+        let handle = edge_relation(&tx, relation, n)?;
+        let as_src = coerce_node(&handle, 0, node)?;
+        let as_dst = coerce_node(&handle, n, node)?;
+        let out_degree = handle.count_prefix(&tx, &as_src)?;
+        let in_degree = match reverse_index(&handle, n) {
+            Some((index, _)) => index.count_prefix(&tx, &as_dst)?,
+            None => handle
+                .scan_all(&tx)
+                .filter_ok(|tuple| tuple[n..2 * n] == as_dst[..])
+                .try_fold(0, |count, tuple| tuple.map(|_| count + 1))?,
+        };
+        Ok((out_degree, in_degree))
+    }
+
+    /// Count the edges from the node `src` to the node `dst`.
+    ///
+    /// See [`in_edges`](Self::in_edges) for the layout of edge relations. There can be
+    /// more than one such edge if the relation has further key columns, distinguishing
+    /// parallel edges. Only the edges between the two nodes are read, without decoding them.
+    pub fn count_edges_between(
+        &'s self,
+        relation: &str,
+        src: &[DataValue],
+        dst: &[DataValue],
+    ) -> Result<usize> {
+        ensure!(
+            src.len() == dst.len(),
+            "source and destination nodes must have the same number of key columns"
+        );
+        let tx = self.transact()?;
+        let n = src.len();
+        let handle = edge_relation(&tx, relation, n)?;
+        let mut prefix = coerce_node(&handle, 0, src)?;
+        prefix.extend(coerce_node(&handle, n, dst)?);
+        handle.count_prefix(&tx, &prefix)
+    }
+
+    /// Walk the edges breadth-first from the node `start`, following at most `max_depth` edges.
+    ///
+    /// See [`in_edges`](Self::in_edges) for the layout of edge relations. Returns each
+    /// reachable node once together with its distance from `start`, in the order they are
+    /// reached, starting with `start` itself at distance 0.
+    #[allow(clippy::mutable_key_type)]
+    pub fn traverse(
+        &'s self,
+        relation: &str,
+        start: &[DataValue],
+        max_depth: usize,
+    ) -> Result<Vec<(Vec<DataValue>, usize)>> {
+        let tx = self.transact()?;
+        let handle = edge_relation(&tx, relation, start.len())?;
+        let start = coerce_node(&handle, 0, start)?;
+        let mut visited = BTreeSet::from([start.clone()]);
+        let mut ret = vec![(start, 0)];
+        let mut frontier = 0..1;
+        for depth in 1..=max_depth {
+            if frontier.is_empty() {
+                break;
+            }
+            for i in frontier.clone() {
+                let node = ret[i].0.clone();
+                for next in successors(&tx, &handle, &node)? {
+                    if visited.insert(next.clone()) {
+                        ret.push((next, depth));
+                    }
+                }
+            }
+            frontier = frontier.end..ret.len();
+        }
+        Ok(ret)
+    }
+
+    /// Enumerate the paths from the node `start` following between 1 and `max_depth` edges.
+    ///
+    /// See [`in_edges`](Self::in_edges) for the layout of edge relations. Each path is
+    /// returned as the list of nodes on it, starting with `start`, and visits no node
+    /// twice, so cycles are not followed. Shorter paths come first, then paths of the
+    /// same length are ordered by key. The number of paths can grow exponentially
+    /// with `max_depth` on densely connected graphs.
+    pub fn paths(
+        &'s self,
+        relation: &str,
+        start: &[DataValue],
+        max_depth: usize,
+    ) -> Result<Vec<Vec<Vec<DataValue>>>> {
+        let tx = self.transact()?;
+        let handle = edge_relation(&tx, relation, start.len())?;
+        let start = coerce_node(&handle, 0, start)?;
+        let mut ret = vec![];
+        let mut frontier = vec![vec![start]];
+        for _ in 0..max_depth {
+            let mut extended = vec![];
+            for path in &frontier {
+                for next in successors(&tx, &handle, path.last().unwrap())? {
+                    if !path.contains(&next) {
+                        let mut path = path.clone();
+                        path.push(next);
+                        extended.push(path);
+                    }
+                }
+            }
+            if extended.is_empty() {
+                break;
+            }
+            ret.extend_from_slice(&extended);
+            frontier = extended;
+        }
+        Ok(ret)
+    }
+
+    /// Find a shortest path from the node `src` to the node `dst`, counting every edge as one step.
+    ///
+    /// See [`in_edges`](Self::in_edges) for the layout of edge relations. The path is
+    /// returned as the list of nodes on it, including both ends, or `None` if `dst`
+    /// cannot be reached from `src`.
+    #[allow(clippy::mutable_key_type)]
+    pub fn shortest_path(
+        &'s self,
+        relation: &str,
+        src: &[DataValue],
+        dst: &[DataValue],
+    ) -> Result<Option<Vec<Vec<DataValue>>>> {
+        ensure!(
+            src.len() == dst.len(),
+            "source and destination nodes must have the same number of key columns"
+        );
+        let tx = self.transact()?;
+        let handle = edge_relation(&tx, relation, src.len())?;
+        let src = coerce_node(&handle, 0, src)?;
+        let dst = coerce_node(&handle, src.len(), dst)?;
+        // maps every node reached to the node it was first reached from
+        let mut parents = BTreeMap::from([(src.clone(), None)]);
+        let mut queue = VecDeque::from([src]);
+        while let Some(node) = queue.pop_front() {
+            if node == dst {
+                let mut path = vec![];
+                let mut cur = Some(node);
+                while let Some(node) = cur {
+                    cur = parents[&node].clone();
+                    path.push(node);
+                }
+                path.reverse();
+                return Ok(Some(path));
+            }
+            for next in successors(&tx, &handle, &node)? {
+                if let Entry::Vacant(ent) = parents.entry(next) {
+                    queue.push_back(ent.key().clone());
+                    ent.insert(Some(node.clone()));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
@@ -11,6 +11,7 @@ use std::sync::atomic::Ordering;
 
 use either::{Either, Left, Right};
 use itertools::Itertools;
+use log::debug;
 use miette::{bail, Diagnostic, Report, Result};
 use smartstring::{LazyCompact, SmartString};
 use thiserror::Error;
@@ -115,9 +116,10 @@ impl<'s, S: Storage<'s>> Db<S> {
                     return Ok(Right(ControlCode::Termination(*current.unwrap())));
                 }
                 ImperativeStmt::TempDebug { temp, .. } => {
+                    // the rows are returned, so they are visible without any logger
                     let relation = tx.get_relation(temp, false)?;
-                    println!("{}: {:?}", temp, relation.as_named_rows(tx)?);
-                    ret = NamedRows::default();
+                    ret = relation.as_named_rows(tx)?;
+                    debug!("{}: {:?}", temp, ret);
                 }
                 ImperativeStmt::SysOp { sysop, .. } => {
                     ret = self.run_sys_op_with_tx(tx, &sysop.sysop, readonly, true)?;
@@ -8,6 +8,7 @@
 
 pub(crate) mod callback;
 pub(crate) mod db;
+pub(crate) mod graph;
 pub(crate) mod imperative;
 pub(crate) mod relation;
 pub(crate) mod temp_store;
@@ -6,8 +6,10 @@
  * You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fmt::{Debug, Display, Formatter};
+use std::ops::Bound;
 use std::sync::atomic::Ordering;
 
 use itertools::Itertools;
@@ -18,10 +20,12 @@ use rmp_serde::Serializer;
 use serde::Serialize;
 use smartstring::{LazyCompact, SmartString};
 use thiserror::Error;
+use uuid::Uuid;
 
+use crate::data::functions::current_validity;
 use crate::data::memcmp::MemCmpEncoder;
 use crate::data::relation::{ColType, ColumnDef, NullableColType, StoredRelationMetadata};
-use crate::data::symb::Symbol;
+use crate::data::symb::{fold_name, Symbol};
 use crate::data::tuple::{decode_tuple_from_key, Tuple, TupleT, ENCODED_KEY_MIN_LEN};
 use crate::data::value::{DataValue, ValidityTs};
 use crate::fts::FtsIndexManifest;
@@ -90,6 +94,61 @@ pub(crate) struct RelationHandle {
         (RelationHandle, RelationHandle, MinHashLshIndexManifest),
     >,
     pub(crate) description: SmartString<LazyCompact>,
+    /// The regular indices whose leading columns must be unique, with the number of
+    /// these columns. Absent in metadata written by older versions.
+    #[serde(default)]
+    pub(crate) unique_indices: BTreeMap<SmartString<LazyCompact>, usize>,
+}
+
+/// Structured definition of a stored relation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelationDef {
+    /// Name of the relation
+    pub name: String,
+    /// Key columns, in storage order
+    pub keys: Vec<ColumnInfo>,
+    /// Non-key columns, in storage order
+    pub non_keys: Vec<ColumnInfo>,
+    /// Description of the relation, empty if none was given
+    pub description: String,
+}
+
+/// Statistics of a stored relation, maintained as it is written to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelationStats {
+    /// Number of rows in the relation
+    pub rows: u64,
+}
+
+/// Definition of a single column of a stored relation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnInfo {
+    /// Name of the column
+    pub name: String,
+    /// Declared type of the column
+    pub typing: NullableColType,
+    /// Whether a default value is generated when the column is omitted
+    pub has_default: bool,
+}
+
+impl From<&RelationHandle> for RelationDef {
+    fn from(handle: &RelationHandle) -> Self {
+        let to_info = |cols: &[ColumnDef]| {
+            cols.iter()
+                .map(|col| ColumnInfo {
+                    name: col.name.to_string(),
+                    typing: col.typing.clone(),
+                    has_default: col.default_gen.is_some(),
+                })
+                .collect_vec()
+        };
+        RelationDef {
+            name: handle.name.to_string(),
+            keys: to_info(&handle.metadata.keys),
+            non_keys: to_info(&handle.metadata.non_keys),
+            description: handle.description.to_string(),
+        }
+    }
 }
 
 impl RelationHandle {
@@ -105,6 +164,58 @@ impl RelationHandle {
             && self.fts_indices.is_empty()
             && self.lsh_indices.is_empty()
     }
+    /// Ensure that the entry `idx_tup` can be put into the regular index `index_name`,
+    /// i.e. if the index is unique, no other row has the same values in its unique columns.
+    /// This must be called after the entry of the row being overwritten is removed.
+    ///
+    /// For stored relations, a sentinel key for the unique values is read with a lock and
+    /// written, so that concurrent transactions putting the same values conflict instead of
+    /// both passing the check against their own snapshots.
+    pub(crate) fn ensure_unique_in_index(
+        &self,
+        tx: &mut SessionTx<'_>,
+        index_name: &str,
+        idx_tup: &[DataValue],
+    ) -> Result<()> {
+        if let Some(n) = self.unique_indices.get(index_name) {
+            let (idx_rel, _) = &self.indices[index_name];
+            if !idx_rel.is_temp {
+                let sentinel = unique_sentinel_key(idx_rel.id, &idx_tup[..*n]);
+                tx.store_tx.get(&sentinel, true)?;
+                tx.store_tx.put(&sentinel, &[])?;
+            }
+            for existing in idx_rel.scan_prefix(tx, &idx_tup[..*n].to_vec()) {
+                // the remaining columns of the index identify the row
+                if existing?[*n..] != idx_tup[*n..] {
+                    bail!(DuplicateKey(
+                        idx_rel.name.to_string(),
+                        idx_tup[..*n].to_vec()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Delete the entry `idx_tup` from the regular index `index_name`. For stored relations
+    /// with a unique index, the sentinel key of the unique values goes too, as the values
+    /// are no longer taken by this row.
+    pub(crate) fn del_from_index(
+        &self,
+        tx: &mut SessionTx<'_>,
+        index_name: &str,
+        idx_tup: &[DataValue],
+    ) -> Result<()> {
+        let (idx_rel, _) = &self.indices[index_name];
+        let encoded = idx_rel.encode_key_for_store(idx_tup, Default::default())?;
+        tx.store_tx.del(&encoded)?;
+        if let Some(n) = self.unique_indices.get(index_name) {
+            if !idx_rel.is_temp {
+                tx.store_tx
+                    .del(&unique_sentinel_key(idx_rel.id, &idx_tup[..*n]))?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(
@@ -299,10 +410,10 @@ impl RelationHandle {
         inp: &InputRelationHandle,
         is_remove_or_update: bool,
     ) -> Result<()> {
-        let InputRelationHandle { metadata, .. } = inp;
-        // check that every given key is found and compatible
-        for col in metadata.keys.iter().chain(self.metadata.non_keys.iter()) {
-            self.metadata.compatible_with_col(col)?
+        let InputRelationHandle { metadata, span, .. } = inp;
+        // check that every given column is found and compatible
+        for col in metadata.keys.iter().chain(metadata.non_keys.iter()) {
+            self.metadata.compatible_with_col(&self.name, col, *span)?
         }
         // check that every key is provided or has default
         for col in &self.metadata.keys {
@@ -367,6 +478,40 @@ impl RelationHandle {
         }
     }
 
+    /// The key columns of every row, in key order, leaving the non-key columns undecoded.
+    pub(crate) fn scan_keys<'a>(
+        &self,
+        tx: &'a SessionTx<'_>,
+    ) -> impl Iterator<Item = Result<Tuple>> + 'a {
+        let lower = Tuple::default().encode_as_key(self.id);
+        let upper = Tuple::default().encode_as_key(self.id.next());
+        let n_keys = self.metadata.keys.len();
+        let it = if self.is_temp {
+            tx.temp_store_tx.range_scan(&lower, &upper)
+        } else {
+            tx.store_tx.range_scan(&lower, &upper)
+        };
+        it.map_ok(move |(k, _)| decode_tuple_from_key(&k, n_keys))
+    }
+
+    /// The row with the smallest key. Only the first entry of the relation is read.
+    pub(crate) fn first_row(&self, tx: &SessionTx<'_>) -> Result<Option<Tuple>> {
+        self.scan_all(tx).next().transpose()
+    }
+
+    /// The row with the largest key. Engines that cannot seek backwards, see
+    /// [StoreTx::range_last], scan the whole relation to find it.
+    pub(crate) fn last_row(&self, tx: &SessionTx<'_>) -> Result<Option<Tuple>> {
+        let lower = Tuple::default().encode_as_key(self.id);
+        let upper = Tuple::default().encode_as_key(self.id.next());
+        let found = if self.is_temp {
+            tx.temp_store_tx.range_last(&lower, &upper)?
+        } else {
+            tx.store_tx.range_last(&lower, &upper)?
+        };
+        Ok(found.map(|(k, v)| decode_tuple_from_kv(&k, &v, Some(self.arity()))))
+    }
+
     pub(crate) fn skip_scan_all<'a>(
         &self,
         tx: &'a SessionTx<'_>,
@@ -445,6 +590,21 @@ impl RelationHandle {
         }
     }
 
+    /// Count the rows whose keys start with `prefix`, without decoding them.
+    pub(crate) fn count_prefix(&self, tx: &SessionTx<'_>, prefix: &[DataValue]) -> Result<usize> {
+        let mut lower = prefix.to_vec();
+        lower.truncate(self.metadata.keys.len());
+        let mut upper = lower.clone();
+        upper.push(DataValue::Bot);
+        let lower_encoded = lower.encode_as_key(self.id);
+        let upper_encoded = upper.encode_as_key(self.id);
+        if self.is_temp {
+            tx.temp_store_tx.range_count(&lower_encoded, &upper_encoded)
+        } else {
+            tx.store_tx.range_count(&lower_encoded, &upper_encoded)
+        }
+    }
+
     pub(crate) fn skip_scan_prefix<'a>(
         &self,
         tx: &'a SessionTx<'_>,
@@ -487,6 +647,30 @@ impl RelationHandle {
             tx.store_tx.range_scan_tuple(&lower_encoded, &upper_encoded)
         }
     }
+    /// Scan the rows whose first key column falls within the bounds.
+    pub(crate) fn scan_key_range<'a>(
+        &self,
+        tx: &'a SessionTx<'_>,
+        lower: Bound<&DataValue>,
+        upper: Bound<&DataValue>,
+    ) -> impl Iterator<Item = Result<Tuple>> + 'a {
+        let lower_encoded = match lower {
+            Bound::Included(v) => vec![v.clone()].encode_as_key(self.id),
+            Bound::Excluded(v) => vec![v.clone(), DataValue::Bot].encode_as_key(self.id),
+            Bound::Unbounded => Tuple::default().encode_as_key(self.id),
+        };
+        let upper_encoded = match upper {
+            Bound::Included(v) => vec![v.clone(), DataValue::Bot].encode_as_key(self.id),
+            Bound::Excluded(v) => vec![v.clone()].encode_as_key(self.id),
+            Bound::Unbounded => Tuple::default().encode_as_key(self.id.next()),
+        };
+        if self.is_temp {
+            tx.temp_store_tx
+                .range_scan_tuple(&lower_encoded, &upper_encoded)
+        } else {
+            tx.store_tx.range_scan_tuple(&lower_encoded, &upper_encoded)
+        }
+    }
     pub(crate) fn skip_scan_bounded_prefix<'a>(
         &self,
         tx: &'a SessionTx<'_>,
@@ -540,8 +724,67 @@ pub(crate) struct IndexAlreadyExists(String, String);
 #[diagnostic(code(eval::rel_name_conflict))]
 struct RelNameConflictError(String);
 
+/// Key in the system relation holding the number of rows of a stored relation.
+/// It starts with null like the storage version, so it sorts before the names of relations.
+///
+/// Without a part, this is the base count. Each writing transaction keeps the change it
+/// makes to the count under its own part, so that concurrent writers never contend on
+/// one key; the count is the sum of the base and of all parts.
+fn row_count_key(id: RelationId, part: Option<Uuid>) -> Vec<u8> {
+    let mut key = vec![
+        DataValue::Null,
+        DataValue::from("ROW_COUNT"),
+        DataValue::from(id.0 as i64),
+    ];
+    if let Some(part) = part {
+        key.push(DataValue::uuid(part));
+    }
+    key.encode_as_key(RelationId::SYSTEM)
+}
+
+/// Number of parts of a row count above which they are folded into the base count.
+const ROW_COUNT_MAX_PARTS: usize = 64;
+
+/// Key in the system relation locked by writers of the given values into a unique index.
+fn unique_sentinel_key(idx_id: RelationId, vals: &[DataValue]) -> Vec<u8> {
+    let mut key = vec![
+        DataValue::Null,
+        DataValue::from("UNIQUE"),
+        DataValue::from(idx_id.0 as i64),
+    ];
+    key.extend_from_slice(vals);
+    key.encode_as_key(RelationId::SYSTEM)
+}
+
+/// Key in the system relation holding the query defining a view.
+fn view_key(name: &str) -> Vec<u8> {
+    vec![
+        DataValue::Null,
+        DataValue::from("VIEW"),
+        DataValue::from(name),
+    ]
+    .encode_as_key(RelationId::SYSTEM)
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Cannot find requested view '{0}'")]
+#[diagnostic(code(tx::view_not_found))]
+struct ViewNotFound(String);
+
+fn decode_row_count(bytes: &[u8]) -> i64 {
+    i64::from_be_bytes(bytes.try_into().unwrap())
+}
+
 impl<'a> SessionTx<'a> {
+    fn folded_name<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        if self.fold_case {
+            Cow::Owned(fold_name(name).into())
+        } else {
+            Cow::Borrowed(name)
+        }
+    }
     pub(crate) fn relation_exists(&self, name: &str) -> Result<bool> {
+        let name = &*self.folded_name(name);
         let key = DataValue::from(name);
         let encoded = vec![key].encode_as_key(RelationId::SYSTEM);
         if name.starts_with('_') {
@@ -596,19 +839,17 @@ impl<'a> SessionTx<'a> {
             if self.store_tx.exists(&encoded, true)? {
                 bail!(RelNameConflictError(input_meta.name.to_string()))
             };
-        } else if self.temp_store_tx.exists(&encoded, true)? {
+        } else if self.temp_store_tx.exists(&encoded, true)?
+            || self.store_tx.exists(&view_key(&input_meta.name), true)?
+        {
             bail!(RelNameConflictError(input_meta.name.to_string()))
         }
 
         let metadata = input_meta.metadata.clone();
-        let last_id = if is_temp {
-            self.temp_store_id.fetch_add(1, Ordering::Relaxed) as u64
-        } else {
-            self.relation_store_id.fetch_add(1, Ordering::SeqCst)
-        };
+        let id = self.next_relation_id(is_temp)?;
         let meta = RelationHandle {
             name: input_meta.name.name,
-            id: RelationId::new(last_id + 1),
+            id,
             metadata,
             put_triggers: vec![],
             rm_triggers: vec![],
@@ -620,47 +861,194 @@ impl<'a> SessionTx<'a> {
             fts_indices: Default::default(),
             lsh_indices: Default::default(),
             description: Default::default(),
+            unique_indices: Default::default(),
         };
 
         let name_key = vec![DataValue::Str(meta.name.clone())].encode_as_key(RelationId::SYSTEM);
         let mut meta_val = vec![];
         meta.serialize(&mut Serializer::new(&mut meta_val).with_struct_map())
             .unwrap();
-        let tuple = vec![DataValue::Null];
-        let t_encoded = tuple.encode_as_key(RelationId::SYSTEM);
 
         if is_temp {
             self.temp_store_tx.put(&encoded, &meta.id.raw_encode())?;
             self.temp_store_tx.put(&name_key, &meta_val)?;
-            self.temp_store_tx.put(&t_encoded, &meta.id.raw_encode())?;
         } else {
             self.store_tx.put(&encoded, &meta.id.raw_encode())?;
             self.store_tx.put(&name_key, &meta_val)?;
-            self.store_tx.put(&t_encoded, &meta.id.raw_encode())?;
+            if !meta.name.contains(':') {
+                self.store_tx
+                    .put(&row_count_key(meta.id, None), &0i64.to_be_bytes())?;
+            }
         }
 
         Ok(meta)
     }
+    /// Number of rows in a stored relation, read from the persisted count.
+    /// Relations created by older versions have no count, and are scanned instead.
+    pub(crate) fn row_count(&self, handle: &RelationHandle) -> Result<u64> {
+        if handle.is_temp {
+            return self.scan_row_count(handle);
+        }
+        let lower = row_count_key(handle.id, None);
+        let upper = row_count_key(handle.id.next(), None);
+        let mut count = None;
+        for kv in self.store_tx.range_scan(&lower, &upper) {
+            let (_, v) = kv?;
+            *count.get_or_insert(0i64) += decode_row_count(&v);
+        }
+        match count {
+            Some(count) => Ok(count.max(0) as u64),
+            None => self.scan_row_count(handle),
+        }
+    }
+    /// Change the persisted row count of a stored relation by `delta` rows,
+    /// as part of the transaction changing the rows themselves.
+    ///
+    /// Only the part of the count belonging to this transaction is written, and the
+    /// parts are folded into the base count once there are too many of them.
+    pub(crate) fn adjust_row_count(&mut self, handle: &RelationHandle, delta: i64) -> Result<()> {
+        if handle.is_temp || delta == 0 {
+            return Ok(());
+        }
+        let key = row_count_key(handle.id, Some(self.row_count_part));
+        let current = match self.store_tx.get(&key, false)? {
+            Some(bytes) => decode_row_count(&bytes),
+            None => {
+                let base = row_count_key(handle.id, None);
+                if !self.store_tx.exists(&base, false)? {
+                    // relations from older versions are not counted
+                    return Ok(());
+                }
+                self.compact_row_count(handle)?;
+                0
+            }
+        };
+        self.store_tx.put(&key, &(current + delta).to_be_bytes())
+    }
+    fn compact_row_count(&mut self, handle: &RelationHandle) -> Result<()> {
+        let lower = row_count_key(handle.id, None);
+        let upper = row_count_key(handle.id.next(), None);
+        let parts: Vec<_> = self
+            .store_tx
+            .range_scan(&lower, &upper)
+            .skip(1)
+            .collect::<Result<_>>()?;
+        if parts.len() <= ROW_COUNT_MAX_PARTS {
+            return Ok(());
+        }
+        // locking the base count keeps concurrent compactions from folding a part twice
+        let mut count = match self.store_tx.get(&lower, true)? {
+            Some(bytes) => decode_row_count(&bytes),
+            None => return Ok(()),
+        };
+        for (k, v) in parts {
+            count += decode_row_count(&v);
+            self.store_tx.del(&k)?;
+        }
+        self.store_tx.put(&lower, &count.to_be_bytes())
+    }
+    /// The query defining the view `name`, if there is such a view.
+    pub(crate) fn view_definition(&self, name: &str) -> Result<Option<String>> {
+        let key = view_key(&self.folded_name(name));
+        Ok(self
+            .store_tx
+            .get(&key, false)?
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+    }
+    /// Persist `script` as the query defining the view `name`. The name is shared
+    /// with stored relations, so it must not be taken by either.
+    pub(crate) fn create_view(&mut self, name: &Symbol, script: &str) -> Result<()> {
+        if name.is_temp_store_name() {
+            bail!("Views cannot have names starting with an underscore");
+        }
+        let key = view_key(name);
+        if self.relation_exists(name)? || self.store_tx.exists(&key, true)? {
+            bail!(RelNameConflictError(name.to_string()))
+        }
+        self.store_tx.put(&key, script.as_bytes())
+    }
+    /// Remove the definition of the view `name`, leaving the relations it reads untouched.
+    pub(crate) fn remove_view(&mut self, name: &Symbol) -> Result<()> {
+        let key = view_key(name);
+        if !self.store_tx.exists(&key, true)? {
+            bail!(ViewNotFound(name.to_string()))
+        }
+        self.store_tx.del(&key)
+    }
+    /// Count the rows of a stored relation afresh and persist the count.
+    pub(crate) fn recount_rows(&mut self, handle: &RelationHandle) -> Result<()> {
+        let count = self.scan_row_count(handle)? as i64;
+        let lower = row_count_key(handle.id, None);
+        let upper = row_count_key(handle.id.next(), None);
+        let parts: Vec<_> = self
+            .store_tx
+            .range_scan(&lower, &upper)
+            .map_ok(|(k, _)| k)
+            .collect::<Result<_>>()?;
+        for k in parts {
+            self.store_tx.del(&k)?;
+        }
+        self.store_tx.put(&lower, &count.to_be_bytes())
+    }
+    fn scan_row_count(&self, handle: &RelationHandle) -> Result<u64> {
+        let lower = Tuple::default().encode_as_key(handle.id);
+        let upper = Tuple::default().encode_as_key(handle.id.next());
+        Ok(self.store_tx.range_count(&lower, &upper)? as u64)
+    }
+    /// Allocate the id for a new relation.
+    ///
+    /// Ids are strictly increasing and never reused, even after the relation holding
+    /// an id is destroyed: data under the prefix of a dropped relation may not have
+    /// been cleaned up yet, and a reused id would resurrect it. For stored relations,
+    /// the last allocated id is persisted under the `[Null]` key of the system relation
+    /// in the same transaction as the new relation, so allocation survives restarts.
+    /// The persisted counter is read with a lock and takes precedence over the in-memory
+    /// one if it is ahead, so concurrent transactions cannot roll it back.
+    pub(crate) fn next_relation_id(&mut self, is_temp: bool) -> Result<RelationId> {
+        let t_encoded = vec![DataValue::Null].encode_as_key(RelationId::SYSTEM);
+        if is_temp {
+            let id = RelationId::new(self.temp_store_id.fetch_add(1, Ordering::Relaxed) as u64 + 1);
+            self.temp_store_tx.put(&t_encoded, &id.raw_encode())?;
+            return Ok(id);
+        }
+        let persisted = match self.store_tx.get(&t_encoded, true)? {
+            Some(slice) => RelationId::raw_decode(&slice).0,
+            None => 0,
+        };
+        let last = self
+            .relation_store_id
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |cur| {
+                Some(cur.max(persisted) + 1)
+            })
+            .unwrap();
+        let id = RelationId::new(last.max(persisted) + 1);
+        self.store_tx.put(&t_encoded, &id.raw_encode())?;
+        Ok(id)
+    }
     pub(crate) fn get_relation(&self, name: &str, lock: bool) -> Result<RelationHandle> {
         #[derive(Error, Diagnostic, Debug)]
         #[error("Cannot find requested stored relation '{0}'")]
         #[diagnostic(code(query::relation_not_found))]
         struct StoredRelationNotFoundError(String);
 
+        self.resolve_relation(name, lock)?
+            .ok_or_else(|| StoredRelationNotFoundError(name.to_string()).into())
+    }
+    pub(crate) fn resolve_relation(
+        &self,
+        name: &str,
+        lock: bool,
+    ) -> Result<Option<RelationHandle>> {
+        let name = &*self.folded_name(name);
         let key = DataValue::from(name);
         let encoded = vec![key].encode_as_key(RelationId::SYSTEM);
 
         let found = if name.starts_with('_') {
-            self.temp_store_tx
-                .get(&encoded, lock)?
-                .ok_or_else(|| StoredRelationNotFoundError(name.to_string()))?
+            self.temp_store_tx.get(&encoded, lock)?
         } else {
-            self.store_tx
-                .get(&encoded, lock)?
-                .ok_or_else(|| StoredRelationNotFoundError(name.to_string()))?
+            self.store_tx.get(&encoded, lock)?
         };
-        let metadata = RelationHandle::decode(&found)?;
-        Ok(metadata)
+        found.map(|v| RelationHandle::decode(&v)).transpose()
     }
     pub(crate) fn describe_relation(&mut self, name: &str, description: &str) -> Result<()> {
         let mut meta = self.get_relation(name, true)?;
@@ -716,6 +1104,14 @@ impl<'a> SessionTx<'a> {
             self.temp_store_tx.del(&encoded)?;
         } else {
             self.store_tx.del(&encoded)?;
+            to_clean.push((
+                row_count_key(store.id, None),
+                row_count_key(store.id.next(), None),
+            ));
+            to_clean.push((
+                unique_sentinel_key(store.id, &[]),
+                unique_sentinel_key(store.id.next(), &[]),
+            ));
         }
         let lower_bound = Tuple::default().encode_as_key(store.id);
         let upper_bound = Tuple::default().encode_as_key(store.id.next());
@@ -1034,10 +1430,10 @@ impl<'a> SessionTx<'a> {
                 .enumerate()
             {
                 if col.name == *field {
-                    let mut col_type = col.typing.coltype.clone();
-                    if let ColType::List { eltype, .. } = &col_type {
-                        col_type = eltype.coltype.clone();
-                    }
+                    let col_type = match col.typing.element_type() {
+                        Some(eltype) => eltype.strip_optional().clone(),
+                        None => col.typing.strip_optional().clone(),
+                    };
 
                     if let ColType::Vec { eltype, len } = col_type {
                         if eltype != config.dtype {
@@ -1234,6 +1630,7 @@ impl<'a> SessionTx<'a> {
         rel_name: &Symbol,
         idx_name: &Symbol,
         cols: &[Symbol],
+        unique: bool,
     ) -> Result<()> {
         // Get relation handle
         let mut rel_handle = self.get_relation(rel_name, true)?;
@@ -1350,6 +1747,26 @@ impl<'a> SessionTx<'a> {
             }
         }
 
+        if unique {
+            // the index is sorted by the unique columns, so duplicates are adjacent
+            let mut prev: Option<Tuple> = None;
+            for tuple in idx_handle.scan_all(self) {
+                let tuple = tuple?;
+                if let Some(prev) = &prev {
+                    if prev[..cols.len()] == tuple[..cols.len()] {
+                        bail!(DuplicateKey(
+                            idx_handle.name.to_string(),
+                            tuple[..cols.len()].to_vec()
+                        ));
+                    }
+                }
+                prev = Some(tuple);
+            }
+            rel_handle
+                .unique_indices
+                .insert(idx_name.name.clone(), cols.len());
+        }
+
         // add index to relation
         rel_handle
             .indices
@@ -1379,16 +1796,12 @@ impl<'a> SessionTx<'a> {
             self.tokenizers.named_cache.write().unwrap().clear();
             self.tokenizers.hashed_cache.write().unwrap().clear();
         }
+        rel.unique_indices.remove(&idx_name.name);
         if rel.indices.remove(&idx_name.name).is_none()
             && rel.hnsw_indices.remove(&idx_name.name).is_none()
             && rel.lsh_indices.remove(&idx_name.name).is_none()
             && rel.fts_indices.remove(&idx_name.name).is_none()
         {
-            #[derive(Debug, Error, Diagnostic)]
-            #[error("index {0} for relation {1} not found")]
-            #[diagnostic(code(tx::idx_not_found))]
-            pub(crate) struct IndexNotFound(String, String);
-
             bail!(IndexNotFound(idx_name.to_string(), rel_name.to_string()));
         }
 
@@ -1433,6 +1846,21 @@ impl<'a> SessionTx<'a> {
         }
         rel.name = new.name.clone();
 
+        // indices are stored relations named after the relation they belong to
+        for (idx, _) in rel.indices.values_mut() {
+            self.rename_index_relation(idx, &old.name, &new.name)?;
+        }
+        for (idx, _) in rel.hnsw_indices.values_mut() {
+            self.rename_index_relation(idx, &old.name, &new.name)?;
+        }
+        for (idx, _) in rel.fts_indices.values_mut() {
+            self.rename_index_relation(idx, &old.name, &new.name)?;
+        }
+        for (idx, inv_idx, _) in rel.lsh_indices.values_mut() {
+            self.rename_index_relation(idx, &old.name, &new.name)?;
+            self.rename_index_relation(inv_idx, &old.name, &new.name)?;
+        }
+
         let mut meta_val = vec![];
         rel.serialize(&mut Serializer::new(&mut meta_val)).unwrap();
         self.store_tx.del(&old_encoded)?;
@@ -1440,6 +1868,253 @@ impl<'a> SessionTx<'a> {
 
         Ok(())
     }
+    /// Add a non-key column to a stored relation, filling it in for the existing rows
+    /// with its default value, or null if it is nullable and has no default.
+    pub(crate) fn add_column(&mut self, rel_name: &Symbol, col: &ColumnDef) -> Result<()> {
+        let mut rel = self.get_relation(rel_name, true)?;
+        if rel.access_level < AccessLevel::Normal {
+            bail!(InsufficientAccessLevel(
+                rel.name.to_string(),
+                "altering relation".to_string(),
+                rel.access_level
+            ));
+        }
+        if rel
+            .metadata
+            .keys
+            .iter()
+            .chain(rel.metadata.non_keys.iter())
+            .any(|c| c.name == col.name)
+        {
+            #[derive(Debug, Error, Diagnostic)]
+            #[error("Relation {0} already has a column named {1}")]
+            #[diagnostic(code(tx::col_exists))]
+            pub(crate) struct ColumnExists(String, String);
+
+            bail!(ColumnExists(rel.name.to_string(), col.name.to_string()))
+        }
+        if col.default_gen.is_none() && !col.typing.nullable {
+            #[derive(Debug, Error, Diagnostic)]
+            #[error("Column {0} needs a default value to fill in the existing rows of {1}")]
+            #[diagnostic(code(tx::added_col_without_default))]
+            #[diagnostic(help("Give the column a default, or make it nullable"))]
+            pub(crate) struct AddedColumnWithoutDefault(String, String);
+
+            bail!(AddedColumnWithoutDefault(
+                col.name.to_string(),
+                rel.name.to_string()
+            ))
+        }
+
+        // the new column comes last, so the positions used by indices are unchanged
+        rel.metadata.non_keys.push(col.clone());
+        let cur_vld = current_validity();
+        let mut existing = TempCollector::default();
+        for tuple in rel.scan_all(self) {
+            existing.push(tuple?);
+        }
+        for mut tuple in existing.into_iter() {
+            let val = match &col.default_gen {
+                Some(gen) => gen.clone().eval_to_const()?,
+                None => DataValue::Null,
+            };
+            tuple.push(val.coerce_to(&col.typing, cur_vld)?);
+            let key = rel.encode_key_for_store(&tuple, Default::default())?;
+            let val = rel.encode_val_for_store(&tuple, Default::default())?;
+            if rel.is_temp {
+                self.temp_store_tx.put(&key, &val)?;
+            } else {
+                self.store_tx.put(&key, &val)?;
+            }
+        }
+
+        self.put_altered_relation(&rel)
+    }
+    /// Remove a non-key column from a stored relation, together with its values.
+    pub(crate) fn drop_column(&mut self, rel_name: &Symbol, col: &Symbol) -> Result<()> {
+        let mut rel = self.get_relation(rel_name, true)?;
+        if rel.access_level < AccessLevel::Normal {
+            bail!(InsufficientAccessLevel(
+                rel.name.to_string(),
+                "altering relation".to_string(),
+                rel.access_level
+            ));
+        }
+        if rel.metadata.keys.iter().any(|c| c.name == col.name) {
+            #[derive(Debug, Error, Diagnostic)]
+            #[error("Cannot drop key column {0} of relation {1}")]
+            #[diagnostic(code(tx::drop_key_col))]
+            pub(crate) struct DropKeyColumn(String, String, #[label] SourceSpan);
+
+            bail!(DropKeyColumn(
+                col.name.to_string(),
+                rel.name.to_string(),
+                col.span
+            ))
+        }
+        let n_keys = rel.metadata.keys.len();
+        let pos = match rel
+            .metadata
+            .non_keys
+            .iter()
+            .position(|c| c.name == col.name)
+        {
+            Some(i) => i + n_keys,
+            None => {
+                #[derive(Debug, Error, Diagnostic)]
+                #[error("Relation {0} has no column named {1}")]
+                #[diagnostic(code(tx::col_not_found))]
+                pub(crate) struct ColumnNotFound(String, String, #[label] SourceSpan);
+
+                bail!(ColumnNotFound(
+                    rel.name.to_string(),
+                    col.name.to_string(),
+                    col.span
+                ))
+            }
+        };
+        // search indices refer to columns in their own ways, so they are not adjusted
+        let in_index = rel
+            .indices
+            .values()
+            .any(|(_, mapping)| mapping.contains(&pos));
+        if in_index
+            || !rel.hnsw_indices.is_empty()
+            || !rel.fts_indices.is_empty()
+            || !rel.lsh_indices.is_empty()
+        {
+            #[derive(Debug, Error, Diagnostic)]
+            #[error("Cannot drop column {0} of relation {1} as an index may depend on it")]
+            #[diagnostic(code(tx::drop_indexed_col))]
+            #[diagnostic(help("Drop the index first"))]
+            pub(crate) struct DropIndexedColumn(String, String, #[label] SourceSpan);
+
+            bail!(DropIndexedColumn(
+                col.name.to_string(),
+                rel.name.to_string(),
+                col.span
+            ))
+        }
+
+        let mut existing = TempCollector::default();
+        for tuple in rel.scan_all(self) {
+            existing.push(tuple?);
+        }
+        rel.metadata.non_keys.remove(pos - n_keys);
+        for (_, mapping) in rel.indices.values_mut() {
+            for i in mapping.iter_mut() {
+                if *i > pos {
+                    *i -= 1;
+                }
+            }
+        }
+        for mut tuple in existing.into_iter() {
+            tuple.remove(pos);
+            let key = rel.encode_key_for_store(&tuple, Default::default())?;
+            let val = rel.encode_val_for_store(&tuple, Default::default())?;
+            if rel.is_temp {
+                self.temp_store_tx.put(&key, &val)?;
+            } else {
+                self.store_tx.put(&key, &val)?;
+            }
+        }
+        self.put_altered_relation(&rel)
+    }
+    /// Delete all rows of a stored relation and of its indices, keeping the definitions.
+    /// Returns the number of rows deleted from the relation itself.
+    pub(crate) fn truncate_relation(&mut self, rel_name: &Symbol) -> Result<usize> {
+        let rel = self.get_relation(rel_name, true)?;
+        if rel.access_level < AccessLevel::Protected {
+            bail!(InsufficientAccessLevel(
+                rel.name.to_string(),
+                "truncation".to_string(),
+                rel.access_level
+            ));
+        }
+        let mut ids = vec![rel.id];
+        ids.extend(rel.indices.values().map(|(idx, _)| idx.id));
+        ids.extend(rel.hnsw_indices.values().map(|(idx, _)| idx.id));
+        ids.extend(rel.fts_indices.values().map(|(idx, _)| idx.id));
+        for (idx, inv_idx, _) in rel.lsh_indices.values() {
+            ids.push(idx.id);
+            ids.push(inv_idx.id);
+        }
+        let mut removed = 0;
+        for id in ids {
+            let lower = Tuple::default().encode_as_key(id);
+            let upper = Tuple::default().encode_as_key(id.next());
+            let keys: Vec<_> = if rel.is_temp {
+                self.temp_store_tx
+                    .range_scan(&lower, &upper)
+                    .map_ok(|(k, _)| k)
+                    .try_collect()?
+            } else {
+                self.store_tx
+                    .range_scan(&lower, &upper)
+                    .map_ok(|(k, _)| k)
+                    .try_collect()?
+            };
+            if id == rel.id {
+                removed = keys.len();
+            }
+            for key in keys {
+                if rel.is_temp {
+                    self.temp_store_tx.del(&key)?;
+                } else {
+                    self.store_tx.del(&key)?;
+                }
+            }
+        }
+        if !rel.is_temp {
+            for name in rel.unique_indices.keys() {
+                let idx_id = rel.indices[name].0.id;
+                let lower = unique_sentinel_key(idx_id, &[]);
+                let upper = unique_sentinel_key(idx_id.next(), &[]);
+                let keys: Vec<_> = self
+                    .store_tx
+                    .range_scan(&lower, &upper)
+                    .map_ok(|(k, _)| k)
+                    .try_collect()?;
+                for key in keys {
+                    self.store_tx.del(&key)?;
+                }
+            }
+        }
+        self.adjust_row_count(&rel, -(removed as i64))?;
+        Ok(removed)
+    }
+    fn put_altered_relation(&mut self, rel: &RelationHandle) -> Result<()> {
+        let name_key = vec![DataValue::Str(rel.name.clone())].encode_as_key(RelationId::SYSTEM);
+        let mut meta_val = vec![];
+        rel.serialize(&mut Serializer::new(&mut meta_val)).unwrap();
+        if rel.is_temp {
+            self.temp_store_tx.put(&name_key, &meta_val)?;
+        } else {
+            self.store_tx.put(&name_key, &meta_val)?;
+        }
+        Ok(())
+    }
+    fn rename_index_relation(
+        &mut self,
+        idx: &mut RelationHandle,
+        old_rel: &str,
+        new_rel: &str,
+    ) -> Result<()> {
+        let new_name = SmartString::from(format!("{new_rel}{}", &idx.name[old_rel.len()..]));
+        let mut stored = self.get_relation(&idx.name, true)?;
+        stored.name = new_name.clone();
+
+        let old_encoded = vec![DataValue::Str(idx.name.clone())].encode_as_key(RelationId::SYSTEM);
+        let new_encoded = vec![DataValue::Str(new_name.clone())].encode_as_key(RelationId::SYSTEM);
+        let mut meta_val = vec![];
+        stored
+            .serialize(&mut Serializer::new(&mut meta_val))
+            .unwrap();
+        self.store_tx.del(&old_encoded)?;
+        self.store_tx.put(&new_encoded, &meta_val)?;
+        idx.name = new_name;
+        Ok(())
+    }
     pub(crate) fn rename_temp_relation(&mut self, old: Symbol, new: Symbol) -> Result<()> {
         let new_key = DataValue::Str(new.name.clone());
         let new_encoded = vec![new_key].encode_as_key(RelationId::SYSTEM);
@@ -1463,6 +2138,16 @@ impl<'a> SessionTx<'a> {
     }
 }
 
+#[derive(Debug, Error, Diagnostic)]
+#[error("Duplicate value {1:?} for unique index {0}")]
+#[diagnostic(code(tx::duplicate_key))]
+pub(crate) struct DuplicateKey(pub(crate) String, pub(crate) Vec<DataValue>);
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("index {0} for relation {1} not found")]
+#[diagnostic(code(tx::idx_not_found))]
+pub(crate) struct IndexNotFound(pub(crate) String, pub(crate) String);
+
 #[derive(Debug, Error, Diagnostic)]
 #[error("Insufficient access level {2} for {1} on stored relation '{0}'")]
 #[diagnostic(code(tx::insufficient_access_level))]
@@ -8,6 +8,7 @@
  */
 
 use std::collections::BTreeMap;
+use std::ops::Bound::{Excluded, Included, Unbounded};
 use std::time::Duration;
 
 use itertools::Itertools;
@@ -17,13 +18,21 @@ use smartstring::{LazyCompact, SmartString};
 
 use crate::data::expr::Expr;
 use crate::data::symb::Symbol;
+use crate::data::tuple::TupleT;
 use crate::data::value::DataValue;
 use crate::fixed_rule::FixedRulePayload;
 use crate::fts::{TokenizerCache, TokenizerConfig};
 use crate::parse::SourceSpan;
 use crate::runtime::callback::CallbackOp;
 use crate::runtime::db::Poison;
-use crate::{DbInstance, FixedRule, RegularTempStore, ScriptMutability};
+use crate::runtime::relation::RelationId;
+use crate::runtime::transact::{storage_version_key, CURRENT_STORAGE_VERSION};
+use crate::storage::StoreTx;
+use crate::{
+    evaluate_expressions, format_error_as_json, ColType, ColumnInfo, Db, DbInstance, FixedRule,
+    ImportOptions, JsonData, MemStorage, NamedRows, NullableColType, RegularTempStore, RelationDef,
+    ScriptMutability, Storage,
+};
 
 #[test]
 fn test_limit_offset() {
@@ -57,6 +66,201 @@ fn test_normal_aggr_empty() {
     assert_eq!(res, vec![vec![DataValue::from(0)]]);
 }
 
+#[test]
+fn aggregation_grouped_by_head_columns() {
+    let db = DbInstance::default();
+    db.run_default(
+        r"
+        ?[id, category, score] <- [
+            [1, 'a', 10], [2, 'b', 5], [3, 'a', 20],
+            [4, null, 7], [5, 'b', 1], [6, null, 3], [7, 'a', 30]
+        ]
+        :create person {id => category: String?, score: Int}
+    ",
+    )
+    .unwrap();
+    // the columns in the head that are not aggregated are the grouping key,
+    // and null is a group like any other value
+    let res = db
+        .run_default(
+            "?[category, count(id), sum(score), avg(score), min(score), max(score)] := \
+             *person{id, category, score}",
+        )
+        .unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([
+            [null, 2, 10.0, 5.0, 3, 7],
+            ["a", 3, 60.0, 20.0, 10, 30],
+            ["b", 2, 6.0, 3.0, 1, 5]
+        ])
+    );
+    // without grouping columns, everything is one group
+    let res = db
+        .run_default("?[count(id), sum(score)] := *person{id, score}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[7, 76.0]]));
+}
+
+#[test]
+fn having_filters_groups() {
+    let db = DbInstance::default();
+    db.run_default(
+        r"
+        ?[id, category, score] <- [
+            [1, 'a', 10], [2, 'b', 5], [3, 'a', 20],
+            [4, 'c', 7], [5, 'b', 1], [6, 'c', 3], [7, 'a', 30], [8, 'd', 2]
+        ]
+        :create person {id => category, score}
+    ",
+    )
+    .unwrap();
+    // aggregated columns are referred to as they are written in the head
+    let res = db
+        .run_default(
+            "?[category, count(id), sum(score)] := *person{id, category, score} \
+             :having count(id) > 1",
+        )
+        .unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([["a", 3, 60.0], ["b", 2, 6.0], ["c", 2, 10.0]])
+    );
+    // applied before sorting and limits, and together with the other columns
+    let res = db
+        .run_default(
+            "?[category, count(id), max(score)] := *person{id, category, score} \
+             :having count(id) > 1 && category != 'a' \
+             :order -max(score) \
+             :limit 1",
+        )
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([["c", 2, 7]]));
+    // and before assertions
+    assert!(db
+        .run_default(
+            "?[category, count(id)] := *person{id, category} :having count(id) > 5 :assert some"
+        )
+        .is_err());
+    let err = db
+        .run_default("?[category, count(id)] := *person{id, category} :having n > 1")
+        .unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "eval::bad_binding");
+    // the aggregated variable itself is not an output column
+    let err = db
+        .run_default("?[category, count(id)] := *person{id, category} :having id > 1")
+        .unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "eval::bad_binding");
+    // a variable aggregated twice is told apart by its aggregation
+    let res = db
+        .run_default("?[count(id), max(id)] := *person{id} :having max(id) > 3")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[8, 8]]));
+    let res = db
+        .run_default("?[count(id), max(id)] := *person{id} :having count( id ) < 8")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([]));
+}
+
+#[test]
+fn distinct_aggregation() {
+    let db = DbInstance::default();
+    db.run_default(
+        r"
+        ?[id, category, score] <- [
+            [1, 'a', 10], [2, 'a', 10], [3, 'a', 20], [4, 'a', null],
+            [5, 'b', 5], [6, 'b', null], [7, 'b', null]
+        ]
+        :create person {id => category, score: Int?}
+    ",
+    )
+    .unwrap();
+    let res = db
+        .run_default(
+            "?[category, count(distinct score), sum(distinct score), count(score)] := \
+             *person{id, category, score}",
+        )
+        .unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([["a", 2, 30.0, 4], ["b", 1, 5.0, 3]])
+    );
+    // also for aggregations that are otherwise meets
+    let res = db
+        .run_default("?[category, min(distinct score)] := *person{id, category, score}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([["a", 10], ["b", 5]]));
+    // a variable may still be named `distinct`
+    let res = db
+        .run_default("?[count(distinct)] := *person{id, score: distinct}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[7]]));
+}
+
+#[test]
+fn qualified_column_variables() {
+    let db = DbInstance::default();
+    db.run_default("?[id, name] <- [[1, 'Alice'], [2, 'Bob']] :create person {id => name}")
+        .unwrap();
+    db.run_default(
+        "?[work_id, person_id, name] <- [[10, 1, 'Acme'], [11, 1, 'Initech'], [12, 2, 'Acme']] \
+         :create work_info {work_id => person_id, name}",
+    )
+    .unwrap();
+    // both relations have a `name` column, told apart by the relation they come from
+    let res = db
+        .run_default(
+            "?[person.name, work_info.work_id, work_info.name] := \
+             *person{id}, *work_info{person_id: id}",
+        )
+        .unwrap();
+    assert_eq!(
+        res.headers,
+        vec!["person.name", "work_info.work_id", "work_info.name"]
+    );
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([
+            ["Alice", 10, "Acme"],
+            ["Alice", 11, "Initech"],
+            ["Bob", 12, "Acme"]
+        ])
+    );
+    // in expressions, and together with columns bound in the atoms
+    let res = db
+        .run_default(
+            "?[n] := *person{id, name: n}, *work_info{person_id: id}, \
+             work_info.name == 'Initech', person.name == n",
+        )
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([["Alice"]]));
+
+    let err = db
+        .run_default("?[person.name] := *person{id}, *person{id: 2}")
+        .unwrap_err();
+    assert_eq!(
+        err.code().unwrap().to_string(),
+        "parser::ambiguous_qualified_var"
+    );
+    assert!(db.run_default("?[person.age] := *person{id}").is_err());
+    // a column variable cannot be bound in some other way as well
+    for query in [
+        "?[person.name] := *person{id}, person.name = 'Alice'",
+        "?[person.name] := *person{id}, *work_info{name: person.name}",
+        "?[person.name] := *person{id}, person.name in ['Alice']",
+    ] {
+        let err = db.run_default(query).unwrap_err();
+        assert_eq!(
+            err.code().unwrap().to_string(),
+            "parser::rebound_qualified_var"
+        );
+    }
+    let res = db
+        .run_default("?[person.name] := *person{id: 2, name: person.name}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([["Bob"]]));
+}
+
 #[test]
 fn test_meet_aggr_empty() {
     let db = DbInstance::default();
@@ -515,1042 +719,3561 @@ fn test_index() {
 }
 
 #[test]
-fn test_json_objects() {
+fn index_maintained_by_mutations() {
     let db = DbInstance::default();
-    db.run_default("?[a] := a = {'a': 1}").unwrap();
+    db.run_default(":create person {id: Int => name: String, age: Int}")
+        .unwrap();
+    db.run_default("::index create person:by_name {name}")
+        .unwrap();
+    let ids_named = |name: &str| {
+        db.run_default(&format!("?[id] := *person:by_name{{name: '{name}', id}}"))
+            .unwrap()
+            .rows
+            .into_iter()
+            .map(|row| row[0].get_int().unwrap())
+            .collect_vec()
+    };
     db.run_default(
-        r"?[a] := a = {
-            'a': 1
-        }",
+        "?[id, name, age] <- [[1, 'Alice', 30], [2, 'Bob', 40], [3, 'Alice', 50]] \
+         :put person {id => name, age}",
     )
     .unwrap();
-}
-
-#[test]
-fn test_custom_rules() {
-    let db = DbInstance::default();
-    struct Custom;
-
-    impl FixedRule for Custom {
-        fn arity(
-            &self,
-            _options: &BTreeMap<SmartString<LazyCompact>, Expr>,
-            _rule_head: &[Symbol],
-            _span: SourceSpan,
-        ) -> miette::Result<usize> {
-            Ok(1)
-        }
-
-        fn run(
-            &self,
-            payload: FixedRulePayload<'_, '_>,
-            out: &'_ mut RegularTempStore,
-            _poison: Poison,
-        ) -> miette::Result<()> {
-            let rel = payload.get_input(0)?;
-            let mult = payload.integer_option("mult", Some(2))?;
-            for maybe_row in rel.iter()? {
-                let row = maybe_row?;
-                let mut sum = 0;
-                for col in row {
-                    let d = col.get_int().unwrap_or(0);
-                    sum += d;
-                }
-                sum *= mult;
-                out.put(vec![DataValue::from(sum)])
-            }
-            Ok(())
-        }
-    }
+    assert_eq!(ids_named("Alice"), vec![1, 3]);
+    assert_eq!(ids_named("Bob"), vec![2]);
 
-    db.register_fixed_rule("SumCols".to_string(), Custom)
+    db.run_default("?[id, name] <- [[3, 'Carol']] :update person {id => name}")
         .unwrap();
-    let res = db
+    assert_eq!(ids_named("Alice"), vec![1]);
+    assert_eq!(ids_named("Carol"), vec![3]);
+    db.run_default("?[id] <- [[1]] :rm person {id}").unwrap();
+    assert!(ids_named("Alice").is_empty());
+
+    // index entries are written in the same transaction as the rows
+    assert!(db
         .run_default(
-            r#"
-        rel[] <- [[1,2,3,4],[5,6,7,8]]
-        ?[x] <~ SumCols(rel[], mult: 100)
-    "#,
+            "{?[id, name, age] <- [[4, 'Dave', 20]] :put person {id => name, age}} \
+             {?[x] <- [[1]] :assert none}",
         )
+        .is_err());
+    assert!(ids_named("Dave").is_empty());
+
+    // filters on the indexed column use the index
+    let expl = db
+        .run_default("::explain { ?[id] := *person{id, name: 'Bob'} }")
         .unwrap();
-    assert_eq!(res.into_json()["rows"], json!([[1000], [2600]]));
+    assert!(expl
+        .rows
+        .iter()
+        .any(|row| row[5] == DataValue::from(":person:by_name")));
 }
 
 #[test]
-fn test_index_short() {
+fn index_lookup() {
     let db = DbInstance::default();
-    db.run_default(":create friends {fr: Int, to: Int => data: Any}")
+    db.run_default(":create person {org: String, id: Int => name: String}")
         .unwrap();
-
-    db.run_default(r"?[fr, to, data] <- [[1,2,3],[4,5,6]] :put friends {fr, to => data}")
+    db.run_default("::index create person:by_name {name}")
         .unwrap();
+    db.run_default(
+        "?[org, id, name] <- [['b', 1, 'Alice'], ['a', 2, 'Bob'], \
+                              ['a', 3, 'Alice'], ['c', 1, 'Alice']] \
+         :put person {org, id => name}",
+    )
+    .unwrap();
+    let lookup = |name: &str| db.lookup_by_index("person:by_name", &[DataValue::from(name)]);
+    let key = |org: &str, id: i64| vec![DataValue::from(org), DataValue::from(id)];
+    assert_eq!(
+        lookup("Alice").unwrap(),
+        vec![key("a", 3), key("b", 1), key("c", 1)]
+    );
+    assert_eq!(lookup("Bob").unwrap(), vec![key("a", 2)]);
+    assert!(lookup("Carol").unwrap().is_empty());
+    // the index also holds the remaining key columns, which narrow down the lookup
+    assert_eq!(
+        db.lookup_by_index(
+            "person:by_name",
+            &[DataValue::from("Alice"), DataValue::from("b")]
+        )
+        .unwrap(),
+        vec![key("b", 1)]
+    );
+    assert!(db.lookup_by_index("person:by_age", &[]).is_err());
+    assert!(db.lookup_by_index("person", &[]).is_err());
+}
 
-    db.run_default("::index create friends:rev {to}").unwrap();
-
-    db.run_default(r"?[fr, to, data] <- [[1,2,5],[6,5,7]] :put friends {fr, to => data}")
+#[test]
+fn unique_index() {
+    let db = DbInstance::default();
+    db.run_default(":create user {id: Int => email: String, name: String}")
         .unwrap();
-    db.run_default(r"?[fr, to] <- [[4,5]] :rm friends {fr, to}")
+    db.run_default(
+        "?[id, email, name] <- [[1, 'a@x', 'Alice'], [2, 'b@x', 'Bob']] \
+         :put user {id => email, name}",
+    )
+    .unwrap();
+    db.run_default("::index create unique user:by_email {email}")
         .unwrap();
+    let indices = db.run_default("::indices user").unwrap();
+    assert_eq!(indices.rows[0][1], DataValue::from("unique"));
 
-    let rels_data = db
-        .export_relations(["friends", "friends:rev"].into_iter())
+    // distinct values, and rewriting a row with its own value, are fine
+    db.run_default("?[id, email, name] <- [[3, 'c@x', 'Carol']] :put user {id => email, name}")
         .unwrap();
-    assert_eq!(
-        rels_data["friends"].clone().into_json()["rows"],
-        json!([[1, 2, 5], [6, 5, 7]])
+    db.run_default("?[id, name] <- [[1, 'Alicia']] :update user {id => name}")
+        .unwrap();
+    db.run_default("?[id, email, name] <- [[2, 'b@x', 'Bobby']] :put user {id => email, name}")
+        .unwrap();
+
+    let dup = |script: &str| {
+        let err = db.run_default(script).unwrap_err();
+        assert_eq!(err.code().unwrap().to_string(), "tx::duplicate_key");
+    };
+    dup("?[id, email, name] <- [[4, 'a@x', 'Dave']] :put user {id => email, name}");
+    dup("?[id, email] <- [[3, 'b@x']] :update user {id => email}");
+    dup(
+        "?[id, email, name] <- [[4, 'd@x', 'Dave'], [5, 'd@x', 'Eve']] \
+         :put user {id => email, name}",
     );
+    // a value becomes free once its row no longer holds it
+    db.run_default("?[id, email] <- [[1, 'alicia@x']] :update user {id => email}")
+        .unwrap();
+    db.run_default("?[id, email, name] <- [[4, 'a@x', 'Dave']] :put user {id => email, name}")
+        .unwrap();
+    let res = db
+        .run_default("?[id, email] := *user{id, email} :order id")
+        .unwrap();
     assert_eq!(
-        rels_data["friends:rev"].clone().into_json()["rows"],
-        json!([[2, 1], [5, 6]])
+        res.into_json()["rows"],
+        json!([[1, "alicia@x"], [2, "b@x"], [3, "c@x"], [4, "a@x"]])
     );
 
-    let rels = db.run_default("::relations").unwrap();
-    assert_eq!(rels.rows[1][0], DataValue::from("friends:rev"));
-    assert_eq!(rels.rows[1][1], DataValue::from(2));
-    assert_eq!(rels.rows[1][2], DataValue::from("index"));
-
-    let cols = db.run_default("::columns friends:rev").unwrap();
-    assert_eq!(cols.rows.len(), 2);
-
-    let expl = db
-        .run_default("::explain { ?[fr, data] := *friends{to: 2, fr, data} }")
-        .unwrap()
-        .into_json();
+    db.run_default("::index create unique user:by_name {name}")
+        .unwrap();
+    dup("?[id, email, name] <- [[5, 'e@x', 'Bobby']] :put user {id => email, name}");
+    db.run_default("::index drop user:by_name").unwrap();
+    db.run_default("?[id, email, name] <- [[5, 'e@x', 'Bobby']] :put user {id => email, name}")
+        .unwrap();
+    // existing duplicates prevent creating the index
+    let err = db
+        .run_default("::index create unique user:by_name {name}")
+        .unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "tx::duplicate_key");
+    // a relation may still be called `unique`
+    db.run_default(":create unique {k}").unwrap();
+    db.run_default("::index create unique:by_k {k}").unwrap();
+}
 
-    for row in expl["rows"].as_array().unwrap() {
-        println!("{}", row);
-    }
+#[test]
+fn unique_sentinels_are_released() {
+    let db = Db::new(MemStorage::default()).unwrap();
+    db.initialize().unwrap();
+    let run = |script: &str| {
+        db.run_script(script, Default::default(), ScriptMutability::Mutable)
+            .unwrap()
+    };
+    let sentinels = || {
+        let lower =
+            vec![DataValue::Null, DataValue::from("UNIQUE")].encode_as_key(RelationId::SYSTEM);
+        let upper = vec![DataValue::Null, DataValue::from("UNIQUE"), DataValue::Bot]
+            .encode_as_key(RelationId::SYSTEM);
+        db.transact()
+            .unwrap()
+            .store_tx
+            .range_count(&lower, &upper)
+            .unwrap()
+    };
+    run(":create user {id: Int => email: String}");
+    run("::index create unique user:by_email {email}");
+    run("?[id, email] <- [[1, 'a@x'], [2, 'b@x'], [3, 'c@x']] :put user {id => email}");
+    assert_eq!(sentinels(), 3);
+    // values leaving the index take their sentinels with them
+    run("?[id, email] <- [[1, 'alice@x']] :update user {id => email}");
+    assert_eq!(sentinels(), 3);
+    run("?[id, email] <- [[2, 'b@x']] :put user {id => email}");
+    assert_eq!(sentinels(), 3);
+    run("?[id] <- [[3]] :rm user {id}");
+    assert_eq!(sentinels(), 2);
+    run("::truncate user");
+    assert_eq!(sentinels(), 0);
+}
 
-    let joins = expl["rows"]
-        .as_array()
-        .unwrap()
-        .iter()
-        .map(|row| row.as_array().unwrap()[5].clone())
-        .collect_vec();
-    assert!(joins.contains(&json!(":friends:rev")));
+#[test]
+fn index_on_undefined_relation() {
+    let db = DbInstance::default();
+    let relations = || db.run_default("::relations").unwrap().rows;
+    let code = |script: &str| {
+        db.run_default(script)
+            .unwrap_err()
+            .code()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        code("::index create person:by_name {name}"),
+        "query::relation_not_found"
+    );
+    assert_eq!(
+        code("::fts create person:fts {extractor: name, tokenizer: Simple}"),
+        "query::relation_not_found"
+    );
+    assert!(relations().is_empty());
 
-    let res = db
-        .run_default("?[fr, data] := *friends{to: 2, fr, data}")
+    // a failed definition leaves no metadata behind
+    db.run_default(":create person {id => name}").unwrap();
+    let before = relations();
+    assert_eq!(
+        code("::index create person:by_age {age}"),
+        "tx::col_in_idx_not_found"
+    );
+    assert_eq!(relations(), before);
+    assert!(db.run_default("::indices person").unwrap().rows.is_empty());
+    db.run_default("::index create person:by_age {name}")
         .unwrap();
-    assert_eq!(res.into_json()["rows"], json!([[1, 5]]));
 }
 
 #[test]
-fn test_multi_tx() {
+fn rename_relation_with_indices() {
     let db = DbInstance::default();
-    let tx = db.multi_transaction(true);
-    tx.run_script(":create a {a}", Default::default()).unwrap();
-    tx.run_script("?[a] <- [[1]] :put a {a}", Default::default())
+    db.run_default("?[id, name] <- [[1, 'Alice'], [2, 'Bob']] :create person {id => name}")
         .unwrap();
-    assert!(tx.run_script(":create a {a}", Default::default()).is_err());
-    tx.run_script("?[a] <- [[2]] :put a {a}", Default::default())
+    db.run_default("::index create person:by_name {name}")
         .unwrap();
-    tx.run_script("?[a] <- [[3]] :put a {a}", Default::default())
+    db.run_default(":create pet {name}").unwrap();
+
+    assert_eq!(
+        db.run_default("::rename person -> pet")
+            .unwrap_err()
+            .code()
+            .unwrap()
+            .to_string(),
+        "eval::rel_name_conflict"
+    );
+    db.run_default("::rename person -> human").unwrap();
+    assert!(db.run_default("?[id] := *person{id}").is_err());
+    let res = db.run_default("?[id, name] := *human{id, name}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1, "Alice"], [2, "Bob"]]));
+
+    // the index moves along and is still maintained
+    db.run_default("?[id, name] <- [[3, 'Carol']] :put human {id => name}")
         .unwrap();
-    tx.commit().unwrap();
+    let res = db
+        .run_default("?[id] := *human:by_name{name: 'Carol', id}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[3]]));
+    let names = db
+        .run_default("::relations")
+        .unwrap()
+        .rows
+        .into_iter()
+        .map(|row| row[0].clone())
+        .collect_vec();
     assert_eq!(
-        db.run_default("?[a] := *a[a]").unwrap().into_json()["rows"],
-        json!([[1], [2], [3]])
+        names,
+        vec![
+            DataValue::from("human"),
+            DataValue::from("human:by_name"),
+            DataValue::from("pet")
+        ]
     );
+    db.run_default("::index drop human:by_name").unwrap();
+    db.run_default("::remove human").unwrap();
+}
 
+#[test]
+fn add_column() {
     let db = DbInstance::default();
-    let tx = db.multi_transaction(true);
-    tx.run_script(":create a {a}", Default::default()).unwrap();
-    tx.run_script("?[a] <- [[1]] :put a {a}", Default::default())
+    db.run_default("?[id, name] <- [[1, 'Alice'], [2, 'Bob']] :create person {id => name}")
         .unwrap();
-    assert!(tx.run_script(":create a {a}", Default::default()).is_err());
-    tx.run_script("?[a] <- [[2]] :put a {a}", Default::default())
+    db.run_default("::index create person:by_name {name}")
         .unwrap();
-    tx.run_script("?[a] <- [[3]] :put a {a}", Default::default())
+    db.run_default("::alter person add email: String default 'n/a'")
         .unwrap();
-    tx.abort().unwrap();
-    assert!(db.run_default("?[a] := *a[a]").is_err());
+    db.run_default("::alter person add age: Int?").unwrap();
+    let cols = db.run_default("::columns person").unwrap();
+    assert_eq!(
+        cols.rows.iter().map(|row| row[0].clone()).collect_vec(),
+        ["id", "name", "email", "age"].map(DataValue::from).to_vec()
+    );
+    // old rows read the default, or null
+    let res = db
+        .run_default("?[id, name, email, age] := *person{id, name, email, age}")
+        .unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([[1, "Alice", "n/a", null], [2, "Bob", "n/a", null]])
+    );
+
+    // new rows may omit the columns, and the index still works
+    db.run_default("?[id, name] <- [[3, 'Carol']] :put person {id => name}")
+        .unwrap();
+    db.run_default(
+        "?[id, name, email, age] <- [[4, 'Dave', 'd@x', 40]] :put person {id => name, email, age}",
+    )
+    .unwrap();
+    let res = db
+        .run_default(
+            "?[id, email, age] := *person:by_name{name: 'Carol', id}, *person{id, email, age}",
+        )
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[3, "n/a", null]]));
+    let res = db
+        .run_default("?[email, age] := *person{id: 4, email, age}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([["d@x", 40]]));
+
+    // old rows can be given values for the columns afterwards
+    db.run_default("?[id, age] <- [[1, 30]] :update person {id => age}")
+        .unwrap();
+    let res = db
+        .run_default("?[id, name, email, age] := *person{id, name, email, age}, id < 3")
+        .unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([[1, "Alice", "n/a", 30], [2, "Bob", "n/a", null]])
+    );
+
+    let code = |script: &str| {
+        db.run_default(script)
+            .unwrap_err()
+            .code()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        code("::alter person add score: Int"),
+        "tx::added_col_without_default"
+    );
+    assert_eq!(code("::alter person add name: String?"), "tx::col_exists");
+    assert_eq!(
+        code("::alter person add x: Int default 'x'"),
+        "parser::bad_default_for_col"
+    );
+    assert_eq!(
+        code("::alter nobody add x: Int?"),
+        "query::relation_not_found"
+    );
+    db.run_default("::access_level read_only person").unwrap();
+    assert_eq!(
+        code("::alter person add x: Int?"),
+        "tx::insufficient_access_level"
+    );
 }
 
 #[test]
-fn test_vec_types() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    db.run_default(":create a {k: String => v: <F32; 8>}")
-        .unwrap();
-    db.run_default("?[k, v] <- [['k', [1,2,3,4,5,6,7,8]]] :put a {k => v}")
+fn drop_column() {
+    let db = DbInstance::default();
+    db.run_default(
+        "?[id, name, email, age] <- [[1, 'Alice', 'a@x', 30], [2, 'Bob', 'b@x', 40]] \
+         :create person {id => name, email, age}",
+    )
+    .unwrap();
+    db.run_default("::index create person:by_age {age}")
         .unwrap();
-    let res = db.run_default("?[k, v] := *a{k, v}").unwrap();
+    db.run_default("::alter person drop email").unwrap();
+    let cols = db.run_default("::columns person").unwrap();
     assert_eq!(
-        json!([1., 2., 3., 4., 5., 6., 7., 8.]),
-        res.into_json()["rows"][0][1]
+        cols.rows.iter().map(|row| row[0].clone()).collect_vec(),
+        ["id", "name", "age"].map(DataValue::from).to_vec()
     );
     let res = db
-        .run_default("?[v] <- [[vec([1,2,3,4,5,6,7,8])]]")
+        .run_default("?[id, name, age] := *person{id, name, age}")
         .unwrap();
     assert_eq!(
-        json!([1., 2., 3., 4., 5., 6., 7., 8.]),
-        res.into_json()["rows"][0][0]
+        res.into_json()["rows"],
+        json!([[1, "Alice", 30], [2, "Bob", 40]])
     );
-    let res = db.run_default("?[v] <- [[rand_vec(5)]]").unwrap();
-    assert_eq!(5, res.into_json()["rows"][0][0].as_array().unwrap().len());
+    // the index still points at the right columns
+    db.run_default("?[id, name, age] <- [[3, 'Carol', 50]] :put person {id => name, age}")
+        .unwrap();
     let res = db
-        .run_default(r#"
-            val[v] <- [[vec([1,2,3,4,5,6,7,8])]]
-            ?[x,y,z] := val[v], x=l2_dist(v, v), y=cos_dist(v, v), nv = l2_normalize(v), z=ip_dist(nv, nv)
-        "#)
+        .run_default("?[id, name] := *person:by_age{age: 50, id}, *person{id, name}")
         .unwrap();
-    println!("{}", res.into_json());
+    assert_eq!(res.into_json()["rows"], json!([[3, "Carol"]]));
+
+    let code = |script: &str| {
+        db.run_default(script)
+            .unwrap_err()
+            .code()
+            .unwrap()
+            .to_string()
+    };
+    assert!(db.run_default("?[email] := *person{email}").is_err());
+    assert!(db
+        .run_default(
+            "?[id, name, email, age] <- [[4, 'D', 'd@x', 1]] :put person {id => name, email, age}"
+        )
+        .is_err());
+    assert_eq!(code("::alter person drop id"), "tx::drop_key_col");
+    assert_eq!(code("::alter person drop email"), "tx::col_not_found");
+    assert_eq!(code("::alter person drop age"), "tx::drop_indexed_col");
+    db.run_default("::index drop person:by_age").unwrap();
+    db.run_default("::alter person drop age").unwrap();
+    let res = db.run_default("?[id, name] := *person{id, name}").unwrap();
+    assert_eq!(res.rows.len(), 3);
 }
 
 #[test]
-fn test_vec_index_insertion() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    db.run_default(
-        r"
-        ?[k, v, m] <- [['a', [1,2], true],
-                       ['b', [2,3], false]]
-
-        :create a {k: String => v: <F32; 2>, m: Bool}
-    ",
-    )
-    .unwrap();
+fn truncate_relation() {
+    let db = DbInstance::default();
     db.run_default(
-        r"
-        ::hnsw create a:vec {
-            dim: 2,
-            m: 50,
-            dtype: F32,
-            fields: [v],
-            distance: L2,
-            ef_construction: 20,
-            filter: m,
-            #extend_candidates: true,
-            #keep_pruned_connections: true,
-        }",
+        "?[id, name, age] <- [[1, 'Alice', 30], [2, 'Bob', 40]] :create person {id => name, age}",
     )
     .unwrap();
+    db.run_default("::index create person:by_age {age}")
+        .unwrap();
+    db.run_default("::fts create person:name_fts {extractor: name, tokenizer: Simple}")
+        .unwrap();
+
+    // a failing script rolls the truncation back
+    assert!(db
+        .run_default("{::truncate person} {?[x] <- [[1]] :assert none}")
+        .is_err());
+    assert_eq!(db.stats("person").unwrap().rows, 2);
+
+    let res = db.run_default("::truncate person").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[2]]));
+    let res = db.run_default("?[id] := *person{id}").unwrap();
+    assert!(res.rows.is_empty());
+    let res = db.run_default("?[id] := *person:by_age{id}").unwrap();
+    assert!(res.rows.is_empty());
     let res = db
-        .run_default("?[k] := *a:vec{layer: 0, fr_k, to_k}, k = fr_k or k = to_k")
+        .run_default("?[id] := ~person:name_fts{id | query: 'Alice', k: 10}")
         .unwrap();
-    assert_eq!(res.rows.len(), 1);
-    println!("update!");
-    db.run_default(r#"?[k, m] <- [["a", false]] :update a {}"#)
+    assert!(res.rows.is_empty());
+    assert_eq!(db.stats("person").unwrap().rows, 0);
+
+    // the definition and indices remain usable
+    db.run_default("?[id, name, age] <- [[3, 'Alice', 50]] :put person {id => name, age}")
         .unwrap();
     let res = db
-        .run_default("?[k] := *a:vec{layer: 0, fr_k, to_k}, k = fr_k or k = to_k")
+        .run_default("?[id, name] := *person:by_age{age: 50, id}, *person{id, name}")
         .unwrap();
-    assert_eq!(res.rows.len(), 0);
-    println!("{}", res.into_json());
+    assert_eq!(res.into_json()["rows"], json!([[3, "Alice"]]));
+    let res = db
+        .run_default("?[id] := ~person:name_fts{id | query: 'Alice', k: 10}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[3]]));
+    assert_eq!(db.stats("person").unwrap().rows, 1);
+    assert!(db.run_default("::truncate nobody").is_err());
 }
 
 #[test]
-fn test_vec_index() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    db.run_default(
-        r"
-        ?[k, v] <- [['a', [1,2]],
-                    ['b', [2,3]],
-                    ['bb', [2,3]],
-                    ['c', [3,4]],
-                    ['x', [0,0.1]],
-                    ['a', [112,0]],
-                    ['b', [1,1]]]
-
-        :create a {k: String => v: <F32; 2>}
-    ",
-    )
+fn import_relations_across_relations() {
+    let db = DbInstance::default();
+    db.run_default(":create person {id: Int => name: String}")
+        .unwrap();
+    db.run_default(":create friend {src: Int, dst: Int => since: Int}")
+        .unwrap();
+    db.run_default("::index create friend:rev {dst, src}")
+        .unwrap();
+    let batch = |person_rows: Vec<Vec<DataValue>>, friend_rows: Vec<Vec<DataValue>>| {
+        BTreeMap::from([
+            (
+                "person".to_string(),
+                NamedRows::new(vec!["name".to_string(), "id".to_string()], person_rows),
+            ),
+            (
+                "friend".to_string(),
+                NamedRows::new(
+                    vec!["since".to_string(), "dst".to_string(), "src".to_string()],
+                    friend_rows,
+                ),
+            ),
+        ])
+    };
+    db.import_relations(batch(
+        vec![
+            vec![DataValue::from("Alice"), DataValue::from(1)],
+            vec![DataValue::from("Bob"), DataValue::from(2)],
+        ],
+        vec![vec![
+            DataValue::from(2020),
+            DataValue::from(2),
+            DataValue::from(1),
+        ]],
+    ))
     .unwrap();
-    db.run_default(
-        r"
-        ::hnsw create a:vec {
-            dim: 2,
-            m: 50,
-            dtype: F32,
-            fields: [v],
-            distance: L2,
-            ef_construction: 20,
-            filter: k != 'k1',
-            #extend_candidates: true,
-            #keep_pruned_connections: true,
-        }",
+    let res = db
+        .run_default(
+            "?[a, b, since] := *friend{src, dst, since}, \
+             *person{id: src, name: a}, *person{id: dst, name: b}",
+        )
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([["Alice", "Bob", 2020]]));
+    let res = db
+        .run_default("?[src] := *friend:rev{dst: 2, src}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1]]));
+
+    // a row not fitting its relation rejects the whole batch
+    let err = db
+        .import_relations(batch(
+            vec![vec![DataValue::from("Carol"), DataValue::from(3)]],
+            vec![vec![
+                DataValue::from("yesterday"),
+                DataValue::from(3),
+                DataValue::from(1),
+            ]],
+        ))
+        .unwrap_err();
+    assert!(
+        format!("{err:?}").contains("column since of friend"),
+        "{err:?}"
+    );
+    let err = db
+        .import_relations(BTreeMap::from([(
+            "person".to_string(),
+            NamedRows::new(
+                vec!["id".to_string(), "name".to_string(), "src".to_string()],
+                vec![vec![
+                    DataValue::from(4),
+                    DataValue::from("Dan"),
+                    DataValue::from(1),
+                ]],
+            ),
+        )]))
+        .unwrap_err();
+    assert_eq!(err.to_string(), "relation person has no column named src");
+    let res = db.run_default("?[id] := *person{id}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1], [2]]));
+    assert_eq!(db.stats("person").unwrap().rows, 2);
+}
+
+#[test]
+fn undefined_columns_rejected() {
+    let db = DbInstance::default();
+    db.run_default("?[id, name] <- [[1, 'Alice']] :create person {id => name}")
+        .unwrap();
+    let err = |script: &str| {
+        let err = db.run_default(script).unwrap_err();
+        let code = err.code().unwrap().to_string();
+        (code, err.to_string())
+    };
+    let expected = |col: &str| {
+        (
+            "eval::named_field_not_found".to_string(),
+            format!("stored relation 'person' does not have field '{col}'"),
+        )
+    };
+    assert_eq!(
+        err("?[id, name, age] <- [[2, 'Bob', 40]] :put person {id => name, age}"),
+        expected("age")
+    );
+    assert_eq!(
+        err("?[id, key, name] <- [[2, 0, 'Bob']] :put person {id, key => name}"),
+        expected("key")
+    );
+    assert_eq!(
+        err("?[id, age] <- [[1, 40]] :update person {id => age}"),
+        expected("age")
+    );
+    assert_eq!(err("?[id] := *person{id, age: 40}"), expected("age"));
+    let res = db.run_default("?[id, name] := *person{id, name}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1, "Alice"]]));
+}
+
+#[test]
+fn storage_version_checked_on_open() {
+    let storage = MemStorage::default();
+    let db = Db::new(storage.clone()).unwrap();
+    db.initialize().unwrap();
+    db.run_script(
+        ":create a {x}",
+        Default::default(),
+        ScriptMutability::Mutable,
     )
     .unwrap();
-    db.run_default(
-        r"
-        ?[k, v] <- [
-                    ['a2', [1,25]],
-                    ['b2', [2,34]],
-                    ['bb2', [2,33]],
-                    ['c2', [2,32]],
-                    ['a2', [2,31]],
-                    ['b2', [1,10]]
-                    ]
-        :put a {k => v}
-        ",
+    assert_eq!(db.storage_version().unwrap(), CURRENT_STORAGE_VERSION[0]);
+    // reopening the same storage is fine
+    let reopened = Db::new(storage.clone()).unwrap();
+    reopened.initialize().unwrap();
+    assert_eq!(
+        reopened.storage_version().unwrap(),
+        CURRENT_STORAGE_VERSION[0]
+    );
+
+    let set_version = |version: &[u8]| {
+        let mut tx = storage.transact(true).unwrap();
+        tx.put(&storage_version_key(), version).unwrap();
+        tx.commit().unwrap();
+    };
+    set_version(&[CURRENT_STORAGE_VERSION[0] + 1]);
+    let err = Db::new(storage.clone()).unwrap().initialize().unwrap_err();
+    assert_eq!(
+        err.code().unwrap().to_string(),
+        "db::storage_version_mismatch"
+    );
+    assert!(err.help().unwrap().to_string().contains("newer version"));
+
+    set_version(&[CURRENT_STORAGE_VERSION[0], 1]);
+    let err = Db::new(storage.clone()).unwrap().initialize().unwrap_err();
+    assert!(err.help().unwrap().to_string().contains("newer version"));
+
+    set_version(&CURRENT_STORAGE_VERSION);
+    Db::new(storage.clone()).unwrap().initialize().unwrap();
+
+    // version 0 encoded negative zero apart from zero, and such keys are rewritten on open
+    db.run_script(
+        "?[x] <- [[0.]] :put a {x}",
+        Default::default(),
+        ScriptMutability::Mutable,
     )
     .unwrap();
-
-    println!("all links");
-    for (_, nrows) in db.export_relations(["a:vec"].iter()).unwrap() {
-        let nrows = nrows.rows;
-        for row in nrows {
-            println!("{} {} -> {} {}", row[0], row[1], row[4], row[7]);
-        }
+    let id = db.transact().unwrap().get_relation("a", false).unwrap().id;
+    let key = vec![DataValue::from(0.)].encode_as_key(id);
+    let mut old_key = key.clone();
+    old_key[9..17].copy_from_slice(&0x7fff_ffff_ffff_ffffu64.to_be_bytes());
+    {
+        let mut tx = storage.transact(true).unwrap();
+        let val = tx.get(&key, false).unwrap().unwrap();
+        tx.del(&key).unwrap();
+        tx.put(&old_key, &val).unwrap();
+        tx.commit().unwrap();
     }
-
-    let res = db
-        .run_default(
-            r"
-        #::explain {
-        ?[dist, k, v] := ~a:vec{k, v | query: q, k: 2, ef: 20, bind_distance: dist}, q = vec([200, 34])
-        #}
-        ",
+    set_version(&[0x00]);
+    let upgraded = Db::new(storage.clone()).unwrap();
+    upgraded.initialize().unwrap();
+    assert_eq!(
+        upgraded.storage_version().unwrap(),
+        CURRENT_STORAGE_VERSION[0]
+    );
+    let res = upgraded
+        .run_script(
+            "?[x] := *a{x}, x == 0",
+            Default::default(),
+            ScriptMutability::Immutable,
         )
         .unwrap();
-    println!("results");
-    for row in res.into_json()["rows"].as_array().unwrap() {
-        println!("{} {} {}", row[0], row[1], row[2]);
-    }
+    assert_eq!(res.rows, vec![vec![DataValue::from(0.)]]);
+    assert!(!upgraded
+        .transact()
+        .unwrap()
+        .store_tx
+        .exists(&old_key, false)
+        .unwrap());
 }
 
 #[test]
-fn test_fts_indexing() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    db.run_default(r":create a {k: String => v: String}")
-        .unwrap();
+fn test_json_objects() {
+    let db = DbInstance::default();
+    db.run_default("?[a] := a = {'a': 1}").unwrap();
     db.run_default(
-        r"?[k, v] <- [['a', 'hello world!'], ['b', 'the world is round']] :put a {k => v}",
+        r"?[a] := a = {
+            'a': 1
+        }",
     )
     .unwrap();
+}
+
+#[test]
+fn test_nested_object_projection() {
+    let db = DbInstance::default();
     db.run_default(
-        r"::fts create a:fts {
-            extractor: v,
-            tokenizer: Simple,
-            filters: [Lowercase, Stemmer('English'), Stopwords('en')]
-        }",
+        r"
+        ?[id, name] <- [[1, 'Alice'], [2, 'Bob']]
+        :create person {id => name}
+    ",
     )
     .unwrap();
     db.run_default(
-        r"?[k, v] <- [
-            ['b', 'the world is square!'],
-            ['c', 'see you at the end of the world!'],
-            ['d', 'the world is the world and makes the world go around']
-        ] :put a {k => v}",
+        r"
+        ?[id, work_id, status] <- [[1, 10, 'active'], [2, 20, 'retired']]
+        :create work_info {id => work_id, status}
+    ",
     )
     .unwrap();
     let res = db
         .run_default(
             r"
-        ?[word, src_k, offset_from, offset_to, position, total_length] :=
-            *a:fts{word, src_k, offset_from, offset_to, position, total_length}
-        ",
+        ?[person] := *person{id, name}, *work_info{id, work_id, status},
+                     person = {id, name, 'info': {work_id, status}}
+        :order person
+    ",
         )
-        .unwrap();
-    for row in res.into_json()["rows"].as_array().unwrap() {
-        println!("{}", row);
-    }
-    println!("query");
-    let res = db
-        .run_default(r"?[k, v, s] := ~a:fts{k, v | query: 'world', k: 2, bind_score: s}")
-        .unwrap();
-    for row in res.into_json()["rows"].as_array().unwrap() {
-        println!("{}", row);
-    }
-}
-
-#[test]
-fn test_lsh_indexing2() {
-    for i in 1..10 {
-        let f = i as f64 / 10.;
-        let db = DbInstance::new("mem", "", "").unwrap();
-        db.run_default(r":create a {k: String => v: String}")
-            .unwrap();
-        db.run_script(
-            r"::lsh create a:lsh {extractor: v, tokenizer: NGram, n_gram: 3, target_threshold: $t }",
-            BTreeMap::from([("t".into(), f.into())]),
-            ScriptMutability::Mutable
+        .unwrap()
+        .into_json();
+    assert_eq!(
+        res["rows"],
+        json!([
+            [{"id": 1, "name": "Alice", "info": {"work_id": 10, "status": "active"}}],
+            [{"id": 2, "name": "Bob", "info": {"work_id": 20, "status": "retired"}}]
+        ])
+    );
+}
+
+#[test]
+fn test_custom_rules() {
+    let db = DbInstance::default();
+    struct Custom;
+
+    impl FixedRule for Custom {
+        fn arity(
+            &self,
+            _options: &BTreeMap<SmartString<LazyCompact>, Expr>,
+            _rule_head: &[Symbol],
+            _span: SourceSpan,
+        ) -> miette::Result<usize> {
+            Ok(1)
+        }
+
+        fn run(
+            &self,
+            payload: FixedRulePayload<'_, '_>,
+            out: &'_ mut RegularTempStore,
+            _poison: Poison,
+        ) -> miette::Result<()> {
+            let rel = payload.get_input(0)?;
+            let mult = payload.integer_option("mult", Some(2))?;
+            for maybe_row in rel.iter()? {
+                let row = maybe_row?;
+                let mut sum = 0;
+                for col in row {
+                    let d = col.get_int().unwrap_or(0);
+                    sum += d;
+                }
+                sum *= mult;
+                out.put(vec![DataValue::from(sum)])
+            }
+            Ok(())
+        }
+    }
+
+    db.register_fixed_rule("SumCols".to_string(), Custom)
+        .unwrap();
+    let res = db
+        .run_default(
+            r#"
+        rel[] <- [[1,2,3,4],[5,6,7,8]]
+        ?[x] <~ SumCols(rel[], mult: 100)
+    "#,
         )
-            .unwrap();
-        db.run_default("?[k, v] <- [['a', 'ewiygfspeoighjsfcfxzdfncalsdf']] :put a {k => v}")
-            .unwrap();
-        let res = db
-            .run_default("?[k] := ~a:lsh{k | query: 'ewiygfspeoighjsfcfxzdfncalsdf', k: 1}")
-            .unwrap();
-        assert!(res.rows.len() > 0);
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1000], [2600]]));
+}
+
+#[test]
+fn test_index_short() {
+    let db = DbInstance::default();
+    db.run_default(":create friends {fr: Int, to: Int => data: Any}")
+        .unwrap();
+
+    db.run_default(r"?[fr, to, data] <- [[1,2,3],[4,5,6]] :put friends {fr, to => data}")
+        .unwrap();
+
+    db.run_default("::index create friends:rev {to}").unwrap();
+
+    db.run_default(r"?[fr, to, data] <- [[1,2,5],[6,5,7]] :put friends {fr, to => data}")
+        .unwrap();
+    db.run_default(r"?[fr, to] <- [[4,5]] :rm friends {fr, to}")
+        .unwrap();
+
+    let rels_data = db
+        .export_relations(["friends", "friends:rev"].into_iter())
+        .unwrap();
+    assert_eq!(
+        rels_data["friends"].clone().into_json()["rows"],
+        json!([[1, 2, 5], [6, 5, 7]])
+    );
+    assert_eq!(
+        rels_data["friends:rev"].clone().into_json()["rows"],
+        json!([[2, 1], [5, 6]])
+    );
+
+    let rels = db.run_default("::relations").unwrap();
+    assert_eq!(rels.rows[1][0], DataValue::from("friends:rev"));
+    assert_eq!(rels.rows[1][1], DataValue::from(2));
+    assert_eq!(rels.rows[1][2], DataValue::from("index"));
+
+    let cols = db.run_default("::columns friends:rev").unwrap();
+    assert_eq!(cols.rows.len(), 2);
+
+    let expl = db
+        .run_default("::explain { ?[fr, data] := *friends{to: 2, fr, data} }")
+        .unwrap()
+        .into_json();
+
+    for row in expl["rows"].as_array().unwrap() {
+        println!("{}", row);
     }
+
+    let joins = expl["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row.as_array().unwrap()[5].clone())
+        .collect_vec();
+    assert!(joins.contains(&json!(":friends:rev")));
+
+    let res = db
+        .run_default("?[fr, data] := *friends{to: 2, fr, data}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1, 5]]));
+}
+
+#[test]
+fn test_multi_tx() {
+    let db = DbInstance::default();
+    let tx = db.multi_transaction(true);
+    tx.run_script(":create a {a}", Default::default()).unwrap();
+    tx.run_script("?[a] <- [[1]] :put a {a}", Default::default())
+        .unwrap();
+    assert!(tx.run_script(":create a {a}", Default::default()).is_err());
+    tx.run_script("?[a] <- [[2]] :put a {a}", Default::default())
+        .unwrap();
+    tx.run_script("?[a] <- [[3]] :put a {a}", Default::default())
+        .unwrap();
+    tx.commit().unwrap();
+    assert_eq!(
+        db.run_default("?[a] := *a[a]").unwrap().into_json()["rows"],
+        json!([[1], [2], [3]])
+    );
+
+    let db = DbInstance::default();
+    let tx = db.multi_transaction(true);
+    tx.run_script(":create a {a}", Default::default()).unwrap();
+    tx.run_script("?[a] <- [[1]] :put a {a}", Default::default())
+        .unwrap();
+    assert!(tx.run_script(":create a {a}", Default::default()).is_err());
+    tx.run_script("?[a] <- [[2]] :put a {a}", Default::default())
+        .unwrap();
+    tx.run_script("?[a] <- [[3]] :put a {a}", Default::default())
+        .unwrap();
+    tx.abort().unwrap();
+    assert!(db.run_default("?[a] := *a[a]").is_err());
+}
+
+#[test]
+fn test_vec_types() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(":create a {k: String => v: <F32; 8>}")
+        .unwrap();
+    db.run_default("?[k, v] <- [['k', [1,2,3,4,5,6,7,8]]] :put a {k => v}")
+        .unwrap();
+    let res = db.run_default("?[k, v] := *a{k, v}").unwrap();
+    assert_eq!(
+        json!([1., 2., 3., 4., 5., 6., 7., 8.]),
+        res.into_json()["rows"][0][1]
+    );
+    let res = db
+        .run_default("?[v] <- [[vec([1,2,3,4,5,6,7,8])]]")
+        .unwrap();
+    assert_eq!(
+        json!([1., 2., 3., 4., 5., 6., 7., 8.]),
+        res.into_json()["rows"][0][0]
+    );
+    let res = db.run_default("?[v] <- [[rand_vec(5)]]").unwrap();
+    assert_eq!(5, res.into_json()["rows"][0][0].as_array().unwrap().len());
+    let res = db
+        .run_default(r#"
+            val[v] <- [[vec([1,2,3,4,5,6,7,8])]]
+            ?[x,y,z] := val[v], x=l2_dist(v, v), y=cos_dist(v, v), nv = l2_normalize(v), z=ip_dist(nv, nv)
+        "#)
+        .unwrap();
+    println!("{}", res.into_json());
+}
+
+#[test]
+fn test_vec_index_insertion() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(
+        r"
+        ?[k, v, m] <- [['a', [1,2], true],
+                       ['b', [2,3], false]]
+
+        :create a {k: String => v: <F32; 2>, m: Bool}
+    ",
+    )
+    .unwrap();
+    db.run_default(
+        r"
+        ::hnsw create a:vec {
+            dim: 2,
+            m: 50,
+            dtype: F32,
+            fields: [v],
+            distance: L2,
+            ef_construction: 20,
+            filter: m,
+            #extend_candidates: true,
+            #keep_pruned_connections: true,
+        }",
+    )
+    .unwrap();
+    let res = db
+        .run_default("?[k] := *a:vec{layer: 0, fr_k, to_k}, k = fr_k or k = to_k")
+        .unwrap();
+    assert_eq!(res.rows.len(), 1);
+    println!("update!");
+    db.run_default(r#"?[k, m] <- [["a", false]] :update a {}"#)
+        .unwrap();
+    let res = db
+        .run_default("?[k] := *a:vec{layer: 0, fr_k, to_k}, k = fr_k or k = to_k")
+        .unwrap();
+    assert_eq!(res.rows.len(), 0);
+    println!("{}", res.into_json());
+}
+
+#[test]
+fn test_vec_index() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(
+        r"
+        ?[k, v] <- [['a', [1,2]],
+                    ['b', [2,3]],
+                    ['bb', [2,3]],
+                    ['c', [3,4]],
+                    ['x', [0,0.1]],
+                    ['a', [112,0]],
+                    ['b', [1,1]]]
+
+        :create a {k: String => v: <F32; 2>}
+    ",
+    )
+    .unwrap();
+    db.run_default(
+        r"
+        ::hnsw create a:vec {
+            dim: 2,
+            m: 50,
+            dtype: F32,
+            fields: [v],
+            distance: L2,
+            ef_construction: 20,
+            filter: k != 'k1',
+            #extend_candidates: true,
+            #keep_pruned_connections: true,
+        }",
+    )
+    .unwrap();
+    db.run_default(
+        r"
+        ?[k, v] <- [
+                    ['a2', [1,25]],
+                    ['b2', [2,34]],
+                    ['bb2', [2,33]],
+                    ['c2', [2,32]],
+                    ['a2', [2,31]],
+                    ['b2', [1,10]]
+                    ]
+        :put a {k => v}
+        ",
+    )
+    .unwrap();
+
+    println!("all links");
+    for (_, nrows) in db.export_relations(["a:vec"].iter()).unwrap() {
+        let nrows = nrows.rows;
+        for row in nrows {
+            println!("{} {} -> {} {}", row[0], row[1], row[4], row[7]);
+        }
+    }
+
+    let res = db
+        .run_default(
+            r"
+        #::explain {
+        ?[dist, k, v] := ~a:vec{k, v | query: q, k: 2, ef: 20, bind_distance: dist}, q = vec([200, 34])
+        #}
+        ",
+        )
+        .unwrap();
+    println!("results");
+    for row in res.into_json()["rows"].as_array().unwrap() {
+        println!("{} {} {}", row[0], row[1], row[2]);
+    }
+}
+
+#[test]
+fn test_fts_indexing() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(r":create a {k: String => v: String}")
+        .unwrap();
+    db.run_default(
+        r"?[k, v] <- [['a', 'hello world!'], ['b', 'the world is round']] :put a {k => v}",
+    )
+    .unwrap();
+    db.run_default(
+        r"::fts create a:fts {
+            extractor: v,
+            tokenizer: Simple,
+            filters: [Lowercase, Stemmer('English'), Stopwords('en')]
+        }",
+    )
+    .unwrap();
+    db.run_default(
+        r"?[k, v] <- [
+            ['b', 'the world is square!'],
+            ['c', 'see you at the end of the world!'],
+            ['d', 'the world is the world and makes the world go around']
+        ] :put a {k => v}",
+    )
+    .unwrap();
+    let res = db
+        .run_default(
+            r"
+        ?[word, src_k, offset_from, offset_to, position, total_length] :=
+            *a:fts{word, src_k, offset_from, offset_to, position, total_length}
+        ",
+        )
+        .unwrap();
+    for row in res.into_json()["rows"].as_array().unwrap() {
+        println!("{}", row);
+    }
+    println!("query");
+    let res = db
+        .run_default(r"?[k, v, s] := ~a:fts{k, v | query: 'world', k: 2, bind_score: s}")
+        .unwrap();
+    for row in res.into_json()["rows"].as_array().unwrap() {
+        println!("{}", row);
+    }
+}
+
+#[test]
+fn test_lsh_indexing2() {
+    for i in 1..10 {
+        let f = i as f64 / 10.;
+        let db = DbInstance::new("mem", "", "").unwrap();
+        db.run_default(r":create a {k: String => v: String}")
+            .unwrap();
+        db.run_script(
+            r"::lsh create a:lsh {extractor: v, tokenizer: NGram, n_gram: 3, target_threshold: $t }",
+            BTreeMap::from([("t".into(), f.into())]),
+            ScriptMutability::Mutable
+        )
+            .unwrap();
+        db.run_default("?[k, v] <- [['a', 'ewiygfspeoighjsfcfxzdfncalsdf']] :put a {k => v}")
+            .unwrap();
+        let res = db
+            .run_default("?[k] := ~a:lsh{k | query: 'ewiygfspeoighjsfcfxzdfncalsdf', k: 1}")
+            .unwrap();
+        assert!(res.rows.len() > 0);
+    }
+}
+
+#[test]
+fn test_lsh_indexing3() {
+    for i in 1..10 {
+        let f = i as f64 / 10.;
+        let db = DbInstance::new("mem", "", "").unwrap();
+        db.run_default(r":create text {id: String,  => text: String, url: String? default null, dt: Float default now(), dup_for: String? default null }")
+            .unwrap();
+        db.run_script(
+            r"::lsh create text:lsh {
+                    extractor: text,
+                    # extract_filter: is_null(dup_for),
+                    tokenizer: NGram,
+                    n_perm: 200,
+                    target_threshold: $t,
+                    n_gram: 7,
+                }",
+            BTreeMap::from([("t".into(), f.into())]),
+            ScriptMutability::Mutable,
+        )
+        .unwrap();
+        db.run_default(
+            "?[id, text] <- [['a', 'This function first generates 32 random bytes using the os.urandom function. It then base64 encodes these bytes using base64.urlsafe_b64encode, removes the padding, and decodes the result to a string.']] :put text {id, text}",
+        )
+        .unwrap();
+        let res = db
+            .run_default(
+                r#"?[id, dup_for] :=
+    ~text:lsh{id: id, dup_for: dup_for, | query: "This function first generates 32 random bytes using the os.urandom function. It then base64 encodes these bytes using base64.urlsafe_b64encode, removes the padding, and decodes the result to a string.", }"#,
+            )
+            .unwrap();
+        assert!(res.rows.len() > 0);
+        println!("{}", res.into_json());
+    }
+}
+
+#[test]
+fn filtering() {
+    let db = DbInstance::default();
+    let res = db
+        .run_default(
+            r"
+        {
+            ?[x, y] <- [[1, 2]]
+            :create _rel {x => y}
+            :returning
+        }
+        {
+            ?[x, y] := x = 1, *_rel{x, y: 3}, y = 2
+        }
+    ",
+        )
+        .unwrap();
+    assert_eq!(0, res.rows.len());
+
+    let res = db.run_default(r"
+        {
+            ?[x, u, y] <- [[1, 0, 2]]
+            :create _rel {x, u => y}
+            :returning
+        }
+        {
+            ?[x, y] := x = 1, *_rel{x, y: 3}, y = 2
+        }
+    ")
+        .unwrap();
+    assert_eq!(0, res.rows.len());
+}
+
+#[test]
+fn test_lsh_indexing4() {
+    for i in 1..10 {
+        let f = i as f64 / 10.;
+        let db = DbInstance::new("mem", "", "").unwrap();
+        db.run_default(r":create a {k: String => v: String}")
+            .unwrap();
+        db.run_script(
+            r"::lsh create a:lsh {extractor: v, tokenizer: NGram, n_gram: 3, target_threshold: $t }",
+            BTreeMap::from([("t".into(), f.into())]),
+            ScriptMutability::Mutable
+        )
+            .unwrap();
+        db.run_default("?[k, v] <- [['a', 'ewiygfspeoighjsfcfxzdfncalsdf']] :put a {k => v}")
+            .unwrap();
+        db.run_default("?[k] <- [['a']] :rm a {k}").unwrap();
+        let res = db
+            .run_default("?[k] := ~a:lsh{k | query: 'ewiygfspeoighjsfcfxzdfncalsdf', k: 1}")
+            .unwrap();
+        assert!(res.rows.len() == 0);
+    }
+}
+
+#[test]
+fn test_lsh_indexing() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(r":create a {k: String => v: String}")
+        .unwrap();
+    db.run_default(
+        r"?[k, v] <- [['a', 'hello world!'], ['b', 'the world is round']] :put a {k => v}",
+    )
+    .unwrap();
+    db.run_default(
+        r"::lsh create a:lsh {extractor: v, tokenizer: Simple, n_gram: 3, target_threshold: 0.3 }",
+    )
+    .unwrap();
+    db.run_default(
+        r"?[k, v] <- [
+            ['b', 'the world is square!'],
+            ['c', 'see you at the end of the world!'],
+            ['d', 'the world is the world and makes the world go around'],
+            ['e', 'the world is the world and makes the world not go around']
+        ] :put a {k => v}",
+    )
+    .unwrap();
+    let res = db.run_default("::columns a:lsh").unwrap();
+    for row in res.into_json()["rows"].as_array().unwrap() {
+        println!("{}", row);
+    }
+    let _res = db
+        .run_default(
+            r"
+        ?[src_k, hash] :=
+            *a:lsh{src_k, hash}
+        ",
+        )
+        .unwrap();
+    // for row in _res.into_json()["rows"].as_array().unwrap() {
+    //     println!("{}", row);
+    // }
+    let _res = db
+        .run_default(
+            r"
+        ?[k, minhash] :=
+            *a:lsh:inv{k, minhash}
+        ",
+        )
+        .unwrap();
+    // for row in res.into_json()["rows"].as_array().unwrap() {
+    //     println!("{}", row);
+    // }
+    let res = db
+        .run_default(
+            r"
+            ?[k, v] := ~a:lsh{k, v |
+                query: 'see him at the end of the world',
+            }
+            ",
+        )
+        .unwrap();
+    for row in res.into_json()["rows"].as_array().unwrap() {
+        println!("{}", row);
+    }
+    let res = db.run_default("::indices a").unwrap();
+    for row in res.into_json()["rows"].as_array().unwrap() {
+        println!("{}", row);
+    }
+    db.run_default(r"::lsh drop a:lsh").unwrap();
+}
+
+#[test]
+fn test_insertions() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(r":create a {k => v: <F32; 1536> default rand_vec(1536)}")
+        .unwrap();
+    db.run_default(r"?[k] <- [[1]] :put a {k}").unwrap();
+    db.run_default(r"?[k, v] := *a{k, v}").unwrap();
+    db.run_default(
+        r"::hnsw create a:i {
+            fields: [v], dim: 1536, ef: 16, filter: k % 3 == 0,
+            m: 32
+        }",
+    )
+    .unwrap();
+    db.run_default(r"?[count(fr_k)] := *a:i{fr_k}").unwrap();
+    db.run_default(r"?[k] <- [[1]] :put a {k}").unwrap();
+    db.run_default(r"?[k] := k in int_range(300) :put a {k}")
+        .unwrap();
+    let res = db
+        .run_default(
+            r"?[dist, k] := ~a:i{k | query: v, bind_distance: dist, k:10, ef: 50, filter: k % 2 == 0, radius: 245}, *a{k: 96, v}",
+        )
+        .unwrap();
+    println!("results");
+    for row in res.into_json()["rows"].as_array().unwrap() {
+        println!("{} {}", row[0], row[1]);
+    }
+}
+
+#[test]
+fn tokenizers() {
+    let tokenizers = TokenizerCache::default();
+    let tokenizer = tokenizers
+        .get(
+            "simple",
+            &TokenizerConfig {
+                name: "Simple".into(),
+                args: vec![],
+            },
+            &[],
+        )
+        .unwrap();
+
+    // let tokenizer = TextAnalyzer::from(SimpleTokenizer)
+    //     .filter(RemoveLongFilter::limit(40))
+    //     .filter(LowerCaser)
+    //     .filter(Stemmer::new(Language::English));
+    let mut token_stream = tokenizer.token_stream("It is closer to Apache Lucene than to Elasticsearch or Apache Solr in the sense it is not an off-the-shelf search engine server, but rather a crate that can be used to build such a search engine.");
+    while let Some(token) = token_stream.next() {
+        println!("Token {:?}", token.text);
+    }
+
+    println!("XXXXXXXXXXXXX");
+
+    let tokenizer = tokenizers
+        .get(
+            "cangjie",
+            &TokenizerConfig {
+                name: "Cangjie".into(),
+                args: vec![],
+            },
+            &[],
+        )
+        .unwrap();
+
+    let mut token_stream = tokenizer.token_stream("这个产品Finchat.io是一个相对比较有特色的文档问答类网站，它集成了750多家公司的经融数据。感觉是把财报等数据借助Embedding都向量化了，然后接入ChatGPT进行对话。");
+    while let Some(token) = token_stream.next() {
+        println!("Token {:?}", token.text);
+    }
+}
+
+#[test]
+fn multi_index_vec() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(
+        r#"
+        :create product {
+            id
+            =>
+            name,
+            description,
+            price,
+            name_vec: <F32; 1>,
+            description_vec: <F32; 1>
+        }
+        "#,
+    )
+    .unwrap();
+    db.run_default(
+        r#"
+        ::hnsw create product:semantic{
+            fields: [name_vec, description_vec],
+            dim: 1,
+            ef: 16,
+            m: 32,
+        }
+        "#,
+    )
+    .unwrap();
+    db.run_default(
+        r#"
+        ?[id, name, description, price, name_vec, description_vec] <- [[1, "name", "description", 100, [1], [1]]]
+
+        :put product {id => name, description, price, name_vec, description_vec}
+        "#,
+    ).unwrap();
+    let res = db.run_default("::indices product").unwrap();
+    for row in res.into_json()["rows"].as_array().unwrap() {
+        println!("{}", row);
+    }
+}
+
+#[test]
+fn ensure_not() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(
+        r"
+    %ignore_error { :create id_alloc{id: Int => next_id: Int, last_id: Int}}
+%ignore_error {
+    ?[id, next_id, last_id] <- [[0, 1, 1000]];
+    :ensure_not id_alloc{id => next_id, last_id}
+}
+    ",
+    )
+    .unwrap();
+}
+
+#[test]
+fn insertion() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(r":create a {x => y}").unwrap();
+    assert!(db
+        .run_default(r"?[x, y] <- [[1, 2]] :insert a {x => y}",)
+        .is_ok());
+    assert!(db
+        .run_default(r"?[x, y] <- [[1, 3]] :insert a {x => y}",)
+        .is_err());
+}
+
+#[test]
+fn deletion() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(r":create a {x => y}").unwrap();
+    assert!(db.run_default(r"?[x] <- [[1]] :delete a {x}").is_err());
+    assert!(db
+        .run_default(r"?[x, y] <- [[1, 2]] :insert a {x => y}",)
+        .is_ok());
+    db.run_default(r"?[x] <- [[1]] :delete a {x}").unwrap();
+}
+
+#[test]
+fn returning() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(":create a {x => y}").unwrap();
+    let res = db
+        .run_default(r"?[x, y] <- [[1, 2]] :insert a {x => y} ")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([["OK"]]));
+    // for row in res.into_json()["rows"].as_array().unwrap() {
+    //     println!("{}", row);
+    // }
+
+    let res = db
+        .run_default(r"?[x, y] <- [[1, 3], [2, 4]] :returning :put a {x => y} ")
+        .unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([["inserted", 1, 3], ["inserted", 2, 4], ["replaced", 1, 2]])
+    );
+    // println!("{:?}", res.headers);
+    // for row in res.into_json()["rows"].as_array().unwrap() {
+    //     println!("{}", row);
+    // }
+
+    let res = db
+        .run_default(r"?[x] <- [[1], [4]] :returning :rm a {x} ")
+        .unwrap();
+    // println!("{:?}", res.headers);
+    // for row in res.into_json()["rows"].as_array().unwrap() {
+    //     println!("{}", row);
+    // }
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([
+            ["requested", 1, null],
+            ["requested", 4, null],
+            ["deleted", 1, 3]
+        ])
+    );
+    db.run_default(r":create todo{id:Uuid default rand_uuid_v1() => label: String, done: Bool}")
+        .unwrap();
+    let res = db
+        .run_default(r"?[label,done] <- [['milk',false]] :put todo{label,done} :returning")
+        .unwrap();
+    assert_eq!(res.rows[0].len(), 4);
+    for title in res.headers.iter() {
+        print!("{} ", title);
+    }
+    println!();
+    for row in res.into_json()["rows"].as_array().unwrap() {
+        println!("{}", row);
+    }
+}
+
+#[test]
+fn parser_corner_case() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_default(r#"?[x] := x = 1 or x = 2"#).unwrap();
+    db.run_default(r#"?[C] := C = 1  orx[C] := C = 1"#).unwrap();
+    db.run_default(r#"?[C] := C = true, C  inx[C] := C = 1"#)
+        .unwrap();
+    db.run_default(r#"?[k] := k in int_range(300)"#).unwrap();
+    db.run_default(r#"ywcc[a] <- [[1]] noto[A] := ywcc[A] ?[A] := noto[A]"#)
+        .unwrap();
+}
+
+#[test]
+fn as_store_in_imperative_script() {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    let res = db
+        .run_default(
+            r#"
+    { ?[x, y, z] <- [[1, 2, 3], [4, 5, 6]] } as _store
+    { ?[x, y, z] := *_store{x, y, z} }
+    "#,
+        )
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1, 2, 3], [4, 5, 6]]));
+    let res = db
+        .run_default(
+            r#"
+    {
+        ?[y] <- [[1], [2], [3]]
+        :create a {x default rand_uuid_v1() => y}
+        :returning
+    } as _last
+    {
+        ?[x] := *_last{_kind: 'inserted', x}
+    }
+    "#,
+        )
+        .unwrap();
+    assert_eq!(3, res.rows.len());
+    for row in res.into_json()["rows"].as_array().unwrap() {
+        println!("{}", row);
+    }
+    assert!(db
+        .run_default(
+            r#"
+    {
+        ?[x, x] := x = 1
+    } as _last
+    "#
+        )
+        .is_err());
+
+    let res = db
+        .run_default(
+            r#"
+    {
+        x[y] <- [[1], [2], [3]]
+        ?[sum(y)] := x[y]
+    } as _last
+    {
+        ?[sum_y] := *_last{sum_y}
+    }
+    "#,
+        )
+        .unwrap();
+    assert_eq!(1, res.rows.len());
+    for row in res.into_json()["rows"].as_array().unwrap() {
+        println!("{}", row);
+    }
+}
+
+#[test]
+fn update_shall_not_destroy_values() {
+    let db = DbInstance::default();
+    db.run_default(r"?[x, y] <- [[1, 2]] :create z {x => y default 0}")
+        .unwrap();
+    let r = db.run_default(r"?[x, y] := *z {x, y}").unwrap();
+    assert_eq!(r.into_json()["rows"], json!([[1, 2]]));
+    db.run_default(r"?[x] <- [[1]] :update z {x}").unwrap();
+    let r = db.run_default(r"?[x, y] := *z {x, y}").unwrap();
+    assert_eq!(r.into_json()["rows"], json!([[1, 2]]));
+}
+
+#[test]
+fn update_shall_work() {
+    let db = DbInstance::default();
+    db.run_default(r"?[x, y, z] <- [[1, 2, 3]] :create z {x => y, z}")
+        .unwrap();
+    let r = db.run_default(r"?[x, y, z] := *z {x, y, z}").unwrap();
+    assert_eq!(r.into_json()["rows"], json!([[1, 2, 3]]));
+    db.run_default(r"?[x, y] <- [[1, 4]] :update z {x, y}")
+        .unwrap();
+    let r = db.run_default(r"?[x, y, z] := *z {x, y, z}").unwrap();
+    assert_eq!(r.into_json()["rows"], json!([[1, 4, 3]]));
+}
+
+#[test]
+fn sysop_in_imperatives() {
+    let script = r#"
+    {
+            :create cm_src {
+                aid: String =>
+                title: String,
+                author: String?,
+                kind: String,
+                url: String,
+                domain: String?,
+                pub_time: Float?,
+                dt: Float default now(),
+                weight: Float default 1,
+            }
+        }
+        {
+            :create cm_txt {
+                tid: String =>
+                aid: String,
+                tag: String,
+                follows_tid: String?,
+                dup_for: String?,
+                text: String,
+                info_amount: Int,
+            }
+        }
+        {
+            :create cm_seg {
+                sid: String =>
+                tid: String,
+                tag: String,
+                part: Int,
+                text: String,
+                vec: <F32; 1536>,
+            }
+        }
+        {
+            ::hnsw create cm_seg:vec {
+                dim: 1536,
+                m: 50,
+                dtype: F32,
+                fields: vec,
+                distance: Cosine,
+                ef: 100,
+            }
+        }
+        {
+            ::lsh create cm_txt:lsh {
+                extractor: text,
+                extract_filter: is_null(dup_for),
+                tokenizer: NGram,
+                n_perm: 200,
+                target_threshold: 0.5,
+                n_gram: 7,
+            }
+        }
+        {::relations}
+    "#;
+    let db = DbInstance::default();
+    db.run_default(script).unwrap();
+}
+
+#[test]
+fn puts() {
+    let db = DbInstance::default();
+    db.run_default(
+        r"
+            :create cm_txt {
+                tid: String =>
+                aid: String,
+                tag: String,
+                follows_tid: String? default null,
+                for_qs: [String] default [],
+                dup_for: String? default null,
+                text: String,
+                seg_vecs: [<F32; 1536>],
+                seg_pos: [(Int, Int)],
+                format: String default 'text',
+                info_amount: Int,
+            }
+    ",
+    )
+    .unwrap();
+    db.run_default(
+        r"
+        ?[tid, aid, tag, text, info_amount, dup_for, seg_vecs, seg_pos] := dup_for = null,
+                tid = 'x', aid = 'y', tag = 'z', text = 'w', info_amount = 12,
+                follows_tid = null, for_qs = [], format = 'x',
+                seg_vecs = [], seg_pos = [[0, 10]]
+        :put cm_txt {tid, aid, tag, text, info_amount, seg_vecs, seg_pos, dup_for}
+    ",
+    )
+    .unwrap();
+}
+
+#[test]
+fn short_hand() {
+    let db = DbInstance::default();
+    db.run_default(r":create x {x => y, z}").unwrap();
+    db.run_default(r"?[x, y, z] <- [[1, 2, 3]] :put x {}")
+        .unwrap();
+    let r = db.run_default(r"?[x, y, z] := *x {x, y, z}").unwrap();
+    assert_eq!(r.into_json()["rows"], json!([[1, 2, 3]]));
+}
+
+#[test]
+fn param_shorthand() {
+    let db = DbInstance::default();
+    db.run_script(
+        r"
+        ?[] <- [[$x, $y, $z]]
+        :create x {}
+    ",
+        BTreeMap::from([
+            ("x".to_string(), DataValue::from(1)),
+            ("y".to_string(), DataValue::from(2)),
+            ("z".to_string(), DataValue::from(3)),
+        ]),
+        ScriptMutability::Mutable,
+    )
+    .unwrap();
+    let res = db.run_default(r"?[x, y, z] := *x {x, y, z}");
+    assert_eq!(res.unwrap().into_json()["rows"], json!([[1, 2, 3]]));
+}
+
+#[test]
+fn bound_parameters() {
+    let db = DbInstance::default();
+    db.run_default(":create person {id: Int => name: String}")
+        .unwrap();
+    let insert = "?[id, name] <- [[$id, $name]] :put person {id => name}";
+    let params = |id: i64, name: &str| {
+        BTreeMap::from([
+            ("id".to_string(), DataValue::from(id)),
+            ("name".to_string(), DataValue::from(name)),
+        ])
+    };
+    db.run_script(insert, params(1, "Alice"), ScriptMutability::Mutable)
+        .unwrap();
+    // bound values are never parsed as part of the script
+    let sneaky = "'], [2, 'Mallory'] :rm person {id} #";
+    db.run_script(insert, params(3, sneaky), ScriptMutability::Mutable)
+        .unwrap();
+    let res = db.run_default("?[id, name] := *person{id, name}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1, "Alice"], [3, sneaky]]));
+
+    let only_id = BTreeMap::from([("id".to_string(), DataValue::from(4))]);
+    assert!(db
+        .run_script(insert, only_id, ScriptMutability::Mutable)
+        .is_err());
+    assert_eq!(db.stats("person").unwrap().rows, 2);
+}
+
+#[test]
+fn crashy_imperative() {
+    let db = DbInstance::default();
+    db.run_default(
+        r"
+        {:create _test {a}}
+
+        %loop
+            %if { len[count(x)] := *_test[x]; ?[x] := len[z], x = z >= 10 }
+                %then %return _test
+            %end
+            { ?[a] := a = rand_uuid_v1(); :put _test {a} }
+        %end
+        ",
+    )
+    .unwrap();
+}
+
+#[test]
+fn hnsw_index() {
+    let db = DbInstance::default();
+    db.run_default(
+        r#"
+        :create beliefs {
+            belief_id: Uuid,
+            character_id: Uuid,
+            belief: String,
+            last_accessed_at: Validity default [floor(now()), true],
+            =>
+            details: String default "",
+            parent_belief_id: Uuid? default null,
+            valence: Float default 0,
+            aspects: [(String, Float, String, String)] default [],
+            belief_embedding: <F32; 768>,
+            details_embedding: <F32; 768>,
+        }
+        "#,
+    )
+    .unwrap();
+    db.run_default(
+        r#"
+        ::hnsw create beliefs:embedding_space {
+            dim: 768,
+            m: 50,
+            dtype: F32,
+            fields: [belief_embedding, details_embedding],
+            distance: Cosine,
+            ef_construction: 20,
+            extend_candidates: false,
+            keep_pruned_connections: false,
+        }
+    "#,
+    )
+    .unwrap();
+    db.run_default(r#"
+        ?[belief_id, character_id, belief, belief_embedding, details_embedding] <- [[rand_uuid_v1(), rand_uuid_v1(), "test", rand_vec(768), rand_vec(768)]]
+        :put beliefs {}
+    "#).unwrap();
+    let res = db.run_default(r#"
+            ?[belief, valence, dist, character_id, vector] := ~beliefs:embedding_space{ belief, valence, character_id |
+                query: rand_vec(768),
+                k: 100,
+                ef: 20,
+                radius: 1.0,
+                bind_distance: dist,
+                bind_vector: vector
+            }
+
+            :order -valence
+            :order dist
+    "#).unwrap();
+    println!("{}", res.into_json()["rows"][0][4]);
+}
+
+#[test]
+fn fts_drop() {
+    let db = DbInstance::default();
+    db.run_default(
+        r#"
+            :create entity {name}
+        "#,
+    )
+    .unwrap();
+    db.run_default(
+        r#"
+        ::fts create entity:fts_index { extractor: name,
+            tokenizer: Simple, filters: [Lowercase]
+        }
+    "#,
+    )
+    .unwrap();
+    db.run_default(r#"
+        ::fts drop entity:fts_index
+    "#).unwrap();
 }
 
 #[test]
-fn test_lsh_indexing3() {
-    for i in 1..10 {
-        let f = i as f64 / 10.;
-        let db = DbInstance::new("mem", "", "").unwrap();
-        db.run_default(r":create text {id: String,  => text: String, url: String? default null, dt: Float default now(), dup_for: String? default null }")
-            .unwrap();
-        db.run_script(
-            r"::lsh create text:lsh {
-                    extractor: text,
-                    # extract_filter: is_null(dup_for),
-                    tokenizer: NGram,
-                    n_perm: 200,
-                    target_threshold: $t,
-                    n_gram: 7,
-                }",
-            BTreeMap::from([("t".into(), f.into())]),
-            ScriptMutability::Mutable,
-        )
-        .unwrap();
-        db.run_default(
-            "?[id, text] <- [['a', 'This function first generates 32 random bytes using the os.urandom function. It then base64 encodes these bytes using base64.urlsafe_b64encode, removes the padding, and decodes the result to a string.']] :put text {id, text}",
-        )
-        .unwrap();
-        let res = db
-            .run_default(
-                r#"?[id, dup_for] :=
-    ~text:lsh{id: id, dup_for: dup_for, | query: "This function first generates 32 random bytes using the os.urandom function. It then base64 encodes these bytes using base64.urlsafe_b64encode, removes the padding, and decodes the result to a string.", }"#,
-            )
-            .unwrap();
-        assert!(res.rows.len() > 0);
-        println!("{}", res.into_json());
-    }
+#[cfg(feature = "storage-sqlite")]
+fn relation_ids_survive_reopen() {
+    let path = "_test_relation_ids";
+    let _ = std::fs::remove_file(path);
+
+    let ids_of = |db: &DbInstance, names: &[&str]| match db {
+        DbInstance::Sqlite(db) => {
+            let tx = db.transact().unwrap();
+            names
+                .iter()
+                .map(|name| tx.get_relation(name, false).unwrap().id.0)
+                .collect_vec()
+        }
+        _ => unreachable!(),
+    };
+
+    let first = {
+        let db = DbInstance::new("sqlite", path, "").unwrap();
+        db.run_default(":create a {x}").unwrap();
+        db.run_default(":create b {x}").unwrap();
+        db.run_default(":create c {x}").unwrap();
+        db.run_default("::remove c").unwrap();
+        ids_of(&db, &["a", "b"])
+    };
+
+    let db = DbInstance::new("sqlite", path, "").unwrap();
+    db.run_default(":create d {x}").unwrap();
+    db.run_default(":create c {x}").unwrap();
+    let second = ids_of(&db, &["a", "b", "d", "c"]);
+    drop(db);
+    let _ = std::fs::remove_file(path);
+
+    assert_eq!(first, second[..2]);
+    // `c` was dropped in the first session, its id must not be handed out again
+    assert!(second.windows(2).all(|w| w[0] < w[1]));
+    assert!(second[2] > first[1] + 1);
 }
 
 #[test]
-fn filtering() {
+fn edge_keys_are_type_checked() {
     let db = DbInstance::default();
-    let res = db
+    db.run_default(":create friend {src: Int, dst: Int}").unwrap();
+    db.run_default(
+        r"
+        ?[src, dst] <- [[1, 2]]
+        :put friend {src, dst}
+    ",
+    )
+    .unwrap();
+    assert!(db
         .run_default(
             r"
-        {
-            ?[x, y] <- [[1, 2]]
-            :create _rel {x => y}
-            :returning
-        }
-        {
-            ?[x, y] := x = 1, *_rel{x, y: 3}, y = 2
-        }
+        ?[src, dst] <- [['alice', 2]]
+        :put friend {src, dst}
     ",
         )
-        .unwrap();
-    assert_eq!(0, res.rows.len());
+        .is_err());
+    let res = db.run_default("?[src, dst] := *friend{src, dst}").unwrap();
+    assert_eq!(res.rows.len(), 1);
+}
 
-    let res = db.run_default(r"
-        {
-            ?[x, u, y] <- [[1, 0, 2]]
-            :create _rel {x, u => y}
-            :returning
-        }
-        {
-            ?[x, y] := x = 1, *_rel{x, y: 3}, y = 2
-        }
-    ")
+#[test]
+fn first_and_last_rows() {
+    let db = DbInstance::default();
+    db.run_default(":create seq {k: Int => v: String}").unwrap();
+    assert_eq!(db.first_row("seq").unwrap(), None);
+    assert_eq!(db.last_row("seq").unwrap(), None);
+    db.run_default(
+        r"
+        ?[k, v] <- [[3, 'c'], [-1, 'a'], [10, 'd'], [2, 'b']]
+        :put seq {k => v}
+    ",
+    )
+    .unwrap();
+    assert_eq!(
+        db.first_row("seq").unwrap(),
+        Some(vec![DataValue::from(-1), DataValue::from("a")])
+    );
+    assert_eq!(
+        db.last_row("seq").unwrap(),
+        Some(vec![DataValue::from(10), DataValue::from("d")])
+    );
+
+    // uncommitted changes must be seen by the writer
+    db.run_default(":create nums {k: Int}").unwrap();
+    db.run_default("?[k] <- [[1], [2], [3]] :put nums {k}")
         .unwrap();
-    assert_eq!(0, res.rows.len());
+    let mem_db = match &db {
+        DbInstance::Mem(mem_db) => mem_db,
+        _ => unreachable!(),
+    };
+    let mut tx = mem_db.transact_write().unwrap();
+    let handle = tx.get_relation("nums", false).unwrap();
+    let key = |i: i64| vec![DataValue::from(i)].encode_as_key(handle.id);
+    tx.store_tx.del(&key(3)).unwrap();
+    assert_eq!(
+        handle.last_row(&tx).unwrap(),
+        Some(vec![DataValue::from(2)])
+    );
+    tx.store_tx.del(&key(2)).unwrap();
+    tx.store_tx.put(&key(0), &[]).unwrap();
+    assert_eq!(
+        handle.last_row(&tx).unwrap(),
+        Some(vec![DataValue::from(1)])
+    );
+    tx.store_tx.put(&key(5), &[]).unwrap();
+    assert_eq!(
+        handle.last_row(&tx).unwrap(),
+        Some(vec![DataValue::from(5)])
+    );
 }
 
 #[test]
-fn test_lsh_indexing4() {
-    for i in 1..10 {
-        let f = i as f64 / 10.;
-        let db = DbInstance::new("mem", "", "").unwrap();
-        db.run_default(r":create a {k: String => v: String}")
-            .unwrap();
-        db.run_script(
-            r"::lsh create a:lsh {extractor: v, tokenizer: NGram, n_gram: 3, target_threshold: $t }",
-            BTreeMap::from([("t".into(), f.into())]),
-            ScriptMutability::Mutable
-        )
-            .unwrap();
-        db.run_default("?[k, v] <- [['a', 'ewiygfspeoighjsfcfxzdfncalsdf']] :put a {k => v}")
-            .unwrap();
-        db.run_default("?[k] <- [['a']] :rm a {k}").unwrap();
-        let res = db
-            .run_default("?[k] := ~a:lsh{k | query: 'ewiygfspeoighjsfcfxzdfncalsdf', k: 1}")
-            .unwrap();
-        assert!(res.rows.len() == 0);
+fn scan_key_range() {
+    let db = DbInstance::default();
+    db.run_default(
+        r"
+        ?[id] := id in int_range(1, 11)
+        :create nums {id}
+    ",
+    )
+    .unwrap();
+    let ids = |lower, upper| {
+        db.scan_range("nums", lower, upper)
+            .unwrap()
+            .rows
+            .into_iter()
+            .map(|row| row[0].get_int().unwrap())
+            .collect_vec()
+    };
+    let three = DataValue::from(3);
+    let seven = DataValue::from(7);
+    assert_eq!(
+        ids(Included(three.clone()), Included(seven.clone())),
+        vec![3, 4, 5, 6, 7]
+    );
+    assert_eq!(
+        ids(Excluded(three.clone()), Excluded(seven.clone())),
+        vec![4, 5, 6]
+    );
+    assert_eq!(ids(Unbounded, Excluded(three)), vec![1, 2]);
+    assert_eq!(ids(Included(seven), Unbounded), vec![7, 8, 9, 10]);
+    assert_eq!(ids(Unbounded, Unbounded).len(), 10);
+}
+
+#[test]
+fn scan_keys_only() {
+    let db = DbInstance::default();
+    db.run_default(
+        r"
+        ?[id, tag, v] <- [[3, 'x', 'c'], [-1, 'y', 'a'], [10, 'x', 'd'], [3, 'a', 'b']]
+        :create tagged {id: Int, tag: String => v: String}
+    ",
+    )
+    .unwrap();
+    assert_eq!(
+        db.keys("tagged").unwrap(),
+        vec![
+            vec![DataValue::from(-1), DataValue::from("y")],
+            vec![DataValue::from(3), DataValue::from("a")],
+            vec![DataValue::from(3), DataValue::from("x")],
+            vec![DataValue::from(10), DataValue::from("x")],
+        ]
+    );
+    db.run_default(":create empty {k}").unwrap();
+    assert!(db.keys("empty").unwrap().is_empty());
+    assert!(db.keys("nobody").is_err());
+}
+
+#[test]
+fn deeply_nested_scripts() {
+    let db = DbInstance::default();
+    let n = 100_000;
+    let src = format!("?[x] := x = {}1{}", "(".repeat(n), ")".repeat(n));
+    let err = db.run_default(&src).unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "parser::nesting_too_deep");
+    let src = format!("?[x] <- [[{}1{}]]", "[".repeat(n), "]".repeat(n));
+    assert!(db.run_default(&src).is_err());
+
+    let n = crate::DEFAULT_MAX_NESTING_DEPTH - 3;
+    let src = format!("?[x] := x = {}1{}", "(".repeat(n), ")".repeat(n));
+    assert_eq!(
+        db.run_default(&src).unwrap().rows,
+        vec![vec![DataValue::from(1)]]
+    );
+    let src = format!("?[x] := x = {}1{}", "to_int(".repeat(n), ")".repeat(n));
+    assert_eq!(
+        db.run_default(&src).unwrap().rows,
+        vec![vec![DataValue::from(1)]]
+    );
+    let src = format!("?[x] := x = {}1{}", "[".repeat(n), "]".repeat(n));
+    assert_eq!(db.run_default(&src).unwrap().rows.len(), 1);
+    // brackets in strings and comments do not count
+    let src = format!(
+        "?[x] := x = '{}' # {}",
+        "(".repeat(n * 2),
+        "[".repeat(n * 2)
+    );
+    assert_eq!(db.run_default(&src).unwrap().rows.len(), 1);
+    // backslashes in raw strings are not escapes
+    let src = format!("?[x] := x = concat(_\"\\\"_, \"{}\")", "(".repeat(n * 2));
+    assert_eq!(db.run_default(&src).unwrap().rows.len(), 1);
+
+    // the limit is set for each database on its own
+    let n = crate::DEFAULT_MAX_NESTING_DEPTH + 10;
+    let src = format!("?[x] := x = {}1{}", "(".repeat(n), ")".repeat(n));
+    let other = DbInstance::default();
+    other.set_max_nesting_depth(n + 10);
+    assert_eq!(
+        other.run_default(&src).unwrap().rows,
+        vec![vec![DataValue::from(1)]]
+    );
+    assert!(db.run_default(&src).is_err());
+}
+
+#[test]
+fn nulls_sort_position() {
+    let db = DbInstance::default();
+    let data = "?[a, b] <- [[1, null], [2, 3], [3, null], [4, 1]]";
+    let col = |script: String| {
+        db.run_default(&script)
+            .unwrap()
+            .rows
+            .into_iter()
+            .map(|r| r[0].clone())
+            .collect_vec()
+    };
+    let expected = |ids: [i64; 4]| ids.map(DataValue::from).to_vec();
+    assert_eq!(col(format!("{data} :order b, a")), expected([1, 3, 4, 2]));
+    assert_eq!(
+        col(format!("{data} :order b, a :nulls last")),
+        expected([4, 2, 1, 3])
+    );
+    assert_eq!(
+        col(format!("{data} :order b, a :nulls first")),
+        expected([1, 3, 4, 2])
+    );
+    // the position of nulls does not depend on the direction
+    assert_eq!(
+        col(format!("{data} :order -b, a :nulls first")),
+        expected([1, 3, 2, 4])
+    );
+    assert_eq!(col(format!("{data} :order -b, a")), expected([2, 4, 1, 3]));
+    assert_eq!(
+        col(format!("{data} :order -b, a :nulls last")),
+        expected([2, 4, 1, 3])
+    );
+}
+
+#[test]
+fn sort_order_agrees_with_comparisons() {
+    let db = DbInstance::default();
+    let names = "[['bob'], ['Alice'], ['alice'], ['Bob'], ['ab'], ['a'], [''], ['ä'], ['b']]";
+    let sorted = db
+        .run_default(&format!("?[name] <- {names} :order name"))
+        .unwrap()
+        .rows
+        .into_iter()
+        .map(|r| r[0].clone())
+        .collect_vec();
+    let less = db
+        .run_default(&format!("n[name] <- {names} ?[a, b] := n[a], n[b], a < b"))
+        .unwrap()
+        .rows
+        .into_iter()
+        .map(|r| (r[0].clone(), r[1].clone()))
+        .collect_vec();
+    for (i, a) in sorted.iter().enumerate() {
+        for (j, b) in sorted.iter().enumerate() {
+            assert_eq!(i < j, less.contains(&(a.clone(), b.clone())), "{a} {b}");
+        }
     }
+    // integers and floats are sorted by their numeric values, as `<` compares them
+    let sorted = db
+        .run_default("?[x] <- [[2], [1.5], [-1], [0.5], [-2.5]] :order x")
+        .unwrap()
+        .into_json();
+    assert_eq!(sorted["rows"], json!([[-2.5], [-1], [0.5], [1.5], [2]]));
 }
 
 #[test]
-fn test_lsh_indexing() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    db.run_default(r":create a {k: String => v: String}")
+fn bulk_import_rows() {
+    let db = DbInstance::default();
+    db.run_default(":create person {id: Int => name: String, age: Int default 0}")
+        .unwrap();
+    db.run_default("::index create person:by_age {age, id}")
+        .unwrap();
+    let rows = (0..5000).map(|i| {
+        BTreeMap::from([
+            ("id".to_string(), DataValue::from(i)),
+            ("name".to_string(), DataValue::from(format!("p{i}"))),
+            ("age".to_string(), DataValue::from(i % 100)),
+        ])
+    });
+    let options = ImportOptions {
+        batch_size: 1000,
+        ..Default::default()
+    };
+    assert_eq!(db.import_rows("person", rows, options).unwrap(), 5000);
+    let res = db.run_default("?[count(id)] := *person{id}").unwrap().rows;
+    assert_eq!(res[0][0], DataValue::from(5000));
+    let res = db
+        .run_default("?[count(id)] := *person:by_age{age: 42, id}")
+        .unwrap()
+        .rows;
+    assert_eq!(res[0][0], DataValue::from(50));
+
+    // overwriting rows keeps the index consistent, and missing columns take their defaults
+    let rows = [BTreeMap::from([
+        ("id".to_string(), DataValue::from(42)),
+        ("name".to_string(), DataValue::from("changed")),
+    ])];
+    db.import_rows("person", rows, Default::default()).unwrap();
+    let res = db
+        .run_default("?[name, age] := *person{id: 42, name, age}")
+        .unwrap()
+        .rows;
+    assert_eq!(
+        res,
+        vec![vec![DataValue::from("changed"), DataValue::from(0)]]
+    );
+    let res = db
+        .run_default("?[count(id)] := *person:by_age{age: 42, id}")
+        .unwrap()
+        .rows;
+    assert_eq!(res[0][0], DataValue::from(49));
+
+    let bad_rows = [BTreeMap::from([
+        ("id".to_string(), DataValue::from(1)),
+        ("name".to_string(), DataValue::from("x")),
+        ("height".to_string(), DataValue::from(180)),
+    ])];
+    assert!(db
+        .import_rows("person", bad_rows, Default::default())
+        .is_err());
+    let bad_rows = [BTreeMap::from([("id".to_string(), DataValue::from(1))])];
+    assert!(db
+        .import_rows("person", bad_rows, Default::default())
+        .is_err());
+    assert!(db
+        .import_rows("person:by_age", [], Default::default())
+        .is_err());
+}
+
+#[test]
+fn update_rows_by_filter() {
+    let db = DbInstance::default();
+    db.run_default(":create person {id: Int => email: String, visits: Int}")
+        .unwrap();
+    db.run_default("::index create person:by_email {email}")
         .unwrap();
     db.run_default(
-        r"?[k, v] <- [['a', 'hello world!'], ['b', 'the world is round']] :put a {k => v}",
+        "?[id, email, visits] <- [[3, 'c@x', 0], [6, 'f@x', 1], [8, 'h@x', 2]] \
+         :put person {id => email, visits}",
     )
     .unwrap();
+    let assignments = BTreeMap::from([
+        ("email".to_string(), "$email".to_string()),
+        ("visits".to_string(), "visits + 1".to_string()),
+    ]);
+    let params = BTreeMap::from([("email".to_string(), DataValue::from("x"))]);
+    let updated = db
+        .update_where("person", assignments, "id > 5", params)
+        .unwrap();
+    assert_eq!(updated, 2);
+    let res = db
+        .run_default("?[id, email, visits] := *person{id, email, visits}")
+        .unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([[3, "c@x", 0], [6, "x", 2], [8, "x", 3]])
+    );
+    let res = db
+        .run_default("?[email, id] := *person:by_email{email, id}")
+        .unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([["c@x", 3], ["x", 6], ["x", 8]])
+    );
+
+    let update = |assignments: &[(&str, &str)], filter: &str| {
+        let assignments = assignments
+            .iter()
+            .map(|(col, expr)| (col.to_string(), expr.to_string()))
+            .collect();
+        db.update_where("person", assignments, filter, Default::default())
+    };
+    assert_eq!(update(&[("visits", "0")], "id > 100").unwrap(), 0);
+    // values are coerced to the column types, and keys cannot be assigned
+    assert!(update(&[("visits", "'many'")], "true").is_err());
+    assert!(update(&[("id", "id + 1")], "true").is_err());
+    assert!(update(&[("height", "1")], "true").is_err());
+    let res = db.run_default("?[sum(visits)] := *person{visits}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[5.0]]));
+    // as with `:update`, search indices are maintained and triggers are run
+    db.run_default("::fts create person:email_fts {extractor: email, tokenizer: Simple}")
+        .unwrap();
+    db.run_default(":create visit_log {id: Int => visits: Int}")
+        .unwrap();
     db.run_default(
-        r"::lsh create a:lsh {extractor: v, tokenizer: Simple, n_gram: 3, target_threshold: 0.3 }",
+        "::set_triggers person on put { \
+            ?[id, visits] := _new[id, email, visits] :put visit_log {id => visits} \
+        }",
     )
     .unwrap();
+    assert_eq!(update(&[("email", "'new@x'")], "id == 3").unwrap(), 1);
+    let res = db
+        .run_default("?[id] := ~person:email_fts{id | query: 'new', k: 10}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[3]]));
+    let res = db
+        .run_default("?[id, visits] := *visit_log{id, visits}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[3, 0]]));
+}
+
+#[test]
+fn delete_rows_by_filter() {
+    let db = DbInstance::default();
+    db.run_default(":create person {id: Int => name: String, age: Int}")
+        .unwrap();
+    db.run_default("::index create person:by_age {age}")
+        .unwrap();
     db.run_default(
-        r"?[k, v] <- [
-            ['b', 'the world is square!'],
-            ['c', 'see you at the end of the world!'],
-            ['d', 'the world is the world and makes the world go around'],
-            ['e', 'the world is the world and makes the world not go around']
-        ] :put a {k => v}",
+        "?[id, name, age] <- [[1, 'Alice', 30], [2, 'Bob', 17], \
+                              [3, 'Carol', 12], [4, 'Dave', 45]] \
+         :put person {id => name, age}",
     )
     .unwrap();
-    let res = db.run_default("::columns a:lsh").unwrap();
-    for row in res.into_json()["rows"].as_array().unwrap() {
-        println!("{}", row);
-    }
-    let _res = db
-        .run_default(
-            r"
-        ?[src_k, hash] :=
-            *a:lsh{src_k, hash}
-        ",
-        )
+    let params = BTreeMap::from([("adult".to_string(), DataValue::from(18))]);
+    let deleted = db.delete_where("person", "age < $adult", params).unwrap();
+    assert_eq!(deleted, 2);
+    let res = db
+        .run_default("?[id, name, age] := *person{id, name, age}")
         .unwrap();
-    // for row in _res.into_json()["rows"].as_array().unwrap() {
-    //     println!("{}", row);
-    // }
-    let _res = db
-        .run_default(
-            r"
-        ?[k, minhash] :=
-            *a:lsh:inv{k, minhash}
-        ",
-        )
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([[1, "Alice", 30], [4, "Dave", 45]])
+    );
+    let res = db
+        .run_default("?[age, id] := *person:by_age{age, id}")
         .unwrap();
-    // for row in res.into_json()["rows"].as_array().unwrap() {
-    //     println!("{}", row);
-    // }
+    assert_eq!(res.into_json()["rows"], json!([[30, 1], [45, 4]]));
+    assert_eq!(db.stats("person").unwrap().rows, 2);
+
+    let delete = |filter: &str| db.delete_where("person", filter, Default::default());
+    assert_eq!(delete("name == 'Eve'").unwrap(), 0);
+    assert!(delete("age").is_err());
+    assert!(delete("height > 1").is_err());
+    assert!(db
+        .delete_where("person:by_age", "true", Default::default())
+        .is_err());
+    assert_eq!(delete("true").unwrap(), 2);
+    assert_eq!(db.stats("person").unwrap().rows, 0);
+    // as with `:rm`, search indices are maintained and triggers are run
+    db.run_default("::fts create person:name_fts {extractor: name, tokenizer: Simple}")
+        .unwrap();
+    db.run_default(":create removed {id: Int}").unwrap();
+    db.run_default(
+        "::set_triggers person on rm { ?[id] := _old[id, name, age] :put removed {id} }",
+    )
+    .unwrap();
+    db.run_default(
+        "?[id, name, age] <- [[5, 'Frank', 50], [6, 'Grace', 60]] :put person {id => name, age}",
+    )
+    .unwrap();
+    assert_eq!(delete("age < 55").unwrap(), 1);
     let res = db
-        .run_default(
-            r"
-            ?[k, v] := ~a:lsh{k, v |
-                query: 'see him at the end of the world',
-            }
-            ",
-        )
+        .run_default("?[id] := ~person:name_fts{id | query: 'Frank', k: 10}")
         .unwrap();
-    for row in res.into_json()["rows"].as_array().unwrap() {
-        println!("{}", row);
-    }
-    let res = db.run_default("::indices a").unwrap();
-    for row in res.into_json()["rows"].as_array().unwrap() {
-        println!("{}", row);
-    }
-    db.run_default(r"::lsh drop a:lsh").unwrap();
+    assert!(res.rows.is_empty());
+    let res = db
+        .run_default("?[id] := ~person:name_fts{id | query: 'Grace', k: 10}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[6]]));
+    let res = db.run_default("?[id] := *removed{id}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[5]]));
+    assert_eq!(db.stats("person").unwrap().rows, 1);
 }
 
 #[test]
-fn test_insertions() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    db.run_default(r":create a {k => v: <F32; 1536> default rand_vec(1536)}")
+fn import_json_lines() {
+    let db = DbInstance::default();
+    db.run_default(":create person {id: Int => name: String, score: Float default 0.}")
         .unwrap();
-    db.run_default(r"?[k] <- [[1]] :put a {k}").unwrap();
-    db.run_default(r"?[k, v] := *a{k, v}").unwrap();
+    let data = r#"{"id": 1, "name": "alice", "score": 3}
+{"id": 2, "name": "bob"}
+
+{"id": 3, "name": "carol", "score": 1.5}
+"#;
+    let n = db
+        .import_jsonl("person", data.as_bytes(), true, Default::default())
+        .unwrap();
+    assert_eq!(n, 3);
+    let res = db
+        .run_default("?[id, name, score] := *person{id, name, score}")
+        .unwrap()
+        .rows;
+    assert_eq!(
+        res,
+        vec![
+            vec![
+                DataValue::from(1),
+                DataValue::from("alice"),
+                DataValue::from(3.)
+            ],
+            vec![
+                DataValue::from(2),
+                DataValue::from("bob"),
+                DataValue::from(0.)
+            ],
+            vec![
+                DataValue::from(3),
+                DataValue::from("carol"),
+                DataValue::from(1.5)
+            ],
+        ]
+    );
+
+    let data = r#"{"id": 4, "name": "dave"}
+{"id": 5, "name": "eve"
+[6, "frank"]
+{"id": 7, "name": "grace"}"#;
+    let err = db
+        .import_jsonl("person", data.as_bytes(), true, Default::default())
+        .unwrap_err();
+    assert!(err.to_string().contains("line 2"), "{err}");
+    let n = db
+        .import_jsonl("person", data.as_bytes(), false, Default::default())
+        .unwrap();
+    assert_eq!(n, 2);
+    let res = db.run_default("?[count(id)] := *person{id}").unwrap().rows;
+    assert_eq!(res[0][0], DataValue::from(5));
+}
+
+#[test]
+fn import_csv_by_header() {
+    let db = DbInstance::default();
     db.run_default(
-        r"::hnsw create a:i {
-            fields: [v], dim: 1536, ef: 16, filter: k % 3 == 0,
-            m: 32
-        }",
+        ":create person {id: Int => name: String, height: Float?, active: Bool default true}",
     )
     .unwrap();
-    db.run_default(r"?[count(fr_k)] := *a:i{fr_k}").unwrap();
-    db.run_default(r"?[k] <- [[1]] :put a {k}").unwrap();
-    db.run_default(r"?[k] := k in int_range(300) :put a {k}")
+    let data = "name,id,height,active\nalice,1,1.7,false\nbob,2,,\n\"carol, jr\",3,2,true\n";
+    let n = db
+        .import_csv("person", data.as_bytes(), Default::default())
         .unwrap();
+    assert_eq!(n, 3);
     let res = db
-        .run_default(
-            r"?[dist, k] := ~a:i{k | query: v, bind_distance: dist, k:10, ef: 50, filter: k % 2 == 0, radius: 245}, *a{k: 96, v}",
-        )
-        .unwrap();
-    println!("results");
-    for row in res.into_json()["rows"].as_array().unwrap() {
-        println!("{} {}", row[0], row[1]);
-    }
+        .run_default("?[id, name, height, active] := *person{id, name, height, active}")
+        .unwrap()
+        .rows;
+    assert_eq!(
+        res,
+        vec![
+            vec![
+                DataValue::from(1),
+                DataValue::from("alice"),
+                DataValue::from(1.7),
+                DataValue::from(false)
+            ],
+            vec![
+                DataValue::from(2),
+                DataValue::from("bob"),
+                DataValue::Null,
+                DataValue::from(true)
+            ],
+            vec![
+                DataValue::from(3),
+                DataValue::from("carol, jr"),
+                DataValue::from(2.),
+                DataValue::from(true)
+            ],
+        ]
+    );
+
+    // required column left empty
+    let data = "id,name\n4,\n";
+    assert!(db
+        .import_csv("person", data.as_bytes(), Default::default())
+        .is_err());
+    // cell that does not match the type
+    let data = "id,name\nfive,eve\n";
+    let err = db
+        .import_csv("person", data.as_bytes(), Default::default())
+        .unwrap_err();
+    assert!(format!("{err:?}").contains("line 2"), "{err:?}");
+    // unknown column
+    let data = "id,name,age\n6,frank,30\n";
+    assert!(db
+        .import_csv("person", data.as_bytes(), Default::default())
+        .is_err());
 }
 
 #[test]
-fn tokenizers() {
-    let tokenizers = TokenizerCache::default();
-    let tokenizer = tokenizers
-        .get(
-            "simple",
-            &TokenizerConfig {
-                name: "Simple".into(),
-                args: vec![],
-            },
-            &[],
+fn bytecode_borrows_large_values() {
+    let db = DbInstance::default();
+    let big = "x".repeat(10000);
+    let params = BTreeMap::from([("big".to_string(), DataValue::from(big.as_str()))]);
+    let res = db
+        .run_script(
+            "?[x, y] := x in [1, 2, 3], y = if(x > 1, $big, 'small'), y != 'other'",
+            params.clone(),
+            ScriptMutability::Immutable,
         )
-        .unwrap();
+        .unwrap()
+        .rows;
+    assert_eq!(res.len(), 3);
+    assert_eq!(res[0][1], DataValue::from("small"));
+    assert_eq!(res[2][1], DataValue::from(big.as_str()));
 
-    // let tokenizer = TextAnalyzer::from(SimpleTokenizer)
-    //     .filter(RemoveLongFilter::limit(40))
-    //     .filter(LowerCaser)
-    //     .filter(Stemmer::new(Language::English));
-    let mut token_stream = tokenizer.token_stream("It is closer to Apache Lucene than to Elasticsearch or Apache Solr in the sense it is not an off-the-shelf search engine server, but rather a crate that can be used to build such a search engine.");
-    while let Some(token) = token_stream.next() {
-        println!("Token {:?}", token.text);
-    }
+    // a single op taking many borrowed values
+    let items = vec!["$big"; 300].join(", ");
+    let res = db
+        .run_script(
+            &format!("?[n] := l = [{items}], n = length(l), last(l) == $big"),
+            params.clone(),
+            ScriptMutability::Immutable,
+        )
+        .unwrap()
+        .rows;
+    assert_eq!(res, vec![vec![DataValue::from(300)]]);
 
-    println!("XXXXXXXXXXXXX");
+    // evaluation errors while the stack holds borrowed values
+    for script in [
+        "?[x] := x = $big, x",
+        "?[y] := x = $big, y = x / 2",
+        "?[y] := x = $big, y = [x, $big, x / 2]",
+    ] {
+        assert!(db
+            .run_script(script, params.clone(), ScriptMutability::Immutable)
+            .is_err());
+    }
+}
 
-    let tokenizer = tokenizers
-        .get(
-            "cangjie",
-            &TokenizerConfig {
-                name: "Cangjie".into(),
-                args: vec![],
-            },
-            &[],
+#[test]
+fn explain_mutation() {
+    let db = DbInstance::default();
+    db.run_default(":create users {id: Int, org: String => name: String}")
+        .unwrap();
+    let res = db
+        .run_default(
+            r"::explain {
+                ?[a, b, c] <- [[1, 'x', 'Alice']] :insert users {id = a, org = b => name = c}
+            }",
         )
         .unwrap();
+    let last = res.into_json()["rows"]
+        .as_array()
+        .unwrap()
+        .last()
+        .unwrap()
+        .clone();
+    assert_eq!(last[4], json!("insert"));
+    assert_eq!(last[5], json!(":users"));
+    assert_eq!(last[6], json!(null));
+    assert_eq!(last[8], json!(["a", "b", "c"]));
+    assert_eq!(last[9], json!({"id": "a", "org": "b"}));
 
-    let mut token_stream = tokenizer.token_stream("这个产品Finchat.io是一个相对比较有特色的文档问答类网站，它集成了750多家公司的经融数据。感觉是把财报等数据借助Embedding都向量化了，然后接入ChatGPT进行对话。");
-    while let Some(token) = token_stream.next() {
-        println!("Token {:?}", token.text);
+    // keys are taken from the stored relation when no spec is given
+    let res = db
+        .run_default("::explain { ?[name, org, id] <- [['Bob', 'y', 2]] :put users }")
+        .unwrap();
+    let last = res.into_json()["rows"]
+        .as_array()
+        .unwrap()
+        .last()
+        .unwrap()
+        .clone();
+    assert_eq!(last[4], json!("put"));
+    assert_eq!(last[9], json!({"id": "id", "org": "org"}));
+
+    // nothing is written
+    let res = db.run_default("?[id] := *users{id}").unwrap();
+    assert!(res.rows.is_empty());
+}
+
+#[test]
+fn bad_literal_reports_location() {
+    let db = DbInstance::default();
+    for literal in [
+        "0xFFFFFFFFFFFFFFFFFF",
+        "0o7777777777777777777777",
+        "99999999999999999999",
+    ] {
+        let script = format!("?[a] <- [[1],\n         [{literal}]]");
+        let err = db.run_default(&script).unwrap_err();
+        let rendered = format_error_as_json(err, Some(&script));
+        assert_eq!(rendered["code"], json!("parser::bad_pos_int"));
+        let span = &rendered["labels"][0]["span"];
+        let offset = span["offset"].as_u64().unwrap() as usize;
+        assert_eq!(span["length"], json!(literal.len()));
+        let (line, col) = script[..offset]
+            .lines()
+            .enumerate()
+            .last()
+            .map(|(i, l)| (i + 1, l.len() + 1))
+            .unwrap();
+        assert_eq!((line, col), (2, 11));
     }
 }
 
 #[test]
-fn multi_index_vec() {
-    let db = DbInstance::new("mem", "", "").unwrap();
+fn trailing_commas_in_column_definitions() {
+    let db = DbInstance::default();
+    db.run_default(":create a {k: Int, => v: String,}").unwrap();
+    db.run_default(":create b {k: Int, j: Int,}").unwrap();
+    db.run_default("?[k, v] <- [[1, 'x'],] :put a {k, => v,}")
+        .unwrap();
+    let res = db.run_default("::columns a").unwrap();
+    assert_eq!(res.rows.len(), 2);
+    let res = db.run_default("::columns b").unwrap();
+    assert_eq!(res.rows.len(), 2);
+    let res = db.run_default("?[k, v] := *a{k, v,}").unwrap();
+    assert_eq!(
+        res.rows,
+        vec![vec![DataValue::from(1), DataValue::from("x")]]
+    );
+}
+
+#[test]
+fn resolve_relation_definition() {
+    let db = DbInstance::default();
+    assert_eq!(db.resolve_relation("person").unwrap(), None);
     db.run_default(
-        r#"
-        :create product {
-            id
-            =>
-            name,
-            description,
-            price,
-            name_vec: <F32; 1>,
-            description_vec: <F32; 1>
-        }
-        "#,
+        ":create person {id: Int, org: String? => name: String, tags: [String] default []}",
     )
     .unwrap();
-    db.run_default(
-        r#"
-        ::hnsw create product:semantic{
-            fields: [name_vec, description_vec],
-            dim: 1,
-            ef: 16,
-            m: 32,
+    let def = db.resolve_relation("person").unwrap().unwrap();
+    let col = |name: &str, coltype: ColType, nullable: bool, has_default: bool| ColumnInfo {
+        name: name.to_string(),
+        typing: NullableColType { coltype, nullable },
+        has_default,
+    };
+    assert_eq!(
+        def,
+        RelationDef {
+            name: "person".to_string(),
+            keys: vec![
+                col("id", ColType::Int, false, false),
+                col("org", ColType::String, true, false),
+            ],
+            non_keys: vec![
+                col("name", ColType::String, false, false),
+                col(
+                    "tags",
+                    ColType::List {
+                        eltype: Box::new(NullableColType {
+                            coltype: ColType::String,
+                            nullable: false
+                        }),
+                        len: None
+                    },
+                    false,
+                    true
+                ),
+            ],
+            description: "".to_string(),
         }
-        "#,
-    )
-    .unwrap();
-    db.run_default(
-        r#"
-        ?[id, name, description, price, name_vec, description_vec] <- [[1, "name", "description", 100, [1], [1]]]
+    );
+}
 
-        :put product {id => name, description, price, name_vec, description_vec}
-        "#,
-    ).unwrap();
-    let res = db.run_default("::indices product").unwrap();
-    for row in res.into_json()["rows"].as_array().unwrap() {
-        println!("{}", row);
-    }
+#[test]
+fn empty_collection_literals() {
+    let empty = |src: &str| evaluate_expressions(src, &Default::default(), &Default::default());
+    assert_eq!(empty("[]").unwrap(), DataValue::List(vec![]));
+    assert_eq!(empty("{}").unwrap(), DataValue::Json(JsonData(json!({}))));
+
+    let db = DbInstance::default();
+    let res = db.run_default("?[l, o] := l = [], o = {}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[[], {}]]));
+    db.run_default(":create doc {id: Int => tags: [String?] default []}")
+        .unwrap();
+    db.run_default("?[id] <- [[1]] :put doc {id}").unwrap();
+    db.run_default("?[id, tags] <- [[2, ['a', null]]] :put doc {id => tags}")
+        .unwrap();
+    let res = db.run_default("?[id, tags] := *doc{id, tags}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1, []], [2, ["a", null]]]));
+    // an empty list clears the column
+    db.run_default("?[id, tags] <- [[2, []]] :update doc {id => tags}")
+        .unwrap();
+    let res = db.run_default("?[tags] := *doc{id: 2, tags}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[[]]]));
+}
+
+fn put_and_query<'s, S: Storage<'s>>(db: &'s Db<S>) {
+    let run = |script: &str| {
+        db.run_script(script, Default::default(), ScriptMutability::Mutable)
+            .unwrap()
+    };
+    run(":create person {id: Int => name: String}");
+    run("?[id, name] <- [[1, 'Alice'], [2, 'Bob']] :put person {id => name}");
+    run("?[id] <- [[1]] :rm person {id}");
+    let res = run("?[id, name] := *person{id, name}");
+    assert_eq!(
+        res.rows,
+        vec![vec![DataValue::from(2), DataValue::from("Bob")]]
+    );
 }
 
 #[test]
-fn ensure_not() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    db.run_default(
-        r"
-    %ignore_error { :create id_alloc{id: Int => next_id: Int, last_id: Int}}
-%ignore_error {
-    ?[id, next_id, last_id] <- [[0, 1, 1000]];
-    :ensure_not id_alloc{id => next_id, last_id}
+fn mutations_on_storage_given_by_value() {
+    let db = Db::new(MemStorage::default()).unwrap();
+    db.initialize().unwrap();
+    assert_eq!(db.db.storage_kind(), "mem");
+    put_and_query(&db);
 }
-    ",
-    )
-    .unwrap();
+
+#[cfg(feature = "storage-sqlite")]
+#[test]
+fn mutations_on_persistent_storage() {
+    let path = "_test_storage_trait.db";
+    let _ = std::fs::remove_file(path);
+    let db = crate::new_cozo_sqlite(path).unwrap();
+    assert_eq!(db.db.storage_kind(), "sqlite");
+    put_and_query(&db);
+    drop(db);
+    std::fs::remove_file(path).unwrap();
 }
 
 #[test]
-fn insertion() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    db.run_default(r":create a {x => y}").unwrap();
-    assert!(db
-        .run_default(r"?[x, y] <- [[1, 2]] :insert a {x => y}",)
-        .is_ok());
-    assert!(db
-        .run_default(r"?[x, y] <- [[1, 3]] :insert a {x => y}",)
-        .is_err());
+fn in_memory_instance_touches_no_files() {
+    // the path is ignored by the `mem` engine
+    let path = "_test_in_memory_ignored_path";
+    let db = DbInstance::new("mem", path, "").unwrap();
+    db.run_default(":create person {id: Int => name: String}")
+        .unwrap();
+    db.run_default("?[id, name] <- [[1, 'Alice']] :put person {id => name}")
+        .unwrap();
+    let res = db.run_default("?[name] := *person{id: 1, name}").unwrap();
+    assert_eq!(res.rows, vec![vec![DataValue::from("Alice")]]);
+    assert!(!std::path::Path::new(path).exists());
 }
 
 #[test]
-fn deletion() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    db.run_default(r":create a {x => y}").unwrap();
-    assert!(db.run_default(r"?[x] <- [[1]] :delete a {x}").is_err());
-    assert!(db
-        .run_default(r"?[x, y] <- [[1, 2]] :insert a {x => y}",)
-        .is_ok());
-    db.run_default(r"?[x] <- [[1]] :delete a {x}").unwrap();
+fn key_exists() {
+    let db = DbInstance::default();
+    db.run_default(":create person {id: Int, org: Float => name: String}")
+        .unwrap();
+    db.run_default("?[id, org, name] <- [[1, 2.0, 'Alice']] :put person {id, org => name}")
+        .unwrap();
+    let exists = |key: Vec<DataValue>| db.exists("person", &key);
+    assert!(exists(vec![DataValue::from(1), DataValue::from(2.0)]).unwrap());
+    // keys are coerced to the column types
+    assert!(exists(vec![DataValue::from(1), DataValue::from(2)]).unwrap());
+    assert!(!exists(vec![DataValue::from(2), DataValue::from(2.0)]).unwrap());
+    assert!(exists(vec![DataValue::from(1)]).is_err());
+    assert!(exists(vec![DataValue::from("x"), DataValue::from(2.0)]).is_err());
+    assert!(db.exists("nobody", &[DataValue::from(1)]).is_err());
+}
+
+#[test]
+fn random_sample() {
+    let db = DbInstance::default();
+    db.run_default("?[x, y] := x in int_range(100), y = x * x :create squares {x => y}")
+        .unwrap();
+    let sample = db.sample("squares", 10, Some(42)).unwrap();
+    assert_eq!(sample.headers, vec!["x", "y"]);
+    assert_eq!(sample.rows.len(), 10);
+    for row in &sample.rows {
+        let x = row[0].get_int().unwrap();
+        assert!((0..100).contains(&x));
+        assert_eq!(row[1], DataValue::from(x * x));
+    }
+    // distinct rows in key order
+    assert!(sample.rows.windows(2).all(|w| w[0] < w[1]));
+    // the same seed gives the same sample
+    assert_eq!(
+        db.sample("squares", 10, Some(42)).unwrap().rows,
+        sample.rows
+    );
+    assert!((0..10).any(|seed| db.sample("squares", 10, Some(seed)).unwrap().rows != sample.rows));
+    // asking for more rows than there are gives all of them
+    let all = db.sample("squares", 1000, None).unwrap();
+    assert_eq!(
+        all.rows,
+        db.export_relations(["squares"].iter()).unwrap()["squares"].rows
+    );
+    assert!(db.sample("squares", 0, Some(1)).unwrap().rows.is_empty());
+    assert!(db.sample("nothing", 1, None).is_err());
+}
+
+#[test]
+fn row_count_stats() {
+    let db = DbInstance::default();
+    let rows = |rel: &str| db.stats(rel).unwrap().rows;
+    let actual = |rel: &str| {
+        db.run_default(&format!("?[count(k)] := *{rel}{{k}}"))
+            .unwrap()
+            .rows
+            .first()
+            .map_or(0, |row| row[0].get_int().unwrap() as u64)
+    };
+    db.run_default(":create plain {k => v}").unwrap();
+    db.run_default(":create indexed {k => v}").unwrap();
+    db.run_default("::index create indexed:by_v {v}").unwrap();
+    for rel in ["plain", "indexed"] {
+        assert_eq!(rows(rel), 0);
+        db.run_default(&format!(
+            "?[k, v] := k in int_range(10), v = k % 3 :put {rel} {{k => v}}"
+        ))
+        .unwrap();
+        assert_eq!(rows(rel), 10);
+        // overwriting rows does not add to the count
+        db.run_default(&format!(
+            "?[k, v] := k in int_range(5, 15), v = 0 :put {rel} {{k => v}}"
+        ))
+        .unwrap();
+        assert_eq!(rows(rel), 15);
+        // removing absent keys does not subtract from it
+        db.run_default(&format!("?[k] := k in int_range(12, 20) :rm {rel} {{k}}"))
+            .unwrap();
+        assert_eq!(rows(rel), 12);
+        assert_eq!(rows(rel), actual(rel));
+
+        // failed and aborted transactions leave the count alone
+        let script = format!(
+            "{{?[k, v] <- [[100, 1]] :put {rel} {{k => v}}}} \
+             {{?[k] <- [[1]] :rm {rel} {{k}}}} \
+             {{?[k, v] <- [[2, 2]] :insert {rel} {{k => v}}}}"
+        );
+        assert!(db.run_default(&script).is_err());
+        assert_eq!(rows(rel), 12);
+        let tx = db.multi_transaction(true);
+        tx.run_script(
+            &format!("?[k] := k in int_range(5) :rm {rel} {{k}}"),
+            Default::default(),
+        )
+        .unwrap();
+        tx.abort().unwrap();
+        assert_eq!(rows(rel), 12);
+        assert_eq!(rows(rel), actual(rel));
+    }
+
+    db.import_relations(BTreeMap::from([(
+        "plain".to_string(),
+        NamedRows::new(
+            vec!["k".to_string(), "v".to_string()],
+            vec![
+                vec![DataValue::from(0), DataValue::from(0)],
+                vec![DataValue::from(50), DataValue::from(0)],
+            ],
+        ),
+    )]))
+    .unwrap();
+    assert_eq!(rows("plain"), 13);
+    db.import_rows(
+        "indexed",
+        (20..25).map(|k| BTreeMap::from([("k".to_string(), DataValue::from(k))])),
+        Default::default(),
+    )
+    .unwrap();
+    assert_eq!(rows("indexed"), 17);
+    for rel in ["plain", "indexed"] {
+        assert_eq!(rows(rel), actual(rel));
+    }
+
+    // each transaction keeps its own part of the count, and many parts are folded together
+    for k in 100..250 {
+        db.run_default(&format!("?[k, v] <- [[{k}, 0]] :put indexed {{k => v}}"))
+            .unwrap();
+    }
+    assert_eq!(rows("indexed"), 167);
+    assert_eq!(rows("indexed"), actual("indexed"));
+
+    // replacing starts the count afresh
+    db.run_default("?[k, v] <- [[1, 1]] :replace plain {k => v}")
+        .unwrap();
+    assert_eq!(rows("plain"), 1);
+    db.run_default("::remove plain").unwrap();
+    assert!(db.stats("plain").is_err());
+}
+
+#[test]
+fn distinct_counts() {
+    let db = DbInstance::default();
+    db.run_default(
+        r"
+        ?[id, tag] <- [[1, 'x'], [2, 'y'], [3, 'x'], [4, null], [5, null], [6, 1], [7, 1.0]]
+        :create tagged {id => tag}
+    ",
+    )
+    .unwrap();
+    // the integer 1 and the float 1.0 are different values
+    assert_eq!(db.count_distinct("tagged", "tag", false).unwrap(), 5);
+    assert_eq!(db.count_distinct("tagged", "id", false).unwrap(), 7);
+    // small counts are estimated exactly
+    assert_eq!(db.count_distinct("tagged", "tag", true).unwrap(), 5);
+    assert!(db.count_distinct("tagged", "nothing", false).is_err());
+    assert!(db.count_distinct("nothing", "tag", false).is_err());
+
+    db.run_default(
+        "?[k, v] := k in int_range(60000), v = concat('v', to_string(k % 25000)) \
+         :create big {k => v}",
+    )
+    .unwrap();
+    assert_eq!(db.count_distinct("big", "v", false).unwrap(), 25000);
+    let estimate = db.count_distinct("big", "v", true).unwrap() as f64;
+    assert!(
+        (estimate - 25000.).abs() / 25000. < 0.03,
+        "estimate {estimate} is off"
+    );
 }
 
 #[test]
-fn returning() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    db.run_default(":create a {x => y}").unwrap();
-    let res = db
-        .run_default(r"?[x, y] <- [[1, 2]] :insert a {x => y} ")
+fn vacuum_orphaned_data() {
+    let db = DbInstance::default();
+    db.run_default("?[k, v] := k in int_range(5), v = k * 2 :create kept {k => v}")
         .unwrap();
-    assert_eq!(res.into_json()["rows"], json!([["OK"]]));
-    // for row in res.into_json()["rows"].as_array().unwrap() {
-    //     println!("{}", row);
-    // }
-
-    let res = db
-        .run_default(r"?[x, y] <- [[1, 3], [2, 4]] :returning :put a {x => y} ")
+    db.run_default("?[k] := k in int_range(3) :create orphaned {k}")
         .unwrap();
+    db.run_default("::index create kept:by_v {v}").unwrap();
+    assert_eq!(db.vacuum().unwrap(), 0);
+
+    // remove only the metadata of the relation, leaving its rows behind
+    let mem_db = match &db {
+        DbInstance::Mem(mem_db) => mem_db,
+        _ => unreachable!(),
+    };
+    let mut tx = mem_db.transact_write().unwrap();
+    let handle = tx.get_relation("orphaned", false).unwrap();
+    let name_key = vec![DataValue::from("orphaned")].encode_as_key(RelationId::SYSTEM);
+    tx.store_tx.del(&name_key).unwrap();
+    tx.commit_tx().unwrap();
+    drop(tx);
+    let tx = mem_db.transact().unwrap();
+    assert_eq!(handle.scan_all(&tx).count(), 3);
+    drop(tx);
+
+    assert_eq!(db.vacuum().unwrap(), 3);
+    let tx = mem_db.transact().unwrap();
+    assert_eq!(handle.scan_all(&tx).count(), 0);
+    drop(tx);
+    assert_eq!(db.vacuum().unwrap(), 0);
+    // live relations and their indices are untouched
+    let res = db.run_default("?[k] := *kept:by_v{v: 4, k}").unwrap();
+    assert_eq!(res.rows, vec![vec![DataValue::from(2)]]);
     assert_eq!(
-        res.into_json()["rows"],
-        json!([["inserted", 1, 3], ["inserted", 2, 4], ["replaced", 1, 2]])
+        db.run_default("?[k, v] := *kept{k, v}").unwrap().rows.len(),
+        5
     );
-    // println!("{:?}", res.headers);
-    // for row in res.into_json()["rows"].as_array().unwrap() {
-    //     println!("{}", row);
-    // }
+}
 
-    let res = db
-        .run_default(r"?[x] <- [[1], [4]] :returning :rm a {x} ")
+#[test]
+fn snapshot_and_restore() {
+    let db = DbInstance::default();
+    db.run_default(
+        r#"
+        data[id, name, score, tags, b64] <- [
+            [1, 'Alice', 1.5, ['a', 'b'], 'AP8='],
+            [2, 'Bob', null, [], ''],
+            [3, 'Carol', -0.0, [1, [2, 3]], 'EA==']
+        ]
+        ?[id, name, score, tags, blob] := data[id, name, score, tags, b64],
+                                          blob = decode_base64(b64)
+        :create person {id: Int => name: String, score: Float?, tags: [Any], blob: Bytes,
+                        joined: String default 'never'}
+    "#,
+    )
+    .unwrap();
+    db.run_default("?[a, b] <- [[1, 2], [2, 1]] :create edge {a, b}")
         .unwrap();
-    // println!("{:?}", res.headers);
-    // for row in res.into_json()["rows"].as_array().unwrap() {
-    //     println!("{}", row);
-    // }
-    assert_eq!(
-        res.into_json()["rows"],
-        json!([
-            ["requested", 1, null],
-            ["requested", 4, null],
-            ["deleted", 1, 3]
-        ])
-    );
-    db.run_default(r":create todo{id:Uuid default rand_uuid_v1() => label: String, done: Bool}")
+    db.run_default("::index create person:by_name {name}")
         .unwrap();
-    let res = db
-        .run_default(r"?[label,done] <- [['milk',false]] :put todo{label,done} :returning")
+    db.run_default("::index create unique edge:by_b {b}")
         .unwrap();
-    assert_eq!(res.rows[0].len(), 4);
-    for title in res.headers.iter() {
-        print!("{} ", title);
-    }
-    println!();
-    for row in res.into_json()["rows"].as_array().unwrap() {
-        println!("{}", row);
+    db.run_default("::index create edge:ba {b, a}").unwrap();
+
+    let mut buf = vec![];
+    db.snapshot(&mut buf).unwrap();
+    let restored = DbInstance::default();
+    restored.restore(buf.as_slice()).unwrap();
+
+    for query in [
+        "?[id, name, score, tags, blob, joined] := *person{id, name, score, tags, blob, joined}",
+        "?[a, b] := *edge{a, b}",
+        "?[id] := *person:by_name{name: 'Bob', id}",
+        "?[a, b] := *edge:ba{a, b}",
+        "::relations",
+        "::indices edge",
+    ] {
+        assert_eq!(
+            db.run_default(query).unwrap().rows,
+            restored.run_default(query).unwrap().rows,
+            "{query}"
+        );
     }
+    // indices are maintained, and the column defaults kept
+    restored
+        .run_default(
+            "?[id, name, tags, blob] := id = 4, name = 'Dave', tags = [], blob = decode_base64('') \
+             :put person {id => name, tags, blob}",
+        )
+        .unwrap();
+    let res = restored
+        .run_default("?[id, joined] := *person:by_name{name: 'Dave', id}, *person{id, joined}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[4, "never"]]));
+    let err = restored
+        .run_default("?[a, b] <- [[3, 2]] :put edge {a, b}")
+        .unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "tx::duplicate_key");
+
+    // only a fresh database can be restored into
+    assert!(restored.restore(buf.as_slice()).is_err());
+    assert!(DbInstance::default()
+        .restore(&b"not a snapshot"[..])
+        .is_err());
+    // nor can a snapshot that was cut short
+    assert!(DbInstance::default()
+        .restore(&buf[..buf.len() - 1])
+        .is_err());
 }
 
 #[test]
-fn parser_corner_case() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    db.run_default(r#"?[x] := x = 1 or x = 2"#).unwrap();
-    db.run_default(r#"?[C] := C = 1  orx[C] := C = 1"#).unwrap();
-    db.run_default(r#"?[C] := C = true, C  inx[C] := C = 1"#)
+fn atomic_batch() {
+    let db = DbInstance::default();
+    db.run_default(":create account {name: String => balance: Int}")
         .unwrap();
-    db.run_default(r#"?[k] := k in int_range(300)"#).unwrap();
-    db.run_default(r#"ywcc[a] <- [[1]] noto[A] := ywcc[A] ?[A] := noto[A]"#)
+    db.run_default(":create log {seq: Int => msg: String}")
         .unwrap();
-}
+    db.run_default("?[name, balance] <- [['a', 10], ['b', 0]] :put account {name => balance}")
+        .unwrap();
+    let state = || {
+        db.export_relations(["account", "log"].iter())
+            .unwrap()
+            .into_iter()
+            .map(|(k, v)| (k, v.rows))
+            .collect::<BTreeMap<_, _>>()
+    };
+    db.batch(
+        [
+            "?[name, balance] := *account{name, balance: b}, name = 'a', balance = b - $amount \
+             :put account {name => balance}",
+            "?[name, balance] := *account{name, balance: b}, name = 'b', balance = b + $amount \
+             :put account {name => balance}",
+            "?[seq, msg] <- [[1, 'transfer']] :insert log {seq => msg}",
+        ],
+        BTreeMap::from([("amount".to_string(), DataValue::from(3))]),
+    )
+    .unwrap();
+    let before = state();
+    assert_eq!(
+        before["account"],
+        vec![
+            vec![DataValue::from("a"), DataValue::from(7)],
+            vec![DataValue::from("b"), DataValue::from(3)]
+        ]
+    );
 
-#[test]
-fn as_store_in_imperative_script() {
-    let db = DbInstance::new("mem", "", "").unwrap();
-    let res = db
-        .run_default(
-            r#"
-    { ?[x, y, z] <- [[1, 2, 3], [4, 5, 6]] } as _store
-    { ?[x, y, z] := *_store{x, y, z} }
-    "#,
+    // the second insertion fails as the key exists, so the first one is undone
+    assert!(db
+        .batch(
+            [
+                "?[name, balance] <- [['c', 1]] :insert account {name => balance}",
+                "?[seq, msg] <- [[1, 'again']] :insert log {seq => msg}",
+            ],
+            Default::default(),
         )
-        .unwrap();
-    assert_eq!(res.into_json()["rows"], json!([[1, 2, 3], [4, 5, 6]]));
-    let res = db
-        .run_default(
-            r#"
-    {
-        ?[y] <- [[1], [2], [3]]
-        :create a {x default rand_uuid_v1() => y}
-        :returning
-    } as _last
-    {
-        ?[x] := *_last{_kind: 'inserted', x}
-    }
-    "#,
+        .is_err());
+    assert_eq!(state(), before);
+    // so are parse errors, and scripts other than single queries
+    assert!(db
+        .batch(
+            [
+                "?[name, balance] <- [['c', 1]] :insert account {name => balance}",
+                "?[x] <- [[1]] :insert log {",
+            ],
+            Default::default(),
         )
-        .unwrap();
-    assert_eq!(3, res.rows.len());
-    for row in res.into_json()["rows"].as_array().unwrap() {
-        println!("{}", row);
-    }
+        .is_err());
     assert!(db
-        .run_default(
-            r#"
-    {
-        ?[x, x] := x = 1
-    } as _last
-    "#
+        .batch(
+            [
+                "?[name, balance] <- [['c', 1]] :insert account {name => balance}",
+                "::remove log",
+            ],
+            Default::default(),
         )
         .is_err());
+    assert_eq!(state(), before);
+}
 
-    let res = db
-        .run_default(
-            r#"
-    {
-        x[y] <- [[1], [2], [3]]
-        ?[sum(y)] := x[y]
-    } as _last
-    {
-        ?[sum_y] := *_last{sum_y}
-    }
-    "#,
-        )
+#[test]
+fn incoming_edges() {
+    let db = DbInstance::default();
+    db.run_default(":create friends {fr: Int, to: Int => relation: String}")
         .unwrap();
-    assert_eq!(1, res.rows.len());
-    for row in res.into_json()["rows"].as_array().unwrap() {
-        println!("{}", row);
-    }
+    db.run_default(
+        r"?[fr, to, relation] <- [[1, 3, 'colleague'], [4, 3, 'sibling'], [2, 3, 'neighbour'],
+                                  [3, 1, 'colleague'], [2, 5, 'sibling']]
+          :put friends {fr, to => relation}",
+    )
+    .unwrap();
+    let sources = |dst: i64| {
+        db.in_edges("friends", &[DataValue::from(dst)])
+            .unwrap()
+            .into_iter()
+            .map(|src| src[0].get_int().unwrap())
+            .collect_vec()
+    };
+    assert_eq!(sources(3), vec![1, 2, 4]);
+    assert_eq!(sources(1), vec![3]);
+    assert_eq!(sources(2), Vec::<i64>::new());
+
+    // the same results through the reverse index
+    db.run_default("::index create friends:rev {to}").unwrap();
+    assert_eq!(sources(3), vec![1, 2, 4]);
+    assert_eq!(sources(1), vec![3]);
+    assert_eq!(sources(2), Vec::<i64>::new());
+
+    // keys are coerced, and the node must fit into the key columns
+    assert_eq!(
+        db.in_edges("friends", &[DataValue::from(3.0)])
+            .unwrap()
+            .len(),
+        3
+    );
+    assert!(db
+        .in_edges("friends", &[DataValue::from(1), DataValue::from(2)])
+        .is_err());
+    assert!(db.in_edges("friends", &[]).is_err());
 }
 
 #[test]
-fn update_shall_not_destroy_values() {
+fn outgoing_edges() {
     let db = DbInstance::default();
-    db.run_default(r"?[x, y] <- [[1, 2]] :create z {x => y default 0}")
+    db.run_default(":create friends {fr: Int, to: Int => relation: String, since: Int?}")
         .unwrap();
-    let r = db.run_default(r"?[x, y] := *z {x, y}").unwrap();
-    assert_eq!(r.into_json()["rows"], json!([[1, 2]]));
-    db.run_default(r"?[x] <- [[1]] :update z {x}").unwrap();
-    let r = db.run_default(r"?[x, y] := *z {x, y}").unwrap();
-    assert_eq!(r.into_json()["rows"], json!([[1, 2]]));
+    db.run_default(
+        r"?[fr, to, relation, since] <- [[1, 3, 'colleague', 2019], [1, 2, 'sibling', null],
+                                         [2, 1, 'sibling', null], [3, 4, 'neighbour', 2021]]
+          :put friends {fr, to => relation, since}",
+    )
+    .unwrap();
+    let edges = db.out_edges("friends", &[DataValue::from(1)]).unwrap();
+    assert_eq!(
+        edges,
+        vec![
+            (
+                vec![DataValue::from(2)],
+                vec![DataValue::from("sibling"), DataValue::Null]
+            ),
+            (
+                vec![DataValue::from(3)],
+                vec![DataValue::from("colleague"), DataValue::from(2019)]
+            ),
+        ]
+    );
+    assert!(db
+        .out_edges("friends", &[DataValue::from(4)])
+        .unwrap()
+        .is_empty());
+    assert!(db.out_edges("friends", &[DataValue::from("x")]).is_err());
 }
 
 #[test]
-fn update_shall_work() {
+fn edges_as_rows() {
     let db = DbInstance::default();
-    db.run_default(r"?[x, y, z] <- [[1, 2, 3]] :create z {x => y, z}")
+    db.run_default(":create friends {fr: Int, to: Int => relation: String}")
         .unwrap();
-    let r = db.run_default(r"?[x, y, z] := *z {x, y, z}").unwrap();
-    assert_eq!(r.into_json()["rows"], json!([[1, 2, 3]]));
-    db.run_default(r"?[x, y] <- [[1, 4]] :update z {x, y}")
+    db.run_default("::index create friends:rev {to, fr}")
         .unwrap();
-    let r = db.run_default(r"?[x, y, z] := *z {x, y, z}").unwrap();
-    assert_eq!(r.into_json()["rows"], json!([[1, 4, 3]]));
+    db.run_default(
+        r"?[fr, to, relation] <- [[2, 1, 'sibling'], [1, 3, 'colleague']]
+          :put friends {fr, to => relation}",
+    )
+    .unwrap();
+    let res = db.edges("friends", 1).unwrap();
+    assert_eq!(res.headers, ["_src", "_dst", "relation"]);
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([[1, 3, "colleague"], [2, 1, "sibling"]])
+    );
+
+    db.run_default(":create routes {fr_city: String, fr_stop: Int, to_city: String, to_stop: Int}")
+        .unwrap();
+    db.run_default(
+        "?[fr_city, fr_stop, to_city, to_stop] <- [['a', 1, 'b', 2]] \
+         :put routes {fr_city, fr_stop, to_city, to_stop}",
+    )
+    .unwrap();
+    let res = db.edges("routes", 2).unwrap();
+    assert_eq!(res.headers, ["_src", "_dst"]);
+    assert_eq!(res.into_json()["rows"], json!([[["a", 1], ["b", 2]]]));
+    assert!(db.edges("routes", 3).is_err());
+    assert!(db.edges("friends", 0).is_err());
 }
 
 #[test]
-fn sysop_in_imperatives() {
-    let script = r#"
-    {
-            :create cm_src {
-                aid: String =>
-                title: String,
-                author: String?,
-                kind: String,
-                url: String,
-                domain: String?,
-                pub_time: Float?,
-                dt: Float default now(),
-                weight: Float default 1,
-            }
-        }
-        {
-            :create cm_txt {
-                tid: String =>
-                aid: String,
-                tag: String,
-                follows_tid: String?,
-                dup_for: String?,
-                text: String,
-                info_amount: Int,
-            }
-        }
-        {
-            :create cm_seg {
-                sid: String =>
-                tid: String,
-                tag: String,
-                part: Int,
-                text: String,
-                vec: <F32; 1536>,
-            }
-        }
-        {
-            ::hnsw create cm_seg:vec {
-                dim: 1536,
-                m: 50,
-                dtype: F32,
-                fields: vec,
-                distance: Cosine,
-                ef: 100,
-            }
-        }
-        {
-            ::lsh create cm_txt:lsh {
-                extractor: text,
-                extract_filter: is_null(dup_for),
-                tokenizer: NGram,
-                n_perm: 200,
-                target_threshold: 0.5,
-                n_gram: 7,
-            }
-        }
-        {::relations}
-    "#;
+fn parallel_edges() {
     let db = DbInstance::default();
-    db.run_default(script).unwrap();
+    // the key columns after the destination tell apart edges between the same nodes
+    db.run_default(":create friends {fr: Int, to: Int, at: Int => relation: String}")
+        .unwrap();
+    db.run_default("::index create friends:rev {to, fr}")
+        .unwrap();
+    db.run_default(
+        r"?[fr, to, at, relation] <- [[1, 2, 2001, 'colleague'], [1, 2, 2010, 'sibling']]
+          :put friends {fr, to, at => relation}",
+    )
+    .unwrap();
+    let edges = db.out_edges("friends", &[DataValue::from(1)]).unwrap();
+    assert_eq!(
+        edges,
+        vec![
+            (
+                vec![DataValue::from(2)],
+                vec![DataValue::from(2001), DataValue::from("colleague")]
+            ),
+            (
+                vec![DataValue::from(2)],
+                vec![DataValue::from(2010), DataValue::from("sibling")]
+            ),
+        ]
+    );
+    let res = db.edges("friends", 1).unwrap();
+    assert_eq!(res.headers, ["_src", "_dst", "at", "relation"]);
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([[1, 2, 2001, "colleague"], [1, 2, 2010, "sibling"]])
+    );
+    // the nodes are only connected once
+    assert_eq!(
+        db.in_edges("friends", &[DataValue::from(2)]).unwrap(),
+        vec![vec![DataValue::from(1)]]
+    );
+    assert_eq!(db.degree("friends", &[DataValue::from(1)]).unwrap(), (2, 0));
+
+    // also when the edges between them are apart in the index used
+    db.run_default(
+        "?[fr, to, at, relation] <- [[3, 2, 2005, 'sibling']] \
+         :put friends {fr, to, at => relation}",
+    )
+    .unwrap();
+    db.run_default("::index drop friends:rev").unwrap();
+    db.run_default("::index create friends:by_to_at {to, at}")
+        .unwrap();
+    assert_eq!(
+        db.in_edges("friends", &[DataValue::from(2)]).unwrap(),
+        vec![vec![DataValue::from(1)], vec![DataValue::from(3)]]
+    );
 }
 
 #[test]
-fn puts() {
+fn breadth_first_traversal() {
     let db = DbInstance::default();
+    db.run_default(":create friends {fr: Int, to: Int}")
+        .unwrap();
+    // 1 -> 2 -> 3 -> 1 is a cycle, 3 -> 4 -> 5 leads away from it, 6 is unreachable
     db.run_default(
-        r"
-            :create cm_txt {
-                tid: String =>
-                aid: String,
-                tag: String,
-                follows_tid: String? default null,
-                for_qs: [String] default [],
-                dup_for: String? default null,
-                text: String,
-                seg_vecs: [<F32; 1536>],
-                seg_pos: [(Int, Int)],
-                format: String default 'text',
-                info_amount: Int,
-            }
-    ",
+        "?[fr, to] <- [[1, 2], [2, 3], [3, 1], [3, 4], [1, 3], [4, 5], [6, 1]] :put friends {fr, to}",
     )
     .unwrap();
+    let reached = |max_depth: usize| {
+        db.traverse("friends", &[DataValue::from(1)], max_depth)
+            .unwrap()
+            .into_iter()
+            .map(|(node, dist)| (node[0].get_int().unwrap(), dist))
+            .collect_vec()
+    };
+    assert_eq!(reached(0), vec![(1, 0)]);
+    assert_eq!(reached(1), vec![(1, 0), (2, 1), (3, 1)]);
+    assert_eq!(reached(2), vec![(1, 0), (2, 1), (3, 1), (4, 2)]);
+    assert_eq!(reached(10), vec![(1, 0), (2, 1), (3, 1), (4, 2), (5, 3)]);
+}
+
+#[test]
+fn bounded_path_enumeration() {
+    let db = DbInstance::default();
+    db.run_default(":create friends {fr: Int, to: Int}")
+        .unwrap();
+    // 1 -> 2 -> 3 -> 1 is a cycle, 3 -> 4 leads away from it
+    db.run_default("?[fr, to] <- [[1, 2], [2, 3], [3, 1], [1, 3], [3, 4]] :put friends {fr, to}")
+        .unwrap();
+    let paths = |max_depth: usize| {
+        db.paths("friends", &[DataValue::from(1)], max_depth)
+            .unwrap()
+            .into_iter()
+            .map(|path| {
+                path.iter()
+                    .map(|node| node[0].get_int().unwrap())
+                    .collect_vec()
+            })
+            .collect_vec()
+    };
+    assert!(paths(0).is_empty());
+    assert_eq!(paths(1), vec![vec![1, 2], vec![1, 3]]);
+    // paths back to 1 are cut off instead of going around the cycle
+    assert_eq!(
+        paths(2),
+        vec![vec![1, 2], vec![1, 3], vec![1, 2, 3], vec![1, 3, 4]]
+    );
+    assert_eq!(
+        paths(10),
+        vec![
+            vec![1, 2],
+            vec![1, 3],
+            vec![1, 2, 3],
+            vec![1, 3, 4],
+            vec![1, 2, 3, 4]
+        ]
+    );
+    assert!(db.paths("friends", &[DataValue::from("x")], 2).is_err());
+}
+
+#[test]
+fn shortest_paths() {
+    let db = DbInstance::default();
+    db.run_default(":create road {fr: String, to: String => km: Float}")
+        .unwrap();
     db.run_default(
-        r"
-        ?[tid, aid, tag, text, info_amount, dup_for, seg_vecs, seg_pos] := dup_for = null,
-                tid = 'x', aid = 'y', tag = 'z', text = 'w', info_amount = 12,
-                follows_tid = null, for_qs = [], format = 'x',
-                seg_vecs = [], seg_pos = [[0, 10]]
-        :put cm_txt {tid, aid, tag, text, info_amount, seg_vecs, seg_pos, dup_for}
-    ",
+        r"?[fr, to, km] <- [['a', 'b', 1.0], ['b', 'c', 1.0], ['c', 'd', 1.0], ['a', 'e', 5.0],
+                            ['e', 'd', 5.0], ['d', 'a', 1.0], ['x', 'y', 1.0]]
+          :put road {fr, to => km}",
     )
     .unwrap();
+    let path = |src: &str, dst: &str| {
+        db.shortest_path("road", &[DataValue::from(src)], &[DataValue::from(dst)])
+            .unwrap()
+            .map(|path| {
+                path.into_iter()
+                    .map(|node| node[0].get_str().unwrap().to_string())
+                    .collect_vec()
+            })
+    };
+    // edges are unweighted, so the route through 'e' is shorter
+    assert_eq!(path("a", "d").unwrap(), ["a", "e", "d"]);
+    assert_eq!(path("b", "a").unwrap(), ["b", "c", "d", "a"]);
+    assert_eq!(path("a", "a").unwrap(), ["a"]);
+    assert_eq!(path("a", "x"), None);
+    assert_eq!(path("y", "x"), None);
 }
 
 #[test]
-fn short_hand() {
+fn node_degrees() {
     let db = DbInstance::default();
-    db.run_default(r":create x {x => y, z}").unwrap();
-    db.run_default(r"?[x, y, z] <- [[1, 2, 3]] :put x {}")
+    db.run_default(":create friends {fr: Int, to: Int => relation: String}")
         .unwrap();
-    let r = db.run_default(r"?[x, y, z] := *x {x, y, z}").unwrap();
-    assert_eq!(r.into_json()["rows"], json!([[1, 2, 3]]));
+    db.run_default(
+        r"?[fr, to, relation] <- [[1, 2, 'x'], [1, 3, 'x'], [1, 4, 'x'], [2, 1, 'x'], [3, 1, 'x'],
+                                  [4, 4, 'x']]
+          :put friends {fr, to => relation}",
+    )
+    .unwrap();
+    let degree = |node: i64| db.degree("friends", &[DataValue::from(node)]).unwrap();
+    assert_eq!(degree(1), (3, 2));
+    assert_eq!(degree(4), (1, 2));
+    assert_eq!(degree(5), (0, 0));
+    db.run_default("::index create friends:rev {to}").unwrap();
+    assert_eq!(degree(1), (3, 2));
+    assert_eq!(degree(4), (1, 2));
+    assert_eq!(degree(5), (0, 0));
 }
 
 #[test]
-fn param_shorthand() {
+fn parallel_edge_count() {
     let db = DbInstance::default();
-    db.run_script(
-        r"
-        ?[] <- [[$x, $y, $z]]
-        :create x {}
-    ",
-        BTreeMap::from([
-            ("x".to_string(), DataValue::from(1)),
-            ("y".to_string(), DataValue::from(2)),
-            ("z".to_string(), DataValue::from(3)),
-        ]),
-        ScriptMutability::Mutable,
+    db.run_default(":create friend {fr: Int, to: Int, since: Int => note: String}")
+        .unwrap();
+    db.run_default(
+        r"?[fr, to, since, note] <- [[1, 2, 2001, 'a'], [1, 2, 2005, 'b'], [1, 2, 2010, 'c'],
+                                     [1, 3, 2001, 'd'], [2, 1, 2001, 'e']]
+          :put friend {fr, to, since => note}",
     )
     .unwrap();
-    let res = db.run_default(r"?[x, y, z] := *x {x, y, z}");
-    assert_eq!(res.unwrap().into_json()["rows"], json!([[1, 2, 3]]));
+    let count = |fr: i64, to: i64| {
+        db.count_edges_between("friend", &[DataValue::from(fr)], &[DataValue::from(to)])
+            .unwrap()
+    };
+    assert_eq!(count(1, 2), 3);
+    assert_eq!(count(1, 3), 1);
+    assert_eq!(count(2, 1), 1);
+    assert_eq!(count(3, 1), 0);
+    assert!(db
+        .count_edges_between("friend", &[DataValue::from(1)], &[])
+        .is_err());
 }
 
 #[test]
-fn crashy_imperative() {
+fn optional_columns_and_defaults() {
     let db = DbInstance::default();
-    db.run_default(
-        r"
-        {:create _test {a}}
+    // the value chosen for an absent column: the default if there is one, otherwise null
+    for (typing, expected) in [
+        ("String? default 'x'", Some(DataValue::from("x"))),
+        ("String? default null", Some(DataValue::Null)),
+        ("String?", Some(DataValue::Null)),
+        ("String default 'x'", Some(DataValue::from("x"))),
+        ("Int default -1", Some(DataValue::from(-1))),
+        ("String", None),
+    ] {
+        db.run_default(&format!(":create t {{k: Int => v: {typing}}}"))
+            .unwrap();
+        let res = db.run_default("?[k] <- [[1]] :put t {k}");
+        match expected {
+            Some(val) => {
+                res.unwrap();
+                let rows = db.run_default("?[v] := *t{k: 1, v}").unwrap().rows;
+                assert_eq!(rows, vec![vec![val]], "{typing}");
+            }
+            None => assert!(res.is_err(), "{typing}"),
+        }
+        db.run_default("::remove t").unwrap();
+    }
 
-        %loop
-            %if { len[count(x)] := *_test[x]; ?[x] := len[z], x = z >= 10 }
-                %then %return _test
-            %end
-            { ?[a] := a = rand_uuid_v1(); :put _test {a} }
-        %end
-        ",
+    // defaults that can never be stored are rejected on creation
+    for typing in ["String default null", "Int default 'x'", "Int? default [1]"] {
+        let res = db.run_default(&format!(":create t {{k: Int => v: {typing}}}"));
+        assert!(res.is_err(), "{typing}");
+    }
+}
+
+#[test]
+fn case_insensitive_names() {
+    let db = DbInstance::default();
+    db.run_default(":create Person {Id: Int => Name: String}")
+        .unwrap();
+    db.run_default("?[Id, Name] <- [[1, 'a']] :put Person {Id => Name}")
+        .unwrap();
+    // names are case-sensitive by default
+    assert!(db.run_default("?[id] := *person{id}").is_err());
+    assert!(db.run_default("?[id] := *Person{id}").is_err());
+    assert!(db.stats("person").is_err());
+
+    let db = DbInstance::default();
+    db.set_case_insensitive_names(true);
+    db.run_default(":create Person {Id: Int => Name: String}")
+        .unwrap();
+    db.run_default("?[Id, Name] <- [[1, 'a'], [2, 'b']] :put PERSON {Id => Name}")
+        .unwrap();
+    let res = db.run_default("?[id, name] := *person{id, name}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1, "a"], [2, "b"]]));
+    let res = db.run_default("?[x] := *PERSON{ID: x, NAME: 'b'}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[2]]));
+    let res = db.run_default("?[x] := *Person[x, _]").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1], [2]]));
+
+    db.run_default("::index create PERSON:By_Name {NAME}")
+        .unwrap();
+    let res = db
+        .run_default("?[id] := *person:by_name{name: 'a', id}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1]]));
+    let res = db.run_default("::columns PERSON").unwrap();
+    assert_eq!(res.into_json()["rows"].as_array().unwrap().len(), 2);
+    assert_eq!(db.stats("PERSON").unwrap().rows, 2);
+
+    db.set_case_insensitive_names(false);
+    assert!(db.run_default("?[id] := *Person{id}").is_err());
+    let res = db.run_default("?[id] := *person{id}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1], [2]]));
+}
+
+#[test]
+fn read_only_handle() {
+    let db = DbInstance::default();
+    db.run_default(":create person {id: Int => name: String}")
+        .unwrap();
+    let reader = db.read_only();
+    assert!(reader
+        .run_default("?[id, name] <- [[1, 'Alice']] :put person {id => name}")
+        .is_err());
+    assert!(reader.run_default(":create other {k}").is_err());
+    let data = BTreeMap::from([(
+        "person".to_string(),
+        NamedRows::new(
+            vec!["id".to_string(), "name".to_string()],
+            vec![vec![DataValue::from(1), DataValue::from("Alice")]],
+        ),
+    )]);
+    let err = reader.import_relations(data.clone()).unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "db::read_only");
+    assert!(reader
+        .delete_where("person", "true", Default::default())
+        .is_err());
+    let err = reader.restore_backup("backup.db").unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "db::read_only");
+    assert!(reader.run_default("::compact").is_err());
+
+    // writes through the original handle are seen by the read-only one
+    db.import_relations(data).unwrap();
+    let res = reader
+        .run_default("?[id, name] := *person{id, name}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1, "Alice"]]));
+    assert_eq!(reader.keys("person").unwrap().len(), 1);
+    assert!(reader.run_default("::relations").is_ok());
+
+    // settings are changed for each handle on its own
+    reader.set_case_insensitive_names(true);
+    assert!(reader.run_default("?[id] := *PERSON{id}").is_ok());
+    assert!(db.run_default("?[id] := *PERSON{id}").is_err());
+}
+
+#[test]
+fn raw_strings_are_verbatim() {
+    let db = DbInstance::default();
+    let parse = |lit: &str| {
+        db.run_default(&format!("?[a] <- [[{lit}]]"))
+            .map(|res| res.rows[0][0].clone())
+    };
+    assert_eq!(parse(r#"__"a"_b"__"#).unwrap(), DataValue::from(r#"a"_b"#));
+    assert_eq!(parse(r#"__"a"_"__"#).unwrap(), DataValue::from(r#"a"_"#));
+    assert_eq!(
+        parse(r#"_____"a"__"b"____"c"_____"#).unwrap(),
+        DataValue::from(r#"a"__"b"____"c"#)
+    );
+    assert_eq!(parse(r#"_"""_"#).unwrap(), DataValue::from(r#"""#));
+    assert_eq!(parse(r#"_""_"#).unwrap(), DataValue::from(""));
+    // whitespace, backslashes and comment markers are kept as they are
+    let text = "\n  first line\n\tsecond /* not a comment */ # nor this\n\n  ";
+    assert_eq!(
+        parse(&format!("__\"{text}\"__")).unwrap(),
+        DataValue::from(text)
+    );
+    let text = "\n  first line\n\n";
+    assert_eq!(
+        parse(&format!("_\"{text}\"_")).unwrap(),
+        DataValue::from(text)
+    );
+    // the closing delimiter must follow the quote immediately
+    assert!(parse(r#"_"a" _"#).is_err());
+    assert!(parse(r#"__"a"_"#).is_err());
+}
+
+#[test]
+fn existence_checks_in_rule_bodies() {
+    // a stored relation in a rule body is an existence check: each row of the head
+    // is produced once however many rows of the relation match
+    let db = DbInstance::default();
+    db.run_default(":create person {id: Int => joined: Int}")
+        .unwrap();
+    db.run_default(":create friend {fr: Int, to: Int => since: Int}")
+        .unwrap();
+    db.run_default("?[id, joined] <- [[1, 2000], [2, 2010], [3, 2020]] :put person {id => joined}")
+        .unwrap();
+    db.run_default(
+        "?[fr, to, since] <- [[1, 2, 2001], [1, 3, 2021], [2, 1, 2005]] :put friend {fr, to => since}",
     )
     .unwrap();
+    let ids = |script: &str| {
+        db.run_default(script)
+            .unwrap()
+            .rows
+            .into_iter()
+            .map(|row| row[0].get_int().unwrap())
+            .collect_vec()
+    };
+    assert_eq!(ids("?[id] := *person{id}, *friend{fr: id}"), vec![1, 2]);
+    // the condition on the matching rows may refer to the outer row
+    assert_eq!(
+        ids("?[id] := *person{id, joined}, *friend{fr: id, since}, since > joined"),
+        vec![1]
+    );
+    assert_eq!(ids("?[id] := *person{id}, not *friend{fr: id}"), vec![3]);
+    assert_eq!(
+        ids("?[id] := *person{id, joined}, (joined > 2015 or *friend{to: id, since: 2005})"),
+        vec![1, 3]
+    );
 }
 
 #[test]
-fn hnsw_index() {
+fn join_on_keys() {
     let db = DbInstance::default();
+    db.run_default(":create person {id: Int => name: String}")
+        .unwrap();
+    db.run_default(":create work_info {id: Int => company: String, title: String}")
+        .unwrap();
+    db.run_default(":create tags {id: Int, tag: String}")
+        .unwrap();
+    db.run_default("?[id, name] <- [[1, 'a'], [2, 'b'], [3, 'c']] :put person {id => name}")
+        .unwrap();
     db.run_default(
-        r#"
-        :create beliefs {
-            belief_id: Uuid,
-            character_id: Uuid,
-            belief: String,
-            last_accessed_at: Validity default [floor(now()), true],
-            =>
-            details: String default "",
-            parent_belief_id: Uuid? default null,
-            valence: Float default 0,
-            aspects: [(String, Float, String, String)] default [],
-            belief_embedding: <F32; 768>,
-            details_embedding: <F32; 768>,
-        }
-        "#,
+        "?[id, company, title] <- [[1, 'x', 'ceo'], [3, 'y', 'cto']] \
+         :put work_info {id => company, title}",
     )
     .unwrap();
+
+    let res = db.join_on_keys("person", &["work_info"], false).unwrap();
+    assert_eq!(res.headers, ["id", "name", "company", "title"]);
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([[1, "a", "x", "ceo"], [3, "c", "y", "cto"]])
+    );
+    let res = db.join_on_keys("person", &["work_info"], true).unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([
+            [1, "a", "x", "ceo"],
+            [2, "b", null, null],
+            [3, "c", "y", "cto"]
+        ])
+    );
+    let res = db.join_on_keys("work_info", &["person"], false).unwrap();
+    assert_eq!(res.headers, ["id", "company", "title", "name"]);
+    assert_eq!(res.rows.len(), 2);
+
+    assert!(db.join_on_keys("person", &["tags"], false).is_err());
+    assert!(db.join_on_keys("person", &["person"], false).is_err());
+    assert!(db.join_on_keys("person", &["nothing"], false).is_err());
+}
+
+#[test]
+fn get_row_by_key() {
+    let db = DbInstance::default();
+    db.run_default(":create person {id: Int => name: String, age: Int?, tags: [String]}")
+        .unwrap();
     db.run_default(
-        r#"
-        ::hnsw create beliefs:embedding_space {
-            dim: 768,
-            m: 50,
-            dtype: F32,
-            fields: [belief_embedding, details_embedding],
-            distance: Cosine,
-            ef_construction: 20,
-            extend_candidates: false,
-            keep_pruned_connections: false,
-        }
-    "#,
+        "?[id, name, age, tags] <- [[1, 'Alice', null, ['a', 'b']]] \
+         :put person {id => name, age, tags}",
     )
     .unwrap();
-    db.run_default(r#"
-        ?[belief_id, character_id, belief, belief_embedding, details_embedding] <- [[rand_uuid_v1(), rand_uuid_v1(), "test", rand_vec(768), rand_vec(768)]]
-        :put beliefs {}
-    "#).unwrap();
-    let res = db.run_default(r#"
-            ?[belief, valence, dist, character_id, vector] := ~beliefs:embedding_space{ belief, valence, character_id |
-                query: rand_vec(768),
-                k: 100,
-                ef: 20,
-                radius: 1.0,
-                bind_distance: dist,
-                bind_vector: vector
-            }
-
-            :order -valence
-            :order dist
-    "#).unwrap();
-    println!("{}", res.into_json()["rows"][0][4]);
+    let row = db
+        .get_row("person", &[DataValue::from(1)])
+        .unwrap()
+        .unwrap();
+    assert_eq!(row.len(), 4);
+    assert_eq!(row["id"], DataValue::from(1));
+    assert_eq!(row["name"], DataValue::from("Alice"));
+    assert_eq!(row["age"], DataValue::Null);
+    assert_eq!(
+        row["tags"],
+        DataValue::List(vec![DataValue::from("a"), DataValue::from("b")])
+    );
+    assert!(db
+        .get_row("person", &[DataValue::from(2)])
+        .unwrap()
+        .is_none());
+    assert!(db.get_row("person", &[]).is_err());
+    assert!(db.get_row("nobody", &[DataValue::from(1)]).is_err());
 }
 
 #[test]
-fn fts_drop() {
+fn explain_cost_estimates() {
     let db = DbInstance::default();
     db.run_default(
-        r#"
-            :create entity {name}
-        "#,
+        "?[id, name, age] := id in int_range(100), name = to_string(id), age = id % 20 \
+         :create person {id => name, age}",
     )
     .unwrap();
+    db.run_default("::index create person:by_age {age}")
+        .unwrap();
+
+    let full = db
+        .explain_cost("?[id] := *person{id, name: '42'}", Default::default())
+        .unwrap();
+    assert_eq!(full.rows_scanned, 100);
+    assert_eq!(full.scans["person"], 100);
+
+    let indexed = db
+        .explain_cost("?[id] := *person{id, age: 2}", Default::default())
+        .unwrap();
+    assert!(indexed.rows_scanned < full.rows_scanned);
+    assert!(indexed.scans.contains_key("person:by_age"));
+    assert!(!indexed.scans.contains_key("person"));
+
+    let cost = db
+        .explain_cost(
+            "?[id] := *person{id, age}, age > 10 :limit 5",
+            Default::default(),
+        )
+        .unwrap();
+    assert_eq!(cost.rows_scanned, 100);
+    assert_eq!(cost.rows_returned, 5);
+
+    assert!(db
+        .explain_cost("?[id] := *nobody{id}", Default::default())
+        .is_err());
+}
+
+#[test]
+fn filtered_view() {
+    let db = DbInstance::default();
     db.run_default(
-        r#"
-        ::fts create entity:fts_index { extractor: name,
-            tokenizer: Simple, filters: [Lowercase]
-        }
-    "#,
+        "?[id, name, age] <- [[1, 'Alice', 30], [2, 'Bob', 12], [3, 'Carol', 18]] \
+         :create person {id => name, age}",
     )
     .unwrap();
-    db.run_default(r#"
-        ::fts drop entity:fts_index
-    "#).unwrap();
-}
\ No newline at end of file
+    db.run_default("::view create adults {?[id, name] := *person{id, name, age}, age >= 18}")
+        .unwrap();
+
+    let res = db.run_default("?[name] := *adults{name}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([["Alice"], ["Carol"]]));
+    let res = db
+        .run_default("?[id, name] := *adults[id, name], id > 1")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[3, "Carol"]]));
+
+    // views read the current rows of the relations they are defined on
+    db.run_default("?[id, name, age] <- [[2, 'Bob', 20]] :put person {id => name, age}")
+        .unwrap();
+    let res = db.run_default("?[id] := *adults{id}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1], [2], [3]]));
+
+    // views over views
+    db.run_default("::view create adult_names {?[name] := *adults{name}}")
+        .unwrap();
+    let res = db
+        .run_default("?[count(name)] := *adult_names{name}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[3]]));
+
+    assert!(db
+        .run_default("?[id, name] <- [[4, 'Dave']] :put adults {id => name}")
+        .is_err());
+    assert!(db
+        .run_default("::view create adults {?[id] := *person{id}}")
+        .is_err());
+    assert!(db.run_default(":create adults {id}").is_err());
+    assert!(db
+        .run_default("::view create sorted {?[id] := *person{id} :order id}")
+        .is_err());
+
+    db.run_default("::view drop adults").unwrap();
+    assert!(db.run_default("?[name] := *adults{name}").is_err());
+    let res = db.run_default("?[id] := *person{id}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1], [2], [3]]));
+    assert!(db.run_default("::view drop adults").is_err());
+}
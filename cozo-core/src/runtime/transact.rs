@@ -9,11 +9,13 @@
 use std::sync::atomic::{AtomicU32, AtomicU64};
 use std::sync::Arc;
 
-use miette::{bail, Result};
+use miette::{bail, Diagnostic, Result};
+use thiserror::Error;
+use uuid::Uuid;
 use crate::data::program::ReturnMutation;
 
-use crate::data::tuple::TupleT;
-use crate::data::value::DataValue;
+use crate::data::tuple::{decode_tuple_from_key, TupleT, ENCODED_KEY_MIN_LEN};
+use crate::data::value::{normalize_float, DataValue, Num};
 use crate::fts::TokenizerCache;
 use crate::{CallbackOp, NamedRows};
 use crate::runtime::callback::CallbackCollector;
@@ -27,15 +29,47 @@ pub struct SessionTx<'a> {
     pub(crate) relation_store_id: Arc<AtomicU64>,
     pub(crate) temp_store_id: AtomicU32,
     pub(crate) tokenizers: Arc<TokenizerCache>,
+    /// Whether relation names are looked up case-insensitively.
+    pub(crate) fold_case: bool,
+    /// Key part under which this transaction keeps its changes to row counts.
+    pub(crate) row_count_part: Uuid,
 }
 
-pub const CURRENT_STORAGE_VERSION: [u8; 1] = [0x00];
+/// Version 1 encodes negative zero as zero and all NaNs alike in keys.
+/// Storages of version 0 are upgraded when opened.
+pub const CURRENT_STORAGE_VERSION: [u8; 1] = [0x01];
 
-fn storage_version_key() -> Vec<u8> {
+pub(crate) fn storage_version_key() -> Vec<u8> {
     let storage_version_tuple = vec![DataValue::Null, DataValue::from("STORAGE_VERSION")];
     storage_version_tuple.encode_as_key(RelationId::SYSTEM)
 }
 
+#[derive(Debug, Error, Diagnostic)]
+#[error("Storage version mismatch: expect storage version {0:?}, got {1:?}")]
+#[diagnostic(code(db::storage_version_mismatch))]
+pub(crate) struct StorageVersionMismatch([u8; 1], Vec<u8>, #[help] String);
+
+impl StorageVersionMismatch {
+    fn new(found: Vec<u8>) -> Self {
+        let help = if found[..] > CURRENT_STORAGE_VERSION[..] {
+            "The storage was written by a newer version of Cozo, upgrade to open it"
+        } else {
+            "The storage was written by an older version of Cozo, export the data with \
+             that version and import it into a new storage"
+        };
+        Self(CURRENT_STORAGE_VERSION, found, help.to_string())
+    }
+}
+
+fn has_unnormalized_float(v: &DataValue) -> bool {
+    match v {
+        DataValue::Num(Num::Float(f)) => f.to_bits() != normalize_float(*f).to_bits(),
+        DataValue::List(l) => l.iter().any(has_unnormalized_float),
+        DataValue::Set(s) => s.iter().any(has_unnormalized_float),
+        _ => false,
+    }
+}
+
 const STATUS_STR: &str = "status";
 const OK_STR: &str = "OK";
 
@@ -113,13 +147,14 @@ impl<'a> SessionTx<'a> {
                     None => {
                         bail!("Storage is used but un-versioned, probably created by an ancient version of Cozo.")
                     }
+                    Some(v) if v == [0x00] => {
+                        self.upgrade_float_keys()?;
+                        self.store_tx
+                            .put(&storage_version_key, &CURRENT_STORAGE_VERSION)?;
+                    }
                     Some(v) => {
                         if v != CURRENT_STORAGE_VERSION {
-                            bail!(
-                                "Version mismatch: expect storage version {:?}, got {:?}",
-                                CURRENT_STORAGE_VERSION,
-                                v
-                            )
+                            bail!(StorageVersionMismatch::new(v))
                         }
                     }
                 }
@@ -129,6 +164,36 @@ impl<'a> SessionTx<'a> {
         Ok(ret)
     }
 
+    /// Rewrite the keys holding negative zero or a NaN, as storage version 0 encoded
+    /// them differently from the other zeros and NaNs. If a key of the new encoding
+    /// already exists, its row is kept and the differently encoded one is dropped.
+    fn upgrade_float_keys(&mut self) -> Result<()> {
+        let mut rewrites = vec![];
+        for kv in self.store_tx.total_scan() {
+            let (key, val) = kv?;
+            if key.len() <= ENCODED_KEY_MIN_LEN {
+                continue;
+            }
+            let tuple = decode_tuple_from_key(&key, 8);
+            if tuple.iter().any(has_unnormalized_float) {
+                let new_key = tuple.encode_as_key(RelationId::raw_decode(&key));
+                rewrites.push((key, new_key, val));
+            }
+        }
+        for (key, new_key, val) in rewrites {
+            self.store_tx.del(&key)?;
+            if !self.store_tx.exists(&new_key, false)? {
+                self.store_tx.put(&new_key, &val)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The storage version persisted when the storage was first used.
+    pub(crate) fn storage_version(&self) -> Result<Option<Vec<u8>>> {
+        self.store_tx.get(&storage_version_key(), false)
+    }
+
     pub fn commit_tx(&mut self) -> Result<()> {
         self.store_tx.commit()?;
         Ok(())
@@ -250,6 +250,46 @@ impl<'s> StoreTx<'s> for MemTx<'s> {
         }
     }
 
+    fn range_last<'a>(&'a self, lower: &[u8], upper: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>>
+    where
+        's: 'a,
+    {
+        Ok(match self {
+            MemTx::Reader(rdr) => rdr
+                .range(lower.to_vec()..upper.to_vec())
+                .next_back()
+                .map(|(k, v)| (k.clone(), v.clone())),
+            MemTx::Writer(wtr, cache) => {
+                let mut changes = cache.range(lower.to_vec()..upper.to_vec()).rev().peekable();
+                let mut persisted = wtr.range(lower.to_vec()..upper.to_vec()).rev().peekable();
+                loop {
+                    match (changes.peek(), persisted.peek()) {
+                        (None, None) => break None,
+                        (None, Some((k, v))) => break Some(((*k).clone(), (*v).clone())),
+                        (Some((ck, cv)), db_entry) => {
+                            let db_key = db_entry.map(|(k, _)| *k);
+                            if Some(*ck) >= db_key {
+                                if db_key == Some(*ck) {
+                                    persisted.next();
+                                }
+                                match cv {
+                                    Some(v) => break Some(((*ck).clone(), v.clone())),
+                                    // deleted in this transaction
+                                    None => {
+                                        changes.next();
+                                    }
+                                }
+                            } else {
+                                let (k, v) = persisted.next().unwrap();
+                                break Some((k.clone(), v.clone()));
+                            }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
     fn range_count<'a>(&'a self, lower: &[u8], upper: &[u8]) -> Result<usize>
     where
         's: 'a,
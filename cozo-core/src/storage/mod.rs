@@ -151,6 +151,21 @@ pub trait StoreTx<'s>: Sync {
     where
         's: 'a;
 
+    /// Return the last key-value pair in the range, i.e. the one with the largest key,
+    /// or `None` if the range is empty. `lower` is inclusive whereas `upper` is exclusive.
+    /// The default implementation walks through the whole range: engines that can seek
+    /// backwards should override it.
+    fn range_last<'a>(&'a self, lower: &[u8], upper: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>>
+    where
+        's: 'a,
+    {
+        let mut ret = None;
+        for kv in self.range_scan(lower, upper) {
+            ret = Some(kv?);
+        }
+        Ok(ret)
+    }
+
     /// Return the number of rows in the range.
     fn range_count<'a>(&'a self, lower: &[u8], upper: &[u8]) -> Result<usize>
     where
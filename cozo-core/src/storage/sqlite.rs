@@ -126,7 +126,7 @@ pub struct SqliteTx<'a> {
 
 unsafe impl Sync for SqliteTx<'_> {}
 
-const N_QUERIES: usize = 7;
+const N_QUERIES: usize = 8;
 const N_CACHED_QUERIES: usize = 4;
 const QUERIES: [&str; N_QUERIES] = [
     "select v from cozo where k = ?;",
@@ -136,6 +136,7 @@ const QUERIES: [&str; N_QUERIES] = [
     "select k, v from cozo where k >= ? and k < ? order by k;",
     "select k, v from cozo where k >= ? and k < ? order by k limit 1;",
     "select count(*) from cozo where k >= ? and k < ?;",
+    "select k, v from cozo where k >= ? and k < ? order by k desc limit 1;",
 ];
 
 const GET_QUERY: usize = 0;
@@ -145,6 +146,7 @@ const EXISTS_QUERY: usize = 3;
 const RANGE_QUERY: usize = 4;
 const SKIP_RANGE_QUERY: usize = 5;
 const COUNT_RANGE_QUERY: usize = 6;
+const LAST_RANGE_QUERY: usize = 7;
 
 impl Drop for SqliteTx<'_> {
     fn drop(&mut self) {
@@ -317,6 +319,17 @@ impl<'s> StoreTx<'s> for SqliteTx<'s> {
         Box::new(RawIter(statement))
     }
 
+    fn range_last<'a>(&'a self, lower: &[u8], upper: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>>
+    where
+        's: 'a,
+    {
+        let query = QUERIES[LAST_RANGE_QUERY];
+        let mut statement = self.conn.as_ref().unwrap().prepare(query).unwrap();
+        statement.bind((1, lower)).unwrap();
+        statement.bind((2, upper)).unwrap();
+        RawIter(statement).next().transpose()
+    }
+
     fn range_count<'a>(&'a self, lower: &[u8], upper: &[u8]) -> Result<usize>
     where
         's: 'a,
@@ -128,6 +128,17 @@ impl<'s> StoreTx<'s> for TempTx {
         )
     }
 
+    fn range_last<'a>(&'a self, lower: &[u8], upper: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>>
+    where
+        's: 'a,
+    {
+        Ok(self
+            .store
+            .range(lower.to_vec()..upper.to_vec())
+            .next_back()
+            .map(|(k, v)| (k.clone(), v.clone())))
+    }
+
     fn range_count<'a>(&'a self, lower: &[u8], upper: &[u8]) -> Result<usize> where 's: 'a {
         Ok(self.store.range(lower.to_vec()..upper.to_vec()).count())
     }
@@ -6,6 +6,10 @@
  * You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use std::hash::{Hash, Hasher};
+
+use twox_hash::XxHash64;
+
 #[inline(always)]
 pub(crate) fn swap_option_result<T, E>(d: Result<Option<T>, E>) -> Option<Result<T, E>> {
     match d {
@@ -29,3 +33,42 @@ impl<T: serde::Serialize + for<'a> serde::Deserialize<'a>> TempCollector<T> {
         self.inner.into_iter().map(|v| v.unwrap())
     }
 }
+
+const HLL_PRECISION: u32 = 14;
+
+/// Estimates the number of distinct values inserted with HyperLogLog, using constant memory
+/// (16 KiB of registers) and with a standard error of about 0.8%.
+pub(crate) struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    pub(crate) fn new() -> Self {
+        Self {
+            registers: vec![0; 1 << HLL_PRECISION],
+        }
+    }
+    pub(crate) fn insert(&mut self, val: &impl Hash) {
+        let mut hasher = XxHash64::with_seed(0);
+        val.hash(&mut hasher);
+        let hash = hasher.finish();
+        let idx = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rank = ((hash << HLL_PRECISION).leading_zeros()).min(64 - HLL_PRECISION) as u8 + 1;
+        if rank > self.registers[idx] {
+            self.registers[idx] = rank;
+        }
+    }
+    pub(crate) fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1. + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        // small cardinalities are estimated better by linear counting
+        if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+}
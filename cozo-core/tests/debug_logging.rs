@@ -0,0 +1,53 @@
+/*
+ * Copyright 2023, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::sync::Mutex;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use cozo::DbInstance;
+
+struct CapturingLogger(Mutex<Vec<String>>);
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if record.target().starts_with("cozo") {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(vec![]));
+
+// The logger is process-wide, so both levels are checked within a single test.
+#[test]
+fn debug_statements_go_through_log() {
+    log::set_logger(&LOGGER).unwrap();
+    let db = DbInstance::default();
+    let script = r"
+        {?[a] <- [[1], [2]] :replace _t {a}}
+        %debug _t
+    ";
+
+    log::set_max_level(LevelFilter::Warn);
+    // the rows are returned even when nothing is logged
+    let res = db.run_default(script).unwrap();
+    assert_eq!(res.rows.len(), 2);
+    assert!(LOGGER.0.lock().unwrap().is_empty());
+
+    log::set_max_level(LevelFilter::Trace);
+    db.run_default(script).unwrap();
+    let logged = LOGGER.0.lock().unwrap();
+    assert!(logged.iter().any(|line| line.starts_with("_t: ")));
+}